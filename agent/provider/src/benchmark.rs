@@ -0,0 +1,250 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::hint::black_box;
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ya_agreement_utils::OfferTemplate;
+use ya_utils_path::SwapSave;
+
+const MIB: usize = 1024 * 1024;
+const MEMORY_BUFFER_SIZE: usize = 64 * MIB;
+const DISK_CHUNK_SIZE: usize = 4 * MIB;
+const CPU_BATCH: u64 = 100_000;
+const DISK_TEST_FILE: &str = "benchmark.tmp";
+
+pub const CPU_SINGLE_THREAD_PROPERTY: &str = "golem.inf.cpu.benchmark.single-thread";
+pub const CPU_MULTI_THREAD_PROPERTY: &str = "golem.inf.cpu.benchmark.multi-thread";
+pub const MEMORY_BANDWIDTH_PROPERTY: &str = "golem.inf.mem.benchmark.bandwidth";
+pub const STORAGE_WRITE_PROPERTY: &str = "golem.inf.storage.benchmark.write";
+pub const STORAGE_READ_PROPERTY: &str = "golem.inf.storage.benchmark.read";
+
+/// Results of local hardware benchmarks. CPU scores are in millions
+/// of operations per second, throughputs in MiB per second.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BenchmarkResults {
+    pub cpu_single_thread: f64,
+    pub cpu_multi_thread: f64,
+    pub memory_bandwidth: f64,
+    pub storage_write: f64,
+    pub storage_read: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl BenchmarkResults {
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = OpenOptions::new().read(true).open(path)?;
+        Ok(Some(serde_json::from_reader(BufReader::new(file))?))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        Ok(path.swap_save(serde_json::to_string_pretty(self)?)?)
+    }
+
+    pub fn properties(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            (CPU_SINGLE_THREAD_PROPERTY, self.cpu_single_thread),
+            (CPU_MULTI_THREAD_PROPERTY, self.cpu_multi_thread),
+            (MEMORY_BANDWIDTH_PROPERTY, self.memory_bandwidth),
+            (STORAGE_WRITE_PROPERTY, self.storage_write),
+            (STORAGE_READ_PROPERTY, self.storage_read),
+        ]
+    }
+
+    /// Score published as `property`.
+    pub fn score(&self, property: &str) -> Option<f64> {
+        self.properties()
+            .into_iter()
+            .find(|(name, _)| *name == property)
+            .map(|(_, value)| value)
+    }
+
+    /// Publishes results as offer properties, so Requestors can constrain on them.
+    pub fn add_to_offer(&self, offer: &mut OfferTemplate) {
+        for (property, value) in self.properties() {
+            offer.set_property(property, serde_json::json!(value));
+        }
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Parameters of benchmark run.
+#[derive(Clone, Debug)]
+pub struct Benchmark {
+    /// Duration of each CPU and memory test.
+    pub duration: Duration,
+    pub threads: usize,
+    /// Size of the file written by storage test.
+    pub disk_size_mib: usize,
+}
+
+impl Benchmark {
+    pub fn run(&self, work_dir: &Path) -> Result<BenchmarkResults> {
+        log::info!("Running single thread CPU benchmark...");
+        let cpu_single_thread = cpu_score(1, self.duration);
+        log::info!(
+            "Running multi thread CPU benchmark ({} threads)...",
+            self.threads
+        );
+        let cpu_multi_thread = cpu_score(self.threads.max(1), self.duration);
+        log::info!("Running memory bandwidth benchmark...");
+        let memory_bandwidth = memory_bandwidth(self.duration);
+        log::info!("Running storage benchmark in {}...", work_dir.display());
+        let (storage_write, storage_read) = storage_throughput(work_dir, self.disk_size_mib)?;
+
+        Ok(BenchmarkResults {
+            cpu_single_thread: round(cpu_single_thread),
+            cpu_multi_thread: round(cpu_multi_thread),
+            memory_bandwidth: round(memory_bandwidth),
+            storage_write: round(storage_write),
+            storage_read: round(storage_read),
+            timestamp: Utc::now(),
+        })
+    }
+}
+
+fn cpu_work(seed: u64, iterations: u64) -> u64 {
+    let mut x = seed | 1;
+    for _ in 0..iterations {
+        // xorshift64
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+    }
+    x
+}
+
+/// Millions of xorshift iterations per second summed over all threads.
+fn cpu_score(threads: usize, duration: Duration) -> f64 {
+    let workers = (0..threads)
+        .map(|seed| {
+            thread::spawn(move || {
+                let start = Instant::now();
+                let mut iterations = 0u64;
+                while start.elapsed() < duration {
+                    black_box(cpu_work(black_box(seed as u64), CPU_BATCH));
+                    iterations += CPU_BATCH;
+                }
+                iterations as f64 / start.elapsed().as_secs_f64()
+            })
+        })
+        .collect::<Vec<_>>();
+    let total: f64 = workers
+        .into_iter()
+        .map(|worker| worker.join().unwrap_or(0.0))
+        .sum();
+    total / 1_000_000.0
+}
+
+/// MiB per second copied between two buffers larger than CPU caches.
+fn memory_bandwidth(duration: Duration) -> f64 {
+    let src = vec![1u8; MEMORY_BUFFER_SIZE];
+    let mut dst = vec![0u8; MEMORY_BUFFER_SIZE];
+    let start = Instant::now();
+    let mut copied = 0usize;
+    while start.elapsed() < duration {
+        dst.copy_from_slice(black_box(&src));
+        black_box(&mut dst);
+        copied += MEMORY_BUFFER_SIZE;
+    }
+    (copied / MIB) as f64 / start.elapsed().as_secs_f64()
+}
+
+/// Sequential write and read throughput in MiB per second. Read results
+/// can be inflated by the OS page cache.
+fn storage_throughput(work_dir: &Path, size_mib: usize) -> Result<(f64, f64)> {
+    let path = work_dir.join(DISK_TEST_FILE);
+    let chunks = (size_mib * MIB / DISK_CHUNK_SIZE).max(1);
+    let result = write_and_read(&path, chunks);
+    let _ = fs::remove_file(&path);
+    result
+}
+
+fn write_and_read(path: &Path, chunks: usize) -> Result<(f64, f64)> {
+    let chunk = vec![0xA5u8; DISK_CHUNK_SIZE];
+    let start = Instant::now();
+    let mut file = File::create(path)?;
+    for _ in 0..chunks {
+        file.write_all(&chunk)?;
+    }
+    file.sync_all()?;
+    let write = (chunks * DISK_CHUNK_SIZE / MIB) as f64 / start.elapsed().as_secs_f64();
+
+    let start = Instant::now();
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0u8; DISK_CHUNK_SIZE];
+    let mut read = 0usize;
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    let read = (read / MIB) as f64 / start.elapsed().as_secs_f64();
+    Ok((write, read))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_and_persist() {
+        let dir = tempdir::TempDir::new("benchmark").unwrap();
+        let benchmark = Benchmark {
+            duration: Duration::from_millis(50),
+            threads: 2,
+            disk_size_mib: 4,
+        };
+        let results = benchmark.run(dir.path()).unwrap();
+        assert!(results.cpu_single_thread > 0.0);
+        assert!(results.cpu_multi_thread > 0.0);
+        assert!(results.memory_bandwidth > 0.0);
+        assert!(results.storage_write > 0.0);
+        assert!(!dir.path().join(DISK_TEST_FILE).exists());
+
+        let path = dir.path().join("benchmark.json");
+        assert_eq!(BenchmarkResults::load(&path).unwrap(), None);
+        results.save(&path).unwrap();
+        let loaded = BenchmarkResults::load(&path).unwrap().unwrap();
+        assert_eq!(loaded.timestamp, results.timestamp);
+        for ((_, loaded), (_, measured)) in
+            loaded.properties().into_iter().zip(results.properties())
+        {
+            assert!((loaded - measured).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn offer_properties() {
+        let results = BenchmarkResults {
+            cpu_single_thread: 812.35,
+            cpu_multi_thread: 6498.1,
+            memory_bandwidth: 10240.0,
+            storage_write: 512.0,
+            storage_read: 2048.0,
+            timestamp: Utc::now(),
+        };
+        let mut offer = OfferTemplate::default();
+        results.add_to_offer(&mut offer);
+        assert_eq!(
+            offer.properties[CPU_SINGLE_THREAD_PROPERTY],
+            serde_json::json!(812.35)
+        );
+        assert_eq!(
+            offer.properties[STORAGE_READ_PROPERTY],
+            serde_json::json!(2048.0)
+        );
+    }
+}
@@ -1,12 +1,17 @@
 //! Command line handling
+pub mod benchmark;
 pub mod clean;
 pub mod config;
+pub mod drain;
+pub mod earnings;
 pub mod exe_unit;
 pub mod keystore;
 pub mod pre_install;
 pub mod preset;
 pub mod profile;
+pub mod reputation;
 pub mod rule;
+pub mod schedule;
 pub mod whitelist;
 
 use crate::startup_config::ProviderConfig;
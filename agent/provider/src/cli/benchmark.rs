@@ -0,0 +1,44 @@
+use structopt::StructOpt;
+use ya_utils_cli::{CommandOutput, ResponseTable};
+
+use crate::benchmark::Benchmark;
+use crate::startup_config::ProviderConfig;
+
+/// Results are stored in data directory and published in offers
+/// as `golem.inf.*.benchmark.*` properties. Presets with `benchmark` set are priced
+/// per unit of the chosen score.
+#[derive(StructOpt, Clone, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct BenchmarkConfig {
+    /// Duration of each CPU and memory benchmark
+    #[structopt(long, parse(try_from_str = humantime::parse_duration), default_value = "3s")]
+    pub duration: std::time::Duration,
+    /// Size of the file written by storage benchmark in MiB
+    #[structopt(long, default_value = "256")]
+    pub disk_size: usize,
+}
+
+impl BenchmarkConfig {
+    pub fn run(self, config: ProviderConfig) -> anyhow::Result<()> {
+        let data_dir = config.data_dir.get_or_create()?;
+        let benchmark = Benchmark {
+            duration: self.duration,
+            threads: num_cpus::get(),
+            disk_size_mib: self.disk_size,
+        };
+        let results = benchmark.run(&data_dir)?;
+        results.save(&config.benchmark_file)?;
+
+        let values = results
+            .properties()
+            .into_iter()
+            .map(|(property, value)| serde_json::json! {[property, value]})
+            .collect();
+        let table = ResponseTable {
+            columns: vec!["Property".to_string(), "Value".to_string()],
+            values,
+        };
+        CommandOutput::from(table).print(config.json)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use structopt::{clap, StructOpt};
+
+use ya_utils_process::lock::ProcLock;
+
+use crate::startup_config::ProviderConfig;
+
+/// Running provider unsubscribes its offers, rejects new Proposals and Agreements
+/// and exits when running Agreements finish or `--drain-timeout` elapses.
+#[derive(StructOpt, Clone, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct DrainConfig {}
+
+impl DrainConfig {
+    pub fn run(self, config: ProviderConfig) -> anyhow::Result<()> {
+        let data_dir = config.data_dir.get_or_create()?;
+        let pid = ProcLock::new(clap::crate_name!(), &data_dir)?.read_pid()?;
+        send_drain_signal(pid)?;
+        println!("Drain requested for {} (pid {}).", clap::crate_name!(), pid);
+        Ok(())
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn send_drain_signal(pid: u32) -> anyhow::Result<()> {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+
+    kill(Pid::from_raw(pid as i32), Signal::SIGUSR1)?;
+    Ok(())
+}
+
+#[cfg(target_family = "windows")]
+fn send_drain_signal(_pid: u32) -> anyhow::Result<()> {
+    anyhow::bail!("Draining is not supported on Windows")
+}
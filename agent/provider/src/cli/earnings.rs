@@ -0,0 +1,163 @@
+use chrono::{Duration, NaiveDate, NaiveTime};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use structopt::StructOpt;
+use ya_client::cli::{ApiOpts, ProviderApi};
+use ya_client::model::payment::Invoice;
+use ya_utils_cli::{CommandOutput, ResponseTable};
+
+use crate::earnings::{parse_date, report, AgreementPresets, EarningsRow, GroupBy, LedgerEntry};
+use crate::startup_config::ProviderConfig;
+
+/// Max number of invoices fetched from payment service at once
+const INVOICES_PAGE: u32 = 1000;
+
+/// Invoices and their statuses are read from the payment service of running yagna.
+/// Amounts are listed separately for each payment platform. Accepted amount
+/// includes settled invoices.
+#[derive(StructOpt, Clone, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub struct EarningsConfig {
+    #[structopt(flatten)]
+    pub api: ApiOpts,
+    /// Include invoices issued on this day or later (YYYY-MM-DD, UTC)
+    #[structopt(long, parse(try_from_str = parse_date))]
+    pub from: Option<NaiveDate>,
+    /// Include invoices issued on this day or earlier (YYYY-MM-DD, UTC)
+    #[structopt(long, parse(try_from_str = parse_date))]
+    pub to: Option<NaiveDate>,
+    /// Group earnings by: day, requestor or preset
+    #[structopt(long, default_value = "day")]
+    pub group_by: GroupBy,
+    /// Print report as CSV
+    #[structopt(long)]
+    pub csv: bool,
+}
+
+impl EarningsConfig {
+    pub async fn run(self, config: ProviderConfig) -> anyhow::Result<()> {
+        let presets = AgreementPresets::load(&config.agreement_presets_file)?;
+        let entries = self
+            .invoices()
+            .await?
+            .iter()
+            .filter_map(|invoice| LedgerEntry::from_invoice(invoice, &presets))
+            .collect::<Vec<_>>();
+        let rows = report(&entries, self.from, self.to, self.group_by);
+
+        if self.csv {
+            print_csv(&rows);
+            return Ok(());
+        }
+        if config.json {
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+            return Ok(());
+        }
+
+        let values = rows
+            .iter()
+            .map(|row| {
+                serde_json::json! {[
+                    row.group,
+                    row.payment_platform,
+                    row.invoices,
+                    row.invoiced.to_string(),
+                    row.accepted.to_string(),
+                    row.settled.to_string(),
+                ]}
+            })
+            .collect();
+        let table = ResponseTable {
+            columns: vec![
+                "Group".to_string(),
+                "Platform".to_string(),
+                "Invoices".to_string(),
+                "Invoiced".to_string(),
+                "Accepted".to_string(),
+                "Settled".to_string(),
+            ],
+            values,
+        };
+        CommandOutput::from(table).print(false)?;
+        Ok(())
+    }
+
+    /// Invoices issued by this Provider between `from` and `to` days.
+    async fn invoices(&self) -> anyhow::Result<Vec<Invoice>> {
+        let api = ProviderApi::try_from(&self.api)?;
+        let payees = api
+            .payment
+            .get_provider_accounts()
+            .await?
+            .into_iter()
+            .map(|account| account.address.to_lowercase())
+            .collect::<HashSet<_>>();
+
+        // Invoices are listed by ascending timestamp, strictly after the given one.
+        // Next page starts just before the last timestamp, so invoices sharing it
+        // aren't skipped, and invoices already fetched are recognized by their ids.
+        let mut after = self
+            .from
+            .map(|from| from.and_time(NaiveTime::MIN).and_utc() - Duration::nanoseconds(1));
+        let mut seen = HashSet::new();
+        let mut invoices = Vec::new();
+        loop {
+            let page = api.payment.get_invoices(after, Some(INVOICES_PAGE)).await?;
+            let last = match page.last() {
+                Some(invoice) => invoice.timestamp,
+                None => break,
+            };
+            let full = page.len() == INVOICES_PAGE as usize;
+            let mut new = 0;
+            for invoice in page {
+                if !seen.insert(invoice.invoice_id.clone()) {
+                    continue;
+                }
+                new += 1;
+                if payees.contains(&invoice.payee_addr.to_lowercase()) {
+                    invoices.push(invoice);
+                }
+            }
+            let past_range = self.to.map(|to| last.date_naive() > to).unwrap_or(false);
+            if !full || past_range {
+                break;
+            }
+            after = match new {
+                // Whole page shares the timestamp, so it can't be paged through.
+                0 => {
+                    log::warn!(
+                        "More than {} invoices issued at {}. Some of them may be missing.",
+                        INVOICES_PAGE,
+                        last
+                    );
+                    Some(last)
+                }
+                _ => Some(last - Duration::nanoseconds(1)),
+            };
+        }
+        Ok(invoices)
+    }
+}
+
+fn print_csv(rows: &[EarningsRow]) {
+    println!("group,payment_platform,invoices,invoiced,accepted,settled");
+    for row in rows {
+        println!(
+            "{},{},{},{},{},{}",
+            csv_field(&row.group),
+            csv_field(&row.payment_platform),
+            row.invoices,
+            row.invoiced,
+            row.accepted,
+            row.settled
+        );
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::Utc;
+use structopt::StructOpt;
+
+use ya_client_model::NodeId;
+use ya_utils_cli::{CommandOutput, ResponseTable};
+
+use crate::cli::println_conditional;
+use crate::reputation::ReputationStore;
+use crate::startup_config::ProviderConfig;
+
+#[derive(StructOpt, Clone, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub enum ReputationConfig {
+    /// List Requestors' payment history and reputation scores
+    List,
+    /// Forget payment history of a Requestor
+    Reset {
+        /// Requestor node id
+        #[structopt(required_unless = "all")]
+        requestor_id: Option<NodeId>,
+        /// Forget payment history of all Requestors
+        #[structopt(long, conflicts_with = "requestor-id")]
+        all: bool,
+    },
+}
+
+impl ReputationConfig {
+    pub fn run(self, config: ProviderConfig) -> anyhow::Result<()> {
+        let store = ReputationStore::load_or_create(&config.reputation_file)?;
+        match self {
+            ReputationConfig::List => list(&config, &store),
+            ReputationConfig::Reset { requestor_id, .. } => {
+                let removed = store.reset(requestor_id)?;
+                println_conditional(&config, &format!("Removed {} record(s).", removed));
+                Ok(())
+            }
+        }
+    }
+}
+
+fn list(config: &ProviderConfig, store: &ReputationStore) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let columns = [
+        "Requestor",
+        "Score",
+        "Agreements",
+        "Late debit notes",
+        "Unpaid debit notes",
+        "Broken agreements",
+        "Paid invoices",
+        "Unpaid invoices",
+    ];
+    let values = store
+        .list()
+        .into_iter()
+        .map(|(requestor_id, record)| {
+            serde_json::json! {[
+                requestor_id,
+                format!("{:.2}", record.score(now)),
+                record.agreements,
+                record.late_debit_notes,
+                record.unpaid_debit_notes,
+                record.broken_agreements,
+                record.paid_invoices,
+                record.unpaid_invoices(now),
+            ]}
+        })
+        .collect();
+    let table = ResponseTable {
+        columns: columns.iter().map(ToString::to_string).collect(),
+        values,
+    };
+    CommandOutput::from(table).print(config.json)?;
+    Ok(())
+}
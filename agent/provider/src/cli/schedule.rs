@@ -0,0 +1,90 @@
+use chrono::Local;
+use structopt::StructOpt;
+
+use crate::config::availability::{Availability, AvailabilitySchedule, AvailabilityWindow};
+use crate::config::globals::GlobalsState;
+use crate::startup_config::ProviderConfig;
+
+/// Offers are published only within availability windows, written in local
+/// time as '<days> <HH:MM>-<HH:MM>', e.g. 'mon-fri 20:00-07:00' or 'sat,sun 00:00-24:00'.
+#[derive(StructOpt, Clone, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub enum ScheduleConfig {
+    /// Show availability schedule
+    Show,
+    /// Replace availability schedule
+    Set {
+        /// Availability windows, each one quoted
+        #[structopt(required = true)]
+        windows: Vec<AvailabilityWindow>,
+    },
+    /// Add availability window
+    Add { window: AvailabilityWindow },
+    /// Remove availability window
+    Remove { window: AvailabilityWindow },
+    /// Remove availability schedule, making provider available all the time
+    Clear,
+}
+
+impl ScheduleConfig {
+    pub fn run(self, config: ProviderConfig) -> anyhow::Result<()> {
+        let mut state = GlobalsState::load_or_create(&config.globals_file)?;
+        match self {
+            ScheduleConfig::Show => return show(&config, state.availability),
+            ScheduleConfig::Set { windows } => {
+                state.availability = Some(AvailabilitySchedule { windows });
+            }
+            ScheduleConfig::Add { window } => {
+                let schedule = state.availability.get_or_insert_with(Default::default);
+                if !schedule.windows.contains(&window) {
+                    schedule.windows.push(window);
+                }
+            }
+            ScheduleConfig::Remove { window } => {
+                let schedule = state
+                    .availability
+                    .as_mut()
+                    .filter(|schedule| schedule.windows.contains(&window))
+                    .ok_or_else(|| anyhow::anyhow!("Availability window '{}' not found", window))?;
+                schedule.windows.retain(|w| w != &window);
+                if schedule.windows.is_empty() {
+                    state.availability = None;
+                }
+            }
+            ScheduleConfig::Clear => state.availability = None,
+        }
+        state.save(&config.globals_file)?;
+        show(&config, state.availability)
+    }
+}
+
+fn show(config: &ProviderConfig, schedule: Option<AvailabilitySchedule>) -> anyhow::Result<()> {
+    if config.json {
+        println!("{}", serde_json::to_string_pretty(&schedule)?);
+        return Ok(());
+    }
+
+    let schedule = match schedule {
+        Some(schedule) => schedule,
+        None => {
+            println!("No availability schedule. Provider is available all the time.");
+            return Ok(());
+        }
+    };
+
+    println!("Availability windows:");
+    for window in &schedule.windows {
+        println!("  {}", window);
+    }
+
+    let now = Local::now().naive_local();
+    match schedule.availability(now) {
+        Availability::AlwaysOpen => println!("Available all the time."),
+        Availability::OpenUntil(end) => println!("Available until {}.", end),
+        Availability::Closed => match schedule.next_opening(now) {
+            Some(start) => println!("Unavailable until {}.", start),
+            None => println!("Unavailable."),
+        },
+    }
+    Ok(())
+}
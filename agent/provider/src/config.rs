@@ -1,2 +1,3 @@
+pub mod availability;
 pub mod globals;
 pub mod presets;
@@ -0,0 +1,334 @@
+use anyhow::{anyhow, bail};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// Schedules with windows chained for longer than this are considered always open.
+const HORIZON_DAYS: i64 = 7;
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Recurring window in local time, written as `<days> <HH:MM>-<HH:MM>`,
+/// e.g. `mon-fri 20:00-07:00` or `sat,sun 00:00-24:00`. Days are `*`,
+/// or a comma separated list of day names and day ranges. Windows ending
+/// before they start continue on the next day.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AvailabilityWindow {
+    days: String,
+    weekdays: [bool; 7],
+    start: u32,
+    end: u32,
+}
+
+impl AvailabilityWindow {
+    fn opens_on(&self, date: NaiveDate) -> bool {
+        self.weekdays[date.weekday().num_days_from_monday() as usize]
+    }
+
+    fn interval(&self, date: NaiveDate) -> (NaiveDateTime, NaiveDateTime) {
+        let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+        let start = midnight + Duration::minutes(self.start as i64);
+        let end = match self.end > self.start {
+            true => midnight + Duration::minutes(self.end as i64),
+            false => midnight + Duration::minutes((self.end + MINUTES_PER_DAY) as i64),
+        };
+        (start, end)
+    }
+}
+
+fn parse_weekday(s: &str) -> anyhow::Result<usize> {
+    Weekday::from_str(s)
+        .map(|day| day.num_days_from_monday() as usize)
+        .map_err(|_| anyhow!("Invalid day of week: '{}'", s))
+}
+
+fn parse_minutes(s: &str) -> anyhow::Result<u32> {
+    let (hours, minutes) = s
+        .split_once(':')
+        .filter(|(hours, minutes)| hours.len() == 2 && minutes.len() == 2)
+        .ok_or_else(|| anyhow!("Expected HH:MM time, got '{}'", s))?;
+    let (hours, minutes) = (hours.parse::<u32>()?, minutes.parse::<u32>()?);
+    if minutes >= 60 || hours * 60 + minutes > MINUTES_PER_DAY {
+        bail!("Invalid time: '{}'", s);
+    }
+    Ok(hours * 60 + minutes)
+}
+
+fn format_minutes(minutes: u32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+impl FromStr for AvailabilityWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let (days, range) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(days), Some(range), None) => (days.to_lowercase(), range),
+            _ => bail!("Expected '<days> <HH:MM>-<HH:MM>', got '{}'", s),
+        };
+
+        let mut weekdays = [false; 7];
+        for spec in days.split(',') {
+            if spec == "*" {
+                weekdays = [true; 7];
+                continue;
+            }
+            let (first, last) = match spec.split_once('-') {
+                Some((first, last)) => (parse_weekday(first)?, parse_weekday(last)?),
+                None => (parse_weekday(spec)?, parse_weekday(spec)?),
+            };
+            let mut day = first;
+            loop {
+                weekdays[day] = true;
+                if day == last {
+                    break;
+                }
+                day = (day + 1) % 7;
+            }
+        }
+
+        let (start, end) = range
+            .split_once('-')
+            .ok_or_else(|| anyhow!("Expected <HH:MM>-<HH:MM> time range, got '{}'", range))?;
+        let (start, end) = (parse_minutes(start)?, parse_minutes(end)?);
+        if start == MINUTES_PER_DAY {
+            bail!("Window can't start at 24:00");
+        }
+
+        Ok(AvailabilityWindow {
+            days,
+            weekdays,
+            start,
+            end,
+        })
+    }
+}
+
+impl fmt::Display for AvailabilityWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}-{}",
+            self.days,
+            format_minutes(self.start),
+            format_minutes(self.end)
+        )
+    }
+}
+
+impl Serialize for AvailabilityWindow {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for AvailabilityWindow {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Provider availability at a given moment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Availability {
+    Closed,
+    OpenUntil(NaiveDateTime),
+    AlwaysOpen,
+}
+
+/// Recurring windows, in which provider sells compute.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AvailabilitySchedule {
+    pub windows: Vec<AvailabilityWindow>,
+}
+
+impl AvailabilitySchedule {
+    fn intervals(&self, at: NaiveDateTime) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        (-1..=HORIZON_DAYS + 1)
+            .map(|offset| at.date() + Duration::days(offset))
+            .flat_map(|date| {
+                self.windows
+                    .iter()
+                    .filter(move |w| w.opens_on(date))
+                    .map(move |w| w.interval(date))
+            })
+            .collect()
+    }
+
+    /// Availability at `at`, with end of the continuous open period.
+    pub fn availability(&self, at: NaiveDateTime) -> Availability {
+        let intervals = self.intervals(at);
+        let horizon = at + Duration::days(HORIZON_DAYS);
+
+        let mut open_until = at;
+        while let Some(end) = intervals
+            .iter()
+            .filter(|(start, end)| *start <= open_until && open_until < *end)
+            .map(|(_, end)| *end)
+            .max()
+        {
+            if end > horizon {
+                return Availability::AlwaysOpen;
+            }
+            open_until = end;
+        }
+
+        match open_until > at {
+            true => Availability::OpenUntil(open_until),
+            false => Availability::Closed,
+        }
+    }
+
+    /// Closest window start after `at`.
+    pub fn next_opening(&self, at: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.intervals(at)
+            .into_iter()
+            .map(|(start, _)| start)
+            .filter(|start| *start > at)
+            .min()
+    }
+}
+
+impl fmt::Display for AvailabilitySchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let windows = self
+            .windows
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        write!(f, "{}", windows.join(", "))
+    }
+}
+
+/// Availability schedule shared between ProviderAgent and negotiators.
+/// No schedule means that provider is always available.
+#[derive(Clone, Debug, Default)]
+pub struct ScheduleHandle(Arc<Mutex<Option<AvailabilitySchedule>>>);
+
+impl ScheduleHandle {
+    pub fn new(schedule: Option<AvailabilitySchedule>) -> Self {
+        ScheduleHandle(Arc::new(Mutex::new(schedule)))
+    }
+
+    pub fn set(&self, schedule: Option<AvailabilitySchedule>) {
+        *self.0.lock().unwrap() = schedule;
+    }
+
+    pub fn availability(&self, at: NaiveDateTime) -> Availability {
+        match self.0.lock().unwrap().as_ref() {
+            Some(schedule) => schedule.availability(at),
+            None => Availability::AlwaysOpen,
+        }
+    }
+
+    pub fn next_opening(&self, at: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.0
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|schedule| schedule.next_opening(at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(windows: &[&str]) -> AvailabilitySchedule {
+        AvailabilitySchedule {
+            windows: windows.iter().map(|w| w.parse().unwrap()).collect(),
+        }
+    }
+
+    // 2024-01-01 is a Monday.
+    fn at(day: u32, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("2024-01-{:02} {}", day, time), "%Y-%m-%d %H:%M")
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_window() {
+        let window: AvailabilityWindow = "Sat,Sun 00:00-24:00".parse().unwrap();
+        assert_eq!(window.to_string(), "sat,sun 00:00-24:00");
+        assert_eq!(
+            window.weekdays,
+            [false, false, false, false, false, true, true]
+        );
+
+        let window: AvailabilityWindow = "fri-mon 22:00-06:00".parse().unwrap();
+        assert_eq!(
+            window.weekdays,
+            [true, false, false, false, true, true, true]
+        );
+
+        for invalid in [
+            "mon-fri",
+            "20:00-07:00",
+            "mon-fri 20:00",
+            "moon 20:00-07:00",
+            "mon 24:00-07:00",
+            "mon 20:00-24:01",
+            "mon 7:00-8:00",
+        ] {
+            assert!(
+                invalid.parse::<AvailabilityWindow>().is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let schedule = schedule(&["mon-fri 20:00-07:00", "* 12:00-13:00"]);
+        let json = serde_json::to_string(&schedule).unwrap();
+        assert_eq!(json, r#"["mon-fri 20:00-07:00","* 12:00-13:00"]"#);
+        assert_eq!(
+            serde_json::from_str::<AvailabilitySchedule>(&json).unwrap(),
+            schedule
+        );
+    }
+
+    #[test]
+    fn nights_and_weekends() {
+        let schedule = schedule(&["mon-fri 20:00-07:00", "sat,sun 00:00-24:00"]);
+
+        assert_eq!(schedule.availability(at(1, "12:00")), Availability::Closed);
+        assert_eq!(schedule.next_opening(at(1, "12:00")), Some(at(1, "20:00")));
+        assert_eq!(
+            schedule.availability(at(1, "21:00")),
+            Availability::OpenUntil(at(2, "07:00"))
+        );
+        // Friday night continues into the weekend.
+        assert_eq!(
+            schedule.availability(at(5, "21:00")),
+            Availability::OpenUntil(at(8, "00:00"))
+        );
+        assert_eq!(
+            schedule.availability(at(7, "23:59")),
+            Availability::OpenUntil(at(8, "00:00"))
+        );
+        assert_eq!(schedule.availability(at(8, "00:00")), Availability::Closed);
+    }
+
+    #[test]
+    fn always_open() {
+        let schedule = schedule(&["* 00:00-24:00"]);
+        assert_eq!(
+            schedule.availability(at(3, "10:00")),
+            Availability::AlwaysOpen
+        );
+        assert_eq!(
+            AvailabilitySchedule::default().availability(at(3, "10:00")),
+            Availability::Closed
+        );
+        assert_eq!(
+            ScheduleHandle::default().availability(at(3, "10:00")),
+            Availability::AlwaysOpen
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::config::availability::AvailabilitySchedule;
 use crate::startup_config::NodeConfig;
 use std::path::Path;
 use ya_client::model::NodeId;
@@ -15,15 +16,17 @@ fn default_subnet() -> Option<String> {
 
 #[derive(Clone, Debug, Default, Serialize, derive_more::Display)]
 #[display(
-    fmt = "{}{}{}",
+    fmt = "{}{}{}{}",
     "node_name.as_ref().map(|nn| format!(\"Node name: {}\", nn)).unwrap_or_else(|| \"\".into())",
     "subnet.as_ref().map(|s| format!(\"\nSubnet: {}\", s)).unwrap_or_else(|| \"\".into())",
-    "account.as_ref().map(|a| format!(\"\nAccount: {}\", a)).unwrap_or_else(|| \"\".into())"
+    "account.as_ref().map(|a| format!(\"\nAccount: {}\", a)).unwrap_or_else(|| \"\".into())",
+    "availability.as_ref().map(|a| format!(\"\nAvailability: {}\", a)).unwrap_or_else(|| \"\".into())"
 )]
 pub struct GlobalsState {
     pub node_name: Option<String>,
     pub subnet: Option<String>,
     pub account: Option<NodeId>,
+    pub availability: Option<AvailabilitySchedule>,
 }
 
 impl<'de> Deserialize<'de> for GlobalsState {
@@ -54,6 +57,7 @@ impl<'de> Deserialize<'de> for GlobalsState {
             pub node_name: Option<String>,
             pub subnet: Option<String>,
             pub account: Option<Account>,
+            pub availability: Option<AvailabilitySchedule>,
         }
 
         let s = GenericGlobalsState::deserialize(deserializer)?;
@@ -61,6 +65,7 @@ impl<'de> Deserialize<'de> for GlobalsState {
             node_name: s.node_name,
             subnet: s.subnet,
             account: s.account.map(|a| a.address()),
+            availability: s.availability,
         })
     }
 }
@@ -187,4 +192,26 @@ mod test {
         assert_eq!(g.subnet, Some("community.4".into()));
         assert!(g.account.is_none())
     }
+
+    #[test]
+    fn deserialize_availability() {
+        let g: GlobalsState = serde_json::from_str(
+            r#"
+    {
+      "node_name": "amusing-crate",
+      "subnet": "public",
+      "account": null,
+      "availability": ["mon-fri 20:00-07:00", "sat,sun 00:00-24:00"]
+    }
+    "#,
+        )
+        .unwrap();
+
+        let availability = g.availability.unwrap();
+        assert_eq!(availability.windows.len(), 2);
+        assert_eq!(
+            availability.to_string(),
+            "mon-fri 20:00-07:00, sat,sun 00:00-24:00"
+        );
+    }
 }
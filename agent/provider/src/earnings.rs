@@ -0,0 +1,311 @@
+use anyhow::{anyhow, bail, Result};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use ya_client::model::payment::{DocumentStatus, Invoice};
+
+/// Line of the presets file, appended for every signed Agreement.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct AgreementPreset {
+    agreement_id: String,
+    preset: String,
+}
+
+/// Names of presets, for which Agreements were signed. Everything else needed for
+/// earnings reports comes from invoices stored by the payment service.
+/// Presets are appended to the file, so recording an Agreement doesn't rewrite it.
+#[derive(Clone, Default)]
+pub struct AgreementPresets {
+    file: Option<Arc<Mutex<File>>>,
+}
+
+impl AgreementPresets {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(AgreementPresets {
+            file: Some(Arc::new(Mutex::new(file))),
+        })
+    }
+
+    /// Reads presets without taking ownership of the file,
+    /// which can be appended by running Provider at the same time.
+    pub fn load(path: &Path) -> Result<HashMap<String, String>> {
+        if !path.exists() {
+            return Ok(Default::default());
+        }
+        log::debug!("Loading Agreement presets from: {}", path.display());
+        let file = OpenOptions::new().read(true).open(path)?;
+        let mut presets = HashMap::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            // Last line can be partially written by running Provider.
+            match serde_json::from_str::<AgreementPreset>(&line) {
+                Ok(entry) => presets.insert(entry.agreement_id, entry.preset),
+                Err(_) if line.trim().is_empty() => continue,
+                Err(e) => {
+                    log::debug!("Skipping invalid Agreement preset entry '{}': {}", line, e);
+                    continue;
+                }
+            };
+        }
+        Ok(presets)
+    }
+
+    pub fn record(&self, agreement_id: &str, preset: &str) {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return,
+        };
+        let entry = AgreementPreset {
+            agreement_id: agreement_id.to_string(),
+            preset: preset.to_string(),
+        };
+        // Single write, so that a line is never interleaved with another one.
+        let result = serde_json::to_string(&entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| {
+                Ok(file
+                    .lock()
+                    .unwrap()
+                    .write_all(format!("{}\n", line).as_bytes())?)
+            });
+        if let Err(e) = result {
+            log::warn!(
+                "Failed to save preset of Agreement [{}]: {}",
+                agreement_id,
+                e
+            );
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvoiceStatus {
+    Issued,
+    Accepted,
+    Settled,
+}
+
+/// Invoice issued by Provider, joined with preset of its Agreement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LedgerEntry {
+    pub invoice_id: String,
+    pub agreement_id: String,
+    pub requestor_id: String,
+    pub preset: Option<String>,
+    pub payment_platform: String,
+    pub amount: BigDecimal,
+    pub issued: DateTime<Utc>,
+    pub status: InvoiceStatus,
+}
+
+impl LedgerEntry {
+    /// Cancelled invoices aren't earnings, so they are skipped.
+    pub fn from_invoice(invoice: &Invoice, presets: &HashMap<String, String>) -> Option<Self> {
+        let status = match invoice.status {
+            DocumentStatus::Cancelled => return None,
+            DocumentStatus::Accepted => InvoiceStatus::Accepted,
+            DocumentStatus::Settled => InvoiceStatus::Settled,
+            _ => InvoiceStatus::Issued,
+        };
+        Some(LedgerEntry {
+            invoice_id: invoice.invoice_id.clone(),
+            agreement_id: invoice.agreement_id.clone(),
+            requestor_id: invoice.recipient_id.to_string(),
+            preset: presets.get(&invoice.agreement_id).cloned(),
+            payment_platform: invoice.payment_platform.clone(),
+            amount: invoice.amount.clone(),
+            issued: invoice.timestamp,
+            status,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    Day,
+    Requestor,
+    Preset,
+}
+
+impl FromStr for GroupBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_lowercase().as_str() {
+            "day" => GroupBy::Day,
+            "requestor" => GroupBy::Requestor,
+            "preset" => GroupBy::Preset,
+            _ => bail!("Expected one of: day, requestor, preset. Got '{}'", s),
+        })
+    }
+}
+
+impl GroupBy {
+    fn key(&self, entry: &LedgerEntry) -> String {
+        match self {
+            GroupBy::Day => entry.issued.date_naive().to_string(),
+            GroupBy::Requestor => entry.requestor_id.clone(),
+            GroupBy::Preset => entry.preset.clone().unwrap_or_else(|| "unknown".into()),
+        }
+    }
+}
+
+/// Earnings aggregated for a single group and payment platform.
+/// Accepted amount includes settled invoices.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EarningsRow {
+    pub group: String,
+    pub payment_platform: String,
+    pub invoices: usize,
+    #[serde(serialize_with = "serialize_amount")]
+    pub invoiced: BigDecimal,
+    #[serde(serialize_with = "serialize_amount")]
+    pub accepted: BigDecimal,
+    #[serde(serialize_with = "serialize_amount")]
+    pub settled: BigDecimal,
+}
+
+fn serialize_amount<S: Serializer>(amount: &BigDecimal, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&amount.to_string())
+}
+
+/// Aggregates invoices issued between `from` and `to` dates (inclusive, UTC).
+pub fn report(
+    entries: &[LedgerEntry],
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    group_by: GroupBy,
+) -> Vec<EarningsRow> {
+    let mut rows = BTreeMap::<(String, String), EarningsRow>::new();
+    for entry in entries {
+        let day = entry.issued.date_naive();
+        if from.map(|from| day < from).unwrap_or(false) || to.map(|to| day > to).unwrap_or(false) {
+            continue;
+        }
+
+        let group = group_by.key(entry);
+        let row = rows
+            .entry((group.clone(), entry.payment_platform.clone()))
+            .or_insert_with(|| EarningsRow {
+                group,
+                payment_platform: entry.payment_platform.clone(),
+                invoices: 0,
+                invoiced: BigDecimal::zero(),
+                accepted: BigDecimal::zero(),
+                settled: BigDecimal::zero(),
+            });
+
+        row.invoices += 1;
+        row.invoiced += entry.amount.clone();
+        match entry.status {
+            InvoiceStatus::Issued => (),
+            InvoiceStatus::Accepted => row.accepted += entry.amount.clone(),
+            InvoiceStatus::Settled => {
+                row.accepted += entry.amount.clone();
+                row.settled += entry.amount.clone();
+            }
+        }
+    }
+    rows.into_values().collect()
+}
+
+pub fn parse_date(s: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|e| anyhow!("Invalid date '{}': {}", s, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(
+        invoice_id: &str,
+        day: u32,
+        requestor: &str,
+        platform: &str,
+        amount: &str,
+    ) -> LedgerEntry {
+        LedgerEntry {
+            invoice_id: invoice_id.to_string(),
+            agreement_id: format!("agreement-{}", invoice_id),
+            requestor_id: requestor.to_string(),
+            preset: Some("wasmtime".to_string()),
+            payment_platform: platform.to_string(),
+            amount: BigDecimal::from_str(amount).unwrap(),
+            issued: Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap(),
+            status: InvoiceStatus::Issued,
+        }
+    }
+
+    #[test]
+    fn record_presets() {
+        let dir = tempdir::TempDir::new("earnings").unwrap();
+        let path = dir.path().join("agreement-presets.json");
+        assert!(AgreementPresets::load(&path).unwrap().is_empty());
+
+        let presets = AgreementPresets::open(&path).unwrap();
+        presets.record("agreement-1", "wasmtime");
+        presets.record("agreement-2", "vm");
+        // reopened by restarted Provider
+        AgreementPresets::open(&path)
+            .unwrap()
+            .record("agreement-3", "vm");
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"agreement-id\":\"agree")
+            .unwrap();
+
+        let loaded = AgreementPresets::load(&path).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded["agreement-1"], "wasmtime");
+        assert_eq!(loaded["agreement-3"], "vm");
+    }
+
+    #[test]
+    fn report_groups_by_day_and_platform() {
+        let mut entries = vec![
+            entry("1", 1, "0xa", "erc20-polygon-glm", "1.5"),
+            entry("2", 1, "0xb", "erc20-polygon-glm", "2.5"),
+            entry("3", 1, "0xa", "erc20-holesky-tglm", "10"),
+            entry("4", 2, "0xa", "erc20-polygon-glm", "1"),
+            entry("5", 5, "0xa", "erc20-polygon-glm", "100"),
+        ];
+        entries[0].status = InvoiceStatus::Accepted;
+        entries[1].status = InvoiceStatus::Settled;
+        entries[3].preset = None;
+
+        let to = parse_date("2024-01-02").unwrap();
+        let rows = report(&entries, None, Some(to), GroupBy::Day);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].group, "2024-01-01");
+        assert_eq!(rows[0].payment_platform, "erc20-holesky-tglm");
+        assert_eq!(rows[1].payment_platform, "erc20-polygon-glm");
+        assert_eq!(rows[1].invoices, 2);
+        assert_eq!(rows[1].invoiced, BigDecimal::from(4));
+        assert_eq!(rows[1].accepted, BigDecimal::from(4));
+        assert_eq!(rows[1].settled, BigDecimal::from_str("2.5").unwrap());
+        assert_eq!(rows[2].group, "2024-01-02");
+
+        let from = parse_date("2024-01-02").unwrap();
+        let rows = report(&entries, Some(from), None, GroupBy::Requestor);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].group, "0xa");
+        assert_eq!(rows[0].invoiced, BigDecimal::from(101));
+
+        let rows = report(&entries, Some(from), Some(to), GroupBy::Preset);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].group, "unknown");
+    }
+}
@@ -1,10 +1,10 @@
 pub use task_runner::{
     ActivityDestroyed, CreateActivity, DestroyActivity, GetExeUnit, GetOfferTemplates, Shutdown,
-    TaskRunner, TaskRunnerConfig, TerminateActivity, UpdateActivity,
+    TaskRunner, TaskRunnerConfig, TerminateActivity, UpdateActivity, UpdateRegistry,
 };
 
 pub use self::registry::Configuration;
-pub use self::registry::{ExeUnitDesc, ExeUnitsRegistry};
+pub use self::registry::{matches_file_pattern, ExeUnitDesc, ExeUnitsRegistry};
 pub use self::task_runner::exe_unit_cache_dir;
 pub use self::task_runner::exe_unit_work_dir;
 
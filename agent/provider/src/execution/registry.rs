@@ -141,7 +141,7 @@ impl ExeUnitDesc {
 
 /// Responsible for creating ExeUnits.
 /// Stores registry of ExeUnits that can be created.
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct ExeUnitsRegistry {
     descriptors: HashMap<String, ExeUnitDesc>,
 }
@@ -280,6 +280,28 @@ impl ExeUnitsRegistry {
         self.descriptors.values().cloned().collect()
     }
 
+    /// Names of ExeUnits added, removed or modified in `other` registry.
+    pub fn diff(&self, other: &ExeUnitsRegistry) -> Vec<String> {
+        let mut changed = self
+            .descriptors
+            .iter()
+            .filter(|(name, desc)| match other.descriptors.get(*name) {
+                Some(other) => serde_json::to_value(desc).ok() != serde_json::to_value(other).ok(),
+                None => true,
+            })
+            .map(|(name, _)| name.clone())
+            .chain(
+                other
+                    .descriptors
+                    .keys()
+                    .filter(|name| !self.descriptors.contains_key(*name))
+                    .cloned(),
+            )
+            .collect::<Vec<_>>();
+        changed.sort();
+        changed
+    }
+
     pub fn validate(&self) -> Result<(), RegistryError> {
         let errors = self
             .descriptors
@@ -301,9 +323,23 @@ impl ExeUnitsRegistry {
         if self.descriptors.is_empty() {
             anyhow::bail!("No runtimes available");
         }
+        let names = self.descriptors.keys().cloned().collect::<Vec<_>>();
+        self.test_named_runtimes(data_dir, &names).await
+    }
+
+    /// Tests only given runtimes. Names not found in registry are skipped.
+    pub async fn test_named_runtimes(
+        &self,
+        data_dir: &Path,
+        names: &[String],
+    ) -> anyhow::Result<()> {
         let working_dir = exe_unit_work_dir(data_dir);
         std::fs::create_dir_all(&working_dir)?;
-        for (name, desc) in self.descriptors.iter() {
+        for name in names {
+            let desc = match self.descriptors.get(name) {
+                Some(desc) => desc,
+                None => continue,
+            };
             log::info!("Testing runtime [{}]", name);
             test_runtime(desc, &working_dir)
                 .await
@@ -427,6 +463,37 @@ impl fmt::Display for ExeUnitDesc {
     }
 }
 
+/// Checks if `path` is matched by ExeUnit descriptors `pattern`,
+/// which can contain a single `*` in the file name.
+pub fn matches_file_pattern(pattern: &Path, path: &Path) -> bool {
+    let (pattern, path) = match (normalize_path(pattern), normalize_path(path)) {
+        (Ok(pattern), Ok(path)) => (pattern, path),
+        _ => return false,
+    };
+    if pattern.parent() != path.parent() {
+        return false;
+    }
+    match (
+        pattern.file_name().and_then(|f| f.to_str()),
+        path.file_name().and_then(|f| f.to_str()),
+    ) {
+        (Some(pattern), Some(file_name)) => match pattern.find('*') {
+            Some(pos) => {
+                let (prefix, suffix) = pattern.split_at(pos);
+                file_name_matches(prefix, &suffix[1..], file_name)
+            }
+            None => pattern == file_name,
+        },
+        _ => false,
+    }
+}
+
+fn file_name_matches(prefix: &str, suffix: &str, file_name: &str) -> bool {
+    file_name.len() >= prefix.len() + suffix.len()
+        && file_name.starts_with(prefix)
+        && file_name.ends_with(suffix)
+}
+
 fn expand_filename(pattern: &Path) -> Result<impl IntoIterator<Item = PathBuf>> {
     use std::fs::read_dir;
 
@@ -455,7 +522,7 @@ fn expand_filename(pattern: &Path) -> Result<impl IntoIterator<Item = PathBuf>>
                 let ent = ent.ok()?;
                 let os_file_name = ent.file_name();
                 let file_name = os_file_name.to_str()?;
-                if file_name.starts_with(prefix) && file_name.ends_with(suffix) {
+                if file_name_matches(prefix, suffix, file_name) {
                     Some(ent.path())
                 } else {
                     None
@@ -499,6 +566,42 @@ mod tests {
             .contains("wasm.exe"));
     }
 
+    #[test]
+    fn test_registry_diff() {
+        let mut registry = ExeUnitsRegistry::default();
+        registry
+            .register_exeunits_from_file(&resources_directory().join("example-exeunits.json"))
+            .unwrap();
+
+        let mut other = ExeUnitsRegistry::default();
+        let mut dummy = registry.find_exeunit("dummy").unwrap();
+        dummy.extra_args.push("--verbose".to_string());
+        other.descriptors.insert(dummy.name.clone(), dummy);
+        let mut added = registry.find_exeunit("wasm").unwrap();
+        added.name = "wasm-new".to_string();
+        other.descriptors.insert(added.name.clone(), added);
+
+        assert!(registry.diff(&registry.clone()).is_empty());
+        assert_eq!(registry.diff(&other), vec!["dummy", "wasm", "wasm-new"]);
+    }
+
+    #[test]
+    fn test_matches_file_pattern() {
+        let dir = resources_directory();
+        let pattern = dir.join("ya-*.json");
+        assert!(matches_file_pattern(&pattern, &dir.join("ya-wasi.json")));
+        assert!(matches_file_pattern(&pattern, &dir.join("./ya-vm.json")));
+        assert!(!matches_file_pattern(&pattern, &dir.join("ya-vm.json.swp")));
+        assert!(!matches_file_pattern(&pattern, &dir.join("ya-.jso")));
+        assert!(!matches_file_pattern(&pattern, &dir.join("vm.json")));
+        assert!(!matches_file_pattern(&pattern, &dir.join("sub/ya-vm.json")));
+        assert!(!matches_file_pattern(&pattern, &dir));
+
+        let pattern = dir.join("example-exeunits.json");
+        assert!(matches_file_pattern(&pattern, &pattern));
+        assert!(!matches_file_pattern(&pattern, &dir.join("example.json")));
+    }
+
     #[test]
     fn test_fill_registry_from_local_exe_unit_descriptor() {
         let exe_units_descriptor = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
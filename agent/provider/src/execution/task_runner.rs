@@ -56,6 +56,12 @@ pub struct GetExeUnit {
 #[rtype(result = "Result<HashMap<String, OfferTemplate>>")]
 pub struct GetOfferTemplates(pub Vec<Preset>);
 
+/// Replaces ExeUnits registry used to spawn new tasks. Already running
+/// tasks aren't affected. Returns names of added, removed or modified ExeUnits.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<String>>")]
+pub struct UpdateRegistry(pub ExeUnitsRegistry);
+
 #[derive(Message)]
 #[rtype(result = "Result<()>")]
 pub struct Shutdown;
@@ -224,6 +230,16 @@ impl TaskRunner {
         self.registry.find_exeunit(&msg.name)
     }
 
+    pub fn update_registry(
+        &mut self,
+        msg: UpdateRegistry,
+        _ctx: &mut Context<Self>,
+    ) -> Result<Vec<String>> {
+        let changed = self.registry.diff(&msg.0);
+        self.registry = msg.0;
+        Ok(changed)
+    }
+
     // =========================================== //
     // TaskRunner internals - events dispatching
     // =========================================== //
@@ -620,6 +636,7 @@ impl Actor for TaskRunner {
 forward_actix_handler!(TaskRunner, NewAgreement, on_agreement_approved);
 forward_actix_handler!(TaskRunner, ExeUnitProcessFinished, on_exeunit_exited);
 forward_actix_handler!(TaskRunner, GetExeUnit, get_exeunit);
+forward_actix_handler!(TaskRunner, UpdateRegistry, update_registry);
 actix_signal_handler!(TaskRunner, CreateActivity, activity_created);
 actix_signal_handler!(TaskRunner, ActivityDestroyed, activity_destroyed);
 
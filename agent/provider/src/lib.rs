@@ -1,7 +1,9 @@
+pub mod benchmark;
 pub mod cli;
 pub mod config;
 pub mod dir;
 pub mod display;
+pub mod earnings;
 pub mod events;
 pub mod execution;
 pub mod hardware;
@@ -9,9 +11,11 @@ mod interval;
 pub mod market;
 pub mod payments;
 pub mod provider_agent;
+pub mod reputation;
 pub mod rules;
 pub mod signal;
 pub mod startup_config;
+pub mod status_api;
 pub mod tasks;
 
 pub use config::globals::GlobalsState;
@@ -1,9 +1,9 @@
 use actix::Actor;
 use std::env;
 use structopt::{clap, StructOpt};
-use ya_provider::signal::SignalMonitor;
+use ya_provider::signal::{SignalMonitor, DRAIN_SIGNAL};
 
-use ya_provider::provider_agent::{Initialize, ProviderAgent, Shutdown};
+use ya_provider::provider_agent::{Drain, Initialize, ProviderAgent, Shutdown};
 use ya_provider::startup_config::{Commands, StartupConfig};
 use ya_utils_process::lock::ProcLock;
 
@@ -27,6 +27,9 @@ async fn main() -> anyhow::Result<()> {
     config.presets_file = data_dir.join(config.presets_file);
     config.hardware_file = data_dir.join(config.hardware_file);
     config.rules_file = data_dir.join(config.rules_file);
+    config.reputation_file = data_dir.join(config.reputation_file);
+    config.benchmark_file = data_dir.join(config.benchmark_file);
+    config.agreement_presets_file = data_dir.join(config.agreement_presets_file);
 
     match cli_args.commands {
         Commands::Run(args) => {
@@ -36,7 +39,30 @@ async fn main() -> anyhow::Result<()> {
             agent.send(Initialize).await??;
 
             let signal = SignalMonitor::default().recv().await?;
-            log::info!("{} received, Shutting down {}...", signal, app_name);
+            if signal == DRAIN_SIGNAL {
+                log::info!("{} received, Draining {}...", signal, app_name);
+                let drain = agent.send(Drain);
+                futures::pin_mut!(drain);
+                // Repeated drain requests are ignored, other signals interrupt draining.
+                loop {
+                    tokio::select! {
+                        result = &mut drain => {
+                            result??;
+                            log::info!("Drain finished, Shutting down {}...", app_name);
+                            break;
+                        }
+                        signal = SignalMonitor::default().recv() => match signal? {
+                            DRAIN_SIGNAL => continue,
+                            signal => {
+                                log::info!("{} received, Shutting down {}...", signal, app_name);
+                                break;
+                            }
+                        },
+                    }
+                }
+            } else {
+                log::info!("{} received, Shutting down {}...", signal, app_name);
+            }
 
             agent.send(Shutdown).await??;
 
@@ -51,5 +77,10 @@ async fn main() -> anyhow::Result<()> {
         Commands::Whitelist(whitelist_cmd) => whitelist_cmd.run(config),
         Commands::Clean(clean_cmd) => clean_cmd.run(config),
         Commands::Rule(outbound_cmd) => outbound_cmd.run(config),
+        Commands::Schedule(schedule_cmd) => schedule_cmd.run(config),
+        Commands::Reputation(reputation_cmd) => reputation_cmd.run(config),
+        Commands::Drain(drain_cmd) => drain_cmd.run(config),
+        Commands::Benchmark(benchmark_cmd) => benchmark_cmd.run(config),
+        Commands::Earnings(earnings_cmd) => earnings_cmd.run(config).await,
     }
 }
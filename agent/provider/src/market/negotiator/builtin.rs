@@ -1,14 +1,20 @@
 pub mod demand_validation;
+pub mod drain;
 pub mod expiration;
 pub mod manifest;
 pub mod max_agreements;
 pub mod note_interval;
 pub mod payment_timeout;
 pub mod price;
+pub mod reputation;
+pub mod resources;
 
+pub use drain::Drain;
 pub use expiration::LimitExpiration;
 pub use manifest::ManifestSignature;
 pub use max_agreements::MaxAgreements;
 pub use note_interval::DebitNoteInterval;
 pub use payment_timeout::PaymentTimeout;
 pub use price::PriceNego;
+pub use reputation::RequestorReputation;
+pub use resources::ResourceReservation;
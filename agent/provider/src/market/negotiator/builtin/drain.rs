@@ -0,0 +1,97 @@
+use ya_agreement_utils::OfferDefinition;
+
+use crate::market::negotiator::{
+    AgreementResult, NegotiationResult, NegotiatorComponent, ProposalView,
+};
+use crate::provider_agent::DrainHandle;
+
+/// Negotiator that rejects all Proposals and Agreements, when Provider is draining
+/// before shutdown.
+pub struct Drain {
+    draining: DrainHandle,
+}
+
+impl Drain {
+    pub fn new(draining: DrainHandle) -> Self {
+        Drain { draining }
+    }
+}
+
+impl NegotiatorComponent for Drain {
+    fn negotiate_step(
+        &mut self,
+        demand: &ProposalView,
+        offer: ProposalView,
+    ) -> anyhow::Result<NegotiationResult> {
+        if self.draining.is_draining() {
+            log::info!(
+                "'Drain' negotiator: Reject proposal [{}] from [{}], because Provider is draining.",
+                demand.id,
+                demand.issuer,
+            );
+            return Ok(NegotiationResult::Reject {
+                message: "Provider is shutting down".to_string(),
+                is_final: false,
+            });
+        }
+        Ok(NegotiationResult::Ready { offer })
+    }
+
+    fn fill_template(
+        &mut self,
+        offer_template: OfferDefinition,
+    ) -> anyhow::Result<OfferDefinition> {
+        Ok(offer_template)
+    }
+
+    fn on_agreement_terminated(
+        &mut self,
+        _agreement_id: &str,
+        _result: &AgreementResult,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_agreement_approved(&mut self, _agreement_id: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Utc;
+    use ya_agreement_utils::OfferTemplate;
+    use ya_client_model::market::proposal::State;
+
+    fn proposal() -> ProposalView {
+        ProposalView {
+            content: OfferTemplate {
+                properties: serde_json::json!({}),
+                constraints: "()".to_string(),
+            },
+            id: "proposal-id".to_string(),
+            issuer: "0x979db95461652299c34e15df09441b8dfc4edf7a"
+                .parse()
+                .unwrap(),
+            state: State::Initial,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn rejects_only_when_draining() {
+        let draining = DrainHandle::default();
+        let mut negotiator = Drain::new(draining.clone());
+
+        let result = negotiator.negotiate_step(&proposal(), proposal()).unwrap();
+        assert!(matches!(result, NegotiationResult::Ready { .. }));
+
+        draining.start();
+        match negotiator.negotiate_step(&proposal(), proposal()).unwrap() {
+            NegotiationResult::Reject { is_final, .. } => assert!(!is_final),
+            result => panic!("Expected NegotiationResult::Reject. Got: {:?}", result),
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono::{DateTime, Duration, Local, TimeZone, Utc};
 
 use ya_agreement_utils::{Error, OfferDefinition};
 
+use crate::config::availability::{Availability, ScheduleHandle};
 use crate::display::EnableDisplay;
 use crate::market::negotiator::factory::AgreementExpirationNegotiatorConfig;
 use crate::market::negotiator::{
@@ -12,6 +13,7 @@ use crate::market::negotiator::{
 /// Negotiator that can reject Requestors, that request too long Agreement
 /// expiration time. Expiration limit can be different in case, when Requestor
 /// promises to accept DebitNotes in deadline specified in Agreement.
+/// Agreements are also limited to the end of current availability window.
 pub struct LimitExpiration {
     min_expiration: Duration,
     max_expiration: Duration,
@@ -23,6 +25,8 @@ pub struct LimitExpiration {
 
     /// Minimal DebitNote acceptance timeout.
     min_deadline: i64,
+
+    availability: ScheduleHandle,
 }
 
 pub static DEBIT_NOTE_ACCEPT_TIMEOUT_PROPERTY: &str =
@@ -38,7 +42,10 @@ pub static DEBIT_NOTE_ACCEPT_TIMEOUT_PROPERTY_FLAT: &str =
 pub static AGREEMENT_EXPIRATION_PROPERTY_FLAT: &str = "golem.srv.comp.expiration";
 
 impl LimitExpiration {
-    pub fn new(config: &AgreementExpirationNegotiatorConfig) -> anyhow::Result<LimitExpiration> {
+    pub fn new(
+        config: &AgreementExpirationNegotiatorConfig,
+        availability: ScheduleHandle,
+    ) -> anyhow::Result<LimitExpiration> {
         let component = LimitExpiration {
             min_expiration: chrono::Duration::from_std(config.min_agreement_expiration)?,
             max_expiration: chrono::Duration::from_std(config.max_agreement_expiration)?,
@@ -47,6 +54,7 @@ impl LimitExpiration {
                 config.max_agreement_expiration_without_deadline,
             )?,
             min_deadline: 5,
+            availability,
         };
 
         if component.accept_timeout.num_seconds() < component.min_deadline {
@@ -85,11 +93,13 @@ fn debit_deadline_from(proposal: &ProposalView) -> Result<Option<Duration>> {
     }
 }
 
-impl NegotiatorComponent for LimitExpiration {
-    fn negotiate_step(
+impl LimitExpiration {
+    /// Negotiation step at time `now`
+    fn negotiate_step_at(
         &mut self,
         demand: &ProposalView,
         mut offer: ProposalView,
+        now: DateTime<Utc>,
     ) -> Result<NegotiationResult> {
         let req_deadline = debit_deadline_from(demand)?;
         let our_deadline = debit_deadline_from(&offer)?;
@@ -101,7 +111,6 @@ impl NegotiatorComponent for LimitExpiration {
             None => self.max_expiration_without_deadline,
         };
 
-        let now = Utc::now();
         let max_expiration = now + max_expiration_delta;
         let min_expiration = now + self.min_expiration;
 
@@ -124,6 +133,35 @@ impl NegotiatorComponent for LimitExpiration {
             });
         };
 
+        match self
+            .availability
+            .availability(now.with_timezone(&Local).naive_local())
+        {
+            Availability::Closed => {
+                return Ok(NegotiationResult::Reject {
+                    message: "Provider is outside of its availability window".to_string(),
+                    is_final: false,
+                });
+            }
+            Availability::OpenUntil(end)
+                if req_expiration.with_timezone(&Local).naive_local() > end =>
+            {
+                log::info!(
+                    "Negotiator: Reject proposal [{}] expiring after availability window end.",
+                    demand.id
+                );
+                return Ok(NegotiationResult::Reject {
+                    message: format!(
+                        "Proposal expires at: {} which is after provider's availability window ends at: {}",
+                        req_expiration,
+                        end
+                    ),
+                    is_final: false,
+                });
+            }
+            _ => (),
+        }
+
         // Maybe we negotiated different deadline in previous negotiation iteration?
         Ok(match (req_deadline, our_deadline) {
             // Both Provider and Requestor support DebitNotes acceptance. We must
@@ -177,6 +215,16 @@ impl NegotiatorComponent for LimitExpiration {
             _ => return Err(anyhow!("Shouldn't be in this state.")),
         })
     }
+}
+
+impl NegotiatorComponent for LimitExpiration {
+    fn negotiate_step(
+        &mut self,
+        demand: &ProposalView,
+        offer: ProposalView,
+    ) -> Result<NegotiationResult> {
+        self.negotiate_step_at(demand, offer, Utc::now())
+    }
 
     fn fill_template(&mut self, mut template: OfferDefinition) -> Result<OfferDefinition> {
         template.offer.set_property(
@@ -202,6 +250,7 @@ impl NegotiatorComponent for LimitExpiration {
 #[cfg(test)]
 mod test_expiration_negotiator {
     use super::*;
+    use crate::config::availability::AvailabilitySchedule;
 
     use ya_agreement_utils::agreement::expand;
     use ya_agreement_utils::{InfNodeInfo, NodeInfo, OfferTemplate, ServiceInfo};
@@ -264,7 +313,7 @@ mod test_expiration_negotiator {
     #[test]
     fn test_lower_deadline() {
         let config = expiration_config();
-        let mut negotiator = LimitExpiration::new(&config).unwrap();
+        let mut negotiator = LimitExpiration::new(&config, Default::default()).unwrap();
 
         let offer_proposal = negotiator
             .fill_template(example_offer())
@@ -295,7 +344,7 @@ mod test_expiration_negotiator {
     #[test]
     fn test_greater_deadline() {
         let config = expiration_config();
-        let mut negotiator = LimitExpiration::new(&config).unwrap();
+        let mut negotiator = LimitExpiration::new(&config, Default::default()).unwrap();
 
         let offer_proposal = negotiator
             .fill_template(example_offer())
@@ -324,7 +373,7 @@ mod test_expiration_negotiator {
     #[test]
     fn test_equal_deadline() {
         let config = expiration_config();
-        let mut negotiator = LimitExpiration::new(&config).unwrap();
+        let mut negotiator = LimitExpiration::new(&config, Default::default()).unwrap();
 
         let offer_proposal = negotiator
             .fill_template(example_offer())
@@ -357,7 +406,7 @@ mod test_expiration_negotiator {
     #[test]
     fn test_requestor_doesnt_accept_debit_notes_to_high_expiration() {
         let config = expiration_config();
-        let mut negotiator = LimitExpiration::new(&config).unwrap();
+        let mut negotiator = LimitExpiration::new(&config, Default::default()).unwrap();
 
         let offer_proposal = negotiator
             .fill_template(example_offer())
@@ -387,7 +436,7 @@ mod test_expiration_negotiator {
     #[test]
     fn test_requestor_doesnt_accept_debit_notes_expiration_ok() {
         let config = expiration_config();
-        let mut negotiator = LimitExpiration::new(&config).unwrap();
+        let mut negotiator = LimitExpiration::new(&config, Default::default()).unwrap();
 
         let offer_proposal = negotiator
             .fill_template(example_offer())
@@ -408,4 +457,39 @@ mod test_expiration_negotiator {
             result => panic!("Expected NegotiationResult::Negotiating. Got: {:?}", result),
         }
     }
+
+    /// Provider rejects Proposals expiring after end of its availability window.
+    #[test]
+    fn test_expiration_after_availability_window() {
+        let config = expiration_config();
+        let now = Local
+            .with_ymd_and_hms(2024, 1, 15, 12, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        let availability = ScheduleHandle::new(Some(AvailabilitySchedule {
+            windows: vec!["* 11:00-12:10".parse().unwrap()],
+        }));
+        let mut negotiator = LimitExpiration::new(&config, availability).unwrap();
+
+        let offer_proposal = negotiator
+            .fill_template(example_offer())
+            .unwrap()
+            .to_proposal();
+
+        let proposal = properties_to_proposal(serde_json::json!({
+            AGREEMENT_EXPIRATION_PROPERTY_FLAT: (now + Duration::minutes(15)).timestamp_millis(),
+            DEBIT_NOTE_ACCEPT_TIMEOUT_PROPERTY_FLAT: 120,
+        }));
+
+        match negotiator
+            .negotiate_step_at(&proposal, offer_proposal, now)
+            .unwrap()
+        {
+            NegotiationResult::Reject { message, is_final } => {
+                assert!(message.contains("availability window"));
+                assert!(!is_final)
+            }
+            result => panic!("Expected NegotiationResult::Reject. Got: {:?}", result),
+        }
+    }
 }
@@ -164,6 +164,10 @@ mod tests {
                         &cert_dir,
                     )
                     .unwrap(),
+                    availability: Default::default(),
+                    reputation: Default::default(),
+                    draining: Default::default(),
+                    reservations: Default::default(),
                 },
             ),
             tempdir,
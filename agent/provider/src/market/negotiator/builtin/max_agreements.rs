@@ -11,19 +11,28 @@ use crate::market::negotiator::{
 /// Negotiator that can limit number of running agreements.
 pub struct MaxAgreements {
     active_agreements: HashSet<String>,
-    max_agreements: u32,
+    /// No limit, when concurrency is bounded by resource reservations
+    max_agreements: Option<u32>,
 }
 
 impl MaxAgreements {
-    pub fn new(config: &LimitAgreementsNegotiatorConfig) -> MaxAgreements {
+    pub fn new(config: &LimitAgreementsNegotiatorConfig, reserve_resources: bool) -> MaxAgreements {
+        let max_agreements = match config.max_simultaneous_agreements {
+            Some(max_agreements) => Some(max_agreements),
+            None if reserve_resources => None,
+            None => Some(1),
+        };
         MaxAgreements {
-            max_agreements: config.max_simultaneous_agreements,
+            max_agreements,
             active_agreements: HashSet::new(),
         }
     }
 
     pub fn has_free_slot(&self) -> bool {
-        self.active_agreements.len() < self.max_agreements as usize
+        match self.max_agreements {
+            Some(max_agreements) => self.active_agreements.len() < max_agreements as usize,
+            None => true,
+        }
     }
 }
 
@@ -43,7 +52,7 @@ impl NegotiatorComponent for MaxAgreements {
             Ok(NegotiationResult::Reject {
                 message: format!(
                     "No capacity available. Reached Agreements limit: {}",
-                    self.max_agreements
+                    self.max_agreements.unwrap_or_default()
                 ),
                 is_final: false,
             })
@@ -64,8 +73,10 @@ impl NegotiatorComponent for MaxAgreements {
     ) -> anyhow::Result<()> {
         self.active_agreements.remove(agreement_id);
 
-        let free_slots = self.max_agreements as usize - self.active_agreements.len();
-        log::info!("Negotiator: {} free slot(s) for agreements.", free_slots);
+        if let Some(max_agreements) = self.max_agreements {
+            let free_slots = (max_agreements as usize).saturating_sub(self.active_agreements.len());
+            log::info!("Negotiator: {} free slot(s) for agreements.", free_slots);
+        }
         Ok(())
     }
 
@@ -82,3 +93,27 @@ impl NegotiatorComponent for MaxAgreements {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn max_agreements(max: Option<u32>, reserve_resources: bool) -> MaxAgreements {
+        let config = LimitAgreementsNegotiatorConfig {
+            max_simultaneous_agreements: max,
+        };
+        let mut negotiator = MaxAgreements::new(&config, reserve_resources);
+        for id in ["first", "second"] {
+            negotiator.on_agreement_approved(id).ok();
+        }
+        negotiator
+    }
+
+    #[test]
+    fn reservations_bound_agreements_by_default() {
+        assert!(!max_agreements(None, false).has_free_slot());
+        assert!(max_agreements(None, true).has_free_slot());
+        // explicit limit applies also with reservations
+        assert!(!max_agreements(Some(2), true).has_free_slot());
+    }
+}
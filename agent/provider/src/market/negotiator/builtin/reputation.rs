@@ -0,0 +1,205 @@
+use serde_json::json;
+
+use ya_agreement_utils::OfferDefinition;
+
+use crate::market::negotiator::factory::RequestorReputationConfig;
+use crate::market::negotiator::{
+    AgreementResult, NegotiationResult, NegotiatorComponent, ProposalView,
+};
+use crate::reputation::ReputationStore;
+
+static PRICE_PROPERTY: &str = "/golem/com/pricing/model/linear/coeffs";
+static PRICING_PROPERTY: &str = "/golem/com/pricing";
+static MARKUP_KEY: &str = "markup";
+
+/// Negotiator that rejects Requestors with bad payment history
+/// and charges more to Requestors with poor one.
+pub struct RequestorReputation {
+    reputation: ReputationStore,
+    min_score: f64,
+    markup_score: f64,
+    markup: f64,
+}
+
+impl RequestorReputation {
+    pub fn new(config: &RequestorReputationConfig, reputation: ReputationStore) -> Self {
+        RequestorReputation {
+            reputation,
+            min_score: config.min_requestor_score,
+            markup_score: config.markup_requestor_score,
+            markup: config.requestor_price_markup,
+        }
+    }
+
+    /// Raises linear pricing coefficients once per negotiation. Marker property
+    /// prevents applying the markup again to our own counter-proposals.
+    fn apply_markup(&self, offer: &mut ProposalView) -> bool {
+        let prices = match offer.pointer_typed::<Vec<f64>>(PRICE_PROPERTY) {
+            Ok(prices) => prices,
+            Err(_) => return false,
+        };
+        let multiplier = 1.0 + self.markup;
+        if let Some(pricing) = offer
+            .pointer_mut(PRICING_PROPERTY)
+            .and_then(|pricing| pricing.as_object_mut())
+        {
+            if pricing.contains_key(MARKUP_KEY) {
+                return false;
+            }
+            pricing.insert(MARKUP_KEY.to_string(), json!(multiplier));
+        }
+        if let Some(coeffs) = offer.pointer_mut(PRICE_PROPERTY) {
+            *coeffs = json!(prices.iter().map(|p| p * multiplier).collect::<Vec<_>>());
+        }
+        true
+    }
+}
+
+impl NegotiatorComponent for RequestorReputation {
+    fn negotiate_step(
+        &mut self,
+        demand: &ProposalView,
+        mut offer: ProposalView,
+    ) -> anyhow::Result<NegotiationResult> {
+        let score = self.reputation.score(&demand.issuer);
+
+        if score < self.min_score {
+            log::info!(
+                "'RequestorReputation' negotiator: Reject proposal [{}] from [{}] with reputation score {:.2}.",
+                demand.id,
+                demand.issuer,
+                score
+            );
+            return Ok(NegotiationResult::Reject {
+                message: format!(
+                    "Requestor reputation score {:.2} is below {:.2}",
+                    score, self.min_score
+                ),
+                is_final: true,
+            });
+        }
+
+        if score < self.markup_score && self.apply_markup(&mut offer) {
+            log::info!(
+                "'RequestorReputation' negotiator: Raising prices by {:.0}% for [{}] with reputation score {:.2}.",
+                self.markup * 100.0,
+                demand.issuer,
+                score
+            );
+            return Ok(NegotiationResult::Negotiating { offer });
+        }
+
+        Ok(NegotiationResult::Ready { offer })
+    }
+
+    fn fill_template(
+        &mut self,
+        offer_template: OfferDefinition,
+    ) -> anyhow::Result<OfferDefinition> {
+        Ok(offer_template)
+    }
+
+    fn on_agreement_terminated(
+        &mut self,
+        _agreement_id: &str,
+        _result: &AgreementResult,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_agreement_approved(&mut self, _agreement_id: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Utc;
+    use ya_agreement_utils::agreement::expand;
+    use ya_agreement_utils::OfferTemplate;
+    use ya_client_model::market::proposal::State;
+    use ya_client_model::NodeId;
+
+    use crate::reputation::RequestorEvent;
+
+    fn requestor() -> NodeId {
+        "0x979db95461652299c34e15df09441b8dfc4edf7a"
+            .parse()
+            .unwrap()
+    }
+
+    fn proposal(properties: serde_json::Value) -> ProposalView {
+        ProposalView {
+            content: OfferTemplate {
+                properties: expand(properties),
+                constraints: "()".to_string(),
+            },
+            id: "proposal-id".to_string(),
+            issuer: requestor(),
+            state: State::Initial,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn offer() -> ProposalView {
+        proposal(json!({
+            "golem.com.pricing.model": "linear",
+            "golem.com.pricing.model.linear.coeffs": [0.1, 0.2, 1.0],
+        }))
+    }
+
+    fn negotiator(bad_events: usize) -> RequestorReputation {
+        let reputation = ReputationStore::default();
+        for _ in 0..bad_events {
+            reputation.record(requestor(), RequestorEvent::DebitNoteLate);
+        }
+        let config = RequestorReputationConfig {
+            min_requestor_score: 0.3,
+            markup_requestor_score: 0.6,
+            requestor_price_markup: 0.5,
+        };
+        RequestorReputation::new(&config, reputation)
+    }
+
+    #[test]
+    fn good_requestor_is_ready() {
+        let result = negotiator(0)
+            .negotiate_step(&proposal(json!({})), offer())
+            .unwrap();
+        assert!(matches!(result, NegotiationResult::Ready { .. }));
+    }
+
+    #[test]
+    fn bad_requestor_is_rejected() {
+        let result = negotiator(3)
+            .negotiate_step(&proposal(json!({})), offer())
+            .unwrap();
+        match result {
+            NegotiationResult::Reject { is_final, .. } => assert!(is_final),
+            result => panic!("Expected NegotiationResult::Reject. Got: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn poor_requestor_pays_markup_once() {
+        let mut negotiator = negotiator(1);
+        let demand = proposal(json!({}));
+
+        let offer = match negotiator.negotiate_step(&demand, offer()).unwrap() {
+            NegotiationResult::Negotiating { offer } => offer,
+            result => panic!("Expected NegotiationResult::Negotiating. Got: {:?}", result),
+        };
+        let prices: Vec<f64> = offer.pointer_typed(PRICE_PROPERTY).unwrap();
+        assert_eq!(prices, vec![0.1 * 1.5, 0.2 * 1.5, 1.5]);
+
+        match negotiator.negotiate_step(&demand, offer).unwrap() {
+            NegotiationResult::Ready { offer } => {
+                let prices: Vec<f64> = offer.pointer_typed(PRICE_PROPERTY).unwrap();
+                assert_eq!(prices, vec![0.1 * 1.5, 0.2 * 1.5, 1.5]);
+            }
+            result => panic!("Expected NegotiationResult::Ready. Got: {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::bail;
+
+use ya_agreement_utils::OfferDefinition;
+
+use crate::hardware::Resources;
+use crate::market::negotiator::{
+    AgreementResult, NegotiationResult, NegotiatorComponent, ProposalView,
+};
+use crate::tasks::reservations::{requested_resources, Reservations};
+
+/// Negotiator that accepts only Demands fitting into resources
+/// not reserved by running Agreements, and reserves them on approval.
+pub struct ResourceReservation {
+    reservations: Reservations,
+    /// Resources requested by the last negotiated Demand. Agreement is approved
+    /// right after the negotiation step of its Demand, within the same message.
+    requested: Option<Resources>,
+}
+
+impl ResourceReservation {
+    pub fn new(reservations: Reservations) -> Self {
+        ResourceReservation {
+            reservations,
+            requested: None,
+        }
+    }
+}
+
+impl NegotiatorComponent for ResourceReservation {
+    fn negotiate_step(
+        &mut self,
+        demand: &ProposalView,
+        offer: ProposalView,
+    ) -> anyhow::Result<NegotiationResult> {
+        let requested = requested_resources(&demand.content.constraints);
+        self.requested = Some(requested);
+        if self.reservations.fits(&requested) {
+            return Ok(NegotiationResult::Ready { offer });
+        }
+
+        let remaining = self.reservations.remaining();
+        log::info!(
+            "'ResourceReservation' negotiator: Reject proposal [{}] requesting {:?}. Remaining: {:?}",
+            demand.id,
+            requested,
+            remaining
+        );
+        Ok(NegotiationResult::Reject {
+            message: format!(
+                "Requested resources exceed remaining capacity: {} threads, {:.2} GiB memory, {:.2} GiB storage",
+                remaining.cpu_threads, remaining.mem_gib, remaining.storage_gib
+            ),
+            is_final: false,
+        })
+    }
+
+    fn fill_template(
+        &mut self,
+        offer_template: OfferDefinition,
+    ) -> anyhow::Result<OfferDefinition> {
+        Ok(offer_template)
+    }
+
+    fn on_agreement_terminated(
+        &mut self,
+        agreement_id: &str,
+        _result: &AgreementResult,
+    ) -> anyhow::Result<()> {
+        self.reservations.release(agreement_id);
+        Ok(())
+    }
+
+    fn on_agreement_approved(&mut self, agreement_id: &str) -> anyhow::Result<()> {
+        let requested = match self.requested.take() {
+            Some(requested) => requested,
+            None => bail!("Agreement [{}] approved without negotiation.", agreement_id),
+        };
+        if !self.reservations.try_reserve(agreement_id, requested) {
+            bail!(
+                "Agreement [{}] approved despite not available resources.",
+                agreement_id
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Utc;
+    use ya_agreement_utils::OfferTemplate;
+    use ya_client_model::market::proposal::State;
+
+    use crate::tasks::reservations::ReservationsConfig;
+
+    fn proposal(constraints: &str) -> ProposalView {
+        ProposalView {
+            content: OfferTemplate {
+                properties: serde_json::json!({}),
+                constraints: constraints.to_string(),
+            },
+            id: "proposal-id".to_string(),
+            issuer: "0x979db95461652299c34e15df09441b8dfc4edf7a"
+                .parse()
+                .unwrap(),
+            state: State::Initial,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn is_ready(negotiator: &mut ResourceReservation, demand: &ProposalView) -> bool {
+        let offer = proposal("()");
+        match negotiator.negotiate_step(demand, offer).unwrap() {
+            NegotiationResult::Ready { .. } => true,
+            NegotiationResult::Reject { is_final, .. } => {
+                assert!(!is_final);
+                false
+            }
+            result => panic!("Unexpected negotiation result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn reserve_on_approval() {
+        let config = ReservationsConfig {
+            reserve_resources: true,
+            overcommit_ratio: 1.0,
+        };
+        let capacity = Resources {
+            cpu_threads: 4,
+            mem_gib: 8.,
+            storage_gib: 100.,
+        };
+        let reservations = Reservations::new(&config, capacity);
+        let mut negotiator = ResourceReservation::new(reservations.clone());
+        let demand = proposal("(&(golem.inf.cpu.threads>=3))");
+
+        // both Demands fit, until the first Agreement is approved
+        assert!(is_ready(&mut negotiator, &demand));
+        assert!(is_ready(&mut negotiator, &demand));
+        negotiator.on_agreement_approved("first").unwrap();
+        assert_eq!(reservations.remaining().cpu_threads, 1);
+
+        assert!(!is_ready(&mut negotiator, &demand));
+        assert!(negotiator.on_agreement_approved("second").is_err());
+        assert_eq!(reservations.remaining().cpu_threads, 1);
+
+        negotiator
+            .on_agreement_terminated("first", &AgreementResult::ApprovalFailed)
+            .unwrap();
+        assert!(is_ready(&mut negotiator, &demand));
+    }
+}
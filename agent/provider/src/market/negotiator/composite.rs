@@ -9,9 +9,11 @@ use ya_client::model::market::NewOffer;
 use ya_client_model::market::proposal::State;
 
 use super::builtin::{
-    DebitNoteInterval, LimitExpiration, ManifestSignature, MaxAgreements, PaymentTimeout,
+    DebitNoteInterval, Drain, LimitExpiration, ManifestSignature, MaxAgreements, PaymentTimeout,
+    RequestorReputation, ResourceReservation,
 };
 use super::common::{offer_definition_to_offer, AgreementResponse, Negotiator, ProposalResponse};
+use super::external::{ExternalComponentsFile, ExternalNegotiator};
 use super::{NegotiationResult, NegotiatorsPack};
 use crate::market::negotiator::builtin::demand_validation::DemandValidation;
 use crate::market::negotiator::builtin::PriceNego;
@@ -34,18 +36,34 @@ impl CompositeNegotiator {
         config: &CompositeNegotiatorConfig,
         agent_negotiators_cfg: AgentNegotiatorsConfig,
     ) -> anyhow::Result<CompositeNegotiator> {
-        let components = NegotiatorsPack::default()
+        let mut components = NegotiatorsPack::default()
+            .add_component(
+                "Drain",
+                Box::new(Drain::new(agent_negotiators_cfg.draining.clone())),
+            )
             .add_component(
                 "Validation",
                 Box::new(DemandValidation::new(&config.validation_config)),
             )
             .add_component(
                 "LimitAgreements",
-                Box::new(MaxAgreements::new(&config.limit_agreements_config)),
+                Box::new(MaxAgreements::new(
+                    &config.limit_agreements_config,
+                    agent_negotiators_cfg.reservations.enabled(),
+                )),
+            )
+            .add_component(
+                "ResourceReservation",
+                Box::new(ResourceReservation::new(
+                    agent_negotiators_cfg.reservations.clone(),
+                )),
             )
             .add_component(
                 "LimitExpiration",
-                Box::new(LimitExpiration::new(&config.expire_agreements_config)?),
+                Box::new(LimitExpiration::new(
+                    &config.expire_agreements_config,
+                    agent_negotiators_cfg.availability.clone(),
+                )?),
             )
             .add_component(
                 "DebitNoteInterval",
@@ -55,6 +73,13 @@ impl CompositeNegotiator {
                 "PaymentTimeout",
                 Box::new(PaymentTimeout::new(&config.payment_timeout_config)?),
             )
+            .add_component(
+                "RequestorReputation",
+                Box::new(RequestorReputation::new(
+                    &config.reputation_config,
+                    agent_negotiators_cfg.reputation.clone(),
+                )),
+            )
             .add_component(
                 "ManifestSignature",
                 Box::new(ManifestSignature::new(
@@ -67,6 +92,16 @@ impl CompositeNegotiator {
                 Box::new(PriceNego::new(&config.expire_agreements_config)?),
             );
 
+        if let Some(path) = &config.external_config.negotiators_file {
+            for component in ExternalComponentsFile::load(path)?.components {
+                log::info!("Using external negotiator component '{}'", component.name);
+                components = components.add_component(
+                    &component.name,
+                    Box::new(ExternalNegotiator::new(&component)?),
+                );
+            }
+        }
+
         Ok(CompositeNegotiator { components })
     }
 }
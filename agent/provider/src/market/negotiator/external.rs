@@ -0,0 +1,457 @@
+//! `NegotiatorComponent` implemented by an external process.
+//!
+//! Components communicate over newline delimited JSON-RPC 2.0 messages, either through
+//! stdin / stdout of a process started by the Provider, or through a Unix socket of
+//! an already running process. Each `NegotiatorComponent` function is a method:
+//!
+//! - `negotiate_step` with params `{ "demand": ProposalView, "offer": ProposalView }`,
+//!   returning `NegotiationResult`,
+//! - `fill_template` with params `{ "template": OfferTemplate }`, returning `OfferTemplate`,
+//! - `on_agreement_terminated` with params `{ "agreement_id": String, "result": Termination }`,
+//! - `on_agreement_approved` with params `{ "agreement_id": String }`.
+//!
+//! Calls which time out or break the protocol close the connection (and kill the process).
+//! It is re-established on the first call after `RECONNECT_DELAY`.
+//!
+//! Calls are blocking, so the negotiator using external components runs on its own arbiter.
+use anyhow::{anyhow, bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use ya_agreement_utils::{OfferDefinition, OfferTemplate};
+
+use crate::market::negotiator::{
+    AgreementResult, NegotiationResult, NegotiatorComponent, ProposalView,
+};
+
+const JSONRPC_VERSION: &str = "2.0";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// File describing external negotiator components
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ExternalComponentsFile {
+    pub components: Vec<ExternalComponentConfig>,
+}
+
+impl ExternalComponentsFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Can't read negotiators file {}", path.display()))?;
+        let file: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid negotiators file {}", path.display()))?;
+
+        let mut names = HashSet::new();
+        if let Some(config) = file.components.iter().find(|c| !names.insert(&c.name)) {
+            bail!("Negotiator component '{}' defined twice", config.name);
+        }
+        Ok(file)
+    }
+}
+
+/// External negotiator component. Components named like a built-in one replace it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExternalComponentConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub transport: Transport,
+    /// Call timeout, e.g. `3s` or `500ms`
+    #[serde(default)]
+    pub timeout: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Transport {
+    /// Process started by the Provider, communicating through stdin and stdout
+    Process {
+        command: PathBuf,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// Process listening on a Unix socket
+    Socket { socket: PathBuf },
+}
+
+/// Agreement termination result sent to external components
+#[derive(Clone, Debug, Serialize)]
+pub enum Termination {
+    ApprovalFailed,
+    ClosedByUs,
+    ClosedByRequestor,
+    Broken { reason: String },
+}
+
+impl From<&AgreementResult> for Termination {
+    fn from(result: &AgreementResult) -> Self {
+        match result {
+            AgreementResult::ApprovalFailed => Termination::ApprovalFailed,
+            AgreementResult::ClosedByUs => Termination::ClosedByUs,
+            AgreementResult::ClosedByRequestor => Termination::ClosedByRequestor,
+            AgreementResult::Broken { reason } => Termination::Broken {
+                reason: reason.to_string(),
+            },
+        }
+    }
+}
+
+pub struct ExternalNegotiator {
+    name: String,
+    transport: Transport,
+    timeout: Duration,
+    connection: Option<Connection>,
+    /// Time of the last connection failure
+    failed_at: Option<Instant>,
+    next_id: u64,
+}
+
+impl ExternalNegotiator {
+    pub fn new(config: &ExternalComponentConfig) -> Result<ExternalNegotiator> {
+        let timeout = match &config.timeout {
+            Some(timeout) => humantime::parse_duration(timeout).with_context(|| {
+                format!("Invalid timeout of negotiator component '{}'", config.name)
+            })?,
+            None => DEFAULT_TIMEOUT,
+        };
+
+        Ok(ExternalNegotiator {
+            name: config.name.clone(),
+            transport: config.transport.clone(),
+            timeout,
+            connection: None,
+            failed_at: None,
+            next_id: 0,
+        })
+    }
+
+    fn call<T: DeserializeOwned>(&mut self, method: &str, params: Value) -> Result<T> {
+        let result = self.request(method, params)?;
+        serde_json::from_value(result)
+            .map_err(|e| anyhow!("Invalid '{method}' result of '{}': {e}", self.name))
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        if self.connection.is_none() {
+            if let Some(failed_at) = self.failed_at {
+                if failed_at.elapsed() < RECONNECT_DELAY {
+                    bail!("Negotiator component '{}' is unavailable", self.name);
+                }
+            }
+            let connection = Connection::open(&self.name, &self.transport).map_err(|e| {
+                self.failed_at = Some(Instant::now());
+                anyhow!("Can't start negotiator component '{}': {e}", self.name)
+            })?;
+            self.connection = Some(connection);
+        }
+
+        self.next_id += 1;
+        let request = Request {
+            jsonrpc: JSONRPC_VERSION,
+            id: self.next_id,
+            method,
+            params,
+        };
+
+        let connection = self.connection.as_mut().expect("connection is open");
+        match connection.request(&request, self.timeout) {
+            Ok(response) => match response.error {
+                Some(error) => Err(anyhow!(
+                    "Negotiator component '{}' '{method}' error {}: {}",
+                    self.name,
+                    error.code,
+                    error.message
+                )),
+                None => Ok(response.result),
+            },
+            Err(e) => {
+                log::warn!(
+                    "Negotiator component '{}' failed. Closing connection. {e}",
+                    self.name
+                );
+                self.connection = None;
+                self.failed_at = Some(Instant::now());
+                Err(e)
+            }
+        }
+    }
+}
+
+impl NegotiatorComponent for ExternalNegotiator {
+    fn negotiate_step(
+        &mut self,
+        demand: &ProposalView,
+        offer: ProposalView,
+    ) -> Result<NegotiationResult> {
+        self.call(
+            "negotiate_step",
+            json!({ "demand": demand, "offer": offer }),
+        )
+    }
+
+    fn fill_template(&mut self, mut offer_template: OfferDefinition) -> Result<OfferDefinition> {
+        offer_template.offer = self
+            .call::<OfferTemplate>("fill_template", json!({ "template": offer_template.offer }))?;
+        Ok(offer_template)
+    }
+
+    fn on_agreement_terminated(
+        &mut self,
+        agreement_id: &str,
+        result: &AgreementResult,
+    ) -> Result<()> {
+        self.call(
+            "on_agreement_terminated",
+            json!({ "agreement_id": agreement_id, "result": Termination::from(result) }),
+        )
+    }
+
+    fn on_agreement_approved(&mut self, agreement_id: &str) -> Result<()> {
+        self.call(
+            "on_agreement_approved",
+            json!({ "agreement_id": agreement_id }),
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: Option<u64>,
+    #[serde(default)]
+    result: Value,
+    #[serde(default)]
+    error: Option<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+struct Connection {
+    /// Lines sent to the component, written on a separate thread
+    writes: mpsc::Sender<Vec<u8>>,
+    /// Results of writes, so they can be bounded by the call timeout
+    written: mpsc::Receiver<std::io::Result<()>>,
+    /// Lines received from the component, read on a separate thread
+    lines: mpsc::Receiver<String>,
+    child: Option<Child>,
+    #[cfg(unix)]
+    socket: Option<std::os::unix::net::UnixStream>,
+}
+
+impl Connection {
+    fn open(name: &str, transport: &Transport) -> Result<Connection> {
+        match transport {
+            Transport::Process { command, args } => {
+                let mut child = Command::new(command)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+
+                let writer = child.stdin.take().ok_or_else(|| anyhow!("no stdin"))?;
+                let reader = child.stdout.take().ok_or_else(|| anyhow!("no stdout"))?;
+                if let Some(stderr) = child.stderr.take() {
+                    let name = name.to_string();
+                    std::thread::spawn(move || {
+                        BufReader::new(stderr)
+                            .lines()
+                            .map_while(|line| line.ok())
+                            .for_each(|line| log::debug!("[{name}] {line}"));
+                    });
+                }
+
+                let (writes, written) = spawn_writer(writer);
+                Ok(Connection {
+                    writes,
+                    written,
+                    lines: spawn_reader(reader),
+                    child: Some(child),
+                    #[cfg(unix)]
+                    socket: None,
+                })
+            }
+            #[cfg(unix)]
+            Transport::Socket { socket } => {
+                let stream = std::os::unix::net::UnixStream::connect(socket)?;
+                let (writes, written) = spawn_writer(stream.try_clone()?);
+                Ok(Connection {
+                    writes,
+                    written,
+                    lines: spawn_reader(stream.try_clone()?),
+                    child: None,
+                    socket: Some(stream),
+                })
+            }
+            #[cfg(not(unix))]
+            Transport::Socket { .. } => bail!("Unix sockets are not supported on this platform"),
+        }
+    }
+
+    fn request(&mut self, request: &Request, timeout: Duration) -> Result<Response> {
+        let timed_out = || anyhow!("'{}' timed out after {:?}", request.method, timeout);
+        let deadline = Instant::now() + timeout;
+
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        self.writes
+            .send(line)
+            .map_err(|_| anyhow!("connection closed"))?;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        self.written.recv_timeout(remaining).map_err(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => timed_out(),
+            mpsc::RecvTimeoutError::Disconnected => anyhow!("connection closed"),
+        })??;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let line = self.lines.recv_timeout(remaining).map_err(|e| match e {
+                mpsc::RecvTimeoutError::Timeout => timed_out(),
+                mpsc::RecvTimeoutError::Disconnected => anyhow!("connection closed"),
+            })?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response: Response = serde_json::from_str(&line)
+                .map_err(|e| anyhow!("invalid response '{line}': {e}"))?;
+            match response.id {
+                Some(id) if id == request.id => return Ok(response),
+                _ => log::debug!("Ignoring unexpected response: {line}"),
+            }
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Unblocks the reader and writer threads stuck on a socket
+        #[cfg(unix)]
+        if let Some(socket) = self.socket.take() {
+            let _ = socket.shutdown(std::net::Shutdown::Both);
+        }
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn spawn_writer<W: Write + Send + 'static>(
+    mut writer: W,
+) -> (mpsc::Sender<Vec<u8>>, mpsc::Receiver<std::io::Result<()>>) {
+    let (tx, rx) = mpsc::channel::<Vec<u8>>();
+    let (result_tx, result_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in rx {
+            let result = writer.write_all(&line).and_then(|_| writer.flush());
+            let failed = result.is_err();
+            if result_tx.send(result).is_err() || failed {
+                break;
+            }
+        }
+    });
+    (tx, result_rx)
+}
+
+fn spawn_reader<R: std::io::Read + Send + 'static>(reader: R) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(|line| line.ok()) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Component implemented by a shell script
+    fn component(script: &str, timeout: &str) -> ExternalNegotiator {
+        ExternalNegotiator::new(&ExternalComponentConfig {
+            name: "Test".to_string(),
+            transport: Transport::Process {
+                command: "sh".into(),
+                args: vec!["-c".to_string(), script.to_string()],
+            },
+            timeout: Some(timeout.to_string()),
+        })
+        .unwrap()
+    }
+
+    const REPLY: &str = r#"while read line; do
+        id=$(echo "$line" | sed 's/^{"jsonrpc":"2.0","id":\([0-9]*\),.*/\1/')
+        echo "{\"jsonrpc\":\"2.0\",\"id\":$id,$RESPONSE}"
+    done"#;
+
+    #[test]
+    fn call_external_component() {
+        let mut nego = component(&format!("RESPONSE='\"result\":null'; {REPLY}"), "5s");
+        nego.on_agreement_approved("agreement").unwrap();
+        nego.on_agreement_terminated("agreement", &AgreementResult::ClosedByUs)
+            .unwrap();
+    }
+
+    #[test]
+    fn external_component_error() {
+        let mut nego = component(
+            &format!(r#"RESPONSE='"error":{{"code":-1,"message":"not today"}}'; {REPLY}"#),
+            "5s",
+        );
+        let error = nego.on_agreement_approved("agreement").unwrap_err();
+        assert!(error.to_string().contains("not today"));
+        // component errors keep the connection open
+        assert!(nego.connection.is_some());
+    }
+
+    #[test]
+    fn external_component_timeout() {
+        let mut nego = component("sleep 10", "200ms");
+        assert!(nego.on_agreement_approved("agreement").is_err());
+        assert!(nego.connection.is_none());
+        // not restarted before the reconnect delay
+        let error = nego.on_agreement_approved("agreement").unwrap_err();
+        assert!(error.to_string().contains("unavailable"));
+    }
+
+    #[test]
+    fn external_component_write_timeout() {
+        // Never reads stdin, so the request doesn't fit into the pipe buffer
+        let mut nego = component("sleep 10", "200ms");
+        let started = Instant::now();
+        let error = nego
+            .request("fill_template", json!("x".repeat(1 << 20)))
+            .unwrap_err();
+        assert!(error.to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(nego.connection.is_none());
+    }
+
+    #[test]
+    fn external_component_crash() {
+        let mut nego = component("exit 1", "5s");
+        assert!(nego.on_agreement_approved("agreement").is_err());
+        assert!(nego.connection.is_none());
+    }
+}
@@ -1,5 +1,6 @@
-use actix::Addr;
+use actix::{Addr, Arbiter};
 use humantime;
+use std::path::PathBuf;
 use std::sync::Arc;
 use structopt::StructOpt;
 
@@ -21,8 +22,10 @@ pub struct DemandValidationNegotiatorConfig {
 /// Configuration for LimitAgreements Negotiator.
 #[derive(StructOpt, Clone, Debug)]
 pub struct LimitAgreementsNegotiatorConfig {
-    #[structopt(long, env, default_value = "1")]
-    pub max_simultaneous_agreements: u32,
+    /// Max number of Agreements running at once. Defaults to 1, unless
+    /// `--reserve-resources` is set, in which case reserved resources limit Agreements
+    #[structopt(long, env)]
+    pub max_simultaneous_agreements: Option<u32>,
 }
 
 /// Configuration for LimitAgreements Negotiator.
@@ -62,6 +65,28 @@ pub struct PaymentTimeoutConfig {
     pub payment_timeout_required_duration: std::time::Duration,
 }
 
+/// Configuration for RequestorReputation negotiator
+#[derive(StructOpt, Clone, Debug)]
+pub struct RequestorReputationConfig {
+    /// Reject Demands from Requestors with reputation score (0-1) below this value
+    #[structopt(long, env, default_value = "0.0")]
+    pub min_requestor_score: f64,
+    /// Raise prices for Requestors with reputation score (0-1) below this value
+    #[structopt(long, env, default_value = "0.0")]
+    pub markup_requestor_score: f64,
+    /// Price increase for Requestors with low reputation score (0.5 means +50%)
+    #[structopt(long, env, default_value = "0.5")]
+    pub requestor_price_markup: f64,
+}
+
+/// Configuration for negotiator components running in external processes
+#[derive(StructOpt, Clone, Debug)]
+pub struct ExternalNegotiatorsConfig {
+    /// Descriptor file (JSON) for external negotiator components
+    #[structopt(long, env)]
+    pub negotiators_file: Option<PathBuf>,
+}
+
 /// Configuration for LimitAgreements Negotiator.
 #[derive(StructOpt, Clone, Debug)]
 pub struct CompositeNegotiatorConfig {
@@ -77,6 +102,10 @@ pub struct CompositeNegotiatorConfig {
     pub payment_timeout_config: PaymentTimeoutConfig,
     #[structopt(flatten)]
     pub policy_config: PolicyConfig,
+    #[structopt(flatten)]
+    pub reputation_config: RequestorReputationConfig,
+    #[structopt(flatten)]
+    pub external_config: ExternalNegotiatorsConfig,
 }
 
 #[derive(StructOpt, Clone, Debug)]
@@ -91,20 +120,34 @@ pub fn create_negotiator(
     agent_negotiators_cfg: &AgentNegotiatorsConfig,
 ) -> Arc<NegotiatorAddr> {
     let negotiator = match &config.negotiator_type[..] {
-        "Composite" => NegotiatorAddr::from(
-            CompositeNegotiator::new(
-                market,
-                &config.negotiator_config.composite_config,
-                agent_negotiators_cfg.clone(),
-            )
-            .unwrap(),
-        ),
+        "Composite" => start_composite(
+            market,
+            config.negotiator_config.composite_config.clone(),
+            agent_negotiators_cfg.clone(),
+        )
+        .unwrap(),
         "AcceptAll" => NegotiatorAddr::from(AcceptAllNegotiator),
         _ => Default::default(),
     };
     Arc::new(negotiator)
 }
 
+/// Starts `CompositeNegotiator` on a separate arbiter, since its components may block
+/// on IO (e.g. external components), which would stall the rest of the Provider.
+fn start_composite(
+    market: Addr<ProviderMarket>,
+    config: CompositeNegotiatorConfig,
+    agent_negotiators_cfg: AgentNegotiatorsConfig,
+) -> anyhow::Result<NegotiatorAddr> {
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    Arbiter::new().spawn_fn(move || {
+        let negotiator = CompositeNegotiator::new(market, &config, agent_negotiators_cfg)
+            .map(NegotiatorAddr::from);
+        let _ = tx.send(negotiator);
+    });
+    rx.recv()?
+}
+
 impl Default for NegotiatorAddr {
     fn default() -> Self {
         NegotiatorAddr::from(AcceptAllNegotiator)
@@ -23,6 +23,10 @@ pub struct Preset {
     pub initial_price: f64,
     // It's important that all values are sorted, so that other tools can easily detect changes.
     pub usage_coeffs: BTreeMap<String, f64>,
+    /// Benchmark property (e.g. `golem.inf.cpu.benchmark.multi-thread`). When set, usage
+    /// coefficients are prices per its unit and are multiplied by the published score.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub benchmark: Option<String>,
 }
 
 impl Preset {
@@ -226,6 +230,7 @@ impl Default for Preset {
             exeunit_name: "wasmtime".to_string(),
             pricing_model: "linear".to_string(),
             usage_coeffs,
+            benchmark: None,
         }
     }
 }
@@ -236,6 +241,7 @@ impl PartialEq for Preset {
             && self.exeunit_name == other.exeunit_name
             && self.pricing_model == other.pricing_model
             && self.usage_coeffs == other.usage_coeffs
+            && self.benchmark == other.benchmark
     }
 }
 
@@ -273,6 +279,9 @@ fn display_preset(
         preset.pricing_model,
         width = align
     )?;
+    if let Some(benchmark) = &preset.benchmark {
+        writeln!(f, "{:width$}{}", "Priced per:", benchmark, width = align)?;
+    }
     writeln!(f, "Coefficients:")?;
 
     let exe_unit = registry.find_exeunit(&preset.exeunit_name).ok();
@@ -6,7 +6,8 @@ use chrono::Utc;
 use derive_more::Display;
 use futures::prelude::*;
 use futures_util::FutureExt;
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -56,6 +57,18 @@ pub struct Shutdown;
 #[rtype(result = "Result<()>")]
 pub struct Unsubscribe(pub OfferKind);
 
+/// Returns Offers currently subscribed on market, grouped by preset name.
+#[derive(Message)]
+#[rtype(result = "Result<BTreeMap<String, Vec<ActiveOffer>>>")]
+pub struct GetActiveOffers;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveOffer {
+    pub subscription_id: String,
+    pub offer: NewOffer,
+}
+
 pub enum OfferKind {
     Any,
     WithPresets(Vec<String>),
@@ -68,6 +81,8 @@ pub enum OfferKind {
 #[rtype(result = "Result<()>")]
 pub struct NewAgreement {
     pub agreement: AgreementView,
+    /// Name of the preset, for which Offer was created.
+    pub preset: String,
 }
 
 // =========================================== //
@@ -182,6 +197,24 @@ impl ProviderMarket {
         Ok(())
     }
 
+    fn active_offers(
+        &mut self,
+        _msg: GetActiveOffers,
+        _ctx: &mut Context<Self>,
+    ) -> Result<BTreeMap<String, Vec<ActiveOffer>>> {
+        let mut offers = BTreeMap::<String, Vec<ActiveOffer>>::new();
+        for subscription in self.subscriptions.values() {
+            offers
+                .entry(subscription.preset.name.clone())
+                .or_default()
+                .push(ActiveOffer {
+                    subscription_id: subscription.id.clone(),
+                    offer: subscription.offer.clone(),
+                });
+        }
+        Ok(offers)
+    }
+
     // =========================================== //
     // Market internals - proposals and agreements reactions
     // =========================================== //
@@ -416,6 +449,7 @@ async fn process_agreement(
             ctx.market
                 .send(NewAgreement {
                     agreement: agreement.clone(),
+                    preset: subscription.preset.name.clone(),
                 })
                 .await?
                 .ok();
@@ -914,6 +948,7 @@ impl Handler<Unsubscribe> for ProviderMarket {
 
 forward_actix_handler!(ProviderMarket, Subscription, on_subscription);
 forward_actix_handler!(ProviderMarket, NewAgreement, on_agreement_approved);
+forward_actix_handler!(ProviderMarket, GetActiveOffers, active_offers);
 actix_signal_handler!(ProviderMarket, CloseAgreement, agreement_terminated_signal);
 actix_signal_handler!(ProviderMarket, NewAgreement, agreement_signed_signal);
 
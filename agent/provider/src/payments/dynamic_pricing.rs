@@ -0,0 +1,299 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::NaiveTime;
+use std::str::FromStr;
+use std::time::Duration;
+use structopt::StructOpt;
+
+use crate::market::presets::Preset;
+
+/// Configuration of utilization-aware pricing.
+#[derive(StructOpt, Clone, Debug)]
+pub struct DynamicPricingConfig {
+    /// Scale preset prices with current load and time of day
+    #[structopt(long, env)]
+    pub dynamic_pricing: bool,
+    /// Price increase at full utilization (0.5 means +50%)
+    #[structopt(long, env, default_value = "0.5")]
+    pub price_load_factor: f64,
+    /// Time-of-day price multipliers, e.g. "00:00-08:00=0.8,18:00-22:00=1.2"
+    #[structopt(long, env)]
+    pub price_schedule: Option<PriceSchedule>,
+    /// Minimal relative price change triggering offer re-publication
+    #[structopt(long, env, default_value = "0.05")]
+    pub price_update_threshold: f64,
+    /// Number of consecutive updates, for which the price has to stay changed
+    /// before offers are re-published. Prevents reacting to short Agreements.
+    #[structopt(long, env, default_value = "3")]
+    pub price_update_confirmations: u32,
+    #[structopt(long, env, parse(try_from_str = humantime::parse_duration), default_value = "1min")]
+    pub price_update_interval: Duration,
+}
+
+/// Time window with a price multiplier. Windows ending before they
+/// start wrap around midnight.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduleWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub multiplier: f64,
+}
+
+impl ScheduleWindow {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PriceSchedule {
+    windows: Vec<ScheduleWindow>,
+}
+
+impl PriceSchedule {
+    /// Multiplier of the first window containing `time`, 1.0 outside of windows.
+    pub fn multiplier_at(&self, time: NaiveTime) -> f64 {
+        self.windows
+            .iter()
+            .find(|w| w.contains(time))
+            .map(|w| w.multiplier)
+            .unwrap_or(1.0)
+    }
+}
+
+impl FromStr for PriceSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let windows = s
+            .split(',')
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .map(|w| {
+                let (range, multiplier) = w
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Expected <start>-<end>=<multiplier>, got '{}'", w))?;
+                let (start, end) = range
+                    .split_once('-')
+                    .ok_or_else(|| anyhow!("Expected <start>-<end> time range, got '{}'", range))?;
+                let multiplier = f64::from_str(multiplier.trim())?;
+                if !multiplier.is_finite() || multiplier <= 0.0 {
+                    bail!("Invalid price multiplier: {}", multiplier);
+                }
+                Ok(ScheduleWindow {
+                    start: NaiveTime::parse_from_str(start.trim(), "%H:%M")?,
+                    end: NaiveTime::parse_from_str(end.trim(), "%H:%M")?,
+                    multiplier,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(PriceSchedule { windows })
+    }
+}
+
+/// Provider load used for computing prices.
+#[derive(Clone, Copy, Debug)]
+pub struct Load {
+    utilization: f64,
+}
+
+impl Load {
+    /// Load with share of resources used, between 0 and 1
+    pub fn new(utilization: f64) -> Self {
+        Load {
+            utilization: utilization.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn utilization(&self) -> f64 {
+        self.utilization
+    }
+}
+
+/// Tracks the price multiplier applied to presets.
+pub struct DynamicPricing {
+    config: DynamicPricingConfig,
+    multiplier: f64,
+    /// Changed multiplier with the number of updates it was computed for
+    pending: Option<(f64, u32)>,
+}
+
+impl DynamicPricing {
+    pub fn new(config: DynamicPricingConfig) -> Self {
+        DynamicPricing {
+            config,
+            multiplier: 1.0,
+            pending: None,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.dynamic_pricing
+    }
+
+    pub fn update_interval(&self) -> Duration {
+        self.config.price_update_interval
+    }
+
+    pub fn multiplier(&self) -> f64 {
+        self.multiplier
+    }
+
+    pub fn compute(&self, load: Load, time: NaiveTime) -> f64 {
+        let schedule = self
+            .config
+            .price_schedule
+            .as_ref()
+            .map(|s| s.multiplier_at(time))
+            .unwrap_or(1.0);
+        schedule * (1.0 + self.config.price_load_factor * load.utilization())
+    }
+
+    /// Sets the multiplier, before any offer was published.
+    pub fn reset(&mut self, load: Load, time: NaiveTime) {
+        self.multiplier = self.compute(load, time);
+        self.pending = None;
+    }
+
+    /// Recomputes the multiplier. Returns the new value if it differs from the
+    /// current one by more than the configured threshold, for the configured
+    /// number of consecutive updates.
+    pub fn update(&mut self, load: Load, time: NaiveTime) -> Option<f64> {
+        let multiplier = self.compute(load, time);
+        if !self.differs(self.multiplier, multiplier) {
+            self.pending = None;
+            return None;
+        }
+
+        let confirmations = match self.pending {
+            Some((pending, confirmations)) if !self.differs(pending, multiplier) => {
+                confirmations + 1
+            }
+            _ => 1,
+        };
+        if confirmations < self.config.price_update_confirmations {
+            self.pending = Some((multiplier, confirmations));
+            return None;
+        }
+
+        self.pending = None;
+        self.multiplier = multiplier;
+        Some(multiplier)
+    }
+
+    fn differs(&self, current: f64, multiplier: f64) -> bool {
+        ((multiplier - current) / current).abs() > self.config.price_update_threshold
+    }
+
+    /// Scales preset coefficients and initial price by the current multiplier.
+    pub fn apply(&self, mut preset: Preset) -> Preset {
+        if !self.enabled() {
+            return preset;
+        }
+        preset
+            .usage_coeffs
+            .values_mut()
+            .for_each(|c| *c *= self.multiplier);
+        preset.initial_price *= self.multiplier;
+        preset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(schedule: Option<&str>) -> DynamicPricingConfig {
+        DynamicPricingConfig {
+            dynamic_pricing: true,
+            price_load_factor: 0.5,
+            price_schedule: schedule.map(|s| s.parse().unwrap()),
+            price_update_threshold: 0.05,
+            price_update_confirmations: 1,
+            price_update_interval: Duration::from_secs(60),
+        }
+    }
+
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    fn load(active: usize, capacity: usize) -> Load {
+        Load::new(active as f64 / capacity as f64)
+    }
+
+    #[test]
+    fn schedule_windows() {
+        let schedule: PriceSchedule = "00:00-08:00=0.8, 22:00-02:00=1.5".parse().unwrap();
+        assert_eq!(schedule.multiplier_at(time("01:00")), 0.8);
+        assert_eq!(schedule.multiplier_at(time("08:00")), 1.0);
+        assert_eq!(schedule.multiplier_at(time("23:00")), 1.5);
+        assert_eq!(schedule.multiplier_at(time("12:00")), 1.0);
+    }
+
+    #[test]
+    fn schedule_invalid() {
+        assert!("00:00-08:00".parse::<PriceSchedule>().is_err());
+        assert!("00:00=0.8".parse::<PriceSchedule>().is_err());
+        assert!("00:00-25:00=0.8".parse::<PriceSchedule>().is_err());
+        assert!("00:00-08:00=-1".parse::<PriceSchedule>().is_err());
+    }
+
+    #[test]
+    fn multiplier_follows_load_and_schedule() {
+        let pricing = DynamicPricing::new(config(Some("18:00-22:00=1.2")));
+        assert_eq!(pricing.compute(load(0, 4), time("12:00")), 1.0);
+        assert_eq!(pricing.compute(load(2, 4), time("12:00")), 1.25);
+        assert_eq!(pricing.compute(load(8, 4), time("12:00")), 1.5);
+        assert_eq!(pricing.compute(load(0, 4), time("19:00")), 1.2);
+    }
+
+    #[test]
+    fn update_respects_threshold() {
+        let mut pricing = DynamicPricing::new(config(None));
+        assert_eq!(pricing.update(load(0, 20), time("12:00")), None);
+        assert_eq!(pricing.update(load(1, 20), time("12:00")), None);
+        assert_eq!(pricing.update(load(4, 20), time("12:00")), Some(1.1));
+        assert_eq!(pricing.multiplier(), 1.1);
+    }
+
+    #[test]
+    fn update_requires_confirmations() {
+        let mut pricing = DynamicPricing::new(DynamicPricingConfig {
+            price_update_confirmations: 3,
+            ..config(None)
+        });
+        // short load peaks don't change the price
+        assert_eq!(pricing.update(load(1, 1), time("12:00")), None);
+        assert_eq!(pricing.update(load(1, 1), time("12:00")), None);
+        assert_eq!(pricing.update(load(0, 1), time("12:00")), None);
+        assert_eq!(pricing.update(load(1, 1), time("12:00")), None);
+        assert_eq!(pricing.update(load(1, 1), time("12:00")), None);
+        assert_eq!(pricing.multiplier(), 1.0);
+
+        assert_eq!(pricing.update(load(1, 1), time("12:00")), Some(1.5));
+        assert_eq!(pricing.update(load(0, 1), time("12:00")), None);
+        assert_eq!(pricing.multiplier(), 1.5);
+
+        pricing.reset(load(0, 1), time("12:00"));
+        assert_eq!(pricing.multiplier(), 1.0);
+    }
+
+    #[test]
+    fn apply_scales_preset() {
+        let mut pricing = DynamicPricing::new(config(None));
+        pricing.update(load(1, 1), time("12:00"));
+
+        let preset = Preset {
+            initial_price: 2.0,
+            usage_coeffs: [("golem.usage.cpu_sec".to_string(), 0.1)].into(),
+            ..Default::default()
+        };
+        let preset = pricing.apply(preset);
+        assert_eq!(preset.initial_price, 3.0);
+        assert!((preset.usage_coeffs["golem.usage.cpu_sec"] - 0.15).abs() < f64::EPSILON);
+    }
+}
@@ -1,10 +1,12 @@
 mod agreement;
+mod dynamic_pricing;
 mod factory;
 mod model;
 #[allow(clippy::module_inception)]
 mod payments;
 mod pricing;
 
+pub use dynamic_pricing::{DynamicPricing, DynamicPricingConfig, Load};
 pub use factory::PaymentModelFactory;
-pub use payments::{Payments, PaymentsConfig};
+pub use payments::{AgreementCosts, GetCostsSummaries, Payments, PaymentsConfig};
 pub use pricing::{AccountView, LinearPricing, LinearPricingOffer, PricingOffer};
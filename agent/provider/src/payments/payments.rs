@@ -11,11 +11,13 @@ use chrono::{DateTime, Utc};
 use futures_util::FutureExt;
 use humantime;
 use log;
+use serde::Serialize;
 use serde_json::json;
 use structopt::StructOpt;
 use ya_client::activity::ActivityProviderApi;
 use ya_client::model::payment::{DebitNote, Invoice, NewDebitNote, NewInvoice};
 use ya_client::model::payment::{DebitNoteEvent, DebitNoteEventType, InvoiceEventType};
+use ya_client::model::NodeId;
 use ya_client::payment::PaymentApi;
 
 use ya_std_utils::LogErr;
@@ -26,15 +28,37 @@ use ya_utils_actix::deadline_checker::{
 };
 use ya_utils_actix::{actix_signal_handler, forward_actix_handler};
 
+use crate::earnings::AgreementPresets;
 use crate::execution::{ActivityDestroyed, CreateActivity};
 use crate::interval::RelativeInterval;
 use crate::market::provider_market::NewAgreement;
 use crate::market::termination_reason::BreakReason;
+use crate::reputation::{ReputationStore, RequestorEvent};
 use crate::tasks::{AgreementBroken, AgreementClosed, BreakAgreement};
 
 use super::agreement::{compute_cost, ActivityPayment, AgreementPayment, CostInfo};
 use super::model::PaymentModel;
 
+// =========================================== //
+// Public exposed messages
+// =========================================== //
+
+/// Returns costs of all Agreements handled by Payments.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<AgreementCosts>>")]
+pub struct GetCostsSummaries;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgreementCosts {
+    pub agreement_id: String,
+    pub requestor_id: NodeId,
+    pub activities: Vec<String>,
+    /// Usage and cost of finalized Activities.
+    pub usage: Vec<f64>,
+    pub cost: String,
+}
+
 // =========================================== //
 // Internal messages
 // =========================================== //
@@ -143,6 +167,8 @@ pub struct Payments {
 
     invoices_to_pay: Vec<Invoice>,
     earnings: BigDecimal,
+    reputation: ReputationStore,
+    presets: AgreementPresets,
 
     break_agreement_signal: SignalSlot<BreakAgreement>,
 }
@@ -154,6 +180,8 @@ impl Payments {
         activity_api: ActivityProviderApi,
         payment_api: PaymentApi,
         config: PaymentsConfig,
+        reputation: ReputationStore,
+        presets: AgreementPresets,
     ) -> Payments {
         let provider_ctx = ProviderCtx {
             activity_api: Arc::new(activity_api),
@@ -168,6 +196,8 @@ impl Payments {
             context: Arc::new(provider_ctx),
             invoices_to_pay: vec![],
             earnings: BigDecimal::zero(),
+            reputation,
+            presets,
             break_agreement_signal: SignalSlot::<BreakAgreement>::default(),
         }
     }
@@ -182,8 +212,11 @@ impl Payments {
             &msg.agreement.id
         );
 
+        self.presets.record(&msg.agreement.id, &msg.preset);
         match AgreementPayment::new(&msg.agreement) {
             Ok(agreement) => {
+                self.reputation
+                    .record(agreement.requestor_id, RequestorEvent::AgreementSigned);
                 self.agreements.insert(msg.agreement.id.clone(), agreement);
                 Ok(())
             }
@@ -197,6 +230,29 @@ impl Payments {
             }
         }
     }
+
+    fn costs_summaries(
+        &mut self,
+        _msg: GetCostsSummaries,
+        _ctx: &mut Context<Self>,
+    ) -> Result<Vec<AgreementCosts>> {
+        let mut costs = self
+            .agreements
+            .values()
+            .map(|agreement| {
+                let summary = agreement.cost_summary();
+                AgreementCosts {
+                    agreement_id: agreement.agreement_id.clone(),
+                    requestor_id: agreement.requestor_id,
+                    activities: agreement.list_activities(),
+                    usage: summary.usage,
+                    cost: summary.cost.to_string(),
+                }
+            })
+            .collect::<Vec<_>>();
+        costs.sort_by(|a, b| a.agreement_id.cmp(&b.agreement_id));
+        Ok(costs)
+    }
 }
 
 async fn send_debit_note(
@@ -493,6 +549,7 @@ async fn compute_cost_and_send_debit_note(
 }
 
 forward_actix_handler!(Payments, NewAgreement, on_signed_agreement);
+forward_actix_handler!(Payments, GetCostsSummaries, costs_summaries);
 
 impl Handler<CreateActivity> for Payments {
     type Result = anyhow::Result<()>;
@@ -820,6 +877,7 @@ impl Handler<IssueInvoice> for Payments {
         };
 
         let provider_ctx = self.context.clone();
+        let reputation = self.reputation.clone();
         async move {
             log::debug!("Issuing invoice {}.", serde_json::to_string(&invoice)?);
 
@@ -827,6 +885,13 @@ impl Handler<IssueInvoice> for Payments {
                 match provider_ctx.payment_api.issue_invoice(&invoice).await {
                     Ok(invoice) => {
                         log::info!("Invoice [{}] issued.", invoice.invoice_id);
+                        reputation.record(
+                            invoice.recipient_id,
+                            RequestorEvent::InvoiceIssued {
+                                invoice_id: invoice.invoice_id.clone(),
+                                due_date: invoice.payment_due_date,
+                            },
+                        );
                         return Ok(invoice);
                     }
                     Err(e) => {
@@ -873,12 +938,21 @@ impl Handler<AgreementBroken> for Payments {
     type Result = ActorResponse<Self, Result<(), Error>>;
 
     fn handle(&mut self, msg: AgreementBroken, ctx: &mut Context<Self>) -> Self::Result {
-        if !self.agreements.contains_key(&msg.agreement_id) {
-            log::warn!(
-                "Payments - agreement [{}] does not exist -- not broken.",
-                &msg.agreement_id
-            );
-            return ActorResponse::reply(Ok(()));
+        let agreement = match self.agreements.get(&msg.agreement_id) {
+            Some(agreement) => agreement,
+            None => {
+                log::warn!(
+                    "Payments - agreement [{}] does not exist -- not broken.",
+                    &msg.agreement_id
+                );
+                return ActorResponse::reply(Ok(()));
+            }
+        };
+        // Missed deadlines were already recorded when they elapsed. Unreachable
+        // Requestors may be offline because of network issues, so they aren't penalized.
+        if let BreakReason::DebitNoteRejected(_) = msg.reason {
+            self.reputation
+                .record(agreement.requestor_id, RequestorEvent::AgreementBroken);
         }
 
         let address = ctx.address();
@@ -930,6 +1004,12 @@ impl Handler<InvoiceSettled> for Payments {
                         invoice.agreement_id,
                         invoice.amount
                     );
+                    myself.reputation.record(
+                        invoice.recipient_id,
+                        RequestorEvent::InvoiceSettled {
+                            invoice_id: invoice.invoice_id.clone(),
+                        },
+                    );
                     myself.agreements.remove(&invoice.agreement_id);
                     myself
                         .invoices_to_pay
@@ -977,6 +1057,8 @@ impl Handler<DeadlineElapsed> for Payments {
                     );
 
                     agreement.deadline_elapsed = true;
+                    self.reputation
+                        .record(agreement.requestor_id, RequestorEvent::DebitNoteLate);
                     BreakReason::DebitNotesDeadline(timeout)
                 }
                 None => return,
@@ -990,6 +1072,8 @@ impl Handler<DeadlineElapsed> for Payments {
                         msg.id,
                         msg.category,
                     );
+                    self.reputation
+                        .record(agreement.requestor_id, RequestorEvent::DebitNoteUnpaid);
                     BreakReason::DebitNoteNotPaid(timeout)
                 }
                 None => return,
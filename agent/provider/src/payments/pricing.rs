@@ -7,6 +7,7 @@ use ya_client::model::{payment::Account, NodeId};
 use ya_core_model::payment::local::NetworkName;
 
 use super::model::{PaymentDescription, PaymentModel};
+use crate::benchmark::BenchmarkResults;
 use crate::market::presets::Preset;
 
 #[derive(Clone, Debug)]
@@ -27,7 +28,7 @@ impl From<Account> for AccountView {
 }
 
 pub trait PricingOffer {
-    fn prices(&self, preset: &Preset) -> Vec<(String, f64)>;
+    fn prices(&self, preset: &Preset) -> Result<Vec<(String, f64)>>;
     fn build(
         &self,
         accounts: &[AccountView],
@@ -79,11 +80,15 @@ impl LinearPricing {
 /// Helper for building offer.
 pub struct LinearPricingOffer {
     interval: f64,
+    benchmark: Option<BenchmarkResults>,
 }
 
 impl Default for LinearPricingOffer {
     fn default() -> Self {
-        LinearPricingOffer { interval: 120.0 }
+        LinearPricingOffer {
+            interval: 120.0,
+            benchmark: None,
+        }
     }
 }
 
@@ -93,11 +98,36 @@ impl LinearPricingOffer {
         self.interval = seconds;
         self
     }
+
+    /// Benchmark results scaling prices of presets priced per benchmark unit.
+    pub fn benchmark(mut self, benchmark: Option<BenchmarkResults>) -> Self {
+        self.benchmark = benchmark;
+        self
+    }
 }
 
 impl PricingOffer for LinearPricingOffer {
-    fn prices(&self, preset: &Preset) -> Vec<(String, f64)> {
-        preset.usage_coeffs.clone().into_iter().collect()
+    fn prices(&self, preset: &Preset) -> Result<Vec<(String, f64)>> {
+        let multiplier = match &preset.benchmark {
+            Some(property) => self
+                .benchmark
+                .as_ref()
+                .and_then(|benchmark| benchmark.score(property))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Preset [{}] is priced per '{}', but there is no such benchmark result. \
+                         Run `ya-provider benchmark`.",
+                        preset.name,
+                        property
+                    )
+                })?,
+            None => 1.0,
+        };
+        Ok(preset
+            .usage_coeffs
+            .iter()
+            .map(|(name, price)| (name.clone(), price * multiplier))
+            .collect())
     }
 
     fn build(
@@ -1,12 +1,17 @@
 use actix::prelude::*;
+use actix_web::dev::ServerHandle;
 use anyhow::{anyhow, Error};
+use chrono::Local;
 use futures::{FutureExt, StreamExt, TryFutureExt};
 use ya_client::net::NetApi;
 
 use std::convert::TryFrom;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::watch;
 use tokio_stream::wrappers::WatchStream;
 
 use ya_agreement_utils::agreement::TypedArrayPointer;
@@ -16,23 +21,32 @@ use ya_core_model::payment::local::NetworkName;
 use ya_file_logging::{start_logger, LoggerHandle};
 use ya_manifest_utils::{manifest, Feature};
 
+use crate::benchmark::BenchmarkResults;
+use crate::config::availability::{Availability, ScheduleHandle};
 use crate::config::globals::GlobalsState;
 use crate::dir::clean_provider_dir;
+use crate::earnings::AgreementPresets;
 use crate::events::Event;
 use crate::execution::{
-    ExeUnitDesc, GetExeUnit, GetOfferTemplates, Shutdown as ShutdownExecution, TaskRunner,
-    UpdateActivity,
+    matches_file_pattern, ExeUnitDesc, ExeUnitsRegistry, GetExeUnit, GetOfferTemplates,
+    Shutdown as ShutdownExecution, TaskRunner, UpdateActivity, UpdateRegistry,
 };
-use crate::hardware;
+use crate::hardware::{self, Resources};
 use crate::market::provider_market::{OfferKind, Shutdown as MarketShutdown, Unsubscribe};
 use crate::market::{CreateOffer, Preset, PresetManager, ProviderMarket};
-use crate::payments::{AccountView, LinearPricingOffer, Payments, PricingOffer};
+use crate::payments::{
+    AccountView, DynamicPricing, LinearPricingOffer, Load, Payments, PricingOffer,
+};
+use crate::reputation::ReputationStore;
 use crate::rules::RulesManager;
 use crate::startup_config::{FileMonitor, NodeConfig, ProviderConfig, RunConfig};
-use crate::tasks::task_manager::{InitializeTaskManager, TaskManager};
+use crate::status_api::{self, StatusSources};
+use crate::tasks::reservations::Reservations;
+use crate::tasks::task_manager::{GetActiveAgreements, InitializeTaskManager, TaskManager};
 
 struct GlobalsManager {
     state: Arc<Mutex<GlobalsState>>,
+    availability: ScheduleHandle,
     monitor: Option<FileMonitor>,
 }
 
@@ -42,6 +56,7 @@ impl GlobalsManager {
         state.update_and_save(node_config, globals_file)?;
 
         Ok(Self {
+            availability: ScheduleHandle::new(state.availability.clone()),
             state: Arc::new(Mutex::new(state)),
             monitor: None,
         })
@@ -49,8 +64,10 @@ impl GlobalsManager {
 
     fn spawn_monitor(&mut self, globals_file: &Path) -> anyhow::Result<()> {
         let state = self.state.clone();
+        let availability = self.availability.clone();
         let handler = move |p: PathBuf| match GlobalsState::load(&p) {
             Ok(new_state) => {
+                availability.set(new_state.availability.clone());
                 *state.lock().unwrap() = new_state;
             }
             Err(e) => log::warn!("Error updating global configuration from {:?}: {:?}", p, e),
@@ -65,9 +82,155 @@ impl GlobalsManager {
     }
 }
 
+/// Watches directory with ExeUnit descriptors, so newly installed
+/// runtimes can be used without restarting Provider.
+struct ExeUnitsManager {
+    exe_unit_path: PathBuf,
+    data_dir: PathBuf,
+    sender: Option<watch::Sender<Event>>,
+    receiver: watch::Receiver<Event>,
+    monitor: Option<FileMonitor>,
+    /// Registry used by TaskRunner. Only runtimes changed since then are tested on reload.
+    registry: ExeUnitsRegistry,
+    reloading: bool,
+    /// Descriptors changed during reload, so it has to be repeated.
+    pending: bool,
+}
+
+impl ExeUnitsManager {
+    fn new(exe_unit_path: &Path, data_dir: &Path, registry: ExeUnitsRegistry) -> Self {
+        let (sender, receiver) = watch::channel(Event::Initialized);
+        ExeUnitsManager {
+            exe_unit_path: exe_unit_path.to_path_buf(),
+            data_dir: data_dir.to_path_buf(),
+            sender: Some(sender),
+            receiver,
+            monitor: None,
+            registry,
+            reloading: false,
+            pending: false,
+        }
+    }
+
+    fn spawn_monitor(&mut self) -> anyhow::Result<()> {
+        // Descriptor path can be a file pattern, so we watch the whole directory.
+        let dir = match self.exe_unit_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let pattern = self.exe_unit_path.clone();
+        let tx = self.sender.take().unwrap();
+        let handler = move |p: PathBuf| {
+            if !matches_file_pattern(&pattern, &p) {
+                return;
+            }
+            log::debug!("ExeUnit descriptors changed: {}", p.display());
+            tx.send(Event::ExeUnitsChanged).unwrap_or_default();
+        };
+        let monitor = FileMonitor::spawn(dir, FileMonitor::on_modified(handler))?;
+        self.monitor = Some(monitor);
+        Ok(())
+    }
+
+    fn event_receiver(&self) -> watch::Receiver<Event> {
+        self.receiver.clone()
+    }
+
+    /// Loads registry from descriptors and tests all runtimes.
+    async fn load(exe_unit_path: PathBuf, data_dir: PathBuf) -> anyhow::Result<ExeUnitsRegistry> {
+        let mut registry = ExeUnitsRegistry::default();
+        registry.register_from_file_pattern(&exe_unit_path)?;
+        registry.validate()?;
+        registry.test_runtimes(&data_dir).await?;
+        Ok(registry)
+    }
+
+    /// Loads registry from descriptors and tests only runtimes changed since `current`.
+    /// Returns new registry with names of changed runtimes.
+    async fn load_changed(
+        exe_unit_path: PathBuf,
+        data_dir: PathBuf,
+        current: ExeUnitsRegistry,
+    ) -> anyhow::Result<(ExeUnitsRegistry, Vec<String>)> {
+        let mut registry = ExeUnitsRegistry::default();
+        registry.register_from_file_pattern(&exe_unit_path)?;
+        registry.validate()?;
+        if registry.list().is_empty() {
+            anyhow::bail!("No runtimes available");
+        }
+        let changed = current.diff(&registry);
+        registry.test_named_runtimes(&data_dir, &changed).await?;
+        Ok((registry, changed))
+    }
+
+    /// Returns false if reload is already in progress. It will be repeated
+    /// after the current one finishes, to pick up the latest descriptors.
+    fn start_reload(&mut self) -> bool {
+        if self.reloading {
+            self.pending = true;
+            return false;
+        }
+        self.reloading = true;
+        true
+    }
+
+    /// Returns next reload to schedule with its delay. Failed reload is retried
+    /// a few times, because descriptors can be still being written by installer.
+    fn finish_reload(
+        &mut self,
+        result: anyhow::Result<ExeUnitsRegistry>,
+        attempt: u32,
+    ) -> Option<(ReloadExeUnits, Duration)> {
+        self.reloading = false;
+        let pending = std::mem::take(&mut self.pending);
+        match result {
+            Ok(registry) => self.registry = registry,
+            Err(e) if pending => log::warn!("Cannot reload ExeUnits, reloading again: {}", e),
+            Err(e) if attempt < RELOAD_RETRIES => {
+                log::warn!(
+                    "Cannot reload ExeUnits, retrying in {}s: {}",
+                    RELOAD_RETRY_DELAY.as_secs(),
+                    e
+                );
+                let retry = ReloadExeUnits {
+                    attempt: attempt + 1,
+                };
+                return Some((retry, RELOAD_RETRY_DELAY));
+            }
+            Err(e) => log::error!("Cannot reload ExeUnits, keeping previous ones: {}", e),
+        }
+        pending.then(|| (ReloadExeUnits { attempt: 0 }, Duration::from_secs(0)))
+    }
+
+    fn stop(&mut self) {
+        if let Some(monitor) = self.monitor.as_mut() {
+            monitor.stop();
+        }
+    }
+}
+
+/// Flag shared between ProviderAgent and negotiators. Once set, Provider
+/// doesn't take new work and waits for running Agreements to finish.
+#[derive(Clone, Debug, Default)]
+pub struct DrainHandle(Arc<AtomicBool>);
+
+impl DrainHandle {
+    pub fn start(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
 #[derive(Clone)]
 pub struct AgentNegotiatorsConfig {
     pub rules_manager: RulesManager,
+    pub availability: ScheduleHandle,
+    pub reputation: ReputationStore,
+    pub draining: DrainHandle,
+    pub reservations: Reservations,
 }
 
 pub struct ProviderAgent {
@@ -75,15 +238,29 @@ pub struct ProviderAgent {
     market: Addr<ProviderMarket>,
     runner: Addr<TaskRunner>,
     task_manager: Addr<TaskManager>,
+    payments: Addr<Payments>,
     presets: PresetManager,
     hardware: hardware::Manager,
+    reservations: Reservations,
+    /// Remaining capacity published in offers, when reservations are enabled.
+    advertised: Option<Resources>,
+    reputation: ReputationStore,
+    pricing: DynamicPricing,
+    available: bool,
+    draining: DrainHandle,
+    drain_timeout: Duration,
     accounts: Vec<AccountView>,
     log_handler: LoggerHandle,
     networks: Vec<NetworkName>,
     rulestore_monitor: FileMonitor,
     keystore_monitor: FileMonitor,
     whitelist_monitor: FileMonitor,
+    reputation_monitor: FileMonitor,
+    exe_units: ExeUnitsManager,
     net_api: NetApi,
+    status_api_addr: Option<SocketAddr>,
+    status_api: Option<ServerHandle>,
+    benchmark_file: PathBuf,
 }
 
 impl ProviderAgent {
@@ -124,9 +301,8 @@ impl ProviderAgent {
             .map(Into::into)
             .collect();
         log::info!("Payment accounts: {:#?}", accounts);
-        let registry = config.registry()?;
-        registry.validate()?;
-        registry.test_runtimes(&data_dir).await?;
+        let registry =
+            ExeUnitsManager::load(config.exe_unit_path.clone(), data_dir.clone()).await?;
 
         // Generate session id from node name and process id to make sure it's unique.
         let name = args
@@ -169,14 +345,42 @@ impl ProviderAgent {
         hardware.spawn_monitor(&config.hardware_file)?;
         let (rulestore_monitor, keystore_monitor, whitelist_monitor) =
             rules_manager.spawn_file_monitors()?;
-
-        let agent_negotiators_cfg = AgentNegotiatorsConfig { rules_manager };
+        let reputation = ReputationStore::load_or_create(&config.reputation_file)?;
+        let reputation_monitor = reputation.spawn_monitor()?;
+        let mut exe_units =
+            ExeUnitsManager::new(&config.exe_unit_path, &data_dir, registry.clone());
+        exe_units.spawn_monitor()?;
+        let agreement_presets = AgreementPresets::open(&config.agreement_presets_file)?;
+
+        let draining = DrainHandle::default();
+        let reservations = Reservations::new(&args.tasks.reservations, hardware.capped());
+        let agent_negotiators_cfg = AgentNegotiatorsConfig {
+            rules_manager,
+            availability: globals.availability.clone(),
+            reputation: reputation.clone(),
+            draining: draining.clone(),
+            reservations: reservations.clone(),
+        };
+        let pricing = DynamicPricing::new(args.pricing);
 
         let market = ProviderMarket::new(api.market, args.market, agent_negotiators_cfg).start();
-        let payments = Payments::new(api.activity.clone(), api.payment, args.payment).start();
+        let payments = Payments::new(
+            api.activity.clone(),
+            api.payment,
+            args.payment,
+            reputation.clone(),
+            agreement_presets,
+        )
+        .start();
         let runner = TaskRunner::new(api.activity, args.runner, registry, data_dir)?.start();
-        let task_manager =
-            TaskManager::new(market.clone(), runner.clone(), payments, args.tasks)?.start();
+        let task_manager = TaskManager::new(
+            market.clone(),
+            runner.clone(),
+            payments.clone(),
+            args.tasks,
+            reservations.clone(),
+        )?
+        .start();
         let net_api = api.net;
 
         Ok(ProviderAgent {
@@ -184,15 +388,28 @@ impl ProviderAgent {
             market,
             runner,
             task_manager,
+            payments,
             presets,
             hardware,
+            reservations,
+            advertised: None,
+            reputation,
+            pricing,
+            available: true,
+            draining,
+            drain_timeout: args.drain_timeout,
             accounts,
             log_handler,
             networks,
             rulestore_monitor,
             keystore_monitor,
             whitelist_monitor,
+            reputation_monitor,
+            exe_units,
             net_api,
+            status_api_addr: args.status.status_api,
+            status_api: None,
+            benchmark_file: config.benchmark_file,
         })
     }
 
@@ -203,6 +420,7 @@ impl ProviderAgent {
         runner: Addr<TaskRunner>,
         market: Addr<ProviderMarket>,
         accounts: Vec<AccountView>,
+        benchmark: Option<BenchmarkResults>,
     ) -> anyhow::Result<()> {
         if presets.is_empty() {
             return Err(anyhow!("No Presets were selected. Can't create offers."));
@@ -213,10 +431,13 @@ impl ProviderAgent {
         let offer_templates = runner.send(GetOfferTemplates(presets.clone())).await??;
 
         for preset in presets {
-            let offer: OfferTemplate = offer_templates
+            let mut offer: OfferTemplate = offer_templates
                 .get(&preset.name)
                 .ok_or_else(|| anyhow!("Offer template not found for preset [{}]", preset.name))?
                 .clone();
+            if let Some(benchmark) = &benchmark {
+                benchmark.add_to_offer(&mut offer);
+            }
             let exeunit_name = preset.exeunit_name.clone();
             let exeunit_desc = runner
                 .send(GetExeUnit { name: exeunit_name })
@@ -236,6 +457,7 @@ impl ProviderAgent {
                 preset,
                 offer,
                 exeunit_desc,
+                benchmark.clone(),
             )?;
 
             market.send(offer).await??;
@@ -250,9 +472,10 @@ impl ProviderAgent {
         preset: Preset,
         mut offer: OfferTemplate,
         exeunit_desc: ExeUnitDesc,
+        benchmark: Option<BenchmarkResults>,
     ) -> anyhow::Result<CreateOffer> {
         let pricing_model: Box<dyn PricingOffer> = match preset.pricing_model.as_str() {
-            "linear" => Box::<LinearPricingOffer>::default(),
+            "linear" => Box::new(LinearPricingOffer::default().benchmark(benchmark)),
             other => return Err(anyhow!("Unsupported pricing model: {}", other)),
         };
         let (initial_price, prices) = get_prices(pricing_model.as_ref(), &preset, &offer)?;
@@ -318,6 +541,144 @@ impl ProviderAgent {
             .support_multi_activity(true))
     }
 
+    /// Share of capped hardware resources requested by running Agreements
+    fn load(&self) -> Load {
+        self.reservations.set_capacity(self.hardware.capped());
+        Load::new(self.reservations.utilization())
+    }
+
+    /// Recomputes price multiplier and re-publishes offers of presets,
+    /// whose prices changed enough.
+    fn update_prices(&mut self, ctx: &mut Context<Self>) {
+        let presets = match self.presets.list_matching(&self.presets.active()) {
+            Ok(presets) => presets,
+            Err(e) => {
+                log::warn!("Unable to list active presets: {}", e);
+                return;
+            }
+        };
+        let previous = presets
+            .iter()
+            .map(|preset| self.pricing.apply(preset.clone()))
+            .collect::<Vec<_>>();
+
+        let load = self.load();
+        let multiplier = match self.pricing.update(load, Local::now().time()) {
+            Some(multiplier) => multiplier,
+            None => return,
+        };
+
+        let changed = presets
+            .into_iter()
+            .zip(previous)
+            .filter(|(preset, previous)| {
+                let current = self.pricing.apply(preset.clone());
+                current.initial_price != previous.initial_price
+                    || current.usage_coeffs != previous.usage_coeffs
+            })
+            .map(|(preset, _)| preset.name)
+            .collect::<Vec<_>>();
+        if changed.is_empty() {
+            return;
+        }
+
+        log::info!(
+            "Price multiplier changed to {:.3} (utilization {:.0}%). Re-publishing offers for presets: {:?}.",
+            multiplier,
+            load.utilization() * 100.0,
+            changed
+        );
+        let market = self.market.clone();
+        let agent = ctx.address();
+        ctx.spawn(
+            async move {
+                let _ = market
+                    .send(Unsubscribe(OfferKind::WithPresets(changed.clone())))
+                    .map_err(|e| log::error!("Cannot unsubscribe offers: {}", e))
+                    .await;
+                let _ = agent
+                    .send(CreateOffers(OfferKind::WithPresets(changed)))
+                    .map_err(|e| log::error!("Cannot create offers: {}", e))
+                    .await;
+            }
+            .into_actor(self),
+        );
+    }
+
+    fn is_available(&self) -> bool {
+        let now = Local::now().naive_local();
+        self.globals.availability.availability(now) != Availability::Closed
+    }
+
+    /// Subscribes or unsubscribes offers, when availability window opens or closes.
+    fn check_availability(&mut self, ctx: &mut Context<Self>) {
+        let available = self.is_available();
+        if available == self.available {
+            return;
+        }
+        self.available = available;
+
+        let market = self.market.clone();
+        let agent = ctx.address();
+        if available {
+            log::info!("Availability window opened. Subscribing offers.");
+            ctx.spawn(
+                async move {
+                    let _ = agent
+                        .send(CreateOffers(OfferKind::Any))
+                        .map_err(|e| log::error!("Cannot create offers: {}", e))
+                        .await;
+                }
+                .into_actor(self),
+            );
+        } else {
+            log::info!("Availability window closed. Unsubscribing offers.");
+            ctx.spawn(
+                async move {
+                    let _ = market
+                        .send(Unsubscribe(OfferKind::Any))
+                        .map_err(|e| log::error!("Cannot unsubscribe offers: {}", e))
+                        .await;
+                }
+                .into_actor(self),
+            );
+        }
+    }
+
+    /// Re-publishes offers, when remaining capacity changed since they were published.
+    /// Checked periodically, so that Agreements starting and ending in a row
+    /// don't re-publish offers every time.
+    fn check_reservations(&mut self, ctx: &mut Context<Self>) {
+        if !self.available || self.draining.is_draining() {
+            return;
+        }
+        self.reservations.set_capacity(self.hardware.capped());
+        let advertised = self.reservations.advertised();
+        if self.advertised.is_none() || self.advertised == Some(advertised) {
+            return;
+        }
+        log::info!(
+            "Remaining capacity changed to {:?}. Re-publishing offers.",
+            advertised
+        );
+
+        let market = self.market.clone();
+        let agent = ctx.address();
+        ctx.spawn(
+            async move {
+                let _ = market
+                    .send(Unsubscribe(OfferKind::Any))
+                    .map_err(|e| log::error!("Cannot unsubscribe offers: {}", e))
+                    .await;
+                let _ = agent
+                    .send(CreateOffers(OfferKind::Any))
+                    .map_err(|e| log::error!("Cannot create offers: {}", e))
+                    .await;
+            }
+            .into_actor(self),
+        );
+    }
+
     fn accounts(&self, networks: &Vec<NetworkName>) -> anyhow::Result<Vec<AccountView>> {
         let globals = self.globals.get_state();
         if let Some(address) = &globals.account {
@@ -385,7 +746,7 @@ fn get_prices(
         .get_initial_price()
         .ok_or_else(|| anyhow!("Preset [{}] is missing the initial price", preset.name))?;
     let prices = pricing_model
-        .prices(preset)
+        .prices(preset)?
         .into_iter()
         .filter_map(|(prop, v)| match offer_usage_vec.contains(&prop.as_str()) {
             true => Some((prop, v)),
@@ -413,6 +774,12 @@ fn get_usage_vector_value(prices: &[(String, f64)]) -> serde_json::Value {
     serde_json::Value::Array(vec)
 }
 
+const AVAILABILITY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const RESERVATIONS_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const DRAIN_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const RELOAD_RETRY_DELAY: Duration = Duration::from_secs(10);
+const RELOAD_RETRIES: u32 = 3;
+
 async fn process_activity_events(runner: Addr<TaskRunner>) {
     const ZERO: Duration = Duration::from_secs(0);
     const DEFAULT: Duration = Duration::from_secs(4);
@@ -434,6 +801,22 @@ impl Actor for ProviderAgent {
     fn started(&mut self, ctx: &mut Context<Self>) {
         let runner = self.runner.clone();
         ctx.spawn(process_activity_events(runner).into_actor(self));
+
+        ctx.run_interval(AVAILABILITY_CHECK_INTERVAL, |myself, ctx| {
+            myself.check_availability(ctx)
+        });
+
+        if self.reservations.enabled() {
+            ctx.run_interval(RESERVATIONS_CHECK_INTERVAL, |myself, ctx| {
+                myself.check_reservations(ctx)
+            });
+        }
+
+        if self.pricing.enabled() {
+            ctx.run_interval(self.pricing.update_interval(), |myself, ctx| {
+                myself.update_prices(ctx)
+            });
+        }
     }
 }
 
@@ -448,6 +831,7 @@ impl Handler<Initialize> for ProviderAgent {
         let rx = futures::stream::select_all(vec![
             WatchStream::new(self.hardware.event_receiver()),
             WatchStream::new(self.presets.event_receiver()),
+            WatchStream::new(self.exe_units.event_receiver()),
         ]);
 
         tokio::task::spawn_local(async move {
@@ -463,6 +847,8 @@ impl Handler<Initialize> for ProviderAgent {
                             .map_err(|e| log::error!("Cannot create offers: {}", e))
                             .await;
                     }
+                    // Testing runtimes can take a while, so we don't wait for it here.
+                    Event::ExeUnitsChanged => agent.do_send(ReloadExeUnits { attempt: 0 }),
                     Event::PresetsChanged {
                         presets,
                         updated,
@@ -502,6 +888,29 @@ impl Handler<Initialize> for ProviderAgent {
             .await;
         });
 
+        if self.pricing.enabled() {
+            // No agreements yet, so only the price schedule applies.
+            let load = self.load();
+            self.pricing.reset(load, Local::now().time());
+            log::info!(
+                "Dynamic pricing enabled. Initial price multiplier: {:.3}",
+                self.pricing.multiplier()
+            );
+        }
+
+        if let Some(addr) = self.status_api_addr {
+            let sources = StatusSources {
+                agreements: self.task_manager.clone().recipient(),
+                offers: self.market.clone().recipient(),
+                costs: self.payments.clone().recipient(),
+                resources: ctx.address().recipient(),
+            };
+            match status_api::start(addr, sources) {
+                Ok(handle) => self.status_api = Some(handle),
+                Err(e) => return async move { Err(e) }.boxed_local(),
+            }
+        }
+
         let agent = ctx.address();
         let task_manager = self.task_manager.clone();
         async move {
@@ -520,13 +929,23 @@ impl Handler<Shutdown> for ProviderAgent {
         let market = self.market.clone();
         let runner = self.runner.clone();
         let log_handler = self.log_handler.clone();
+        let reputation = self.reputation.clone();
         self.keystore_monitor.stop();
         self.rulestore_monitor.stop();
         self.whitelist_monitor.stop();
+        self.reputation_monitor.stop();
+        self.exe_units.stop();
+        let status_api = self.status_api.take();
 
         async move {
+            if let Some(status_api) = status_api {
+                status_api.stop(true).await;
+            }
             market.send(MarketShutdown).await??;
             runner.send(ShutdownExecution).await??;
+            if let Err(e) = reputation.flush() {
+                log::warn!("Failed to save requestors reputation: {}", e);
+            }
             log_handler.shutdown();
             Ok(())
         }
@@ -534,18 +953,163 @@ impl Handler<Shutdown> for ProviderAgent {
     }
 }
 
+impl Handler<Drain> for ProviderAgent {
+    type Result = ResponseFuture<Result<(), Error>>;
+
+    fn handle(&mut self, _: Drain, _: &mut Context<Self>) -> Self::Result {
+        self.draining.start();
+
+        let market = self.market.clone();
+        let task_manager = self.task_manager.clone();
+        let deadline = Instant::now() + self.drain_timeout;
+
+        async move {
+            log::info!("Draining. Unsubscribing offers and rejecting new Proposals.");
+            market.send(Unsubscribe(OfferKind::Any)).await??;
+
+            loop {
+                let active = task_manager.send(GetActiveAgreements).await??;
+                if active == 0 {
+                    log::info!("All Agreements finished.");
+                    return Ok(());
+                }
+                if Instant::now() >= deadline {
+                    log::warn!(
+                        "Drain timeout elapsed with {} Agreement(s) still active.",
+                        active
+                    );
+                    return Ok(());
+                }
+                log::info!("Waiting for {} active Agreement(s) to finish.", active);
+                tokio::time::sleep(DRAIN_CHECK_INTERVAL).await;
+            }
+        }
+        .boxed_local()
+    }
+}
+
+impl Handler<ReloadExeUnits> for ProviderAgent {
+    type Result = ActorResponse<Self, ()>;
+
+    fn handle(&mut self, msg: ReloadExeUnits, ctx: &mut Context<Self>) -> Self::Result {
+        if !self.exe_units.start_reload() {
+            log::debug!("ExeUnits reload in progress, it will be repeated.");
+            return ActorResponse::reply(());
+        }
+
+        let exe_unit_path = self.exe_units.exe_unit_path.clone();
+        let data_dir = self.exe_units.data_dir.clone();
+        let current = self.exe_units.registry.clone();
+        let presets = self.presets.list_matching(&self.presets.active());
+        let runner = self.runner.clone();
+        let market = self.market.clone();
+        let agent = ctx.address();
+
+        let future = async move {
+            log::info!("Reloading ExeUnits from: {}", exe_unit_path.display());
+            let (registry, changed) =
+                ExeUnitsManager::load_changed(exe_unit_path, data_dir, current).await?;
+            if changed.is_empty() {
+                log::info!("ExeUnits unchanged.");
+                return Ok(registry);
+            }
+            log::info!("ExeUnits changed: {:?}", changed);
+
+            let (available, removed): (Vec<_>, Vec<_>) = presets?
+                .into_iter()
+                .partition(|preset| registry.find_exeunit(&preset.exeunit_name).is_ok());
+
+            runner.send(UpdateRegistry(registry.clone())).await??;
+
+            for preset in &removed {
+                log::warn!(
+                    "ExeUnit [{}] was removed. Offer for preset [{}] won't be created.",
+                    preset.exeunit_name,
+                    preset.name
+                );
+            }
+            let affected = |presets: Vec<Preset>| {
+                presets
+                    .into_iter()
+                    .filter(|preset| changed.contains(&preset.exeunit_name))
+                    .map(|preset| preset.name)
+                    .collect::<Vec<_>>()
+            };
+            let to_create = affected(available);
+            let mut to_unsub = affected(removed);
+            to_unsub.extend(to_create.iter().cloned());
+
+            if !to_unsub.is_empty() {
+                market
+                    .send(Unsubscribe(OfferKind::WithPresets(to_unsub)))
+                    .await??;
+            }
+            if !to_create.is_empty() {
+                agent
+                    .send(CreateOffers(OfferKind::WithPresets(to_create)))
+                    .await??;
+            }
+            Ok(registry)
+        };
+
+        ActorResponse::r#async(future.into_actor(self).map(
+            move |result: Result<ExeUnitsRegistry, Error>, myself, ctx| {
+                if let Some((next, delay)) = myself.exe_units.finish_reload(result, msg.attempt) {
+                    ctx.notify_later(next, delay);
+                }
+            },
+        ))
+    }
+}
+
+impl Handler<GetResources> for ProviderAgent {
+    type Result = Result<Resources, Error>;
+
+    fn handle(&mut self, _: GetResources, _: &mut Context<Self>) -> Self::Result {
+        Ok(self.hardware.capped())
+    }
+}
+
 impl Handler<CreateOffers> for ProviderAgent {
     type Result = ResponseFuture<Result<(), Error>>;
 
     #[inline]
     fn handle(&mut self, msg: CreateOffers, _: &mut Context<Self>) -> Self::Result {
+        if self.draining.is_draining() {
+            log::debug!("Provider is draining. Offers won't be created.");
+            return Box::pin(async { Ok(()) });
+        }
+
+        self.available = self.is_available();
+        if !self.available {
+            let now = Local::now().naive_local();
+            match self.globals.availability.next_opening(now) {
+                Some(at) => log::info!(
+                    "Outside of availability window. Offers will be created at {}.",
+                    at
+                ),
+                None => log::info!("Outside of availability window. Offers won't be created."),
+            }
+            return Box::pin(async { Ok(()) });
+        }
+
         let runner = self.runner.clone();
         let market = self.market.clone();
         let accounts = match self.accounts(&self.networks) {
             Ok(acc) => acc,
             Err(e) => return Box::pin(async { Err(e) }),
         };
-        let inf_node_info = InfNodeInfo::from(self.hardware.capped());
+        self.reservations.set_capacity(self.hardware.capped());
+        let inf_node_info = match self.reservations.enabled() {
+            true => {
+                let advertised = self.reservations.advertised();
+                if let OfferKind::Any = msg.0 {
+                    self.advertised = Some(advertised);
+                }
+                InfNodeInfo::from(advertised)
+            }
+            false => InfNodeInfo::from(self.hardware.capped()),
+        };
         let preset_names = match msg.0 {
             OfferKind::Any => self.presets.active(),
             OfferKind::WithPresets(names) => names,
@@ -554,13 +1118,31 @@ impl Handler<CreateOffers> for ProviderAgent {
                 vec![]
             }
         };
-        let presets = self.presets.list_matching(&preset_names);
+        let presets = self.presets.list_matching(&preset_names).map(|presets| {
+            presets
+                .into_iter()
+                .map(|preset| self.pricing.apply(preset))
+                .collect::<Vec<_>>()
+        });
         let globals = self.globals.get_state();
         let net_api = self.net_api.clone();
+        let benchmark = BenchmarkResults::load(&self.benchmark_file).unwrap_or_else(|e| {
+            log::warn!("Failed to load benchmark results: {}", e);
+            None
+        });
 
         async move {
             let node_info = Self::build_node_info(globals, net_api).await?;
-            Self::create_offers(presets?, node_info, inf_node_info, runner, market, accounts).await
+            Self::create_offers(
+                presets?,
+                node_info,
+                inf_node_info,
+                runner,
+                market,
+                accounts,
+                benchmark,
+            )
+            .await
         }
         .boxed_local()
     }
@@ -574,21 +1156,47 @@ pub struct Initialize;
 #[rtype(result = "Result<(), Error>")]
 pub struct Shutdown;
 
+/// Stops taking new work and resolves, when all Agreements finished
+/// or drain timeout elapsed.
+#[derive(Message)]
+#[rtype(result = "Result<(), Error>")]
+pub struct Drain;
+
+/// Returns hardware resources capped by the active profile.
+#[derive(Message)]
+#[rtype(result = "Result<Resources, Error>")]
+pub struct GetResources;
+
 #[derive(Message)]
 #[rtype(result = "Result<(), Error>")]
 struct CreateOffers(pub OfferKind);
 
+/// Reloads ExeUnits registry after descriptors changed and recreates
+/// offers for affected presets. Running tasks aren't interrupted.
+#[derive(Message)]
+#[rtype(result = "()")]
+struct ReloadExeUnits {
+    /// Number of previous failed attempts.
+    attempt: u32,
+}
+
 /// Tests
 
 #[cfg(test)]
 mod tests {
+    use anyhow::anyhow;
+    use std::path::Path;
+    use std::time::Duration;
     use test_case::test_case;
     use ya_agreement_utils::{InfNodeInfo, NodeInfo, OfferTemplate};
     use ya_manifest_utils::manifest;
 
     use crate::{
-        execution::ExeUnitDesc, market::Preset, payments::AccountView,
-        provider_agent::ProviderAgent,
+        benchmark::{BenchmarkResults, CPU_MULTI_THREAD_PROPERTY},
+        execution::ExeUnitDesc,
+        market::Preset,
+        payments::AccountView,
+        provider_agent::{ExeUnitsManager, ProviderAgent, RELOAD_RETRIES, RELOAD_RETRY_DELAY},
     };
 
     #[test_case(true,  r#"["inet", "vpn", "manifest-support"]"#  ; "Supported with 'inet', 'vpn', and 'manifest-support'")]
@@ -614,6 +1222,7 @@ mod tests {
             fake.preset,
             fake.offer_template,
             fake.exeunit_desc,
+            None,
         )
         .expect("Failed to build offer");
 
@@ -626,6 +1235,42 @@ mod tests {
         assert_eq!(payload_manifest_prop, expected_manifest_suport);
     }
 
+    #[test]
+    fn benchmark_unit_pricing() {
+        let build = |score: Option<f64>| {
+            let mut fake = fake_data();
+            fake.preset.benchmark = Some(CPU_MULTI_THREAD_PROPERTY.to_string());
+            let benchmark = score.map(|score| BenchmarkResults {
+                cpu_single_thread: 100.0,
+                cpu_multi_thread: score,
+                memory_bandwidth: 1024.0,
+                storage_write: 256.0,
+                storage_read: 512.0,
+                timestamp: chrono::Utc::now(),
+            });
+            ProviderAgent::build_offer(
+                fake.node_info,
+                fake.inf_node_info,
+                &fake.accounts,
+                fake.preset,
+                fake.offer_template,
+                fake.exeunit_desc,
+                benchmark,
+            )
+            .map(|offer| offer.offer_definition.into_json())
+        };
+        let coeffs = |score| {
+            build(Some(score)).expect("Failed to build offer")
+                ["golem.com.pricing.model.linear.coeffs"]
+                .clone()
+        };
+
+        // Usage coefficient is a price per benchmark unit, initial price isn't scaled
+        assert_eq!(coeffs(400.0), serde_json::json!([400.0, 0.0]));
+        assert_eq!(coeffs(800.0), serde_json::json!([800.0, 0.0]));
+        assert!(build(None).is_err());
+    }
+
     /// Test utilities
 
     struct FakeData {
@@ -681,4 +1326,80 @@ mod tests {
             exeunit_desc,
         }
     }
+
+    fn write_descriptors(dir: &Path, names: &[&str]) {
+        let descs = names
+            .iter()
+            .map(|name| {
+                serde_json::json!({
+                    "name": name,
+                    "version": "0.1.0",
+                    "supervisor-path": format!("{}.sh", name),
+                })
+            })
+            .collect::<Vec<_>>();
+        let descs = serde_json::to_string(&descs).unwrap();
+        std::fs::write(dir.join("ya-runtimes.json"), descs).unwrap();
+    }
+
+    #[cfg(unix)]
+    fn write_supervisor(dir: &Path, name: &str, executable: bool) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join(format!("{}.sh", name));
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        let mode = if executable { 0o755 } else { 0o644 };
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[actix_rt::test]
+    async fn reload_exe_units() {
+        let dir = tempdir::TempDir::new("exe-units").unwrap();
+        let pattern = dir.path().join("ya-*.json");
+        let data_dir = dir.path().join("data");
+        let reload =
+            |current| ExeUnitsManager::load_changed(pattern.clone(), data_dir.clone(), current);
+
+        write_supervisor(dir.path(), "wasmtime", true);
+        write_descriptors(dir.path(), &["wasmtime"]);
+        let registry = ExeUnitsManager::load(pattern.clone(), data_dir.clone())
+            .await
+            .unwrap();
+        let mut manager = ExeUnitsManager::new(&pattern, &data_dir, registry);
+
+        // Unchanged runtime isn't tested again, so its broken supervisor doesn't matter.
+        write_supervisor(dir.path(), "wasmtime", false);
+        write_supervisor(dir.path(), "vm", true);
+        write_descriptors(dir.path(), &["wasmtime", "vm"]);
+        assert!(manager.start_reload());
+        let (registry, changed) = reload(manager.registry.clone()).await.unwrap();
+        assert_eq!(changed, vec!["vm"]);
+        assert!(manager.finish_reload(Ok(registry), 0).is_none());
+        assert!(manager.registry.find_exeunit("vm").is_ok());
+
+        // Descriptors changed during reload, so it is repeated right away.
+        write_supervisor(dir.path(), "gpu", false);
+        write_descriptors(dir.path(), &["wasmtime", "vm", "gpu"]);
+        assert!(manager.start_reload());
+        assert!(!manager.start_reload());
+        let result = reload(manager.registry.clone()).await.map(|(r, _)| r);
+        let (next, delay) = manager.finish_reload(result, 0).unwrap();
+        assert_eq!(next.attempt, 0);
+        assert_eq!(delay, Duration::from_secs(0));
+
+        // Failed test of changed runtime is retried, keeping previous registry.
+        assert!(manager.start_reload());
+        let result = reload(manager.registry.clone()).await.map(|(r, _)| r);
+        assert!(result.is_err());
+        let (next, delay) = manager.finish_reload(result, next.attempt).unwrap();
+        assert_eq!(next.attempt, 1);
+        assert_eq!(delay, RELOAD_RETRY_DELAY);
+        assert!(manager.registry.find_exeunit("gpu").is_err());
+
+        assert!(manager.start_reload());
+        let result = Err(anyhow!("Runtime test failure"));
+        assert!(manager.finish_reload(result, RELOAD_RETRIES).is_none());
+        assert!(manager.registry.find_exeunit("vm").is_ok());
+    }
 }
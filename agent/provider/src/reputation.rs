@@ -0,0 +1,380 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::OpenOptions;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use ya_client_model::NodeId;
+use ya_utils_path::SwapSave;
+
+use crate::startup_config::FileMonitor;
+
+/// Delay of saving records after a change, so that bursts of events are saved at once.
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// Payment behaviour of a Requestor observed by Payments.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RequestorEvent {
+    AgreementSigned,
+    /// DebitNote wasn't accepted before its deadline.
+    DebitNoteLate,
+    /// DebitNote wasn't paid before its deadline.
+    DebitNoteUnpaid,
+    /// Agreement was broken because of Requestor's behaviour.
+    AgreementBroken,
+    InvoiceIssued {
+        invoice_id: String,
+        due_date: DateTime<Utc>,
+    },
+    InvoiceSettled {
+        invoice_id: String,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestorRecord {
+    pub agreements: u64,
+    pub late_debit_notes: u64,
+    pub unpaid_debit_notes: u64,
+    pub broken_agreements: u64,
+    pub paid_invoices: u64,
+    /// Invoices waiting for payment with their due dates.
+    pub pending_invoices: BTreeMap<String, DateTime<Utc>>,
+    pub updated: Option<DateTime<Utc>>,
+}
+
+impl RequestorRecord {
+    fn apply(&mut self, event: RequestorEvent) {
+        match event {
+            RequestorEvent::AgreementSigned => self.agreements += 1,
+            RequestorEvent::DebitNoteLate => self.late_debit_notes += 1,
+            RequestorEvent::DebitNoteUnpaid => self.unpaid_debit_notes += 1,
+            RequestorEvent::AgreementBroken => self.broken_agreements += 1,
+            RequestorEvent::InvoiceIssued {
+                invoice_id,
+                due_date,
+            } => {
+                self.pending_invoices.insert(invoice_id, due_date);
+            }
+            RequestorEvent::InvoiceSettled { invoice_id } => {
+                if self.pending_invoices.remove(&invoice_id).is_some() {
+                    self.paid_invoices += 1;
+                }
+            }
+        }
+        self.updated = Some(Utc::now());
+    }
+
+    /// Invoices not paid before their due date.
+    pub fn unpaid_invoices(&self, now: DateTime<Utc>) -> u64 {
+        self.pending_invoices
+            .values()
+            .filter(|due_date| **due_date < now)
+            .count() as u64
+    }
+
+    /// Score in (0, 1] range. Requestors without history have score 1.
+    pub fn score(&self, now: DateTime<Utc>) -> f64 {
+        let penalty = self.late_debit_notes
+            + 2 * self.unpaid_debit_notes
+            + 2 * self.broken_agreements
+            + 4 * self.unpaid_invoices(now);
+        let credit = 1 + self.paid_invoices;
+        credit as f64 / (credit + penalty) as f64
+    }
+}
+
+/// Persistent per-Requestor record of payment behaviour.
+/// Changes are saved to the file on a separate thread.
+#[derive(Clone, Default)]
+pub struct ReputationStore {
+    path: Option<PathBuf>,
+    records: Arc<RwLock<BTreeMap<String, RequestorRecord>>>,
+    /// Notifies the thread saving changed records
+    changes: Option<mpsc::Sender<()>>,
+    /// Content of the file as last saved or reloaded by this store
+    saved: Arc<Mutex<String>>,
+}
+
+impl ReputationStore {
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        let records = if path.exists() {
+            Self::load(path)?
+        } else {
+            Default::default()
+        };
+        let mut store = ReputationStore {
+            path: Some(path.to_path_buf()),
+            records: Arc::new(RwLock::new(records)),
+            changes: None,
+            saved: Default::default(),
+        };
+        store.flush()?;
+
+        let (tx, rx) = mpsc::channel();
+        let saver = store.clone();
+        thread::Builder::new()
+            .name("reputation-saver".to_string())
+            .spawn(move || {
+                while rx.recv().is_ok() {
+                    thread::sleep(SAVE_DELAY);
+                    while rx.try_recv().is_ok() {}
+                    if let Err(e) = saver.flush() {
+                        log::warn!("Failed to save requestors reputation: {}", e);
+                    }
+                }
+            })?;
+        store.changes = Some(tx);
+        Ok(store)
+    }
+
+    fn load(path: &Path) -> Result<BTreeMap<String, RequestorRecord>> {
+        log::debug!("Loading requestors reputation from: {}", path.display());
+        let file = OpenOptions::new().read(true).open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    fn save(&self, records: &BTreeMap<String, RequestorRecord>) -> Result<()> {
+        if let Some(path) = &self.path {
+            let content = serde_json::to_string_pretty(records)?;
+            let mut saved = self.saved.lock().unwrap();
+            path.swap_save(&content)?;
+            *saved = content;
+        }
+        Ok(())
+    }
+
+    /// Merges changes made to the file by another process, e.g. `ya-provider reputation reset`.
+    /// Files written by this store are ignored. Records changed externally replace
+    /// in-memory ones, all other records keep changes not saved yet.
+    fn reload(&self) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut records = self.records.write().unwrap();
+        let mut saved = self.saved.lock().unwrap();
+        let content = std::fs::read_to_string(path)?;
+        if content == *saved {
+            return Ok(());
+        }
+
+        log::debug!("Reloading requestors reputation from: {}", path.display());
+        let external: BTreeMap<String, RequestorRecord> = serde_json::from_str(&content)?;
+        let base: BTreeMap<String, RequestorRecord> = if saved.is_empty() {
+            Default::default()
+        } else {
+            serde_json::from_str(&saved)?
+        };
+        let ids: BTreeSet<String> = base.keys().chain(external.keys()).cloned().collect();
+        for id in ids {
+            if base.get(&id) == external.get(&id) {
+                continue;
+            }
+            match external.get(&id) {
+                Some(record) => records.insert(id, record.clone()),
+                None => records.remove(&id),
+            };
+        }
+        *saved = content;
+
+        // Local changes not present in the file will be saved later
+        if let Some(changes) = &self.changes {
+            changes.send(()).ok();
+        }
+        Ok(())
+    }
+
+    /// Saves all records to the file.
+    pub fn flush(&self) -> Result<()> {
+        self.save(&self.records.read().unwrap())
+    }
+
+    pub fn record(&self, requestor_id: NodeId, event: RequestorEvent) {
+        log::debug!("Requestor [{}] reputation event: {:?}", requestor_id, event);
+        self.records
+            .write()
+            .unwrap()
+            .entry(requestor_id.to_string())
+            .or_default()
+            .apply(event);
+        if let Some(changes) = &self.changes {
+            changes.send(()).ok();
+        }
+    }
+
+    pub fn score(&self, requestor_id: &NodeId) -> f64 {
+        self.get(requestor_id)
+            .map(|record| record.score(Utc::now()))
+            .unwrap_or(1.0)
+    }
+
+    pub fn get(&self, requestor_id: &NodeId) -> Option<RequestorRecord> {
+        self.records
+            .read()
+            .unwrap()
+            .get(&requestor_id.to_string())
+            .cloned()
+    }
+
+    pub fn list(&self) -> Vec<(String, RequestorRecord)> {
+        self.records
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, record)| (id.clone(), record.clone()))
+            .collect()
+    }
+
+    /// Removes history of a single Requestor or all of them. Returns number of removed records.
+    pub fn reset(&self, requestor_id: Option<NodeId>) -> Result<usize> {
+        let mut records = self.records.write().unwrap();
+        let removed = match requestor_id {
+            Some(id) => records.remove(&id.to_string()).map(|_| 1).unwrap_or(0),
+            None => std::mem::take(&mut *records).len(),
+        };
+        self.save(&records)?;
+        Ok(removed)
+    }
+
+    /// Reloads records, when the file was modified by `ya-provider reputation` commands.
+    pub fn spawn_monitor(&self) -> Result<FileMonitor> {
+        let path = self
+            .path
+            .clone()
+            .ok_or_else(|| anyhow!("Reputation store isn't backed by a file"))?;
+        let store = self.clone();
+        let handler = move |p: PathBuf| {
+            if let Err(e) = store.reload() {
+                log::warn!("Error updating requestors reputation from {:?}: {:?}", p, e);
+            }
+        };
+        Ok(FileMonitor::spawn(path, FileMonitor::on_modified(handler))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn requestor() -> NodeId {
+        "0x979db95461652299c34e15df09441b8dfc4edf7a"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn score_without_history() {
+        let store = ReputationStore::default();
+        assert_eq!(store.score(&requestor()), 1.0);
+    }
+
+    #[test]
+    fn score_drops_on_bad_behaviour() {
+        let store = ReputationStore::default();
+        store.record(requestor(), RequestorEvent::AgreementSigned);
+        store.record(requestor(), RequestorEvent::DebitNoteLate);
+        store.record(requestor(), RequestorEvent::AgreementBroken);
+        assert_eq!(store.score(&requestor()), 0.25);
+
+        let record = store.get(&requestor()).unwrap();
+        assert_eq!(record.agreements, 1);
+        assert_eq!(record.late_debit_notes, 1);
+        assert_eq!(record.broken_agreements, 1);
+    }
+
+    #[test]
+    fn overdue_invoices_count_as_unpaid() {
+        let now = Utc::now();
+        let mut record = RequestorRecord::default();
+        record.apply(RequestorEvent::InvoiceIssued {
+            invoice_id: "paid".into(),
+            due_date: now - Duration::days(2),
+        });
+        record.apply(RequestorEvent::InvoiceIssued {
+            invoice_id: "overdue".into(),
+            due_date: now - Duration::days(1),
+        });
+        record.apply(RequestorEvent::InvoiceIssued {
+            invoice_id: "pending".into(),
+            due_date: now + Duration::days(1),
+        });
+        record.apply(RequestorEvent::InvoiceSettled {
+            invoice_id: "paid".into(),
+        });
+
+        assert_eq!(record.paid_invoices, 1);
+        assert_eq!(record.unpaid_invoices(now), 1);
+        assert_eq!(record.score(now), 2.0 / 6.0);
+    }
+
+    #[test]
+    fn reset_and_persist() {
+        let dir = tempdir::TempDir::new("reputation").unwrap();
+        let path = dir.path().join("reputation.json");
+
+        let store = ReputationStore::load_or_create(&path).unwrap();
+        store.record(requestor(), RequestorEvent::DebitNoteUnpaid);
+        store.flush().unwrap();
+
+        let loaded = ReputationStore::load_or_create(&path).unwrap();
+        assert_eq!(loaded.list().len(), 1);
+        assert_eq!(loaded.reset(Some(requestor())).unwrap(), 1);
+        assert_eq!(loaded.reset(None).unwrap(), 0);
+        assert!(ReputationStore::load_or_create(&path)
+            .unwrap()
+            .list()
+            .is_empty());
+    }
+
+    #[test]
+    fn record_during_save_survives_reload() {
+        let dir = tempdir::TempDir::new("reputation").unwrap();
+        let path = dir.path().join("reputation.json");
+
+        let store = ReputationStore::load_or_create(&path).unwrap();
+        store.record(requestor(), RequestorEvent::AgreementSigned);
+        store.flush().unwrap();
+        // Recorded after the save, before the monitor noticed the file change
+        store.record(requestor(), RequestorEvent::DebitNoteLate);
+        store.reload().unwrap();
+
+        let record = store.get(&requestor()).unwrap();
+        assert_eq!(record.agreements, 1);
+        assert_eq!(record.late_debit_notes, 1);
+    }
+
+    #[test]
+    fn reload_merges_external_reset() {
+        let dir = tempdir::TempDir::new("reputation").unwrap();
+        let path = dir.path().join("reputation.json");
+        let other: NodeId = "0x0000000000000000000000000000000000000001"
+            .parse()
+            .unwrap();
+
+        let store = ReputationStore::load_or_create(&path).unwrap();
+        store.record(requestor(), RequestorEvent::AgreementBroken);
+        store.flush().unwrap();
+
+        // `ya-provider reputation reset` run while the Provider is working
+        let cli = ReputationStore::load_or_create(&path).unwrap();
+        assert_eq!(cli.reset(Some(requestor())).unwrap(), 1);
+
+        store.record(other, RequestorEvent::AgreementSigned);
+        store.reload().unwrap();
+        assert!(store.get(&requestor()).is_none());
+        assert_eq!(store.get(&other).unwrap().agreements, 1);
+
+        // Next save doesn't bring the reset record back
+        store.flush().unwrap();
+        let loaded = ReputationStore::load_or_create(&path).unwrap();
+        assert!(loaded.get(&requestor()).is_none());
+        assert!(loaded.get(&other).is_some());
+    }
+}
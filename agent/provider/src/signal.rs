@@ -1,5 +1,8 @@
 pub(crate) type Signal = &'static str;
 
+/// Signal requesting to stop taking new work and exit after running Agreements finish.
+pub const DRAIN_SIGNAL: Signal = "SIGUSR1";
+
 use tokio::task::JoinHandle;
 use tokio::{
     select,
@@ -37,11 +40,13 @@ impl SignalMonitor {
         let mut sigterm = unix::signal(unix::SignalKind::terminate())?;
         let mut sigint = unix::signal(unix::SignalKind::interrupt())?;
         let mut sigquit = unix::signal(unix::SignalKind::quit())?;
+        let mut sigusr1 = unix::signal(unix::SignalKind::user_defined1())?;
         Ok(tokio::spawn(async move {
             select! {
                 _ = sigterm.recv() => stop_tx.send("SIGTERM").expect("Failed to handle SIGTERM event"),
                 _ = sigint.recv() => stop_tx.send("SIGINT").expect("Failed to handle SIGINT event"),
                 _ = sigquit.recv() => stop_tx.send("SIGQUIT").expect("Failed to handle SIGQUIT event"),
+                _ = sigusr1.recv() => stop_tx.send(DRAIN_SIGNAL).expect("Failed to handle SIGUSR1 event"),
             };
         }))
     }
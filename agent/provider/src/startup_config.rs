@@ -15,19 +15,25 @@ use ya_client::{cli::ApiOpts, model::node_id::NodeId};
 use ya_core_model::payment::local::NetworkName;
 use ya_utils_path::data_dir::DataDir;
 
+use crate::cli::benchmark::BenchmarkConfig;
 use crate::cli::clean::CleanConfig;
 use crate::cli::config::ConfigConfig;
+use crate::cli::drain::DrainConfig;
+use crate::cli::earnings::EarningsConfig;
 use crate::cli::exe_unit::ExeUnitsConfig;
 use crate::cli::keystore::KeystoreConfig;
 use crate::cli::pre_install::PreInstallConfig;
 pub use crate::cli::preset::PresetsConfig;
 use crate::cli::profile::ProfileConfig;
+use crate::cli::reputation::ReputationConfig;
 use crate::cli::rule::RuleCommand;
+use crate::cli::schedule::ScheduleConfig;
 use crate::cli::whitelist::WhitelistConfig;
 pub(crate) use crate::config::globals::GLOBALS_JSON;
 use crate::execution::{ExeUnitsRegistry, TaskRunnerConfig};
 use crate::market::config::MarketConfig;
-use crate::payments::PaymentsConfig;
+use crate::payments::{DynamicPricingConfig, PaymentsConfig};
+use crate::status_api::StatusApiConfig;
 use crate::tasks::config::TaskConfig;
 
 lazy_static::lazy_static! {
@@ -38,6 +44,9 @@ pub(crate) const DOMAIN_WHITELIST_JSON: &str = "domain_whitelist.json";
 pub(crate) const RULES_JSON: &str = "rules.json";
 pub(crate) const PRESETS_JSON: &str = "presets.json";
 pub(crate) const HARDWARE_JSON: &str = "hardware.json";
+pub(crate) const REPUTATION_JSON: &str = "reputation.json";
+pub(crate) const BENCHMARK_JSON: &str = "benchmark.json";
+pub(crate) const AGREEMENT_PRESETS_JSON: &str = "agreement-presets.json";
 pub(crate) const CERT_DIR: &str = "cert-dir";
 
 const DATA_DIR_ENV: &str = "DATA_DIR";
@@ -87,6 +96,12 @@ pub struct ProviderConfig {
     pub hardware_file: PathBuf,
     #[structopt(skip = RULES_JSON)]
     pub rules_file: PathBuf,
+    #[structopt(skip = REPUTATION_JSON)]
+    pub reputation_file: PathBuf,
+    #[structopt(skip = BENCHMARK_JSON)]
+    pub benchmark_file: PathBuf,
+    #[structopt(skip = AGREEMENT_PRESETS_JSON)]
+    pub agreement_presets_file: PathBuf,
     /// Max number of available CPU cores
     #[structopt(
         long,
@@ -185,6 +200,13 @@ pub struct RunConfig {
     pub payment: PaymentsConfig,
     #[structopt(flatten)]
     pub tasks: TaskConfig,
+    #[structopt(flatten)]
+    pub pricing: DynamicPricingConfig,
+    /// Maximum time to wait for running Agreements to finish, after drain was requested
+    #[structopt(long, env, parse(try_from_str = humantime::parse_duration), default_value = "1h")]
+    pub drain_timeout: Duration,
+    #[structopt(flatten)]
+    pub status: StatusApiConfig,
     ///changes log level from info to debug
     #[structopt(long)]
     pub debug: bool,
@@ -248,6 +270,16 @@ pub enum Commands {
     Clean(CleanConfig),
     /// Manage Rule config
     Rule(RuleCommand),
+    /// Manage availability windows, in which offers are published
+    Schedule(ScheduleConfig),
+    /// Inspect and reset Requestors' payment reputation
+    Reputation(ReputationConfig),
+    /// Stop taking new work and exit, when running Agreements finish
+    Drain(DrainConfig),
+    /// Measure CPU, memory and storage performance to publish in offers
+    Benchmark(BenchmarkConfig),
+    /// Summarize invoiced, accepted and settled amounts
+    Earnings(EarningsConfig),
 }
 
 #[derive(Debug)]
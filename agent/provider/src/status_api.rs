@@ -0,0 +1,233 @@
+use actix::{MailboxError, Recipient};
+use actix_web::dev::ServerHandle;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use anyhow::bail;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use structopt::StructOpt;
+
+use crate::hardware::Resources;
+use crate::market::provider_market::{ActiveOffer, GetActiveOffers};
+use crate::payments::{AgreementCosts, GetCostsSummaries};
+use crate::provider_agent::GetResources;
+use crate::tasks::task_manager::{AgreementStatus, GetAgreementsStatus};
+
+#[derive(StructOpt, Clone, Debug, Default)]
+#[structopt(rename_all = "kebab-case")]
+pub struct StatusApiConfig {
+    /// Serve provider status as JSON on given localhost address, e.g. 127.0.0.1:7466
+    #[structopt(long, env)]
+    pub status_api: Option<SocketAddr>,
+}
+
+/// Actors queried by status endpoints: TaskManager, ProviderMarket, Payments
+/// and ProviderAgent respectively.
+#[derive(Clone)]
+pub struct StatusSources {
+    pub agreements: Recipient<GetAgreementsStatus>,
+    pub offers: Recipient<GetActiveOffers>,
+    pub costs: Recipient<GetCostsSummaries>,
+    pub resources: Recipient<GetResources>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    agreements: Vec<AgreementStatus>,
+    offers: BTreeMap<String, Vec<ActiveOffer>>,
+    costs: Vec<AgreementCosts>,
+    resources: Resources,
+}
+
+/// Starts read-only HTTP server exposing provider state. Returns handle,
+/// which should be used to stop the server on shutdown.
+pub fn start(addr: SocketAddr, sources: StatusSources) -> anyhow::Result<ServerHandle> {
+    if !addr.ip().is_loopback() {
+        bail!(
+            "Status API can listen only on loopback address, got {}",
+            addr
+        );
+    }
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(sources.clone()))
+            .configure(routes)
+    })
+    .workers(1)
+    // Signals are handled by SignalMonitor.
+    .disable_signals()
+    .bind(addr)?
+    .run();
+
+    let handle = server.handle();
+    actix_rt::spawn(server);
+    log::info!("Status API listening on http://{}", addr);
+    Ok(handle)
+}
+
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/status", web::get().to(status))
+        .route("/agreements", web::get().to(agreements))
+        .route("/offers", web::get().to(offers))
+        .route("/costs", web::get().to(costs))
+        .route("/resources", web::get().to(resources));
+}
+
+async fn query<T>(
+    request: impl Future<Output = Result<anyhow::Result<T>, MailboxError>>,
+) -> actix_web::Result<T> {
+    request
+        .await
+        .map_err(ErrorInternalServerError)?
+        .map_err(ErrorInternalServerError)
+}
+
+async fn status(sources: web::Data<StatusSources>) -> actix_web::Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(Status {
+        agreements: query(sources.agreements.send(GetAgreementsStatus)).await?,
+        offers: query(sources.offers.send(GetActiveOffers)).await?,
+        costs: query(sources.costs.send(GetCostsSummaries)).await?,
+        resources: query(sources.resources.send(GetResources)).await?,
+    }))
+}
+
+async fn agreements(sources: web::Data<StatusSources>) -> actix_web::Result<HttpResponse> {
+    let agreements = query(sources.agreements.send(GetAgreementsStatus)).await?;
+    Ok(HttpResponse::Ok().json(agreements))
+}
+
+async fn offers(sources: web::Data<StatusSources>) -> actix_web::Result<HttpResponse> {
+    let offers = query(sources.offers.send(GetActiveOffers)).await?;
+    Ok(HttpResponse::Ok().json(offers))
+}
+
+async fn costs(sources: web::Data<StatusSources>) -> actix_web::Result<HttpResponse> {
+    let costs = query(sources.costs.send(GetCostsSummaries)).await?;
+    Ok(HttpResponse::Ok().json(costs))
+}
+
+async fn resources(sources: web::Data<StatusSources>) -> actix_web::Result<HttpResponse> {
+    let resources = query(sources.resources.send(GetResources)).await?;
+    Ok(HttpResponse::Ok().json(resources))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix::{Actor, Context, Handler};
+    use actix_web::test;
+    use serde_json::{json, Value};
+
+    /// Answers status queries with fixed values
+    struct Sources;
+
+    impl Actor for Sources {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<GetAgreementsStatus> for Sources {
+        type Result = anyhow::Result<Vec<AgreementStatus>>;
+
+        fn handle(&mut self, _: GetAgreementsStatus, _: &mut Context<Self>) -> Self::Result {
+            Ok(vec![AgreementStatus {
+                agreement_id: "agreement".to_string(),
+                state: "Computing".to_string(),
+                next_state: None,
+                expiration: None,
+                multi_activity: Some(true),
+            }])
+        }
+    }
+
+    impl Handler<GetActiveOffers> for Sources {
+        type Result = anyhow::Result<BTreeMap<String, Vec<ActiveOffer>>>;
+
+        fn handle(&mut self, _: GetActiveOffers, _: &mut Context<Self>) -> Self::Result {
+            Ok(BTreeMap::new())
+        }
+    }
+
+    impl Handler<GetCostsSummaries> for Sources {
+        type Result = anyhow::Result<Vec<AgreementCosts>>;
+
+        fn handle(&mut self, _: GetCostsSummaries, _: &mut Context<Self>) -> Self::Result {
+            Err(anyhow::anyhow!("payments unavailable"))
+        }
+    }
+
+    impl Handler<GetResources> for Sources {
+        type Result = anyhow::Result<Resources>;
+
+        fn handle(&mut self, _: GetResources, _: &mut Context<Self>) -> Self::Result {
+            Ok(Resources {
+                cpu_threads: 4,
+                mem_gib: 8.,
+                storage_gib: 100.,
+            })
+        }
+    }
+
+    fn sources() -> StatusSources {
+        let addr = Sources.start();
+        StatusSources {
+            agreements: addr.clone().recipient(),
+            offers: addr.clone().recipient(),
+            costs: addr.clone().recipient(),
+            resources: addr.recipient(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn agreements_response() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(sources()))
+                .configure(routes),
+        )
+        .await;
+
+        let request = test::TestRequest::get().uri("/agreements").to_request();
+        let body: Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(
+            body,
+            json!([{
+                "agreementId": "agreement",
+                "state": "Computing",
+                "nextState": null,
+                "expiration": null,
+                "multiActivity": true,
+            }])
+        );
+
+        let request = test::TestRequest::get().uri("/resources").to_request();
+        let body: Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["cpu_threads"], 4);
+    }
+
+    #[actix_rt::test]
+    async fn status_response() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(sources()))
+                .configure(routes),
+        )
+        .await;
+
+        let request = test::TestRequest::get().uri("/offers").to_request();
+        let body: Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body, json!({}));
+
+        // failure of any source fails the whole status
+        let request = test::TestRequest::get().uri("/status").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), 500);
+
+        let request = test::TestRequest::get().uri("/costs").to_request();
+        let response = test::call_service(&app, request).await;
+        assert!(response.status().is_server_error());
+    }
+}
@@ -1,4 +1,5 @@
 pub mod config;
+pub mod reservations;
 mod task_info;
 pub mod task_manager;
 mod task_state;
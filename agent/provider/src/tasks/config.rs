@@ -1,9 +1,13 @@
 use humantime;
 use structopt::StructOpt;
 
+use super::reservations::ReservationsConfig;
+
 /// Configuration for TaskManager actor.
 #[derive(StructOpt, Clone, Debug)]
 pub struct TaskConfig {
     #[structopt(long, env, parse(try_from_str = humantime::parse_duration), default_value = "90s")]
     pub idle_agreement_timeout: std::time::Duration,
+    #[structopt(flatten)]
+    pub reservations: ReservationsConfig,
 }
@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use structopt::StructOpt;
+
+use crate::hardware::{Resources, MIN_CAPS};
+
+#[derive(StructOpt, Clone, Debug)]
+pub struct ReservationsConfig {
+    /// Reserve resources requested by each Agreement and advertise
+    /// only remaining capacity in offers. Agreements run side by side as long as
+    /// their resources fit, unless `--max-simultaneous-agreements` is also set
+    #[structopt(long, env)]
+    pub reserve_resources: bool,
+    /// Ratio of hardware capacity, that can be reserved by Agreements,
+    /// e.g. 1.5 allows reserving 150% of resources
+    #[structopt(long, env, default_value = "1.0")]
+    pub overcommit_ratio: f64,
+}
+
+/// Resources requested by Demand constraints on `golem.inf.*` properties.
+/// Only lower bounds (`>=`, `>`, `=`) are requirements, `golem.inf.cpu.threads>N` meaning
+/// `floor(N) + 1` threads. Requestor accepts any branch
+/// of `|` clause, so the least demanding one is taken. Properties without constraints
+/// are assumed to need minimal amount.
+pub fn requested_resources(constraints: &str) -> Resources {
+    let mut filter = Filter { rest: constraints };
+    match filter.parse() {
+        Some(requested) => most(&requested, &MIN_CAPS),
+        None => {
+            log::warn!("Can't parse Demand constraints: {}", constraints);
+            MIN_CAPS
+        }
+    }
+}
+
+/// Parser of constraints filter, e.g. `(&(a>=1)(|(b=2)(!(c<3))))`.
+struct Filter<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Filter<'a> {
+    fn parse(&mut self) -> Option<Resources> {
+        self.expect('(')?;
+        let requested = match self.peek()? {
+            '&' => {
+                self.expect('&')?;
+                self.list()?.iter().fold(MIN_CAPS, |all, r| most(&all, r))
+            }
+            '|' => {
+                self.expect('|')?;
+                let branches = self.list()?;
+                let first = branches.first().copied().unwrap_or(MIN_CAPS);
+                branches.iter().fold(first, |any, r| least(&any, r))
+            }
+            '!' => {
+                self.expect('!')?;
+                self.parse()?;
+                MIN_CAPS
+            }
+            _ => {
+                let end = self.rest.find(')')?;
+                let requested = requirement(&self.rest[..end]);
+                self.rest = &self.rest[end..];
+                requested
+            }
+        };
+        self.expect(')')?;
+        Some(requested)
+    }
+
+    fn list(&mut self) -> Option<Vec<Resources>> {
+        let mut items = Vec::new();
+        while self.peek()? == '(' {
+            items.push(self.parse()?);
+        }
+        Some(items)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.rest = self.rest.trim_start();
+        self.rest.chars().next()
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        if self.peek()? != c {
+            return None;
+        }
+        self.rest = &self.rest[c.len_utf8()..];
+        Some(())
+    }
+}
+
+/// Resources required by single `property operator value` constraint.
+fn requirement(constraint: &str) -> Resources {
+    let mut requested = MIN_CAPS;
+    let split = match constraint.find(['<', '>', '=']) {
+        Some(split) => split,
+        None => return requested,
+    };
+    let (property, rest) = constraint.split_at(split);
+    let (lower_bound, strict, value) = match rest.strip_prefix(">=") {
+        Some(value) => (true, false, value),
+        None if rest.starts_with('<') => (false, false, rest),
+        None => (true, rest.starts_with('>'), &rest[1..]),
+    };
+    let value = match value.trim().parse::<f64>() {
+        Ok(value) if lower_bound => value,
+        _ => return requested,
+    };
+    match property.trim() {
+        "golem.inf.cpu.threads" if strict => requested.cpu_threads = value.floor() as i32 + 1,
+        "golem.inf.cpu.threads" => requested.cpu_threads = value.ceil() as i32,
+        "golem.inf.mem.gib" => requested.mem_gib = value,
+        "golem.inf.storage.gib" => requested.storage_gib = value,
+        _ => (),
+    }
+    requested
+}
+
+fn most(a: &Resources, b: &Resources) -> Resources {
+    Resources {
+        cpu_threads: a.cpu_threads.max(b.cpu_threads),
+        mem_gib: a.mem_gib.max(b.mem_gib),
+        storage_gib: a.storage_gib.max(b.storage_gib),
+    }
+}
+
+fn least(a: &Resources, b: &Resources) -> Resources {
+    Resources {
+        cpu_threads: a.cpu_threads.min(b.cpu_threads),
+        mem_gib: a.mem_gib.min(b.mem_gib),
+        storage_gib: a.storage_gib.min(b.storage_gib),
+    }
+}
+
+struct ReservationsState {
+    capacity: Resources,
+    reserved: HashMap<String, Resources>,
+}
+
+impl ReservationsState {
+    fn remaining(&self, overcommit_ratio: f64) -> Resources {
+        let total = Resources {
+            cpu_threads: (self.capacity.cpu_threads as f64 * overcommit_ratio).floor() as i32,
+            mem_gib: self.capacity.mem_gib * overcommit_ratio,
+            storage_gib: self.capacity.storage_gib * overcommit_ratio,
+        };
+        self.reserved
+            .values()
+            .fold(total, |remaining, reserved| remaining - *reserved)
+    }
+}
+
+/// Resources reserved by running Agreements, shared between negotiator, which
+/// reserves them on approval, TaskManager, which releases them, and ProviderAgent,
+/// which re-publishes offers when remaining capacity changes.
+#[derive(Clone)]
+pub struct Reservations {
+    enabled: bool,
+    overcommit_ratio: f64,
+    state: Arc<Mutex<ReservationsState>>,
+}
+
+impl Reservations {
+    pub fn new(config: &ReservationsConfig, capacity: Resources) -> Self {
+        Reservations {
+            enabled: config.reserve_resources,
+            overcommit_ratio: config.overcommit_ratio.max(0.0),
+            state: Arc::new(Mutex::new(ReservationsState {
+                capacity,
+                reserved: HashMap::new(),
+            })),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_capacity(&self, capacity: Resources) {
+        self.state.lock().unwrap().capacity = capacity;
+    }
+
+    /// Resources not reserved by any Agreement, including overcommit.
+    pub fn remaining(&self) -> Resources {
+        self.state.lock().unwrap().remaining(self.overcommit_ratio)
+    }
+
+    /// Resources to advertise in offers. Single Agreement can't get more than capacity.
+    pub fn advertised(&self) -> Resources {
+        let capacity = self.state.lock().unwrap().capacity;
+        self.remaining().cap(&capacity)
+    }
+
+    pub fn fits(&self, requested: &Resources) -> bool {
+        !self.enabled || fits(requested, &self.remaining())
+    }
+
+    /// Share of hardware capacity reserved by Agreements, for the most reserved resource.
+    pub fn utilization(&self) -> f64 {
+        let state = self.state.lock().unwrap();
+        let empty = Resources {
+            cpu_threads: 0,
+            mem_gib: 0.,
+            storage_gib: 0.,
+        };
+        let reserved = state
+            .reserved
+            .values()
+            .fold(empty, |total, reserved| total + *reserved);
+        let capacity = state.capacity;
+
+        [
+            ratio(reserved.cpu_threads as f64, capacity.cpu_threads as f64),
+            ratio(reserved.mem_gib, capacity.mem_gib),
+            ratio(reserved.storage_gib, capacity.storage_gib),
+        ]
+        .iter()
+        .copied()
+        .fold(0.0, f64::max)
+    }
+
+    /// Reserves resources for approved Agreement, unless they don't fit into remaining
+    /// capacity. Check and reservation are atomic, so concurrent approvals can't
+    /// overcommit. Requested resources are tracked for utilization also when
+    /// reservations are disabled, in which case they always fit.
+    pub fn try_reserve(&self, agreement_id: &str, requested: Resources) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let remaining = state.remaining(self.overcommit_ratio);
+        if self.enabled && !fits(&requested, &remaining) {
+            return false;
+        }
+        state.reserved.insert(agreement_id.to_string(), requested);
+        if self.enabled {
+            log::info!(
+                "Reserved {:?} for Agreement [{}]. Remaining: {:?}",
+                requested,
+                agreement_id,
+                remaining - requested
+            );
+        }
+        true
+    }
+
+    pub fn release(&self, agreement_id: &str) {
+        let released = self.state.lock().unwrap().reserved.remove(agreement_id);
+        if released.is_some() && self.enabled {
+            log::info!(
+                "Released resources of Agreement [{}]. Remaining: {:?}",
+                agreement_id,
+                self.remaining()
+            );
+        }
+    }
+}
+
+fn fits(requested: &Resources, remaining: &Resources) -> bool {
+    requested.cpu_threads <= remaining.cpu_threads
+        && requested.mem_gib <= remaining.mem_gib
+        && requested.storage_gib <= remaining.storage_gib
+}
+
+fn ratio(reserved: f64, capacity: f64) -> f64 {
+    if capacity <= 0.0 {
+        return 1.0;
+    }
+    (reserved / capacity).min(1.0)
+}
+
+impl Default for Reservations {
+    fn default() -> Self {
+        let config = ReservationsConfig {
+            reserve_resources: false,
+            overcommit_ratio: 1.0,
+        };
+        Reservations::new(&config, MIN_CAPS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reservations(overcommit_ratio: f64) -> Reservations {
+        let config = ReservationsConfig {
+            reserve_resources: true,
+            overcommit_ratio,
+        };
+        let capacity = Resources {
+            cpu_threads: 4,
+            mem_gib: 8.,
+            storage_gib: 100.,
+        };
+        Reservations::new(&config, capacity)
+    }
+
+    #[test]
+    fn parse_requested_resources() {
+        let requested = requested_resources(
+            "(&(golem.inf.mem.gib>=2.5)(golem.inf.cpu.threads>=2)(golem.srv.comp.expiration>1))",
+        );
+        assert_eq!(
+            requested,
+            Resources {
+                cpu_threads: 2,
+                mem_gib: 2.5,
+                storage_gib: MIN_CAPS.storage_gib,
+            }
+        );
+        assert_eq!(requested_resources("(golem.runtime.name=vm)"), MIN_CAPS);
+        assert_eq!(requested_resources(""), MIN_CAPS);
+    }
+
+    #[test]
+    fn parse_constraint_operators() {
+        // upper bounds and negations aren't requirements
+        let requested = requested_resources(
+            "(&
+              (golem.inf.mem.gib<=16)
+              (golem.inf.cpu.threads>3)
+              (!(golem.inf.storage.gib>=10))
+              (golem.inf.storage.gib<>5)
+            )",
+        );
+        assert_eq!(
+            requested,
+            Resources {
+                cpu_threads: 4,
+                mem_gib: MIN_CAPS.mem_gib,
+                storage_gib: MIN_CAPS.storage_gib,
+            }
+        );
+
+        // any branch of alternative is enough
+        let requested = requested_resources(
+            "(&(golem.inf.storage.gib=20)
+               (|(&(golem.inf.cpu.threads>=4)(golem.inf.mem.gib>=2))
+                 (&(golem.inf.cpu.threads>=2)(golem.inf.mem.gib>=8))))",
+        );
+        assert_eq!(
+            requested,
+            Resources {
+                cpu_threads: 2,
+                mem_gib: 2.,
+                storage_gib: 20.,
+            }
+        );
+        assert_eq!(requested_resources("(&(golem.inf.mem.gib>=2)"), MIN_CAPS);
+    }
+
+    #[test]
+    fn reserve_and_release() {
+        let reservations = reservations(1.0);
+        let half = Resources {
+            cpu_threads: 2,
+            mem_gib: 4.,
+            storage_gib: 50.,
+        };
+        assert!(reservations.fits(&half));
+        assert!(reservations.try_reserve("a", half));
+        assert!(reservations.fits(&half));
+        assert!(reservations.try_reserve("b", half));
+        assert!(!reservations.fits(&MIN_CAPS));
+        assert!(!reservations.try_reserve("c", MIN_CAPS));
+        assert_eq!(reservations.advertised(), MIN_CAPS);
+
+        reservations.release("a");
+        assert_eq!(reservations.remaining(), half);
+        assert_eq!(reservations.advertised(), half);
+    }
+
+    #[test]
+    fn overcommit() {
+        let reservations = reservations(1.5);
+        let full = Resources {
+            cpu_threads: 4,
+            mem_gib: 8.,
+            storage_gib: 100.,
+        };
+        assert!(reservations.try_reserve("a", full));
+        assert_eq!(reservations.remaining().cpu_threads, 2);
+        assert!(reservations.fits(&Resources {
+            cpu_threads: 2,
+            mem_gib: 4.,
+            storage_gib: 50.,
+        }));
+        assert!(!reservations.fits(&full));
+    }
+
+    #[test]
+    fn utilization() {
+        let reservations = reservations(1.0);
+        assert_eq!(reservations.utilization(), 0.0);
+        assert!(reservations.try_reserve(
+            "a",
+            Resources {
+                cpu_threads: 1,
+                mem_gib: 4.,
+                storage_gib: 10.,
+            },
+        ));
+        assert_eq!(reservations.utilization(), 0.5);
+        assert!(reservations.try_reserve(
+            "b",
+            Resources {
+                cpu_threads: 3,
+                mem_gib: 1.,
+                storage_gib: 10.,
+            },
+        ));
+        assert_eq!(reservations.utilization(), 1.0);
+        reservations.release("b");
+        assert_eq!(reservations.utilization(), 0.5);
+
+        // utilization is tracked also without reservations
+        let disabled = Reservations::default();
+        disabled.set_capacity(Resources {
+            cpu_threads: 4,
+            mem_gib: 8.,
+            storage_gib: 100.,
+        });
+        assert!(disabled.try_reserve(
+            "a",
+            Resources {
+                cpu_threads: 2,
+                mem_gib: 1.,
+                storage_gib: 1.,
+            },
+        ));
+        assert!(disabled.fits(&Resources {
+            cpu_threads: 4,
+            mem_gib: 8.,
+            storage_gib: 100.,
+        }));
+        assert_eq!(disabled.utilization(), 0.5);
+    }
+}
@@ -2,8 +2,9 @@
 
 use actix::prelude::*;
 use anyhow::{anyhow, bail, Error, Result};
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::future::TryFutureExt;
+use serde::Serialize;
 use std::collections::HashMap;
 
 use ya_std_utils::LogErr;
@@ -11,6 +12,7 @@ use ya_utils_actix::actix_handler::ResultTypeGetter;
 use ya_utils_actix::actix_signal::Subscribe;
 use ya_utils_actix::forward_actix_handler;
 
+use super::reservations::Reservations;
 use super::task_info::TaskInfo;
 use super::task_state::{AgreementState, TasksStates};
 use crate::execution::{ActivityDestroyed, CreateActivity, TaskRunner, TerminateActivity};
@@ -89,6 +91,27 @@ pub struct AgreementClosed {
 #[rtype(result = "Result<()>")]
 pub struct InitializeTaskManager;
 
+/// Returns number of Agreements in progress.
+#[derive(Message)]
+#[rtype(result = "Result<usize>")]
+pub struct GetActiveAgreements;
+
+/// Returns states of all Agreements known to TaskManager.
+#[derive(Message)]
+#[rtype(result = "Result<Vec<AgreementStatus>>")]
+pub struct GetAgreementsStatus;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgreementStatus {
+    pub agreement_id: String,
+    pub state: String,
+    /// State Agreement is transitioning to.
+    pub next_state: Option<String>,
+    pub expiration: Option<DateTime<Utc>>,
+    pub multi_activity: Option<bool>,
+}
+
 // =========================================== //
 // TaskManager internal messages
 // =========================================== //
@@ -133,6 +156,7 @@ pub struct TaskManager {
     tasks_props: HashMap<String, TaskInfo>,
 
     tasks_handles: HashMap<String, Vec<SpawnHandle>>,
+    reservations: Reservations,
 }
 
 impl TaskManager {
@@ -141,6 +165,7 @@ impl TaskManager {
         runner: Addr<TaskRunner>,
         payments: Addr<Payments>,
         config: TaskConfig,
+        reservations: Reservations,
     ) -> Result<TaskManager> {
         Ok(TaskManager {
             market,
@@ -150,6 +175,7 @@ impl TaskManager {
             tasks: TasksStates::new(),
             tasks_props: HashMap::new(),
             tasks_handles: HashMap::new(),
+            reservations,
         })
     }
 
@@ -221,6 +247,12 @@ impl TaskManager {
         msg: StartUpdateState,
         _ctx: &mut Context<Self>,
     ) -> Result<()> {
+        if matches!(
+            msg.new_state,
+            AgreementState::Closed | AgreementState::Broken { .. }
+        ) {
+            self.reservations.release(&msg.agreement_id);
+        }
         Ok(self
             .tasks
             .start_transition(&msg.agreement_id, msg.new_state)?)
@@ -236,6 +268,38 @@ impl TaskManager {
             .finish_transition(&msg.agreement_id, msg.new_state)?)
     }
 
+    fn active_agreements(
+        &mut self,
+        _msg: GetActiveAgreements,
+        _ctx: &mut Context<Self>,
+    ) -> Result<usize> {
+        Ok(self.tasks.count_active())
+    }
+
+    fn agreements_status(
+        &mut self,
+        _msg: GetAgreementsStatus,
+        _ctx: &mut Context<Self>,
+    ) -> Result<Vec<AgreementStatus>> {
+        let mut agreements = self
+            .tasks
+            .list()
+            .into_iter()
+            .map(|(agreement_id, state, next_state)| {
+                let props = self.tasks_props.get(&agreement_id);
+                AgreementStatus {
+                    expiration: props.map(|props| props.expiration),
+                    multi_activity: props.map(|props| props.multi_activity),
+                    state: state.to_string(),
+                    next_state: next_state.map(|state| state.to_string()),
+                    agreement_id,
+                }
+            })
+            .collect::<Vec<_>>();
+        agreements.sort_by(|a, b| a.agreement_id.cmp(&b.agreement_id));
+        Ok(agreements)
+    }
+
     fn async_context(&self, ctx: &mut Context<Self>) -> TaskManagerAsyncContext {
         TaskManagerAsyncContext {
             runner: self.runner.clone(),
@@ -271,6 +335,8 @@ forward_actix_handler!(
     ScheduleIdleExpiration,
     schedule_idle_expiration
 );
+forward_actix_handler!(TaskManager, GetActiveAgreements, active_agreements);
+forward_actix_handler!(TaskManager, GetAgreementsStatus, agreements_status);
 forward_actix_handler!(TaskManager, StartUpdateState, start_update_agreement_state);
 forward_actix_handler!(
     TaskManager,
@@ -203,6 +203,25 @@ impl TasksStates {
         }
     }
 
+    /// Number of Agreements, which aren't finalized or being finalized.
+    pub fn count_active(&self) -> usize {
+        self.tasks
+            .keys()
+            .filter(|agreement_id| !self.is_agreement_finalized(agreement_id))
+            .count()
+    }
+
+    /// Current state of every Agreement and state it is transitioning to.
+    pub fn list(&self) -> Vec<(String, AgreementState, Option<AgreementState>)> {
+        self.tasks
+            .values()
+            .map(|task| {
+                let Transition(state, next) = task.state.clone();
+                (task.agreement_id.clone(), state, next)
+            })
+            .collect()
+    }
+
     /// No Activity has been created for this Agreement
     pub fn not_active(&self, agreement_id: &str) -> bool {
         if let Ok(task_state) = self.get_state(agreement_id) {
@@ -1,5 +1,6 @@
 use structopt::StructOpt;
 use ya_core_model::activity::local as acm;
+use ya_core_model::activity::{self, exeunit};
 use ya_core_model::identity as idm;
 use ya_core_model::identity::IdentityInfo;
 use ya_service_api::{CliCtx, CommandOutput};
@@ -12,6 +13,24 @@ pub enum ActivityCli {
         #[structopt(long)]
         id: Option<String>,
     },
+    /// Capture network traffic of a running activity to pcapng files
+    Capture(CaptureCommand),
+}
+
+#[derive(StructOpt, Debug)]
+pub enum CaptureCommand {
+    /// Start capturing VPN and outbound internet traffic
+    Start {
+        activity_id: String,
+        /// File size in bytes, after which the capture continues in a new file
+        #[structopt(long)]
+        max_file_size: Option<u64>,
+        /// Number of kept files. The oldest ones are removed.
+        #[structopt(long)]
+        max_files: Option<usize>,
+    },
+    /// Stop capturing and list capture files
+    Stop { activity_id: String },
 }
 
 impl ActivityCli {
@@ -43,6 +62,28 @@ impl ActivityCli {
 
                 CommandOutput::object(result)
             }
+            ActivityCli::Capture(CaptureCommand::Start {
+                activity_id,
+                max_file_size,
+                max_files,
+            }) => {
+                let dir = bus::service(exeunit::local_bus_id(&activity_id))
+                    .send(activity::StartCapture {
+                        activity_id,
+                        max_file_size,
+                        max_files,
+                    })
+                    .await??;
+
+                CommandOutput::object(dir)
+            }
+            ActivityCli::Capture(CaptureCommand::Stop { activity_id }) => {
+                let files = bus::service(exeunit::local_bus_id(&activity_id))
+                    .send(activity::StopCapture { activity_id })
+                    .await??;
+
+                CommandOutput::object(files)
+            }
         }
     }
 }
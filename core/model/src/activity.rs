@@ -30,6 +30,11 @@ pub mod exeunit {
     pub fn network_id(network_id: &str) -> String {
         format!("/public/vpn/{}", network_id)
     }
+
+    /// Local exeunit bus address for given `activity_id`, not reachable by remote nodes.
+    pub fn local_bus_id(activity_id: &str) -> String {
+        format!("/local/exeunit/{}", activity_id)
+    }
 }
 
 // --------
@@ -155,6 +160,15 @@ pub enum VpnControl {
         network_id: String,
         node_ids: HashSet<String>,
     },
+    SetFirewall {
+        network_id: String,
+        firewall: VpnFirewall,
+    },
+    /// Replaces hostnames of network members
+    SetHosts {
+        network_id: String,
+        hosts: HashMap<String, String>, // hostname -> IP
+    },
 }
 
 impl VpnControl {
@@ -179,6 +193,157 @@ impl RpcMessage for VpnControl {
     type Error = RpcMessageError;
 }
 
+/// Starts capturing VPN and outbound internet traffic of an activity to pcapng files,
+/// replacing the ongoing capture. Returns the directory of capture files.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartCapture {
+    pub activity_id: String,
+    /// File size in bytes, after which the capture continues in a new file
+    pub max_file_size: Option<u64>,
+    /// Number of kept files. The oldest ones are removed.
+    pub max_files: Option<usize>,
+}
+
+impl RpcMessage for StartCapture {
+    const ID: &'static str = "StartCapture";
+    type Item = String;
+    type Error = RpcMessageError;
+}
+
+/// Stops capturing traffic of an activity. Returns paths of the capture files.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StopCapture {
+    pub activity_id: String,
+}
+
+impl RpcMessage for StopCapture {
+    const ID: &'static str = "StopCapture";
+    type Item = Vec<String>;
+    type Error = RpcMessageError;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VpnFirewallAction {
+    #[default]
+    Allow,
+    Deny,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VpnProtocol {
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+impl VpnProtocol {
+    /// Matches an IP protocol number. `Icmp` matches both ICMP and ICMPv6.
+    pub fn matches(&self, protocol: u8) -> bool {
+        match self {
+            VpnProtocol::Tcp => protocol == 6,
+            VpnProtocol::Udp => protocol == 17,
+            VpnProtocol::Icmp => protocol == 1 || protocol == 58,
+        }
+    }
+}
+
+/// Inclusive port range
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VpnPortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl VpnPortRange {
+    pub fn contains(&self, port: u16) -> bool {
+        self.start <= port && port <= self.end
+    }
+}
+
+/// Traffic rule between network members. Missing fields match any value.
+/// `ports` match the destination port of TCP and UDP packets.
+///
+/// Rules match packets initiating a flow. Replies within flows accepted by the rules
+/// are tracked and allowed by the enforcing side.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VpnFirewallRule {
+    pub action: VpnFirewallAction,
+    /// Source node id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// Destination node id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<VpnProtocol>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ports: Option<VpnPortRange>,
+}
+
+impl VpnFirewallRule {
+    fn matches(&self, src: &str, dst: &str, protocol: u8, ports: Option<(u16, u16)>) -> bool {
+        let node = |rule: &Option<String>, id: &str| match rule {
+            Some(rule) => rule.eq_ignore_ascii_case(id),
+            None => true,
+        };
+        let port = |port: Option<u16>| match (&self.ports, port) {
+            (Some(range), Some(port)) => range.contains(port),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        if !self.protocol.map(|p| p.matches(protocol)).unwrap_or(true) {
+            return false;
+        }
+
+        node(&self.from, src) && node(&self.to, dst) && port(ports.map(|p| p.1))
+    }
+}
+
+/// Traffic rules of a single network. Rules are evaluated in order,
+/// the first matching one decides.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VpnFirewall {
+    /// Action taken when no rule matches
+    #[serde(default)]
+    pub default_action: VpnFirewallAction,
+    #[serde(default)]
+    pub rules: Vec<VpnFirewallRule>,
+}
+
+impl VpnFirewall {
+    /// Checks an IP packet sent from node `src` to node `dst`.
+    /// `ports` are the source and destination ports of TCP and UDP packets.
+    pub fn allows(&self, src: &str, dst: &str, protocol: u8, ports: Option<(u16, u16)>) -> bool {
+        let action = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(src, dst, protocol, ports))
+            .map(|rule| rule.action)
+            .unwrap_or(self.default_action);
+        action == VpnFirewallAction::Allow
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.ports)
+            .find(|range| range.start > range.end)
+        {
+            Some(range) => Err(format!("invalid port range: {}-{}", range.start, range.end)),
+            None => Ok(()),
+        }
+    }
+}
+
 /// Network data
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -401,3 +566,83 @@ pub enum RpcMessageError {
     #[error("Timeout")]
     Timeout,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCP: u8 = 6;
+    const UDP: u8 = 17;
+
+    fn rule(
+        action: VpnFirewallAction,
+        from: Option<&str>,
+        to: Option<&str>,
+        protocol: Option<VpnProtocol>,
+        ports: Option<(u16, u16)>,
+    ) -> VpnFirewallRule {
+        VpnFirewallRule {
+            action,
+            from: from.map(ToString::to_string),
+            to: to.map(ToString::to_string),
+            protocol,
+            ports: ports.map(|(start, end)| VpnPortRange { start, end }),
+        }
+    }
+
+    #[test]
+    fn firewall_rules() {
+        let firewall = VpnFirewall {
+            default_action: VpnFirewallAction::Deny,
+            rules: vec![
+                rule(
+                    VpnFirewallAction::Deny,
+                    None,
+                    Some("0xDB"),
+                    Some(VpnProtocol::Tcp),
+                    Some((8080, 8080)),
+                ),
+                rule(
+                    VpnFirewallAction::Allow,
+                    Some("0xa"),
+                    Some("0xdb"),
+                    Some(VpnProtocol::Tcp),
+                    Some((5432, 9000)),
+                ),
+                rule(
+                    VpnFirewallAction::Allow,
+                    None,
+                    None,
+                    Some(VpnProtocol::Udp),
+                    None,
+                ),
+            ],
+        };
+
+        assert!(firewall.allows("0xa", "0xdb", TCP, Some((40000, 5432))));
+        // replies are not matched by rules
+        assert!(!firewall.allows("0xdb", "0xa", TCP, Some((5432, 40000))));
+        // admin port
+        assert!(!firewall.allows("0xa", "0xdb", TCP, Some((40000, 8080))));
+        // other node
+        assert!(!firewall.allows("0xb", "0xdb", TCP, Some((40000, 5432))));
+        // protocol
+        assert!(firewall.allows("0xb", "0xdb", UDP, Some((53, 53))));
+        assert!(!firewall.allows("0xa", "0xdb", 1, None));
+
+        assert!(VpnFirewall::default().allows("0xa", "0xb", TCP, None));
+    }
+
+    #[test]
+    fn validate_firewall() {
+        let mut firewall = VpnFirewall::default();
+        firewall.rules.push(rule(
+            VpnFirewallAction::Deny,
+            None,
+            None,
+            None,
+            Some((10, 1)),
+        ));
+        assert!(firewall.validate().is_err());
+    }
+}
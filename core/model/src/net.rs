@@ -136,7 +136,7 @@ pub mod local {
     #[serde(rename_all = "camelCase")]
     pub struct Status {}
 
-    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[derive(Clone, Debug, Default, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct StatusMetrics {
         pub tx_total: usize,
@@ -147,6 +147,30 @@ pub mod local {
         pub rx_avg: f32,
     }
 
+    /// Number of inbound messages rejected or dropped due to rate limits
+    #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ThrottleStats {
+        pub requests: u64,
+        pub pushes: u64,
+        pub broadcasts: u64,
+        /// Number of nodes currently exceeding any of the limits
+        pub nodes: usize,
+    }
+
+    /// Relay server candidate, ordered by preference
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct RelayInfo {
+        pub address: String,
+        /// Whether the Node is currently connected through this relay
+        pub active: bool,
+        /// Last measured round trip time
+        pub latency: Option<Duration>,
+        /// Number of failed connections or health checks
+        pub failures: u32,
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct StatusResponse {
@@ -155,6 +179,13 @@ pub mod local {
         pub public_address: Option<SocketAddr>,
         pub sessions: usize,
         pub metrics: StatusMetrics,
+        #[serde(default)]
+        pub throttled: ThrottleStats,
+        #[serde(default)]
+        pub relays: Vec<RelayInfo>,
+        /// Number of times the Node switched to another relay
+        #[serde(default)]
+        pub relay_switches: u64,
     }
 
     impl RpcMessage for Status {
@@ -186,6 +217,55 @@ pub mod local {
         pub metrics: StatusMetrics,
     }
 
+    #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Stats {}
+
+    impl RpcMessage for Stats {
+        const ID: &'static str = "Stats";
+        type Item = StatsResponse;
+        type Error = StatusError;
+    }
+
+    /// Traffic counters. Rates are in bytes per second,
+    /// `current` over the last 10 seconds and `avg` over the last minute.
+    #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct TrafficMetrics {
+        pub tx_total: u64,
+        pub tx_messages: u64,
+        pub tx_current: f32,
+        pub tx_avg: f32,
+        pub rx_total: u64,
+        pub rx_messages: u64,
+        pub rx_current: f32,
+        pub rx_avg: f32,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct NodeTraffic {
+        pub node_id: NodeId,
+        #[serde(flatten)]
+        pub metrics: TrafficMetrics,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ServiceTraffic {
+        /// Public service address, or `broadcast/<topic>`
+        pub service: String,
+        #[serde(flatten)]
+        pub metrics: TrafficMetrics,
+    }
+
+    #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct StatsResponse {
+        pub nodes: Vec<NodeTraffic>,
+        pub services: Vec<ServiceTraffic>,
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
     #[serde(rename_all = "camelCase")]
     pub struct Sockets {}
@@ -317,6 +397,95 @@ pub mod local {
         type Item = Vec<NodeId>;
         type Error = StatusError;
     }
+
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        Serialize,
+        Deserialize,
+        Eq,
+        PartialEq,
+        Hash,
+        strum_macros::Display,
+        strum_macros::EnumString,
+        strum_macros::EnumVariantNames,
+    )]
+    #[serde(rename_all = "camelCase")]
+    #[strum(serialize_all = "lowercase")]
+    pub enum AclAction {
+        Allow,
+        Deny,
+    }
+
+    /// Access rule for public services exposed to other nodes.
+    /// Missing `node_id` or `service` match any value.
+    #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+    #[serde(rename_all = "camelCase")]
+    pub struct AclRule {
+        pub action: AclAction,
+        pub node_id: Option<NodeId>,
+        /// Public service address prefix, e.g. `/public/payment`
+        pub service: Option<String>,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct AclResponse {
+        /// Action taken when no rule matches
+        pub default_action: AclAction,
+        pub rules: Vec<AclRule>,
+        /// Number of requests denied since startup
+        pub denied: u64,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+    #[serde(rename_all = "camelCase")]
+    pub struct AclList {}
+
+    impl RpcMessage for AclList {
+        const ID: &'static str = "AclList";
+        type Item = AclResponse;
+        type Error = GenericNetError;
+    }
+
+    /// Appends a rule to the access list. Rules are evaluated in order,
+    /// the first matching one decides.
+    #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+    #[serde(rename_all = "camelCase")]
+    pub struct AclAdd {
+        pub rule: AclRule,
+    }
+
+    impl RpcMessage for AclAdd {
+        const ID: &'static str = "AclAdd";
+        type Item = AclResponse;
+        type Error = GenericNetError;
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+    #[serde(rename_all = "camelCase")]
+    pub struct AclRemove {
+        pub index: usize,
+    }
+
+    impl RpcMessage for AclRemove {
+        const ID: &'static str = "AclRemove";
+        type Item = AclResponse;
+        type Error = GenericNetError;
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+    #[serde(rename_all = "camelCase")]
+    pub struct AclSetDefault {
+        pub action: AclAction,
+    }
+
+    impl RpcMessage for AclSetDefault {
+        const ID: &'static str = "AclSetDefault";
+        type Item = AclResponse;
+        type Error = GenericNetError;
+    }
 }
 
 /// For documentation check local::GsbPing
@@ -7,6 +7,7 @@ use std::time::Duration;
 use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use humantime::format_duration;
 use structopt::*;
+use strum::VariantNames;
 use ya_client_model::NodeId;
 
 use ya_core_model::net::local as model;
@@ -23,6 +24,12 @@ pub enum NetCommand {
     Sessions {},
     /// List virtual sockets
     Sockets {},
+    /// Show traffic statistics per remote node
+    Stats {
+        /// Show statistics per public service address instead
+        #[structopt(long)]
+        services: bool,
+    },
     /// Find node
     Find {
         /// Node information to query for
@@ -44,6 +51,46 @@ pub enum NetCommand {
     Disconnect { node_id: String },
     /// List current neighbors of this Node.
     ListNeighbors { size: u32 },
+    /// Manage access to public services from other Nodes
+    Acl(AclCommand),
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+/// Rules are evaluated in order; the first matching rule decides
+pub enum AclCommand {
+    /// List access rules
+    List {},
+    /// Append a rule allowing access
+    Allow {
+        /// Node to match. If None, all Nodes are matched.
+        #[structopt(long)]
+        node: Option<NodeId>,
+        /// Public service address prefix to match, e.g. /public/payment.
+        /// If None, all services are matched.
+        #[structopt(long)]
+        service: Option<String>,
+    },
+    /// Append a rule denying access
+    Deny {
+        /// Node to match. If None, all Nodes are matched.
+        #[structopt(long)]
+        node: Option<NodeId>,
+        /// Public service address prefix to match, e.g. /public/payment.
+        /// If None, all services are matched.
+        #[structopt(long)]
+        service: Option<String>,
+    },
+    /// Remove a rule
+    Remove {
+        /// Rule index, as displayed by `list`
+        index: usize,
+    },
+    /// Set the action taken when no rule matches
+    Default {
+        #[structopt(possible_values = model::AclAction::VARIANTS)]
+        action: model::AclAction,
+    },
 }
 
 impl NetCommand {
@@ -66,7 +113,20 @@ impl NetCommand {
                         "inKiBps": to_kib(status.metrics.rx_current, is_json),
                         "inAvgKiBps": to_kib(status.metrics.rx_avg, is_json),
                         "inMib": to_mib(status.metrics.rx_total, is_json),
-                    }
+                    },
+                    "throttled": {
+                        "requests": status.throttled.requests,
+                        "pushes": status.throttled.pushes,
+                        "broadcasts": status.throttled.broadcasts,
+                        "nodes": status.throttled.nodes,
+                    },
+                    "relays": status.relays.iter().map(|relay| serde_json::json!({
+                        "address": relay.address,
+                        "active": relay.active,
+                        "latencyMs": relay.latency.map(|latency| latency.as_millis()),
+                        "failures": relay.failures,
+                    })).collect::<Vec<_>>(),
+                    "relaySwitches": status.relay_switches,
                 }))
             }
             NetCommand::Sessions {} => {
@@ -148,6 +208,62 @@ impl NetCommand {
                 }
                 .into())
             }
+            NetCommand::Stats { services } => {
+                let stats: model::StatsResponse = bus::service(model::BUS_ID)
+                    .send(model::Stats {})
+                    .await
+                    .map_err(anyhow::Error::msg)??;
+
+                let (key, rows) = match services {
+                    true => (
+                        "service",
+                        stats
+                            .services
+                            .into_iter()
+                            .map(|s| (s.service, s.metrics))
+                            .collect::<Vec<_>>(),
+                    ),
+                    false => (
+                        "nodeId",
+                        stats
+                            .nodes
+                            .into_iter()
+                            .map(|n| (n.node_id.to_string(), n.metrics))
+                            .collect(),
+                    ),
+                };
+
+                Ok(ResponseTable {
+                    columns: vec![
+                        key.into(),
+                        "out [KiB/s]".into(),
+                        "in [KiB/s]".into(),
+                        "out avg [KiB/s]".into(),
+                        "in avg [KiB/s]".into(),
+                        "out [MiB]".into(),
+                        "in [MiB]".into(),
+                        "out msgs".into(),
+                        "in msgs".into(),
+                    ],
+                    values: rows
+                        .into_iter()
+                        .map(|(key, m)| {
+                            serde_json::json! {[
+                                key,
+                                to_kib(m.tx_current, is_json),
+                                to_kib(m.rx_current, is_json),
+                                to_kib(m.tx_avg, is_json),
+                                to_kib(m.rx_avg, is_json),
+                                to_mib(m.tx_total as usize, is_json),
+                                to_mib(m.rx_total as usize, is_json),
+                                m.tx_messages,
+                                m.rx_messages,
+                            ]}
+                        })
+                        .collect(),
+                }
+                .into())
+            }
             NetCommand::Find { node_id } => {
                 let node: model::FindNodeResponse = bus::service(model::BUS_ID)
                     .send(model::FindNode { node_id })
@@ -224,7 +340,76 @@ impl NetCommand {
                     .map_err(anyhow::Error::msg)??;
                 CommandOutput::object(serde_json::json!(list))
             }
+            NetCommand::Acl(command) => command.run_command(is_json).await,
+        }
+    }
+}
+
+impl AclCommand {
+    async fn run_command(self, is_json: bool) -> anyhow::Result<CommandOutput> {
+        let service = bus::service(model::BUS_ID);
+        let response = match self {
+            AclCommand::List {} => service.send(model::AclList {}).await,
+            AclCommand::Allow { node, service: s } => {
+                let rule = acl_rule(model::AclAction::Allow, node, s);
+                service.send(model::AclAdd { rule }).await
+            }
+            AclCommand::Deny { node, service: s } => {
+                let rule = acl_rule(model::AclAction::Deny, node, s);
+                service.send(model::AclAdd { rule }).await
+            }
+            AclCommand::Remove { index } => service.send(model::AclRemove { index }).await,
+            AclCommand::Default { action } => service.send(model::AclSetDefault { action }).await,
+        }
+        .map_err(anyhow::Error::msg)??;
+
+        if is_json {
+            return CommandOutput::object(response);
         }
+
+        let any = || "*".to_string();
+        let mut values = response
+            .rules
+            .into_iter()
+            .enumerate()
+            .map(|(idx, rule)| {
+                serde_json::json! {[
+                    idx.to_string(),
+                    rule.action.to_string(),
+                    rule.node_id.map(|id| id.to_string()).unwrap_or_else(any),
+                    rule.service.unwrap_or_else(any),
+                ]}
+            })
+            .collect::<Vec<_>>();
+        values.push(serde_json::json! {[
+            "default",
+            response.default_action.to_string(),
+            any(),
+            any(),
+        ]});
+
+        Ok(ResponseTable {
+            columns: vec![
+                "#".into(),
+                "action".into(),
+                "nodeId".into(),
+                "service".into(),
+            ],
+            values,
+        }
+        .with_header(format!("Denied requests: {}", response.denied)))
+    }
+}
+
+fn acl_rule(
+    action: model::AclAction,
+    node_id: Option<NodeId>,
+    service: Option<String>,
+) -> model::AclRule {
+    model::AclRule {
+        action,
+        node_id,
+        service,
     }
 }
 
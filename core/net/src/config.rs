@@ -1,3 +1,6 @@
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::Duration;
 use structopt::StructOpt;
 use strum::VariantNames;
@@ -27,6 +30,8 @@ pub enum NetType {
     Central,
     #[cfg_attr(not(feature = "central-net"), default)]
     Hybrid,
+    /// Direct sessions with peers discovered in the local network, without a relay server
+    Lan,
 }
 
 #[derive(StructOpt, Clone)]
@@ -34,8 +39,19 @@ pub enum NetType {
 pub struct Config {
     #[structopt(env = "YA_NET_TYPE", possible_values = NetType::VARIANTS, default_value = NetType::default().into())]
     pub net_type: NetType,
+    /// Relay server `host:port`, or a comma separated list of relays to fail over to.
+    /// Resolved from `_net_relay._udp` SRV records when not set
     #[structopt(env = "YA_NET_RELAY_HOST")]
     pub host: Option<String>,
+    /// Interval of relay server connectivity checks
+    #[structopt(env = "YA_NET_RELAY_CHECK_INTERVAL", parse(try_from_str = humantime::parse_duration), default_value = "10s")]
+    pub relay_check_interval: Duration,
+    /// Time without a response from the relay server, after which the next relay is used
+    #[structopt(env = "YA_NET_RELAY_TIMEOUT", parse(try_from_str = humantime::parse_duration), default_value = "30s")]
+    pub relay_timeout: Duration,
+    /// Interval of latency measurements of all relay servers, which order relay failover
+    #[structopt(env = "YA_NET_RELAY_PROBE_INTERVAL", parse(try_from_str = humantime::parse_duration), default_value = "5min")]
+    pub relay_probe_interval: Duration,
     #[structopt(env = "YA_NET_BIND_URL", default_value = "udp://0.0.0.0:11500")]
     pub bind_url: Url,
     #[structopt(env = "YA_NET_BROADCAST_SIZE", default_value = "10")]
@@ -44,6 +60,80 @@ pub struct Config {
     pub session_expiration: Duration,
     #[structopt(env = "YA_NET_SESSION_REQUEST_TIMEOUT", parse(try_from_str = humantime::parse_duration), default_value = "3s")]
     pub session_request_timeout: Duration,
+    /// Access list of public services, managed with `yagna net acl`.
+    /// Defaults to `net-acl.json` in the data directory
+    #[structopt(env = "YA_NET_ACL_FILE")]
+    pub acl_file: Option<PathBuf>,
+    /// Inbound requests allowed per remote node, as `<per second>[:<burst>]` or `off`.
+    /// Requests over the limit are delayed, and rejected only after queuing for 10s.
+    /// Disabled by default, as a single gftp download alone makes hundreds of requests per second
+    #[structopt(env = "YA_NET_RATE_LIMIT_REQUEST", default_value = "off")]
+    pub rate_limit_request: RateLimit,
+    /// Inbound pushes (requests without a reply) allowed per remote node, delayed like requests
+    #[structopt(env = "YA_NET_RATE_LIMIT_PUSH", default_value = "off")]
+    pub rate_limit_push: RateLimit,
+    /// Inbound broadcasts allowed per remote node. Broadcasts over the limit are dropped
+    #[structopt(env = "YA_NET_RATE_LIMIT_BROADCAST", default_value = "20:50")]
+    pub rate_limit_broadcast: RateLimit,
+    /// Export per service traffic counters (`net.traffic.*`) through metrics
+    #[structopt(
+        env = "YA_NET_STATS_METRICS",
+        parse(try_from_str),
+        default_value = "false"
+    )]
+    pub stats_metrics: bool,
+    /// LAN net: listen address for direct sessions with other Nodes.
+    /// Sessions are authenticated with identity keys, but not encrypted
+    #[structopt(env = "YA_NET_LAN_LISTEN", default_value = "0.0.0.0:11501")]
+    pub lan_listen: SocketAddr,
+    /// LAN net: multicast group (or broadcast address) and port of peer announcements
+    #[structopt(env = "YA_NET_LAN_DISCOVERY", default_value = "239.255.76.67:11502")]
+    pub lan_discovery: SocketAddrV4,
+    /// LAN net: address of the network interface to announce on.
+    /// Unspecified address selects the default interface
+    #[structopt(env = "YA_NET_LAN_INTERFACE", default_value = "0.0.0.0")]
+    pub lan_interface: Ipv4Addr,
+    /// LAN net: interval of peer announcements. Peers are forgotten after 3 missed intervals
+    #[structopt(env = "YA_NET_LAN_ANNOUNCE_INTERVAL", parse(try_from_str = humantime::parse_duration), default_value = "5s")]
+    pub lan_announce_interval: Duration,
+}
+
+/// Token bucket parameters: refilled with `rate` tokens per second, holding up to `burst` tokens.
+/// A non-positive `rate` disables the limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+impl RateLimit {
+    pub fn is_enabled(&self) -> bool {
+        self.rate > 0.
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("off") {
+            return Ok(RateLimit {
+                rate: 0.,
+                burst: 0.,
+            });
+        }
+
+        let mut split = s.splitn(2, ':');
+        let rate = f64::from_str(split.next().unwrap_or_default().trim())?;
+        let burst = match split.next() {
+            Some(burst) => f64::from_str(burst.trim())?,
+            None => rate.max(1.),
+        };
+        if !rate.is_finite() || !burst.is_finite() || burst < 1. {
+            anyhow::bail!("invalid rate limit: {}", s);
+        }
+        Ok(RateLimit { rate, burst })
+    }
 }
 
 impl Config {
@@ -53,3 +143,34 @@ impl Config {
         Config::from_iter_safe(&[""])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rate_limit() {
+        let limit = RateLimit::from_str("50:100").unwrap();
+        assert_eq!(
+            limit,
+            RateLimit {
+                rate: 50.,
+                burst: 100.
+            }
+        );
+        assert!(limit.is_enabled());
+
+        let limit = RateLimit::from_str("0.5").unwrap();
+        assert_eq!(
+            limit,
+            RateLimit {
+                rate: 0.5,
+                burst: 1.
+            }
+        );
+
+        assert!(!RateLimit::from_str("off").unwrap().is_enabled());
+        assert!(RateLimit::from_str("10:0").is_err());
+        assert!(RateLimit::from_str("x:10").is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::Context;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+
+use ya_core_model::net::local::{
+    AclAction, AclAdd, AclList, AclRemove, AclResponse, AclRule, AclSetDefault, BUS_ID,
+};
+use ya_core_model::net::GenericNetError;
+use ya_core_model::NodeId;
+use ya_service_bus::typed as bus;
+
+/// Access list for public services, exposed to other nodes.
+/// Persisted as JSON after each modification.
+#[derive(Clone)]
+pub(crate) struct Acl {
+    inner: Rc<RefCell<AclInner>>,
+}
+
+struct AclInner {
+    path: Option<PathBuf>,
+    rules: AclRules,
+    denied: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AclRules {
+    default_action: AclAction,
+    rules: Vec<AclRule>,
+}
+
+impl Default for AclRules {
+    fn default() -> Self {
+        Self {
+            default_action: AclAction::Allow,
+            rules: Default::default(),
+        }
+    }
+}
+
+impl Acl {
+    pub fn load(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let rules = match path.as_ref().filter(|p| p.exists()) {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("Unable to read ACL file {}", path.display()))?;
+                serde_json::from_str(&contents)
+                    .with_context(|| format!("Invalid ACL file {}", path.display()))?
+            }
+            None => AclRules::default(),
+        };
+
+        log::info!(
+            "Public service ACL: {} rule(s), default action: {}",
+            rules.rules.len(),
+            rules.default_action
+        );
+
+        Ok(Self {
+            inner: Rc::new(RefCell::new(AclInner {
+                path,
+                rules,
+                denied: 0,
+            })),
+        })
+    }
+
+    /// Checks whether `remote_id` is allowed to call the public service at `address`.
+    /// Denials are counted and logged.
+    pub fn check(&self, remote_id: NodeId, address: &str) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        match inner.rules.action(remote_id, address) {
+            AclAction::Allow => true,
+            AclAction::Deny => {
+                inner.denied += 1;
+                counter!("net.acl.denied", 1);
+                log::debug!("ACL: denied access to {address} for {remote_id}");
+                false
+            }
+        }
+    }
+
+    fn response(&self) -> AclResponse {
+        let inner = self.inner.borrow();
+        AclResponse {
+            default_action: inner.rules.default_action,
+            rules: inner.rules.rules.clone(),
+            denied: inner.denied,
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut AclRules) -> anyhow::Result<()>) -> anyhow::Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        let mut rules = inner.rules.clone();
+        f(&mut rules)?;
+
+        if let Some(path) = inner.path.as_ref() {
+            save(path, &rules)?;
+        }
+        inner.rules = rules;
+        Ok(())
+    }
+}
+
+impl AclRules {
+    fn action(&self, remote_id: NodeId, address: &str) -> AclAction {
+        self.rules
+            .iter()
+            .find(|rule| rule_matches(rule, remote_id, address))
+            .map(|rule| rule.action)
+            .unwrap_or(self.default_action)
+    }
+}
+
+fn rule_matches(rule: &AclRule, remote_id: NodeId, address: &str) -> bool {
+    let node_matches = rule.node_id.map(|id| id == remote_id).unwrap_or(true);
+    let service_matches = rule
+        .service
+        .as_ref()
+        .map(|s| is_prefix_of(s.trim_end_matches('/'), address))
+        .unwrap_or(true);
+    node_matches && service_matches
+}
+
+fn is_prefix_of(prefix: &str, address: &str) -> bool {
+    address.starts_with(prefix)
+        && (address.len() == prefix.len() || address[prefix.len()..].starts_with('/'))
+}
+
+fn save(path: &Path, rules: &AclRules) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, serde_json::to_string_pretty(rules)?)?;
+    std::fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+pub(crate) fn bind_service(acl: Acl) {
+    let acl_ = acl.clone();
+    let _ = bus::bind(BUS_ID, move |_: AclList| {
+        let acl = acl_.clone();
+        async move { Ok(acl.response()) }
+    });
+
+    let acl_ = acl.clone();
+    let _ = bus::bind(BUS_ID, move |msg: AclAdd| {
+        let acl = acl_.clone();
+        async move {
+            acl.update(|rules| {
+                rules.rules.push(msg.rule);
+                Ok(())
+            })
+            .map_err(net_err)?;
+            Ok(acl.response())
+        }
+    });
+
+    let acl_ = acl.clone();
+    let _ = bus::bind(BUS_ID, move |msg: AclRemove| {
+        let acl = acl_.clone();
+        async move {
+            acl.update(|rules| match msg.index < rules.rules.len() {
+                true => {
+                    rules.rules.remove(msg.index);
+                    Ok(())
+                }
+                false => anyhow::bail!("Invalid rule index: {}", msg.index),
+            })
+            .map_err(net_err)?;
+            Ok(acl.response())
+        }
+    });
+
+    let _ = bus::bind(BUS_ID, move |msg: AclSetDefault| {
+        let acl = acl.clone();
+        async move {
+            acl.update(|rules| {
+                rules.default_action = msg.action;
+                Ok(())
+            })
+            .map_err(net_err)?;
+            Ok(acl.response())
+        }
+    });
+}
+
+#[inline]
+fn net_err(e: anyhow::Error) -> GenericNetError {
+    GenericNetError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(action: AclAction, node_id: Option<NodeId>, service: Option<&str>) -> AclRule {
+        AclRule {
+            action,
+            node_id,
+            service: service.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn first_matching_rule() {
+        let allowed: NodeId = "0x0000000000000000000000000000000000000001"
+            .parse()
+            .unwrap();
+        let other: NodeId = "0x0000000000000000000000000000000000000002"
+            .parse()
+            .unwrap();
+
+        let rules = AclRules {
+            default_action: AclAction::Deny,
+            rules: vec![
+                rule(AclAction::Deny, None, Some("/public/payment/admin")),
+                rule(AclAction::Allow, Some(allowed), Some("/public/payment/")),
+                rule(AclAction::Allow, None, Some("/public/market")),
+            ],
+        };
+
+        let action = |id, addr| rules.action(id, addr);
+        assert_eq!(action(allowed, "/public/payment/Send"), AclAction::Allow);
+        assert_eq!(action(allowed, "/public/payment/admin/Op"), AclAction::Deny);
+        assert_eq!(action(other, "/public/payment/Send"), AclAction::Deny);
+        assert_eq!(action(other, "/public/market/Query"), AclAction::Allow);
+        assert_eq!(action(other, "/public/marketplace"), AclAction::Deny);
+        assert_eq!(action(allowed, "/public/activity/Exec"), AclAction::Deny);
+    }
+
+    #[test]
+    fn allow_by_default() {
+        let id: NodeId = "0x0000000000000000000000000000000000000001"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            AclRules::default().action(id, "/public/activity/Exec"),
+            AclAction::Allow
+        );
+    }
+}
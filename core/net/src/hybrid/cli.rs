@@ -18,21 +18,29 @@ use ya_service_bus::timeout::IntoTimeoutFuture;
 use ya_service_bus::typed::ServiceBinder;
 use ya_service_bus::{typed as bus, RpcEndpoint};
 
-pub(crate) fn bind_service(base_client: Client) {
+use crate::hybrid::limiter::RateLimiter;
+use crate::hybrid::relay::{Relays, SharedClient};
+use crate::hybrid::stats::TrafficStats;
+
+pub(crate) fn bind_service(
+    base_client: SharedClient,
+    limiter: RateLimiter,
+    relays: Relays,
+    stats: TrafficStats,
+) {
     let client = base_client.clone();
     let _ = bus::bind(model::BUS_ID, move |ping: model::GsbPing| {
-        cli_ping(client.clone(), ping.nodes)
-            .map_err(|e| StatusError::RuntimeException(e.to_string()))
+        cli_ping(client.get(), ping.nodes).map_err(|e| StatusError::RuntimeException(e.to_string()))
     });
 
     let client = base_client.clone();
     let _ = bus::bind(model::BUS_ID, move |msg: model::Connect| {
-        connect(client.clone(), msg).map_err(|e| GenericNetError(e.to_string()))
+        connect(client.get(), msg).map_err(|e| GenericNetError(e.to_string()))
     });
 
     let client = base_client.clone();
     let _ = bus::bind(model::BUS_ID, move |msg: model::Disconnect| {
-        let client = client.clone();
+        let client = client.get();
         async move {
             client
                 .disconnect(msg.node)
@@ -46,7 +54,10 @@ pub(crate) fn bind_service(base_client: Client) {
 
     let client = base_client.clone();
     let _ = bus::bind(model::BUS_ID, move |_: model::Status| {
-        let client = client.clone();
+        let client = client.get();
+        let throttled = limiter.stats();
+        let relays_info = relays.info();
+        let relay_switches = relays.switches();
         async move {
             Ok(model::StatusResponse {
                 node_id: client.node_id(),
@@ -54,14 +65,22 @@ pub(crate) fn bind_service(base_client: Client) {
                 public_address: client.public_addr().await,
                 sessions: client.sessions().await.len(),
                 metrics: to_status_metrics(&mut client.metrics()),
+                throttled,
+                relays: relays_info,
+                relay_switches,
             })
         }
         .map_err(status_err)
     });
 
+    let _ = bus::bind(model::BUS_ID, move |_: model::Stats| {
+        let stats = stats.snapshot();
+        async move { Ok(stats) }
+    });
+
     let sessions_client = base_client.clone();
     let _ = bus::bind(model::BUS_ID, move |_: model::Sessions| {
-        let client = sessions_client.clone();
+        let client = sessions_client.get();
         async move {
             let mut responses = Vec::new();
             let now = Instant::now();
@@ -105,7 +124,7 @@ pub(crate) fn bind_service(base_client: Client) {
 
     let sockets_client = base_client.clone();
     let _ = bus::bind(model::BUS_ID, move |_: model::Sockets| {
-        let client = sockets_client.clone();
+        let client = sockets_client.get();
         async move {
             let sockets = client
                 .sockets()
@@ -127,7 +146,7 @@ pub(crate) fn bind_service(base_client: Client) {
 
     let find_node_client = base_client.clone();
     let _ = bus::bind(model::BUS_ID, move |find: model::FindNode| {
-        let client = find_node_client.clone();
+        let client = find_node_client.get();
         async move {
             let node_id: NodeId = find.node_id.parse()?;
             let node = client.find_node(node_id).await?;
@@ -151,7 +170,7 @@ pub(crate) fn bind_service(base_client: Client) {
     });
     let client_ = base_client;
     let _ = bus::bind(model::BUS_ID, move |list: model::ListNeighbours| {
-        let client = client_.clone();
+        let client = client_.get();
 
         async move { client.neighbours(list.size).await.map_err(status_err) }
     });
@@ -0,0 +1,298 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use metrics::counter;
+
+use ya_core_model::net::local::ThrottleStats;
+use ya_core_model::NodeId;
+
+use crate::config::{Config, RateLimit};
+
+/// Number of checks after which idle buckets are removed
+const CLEANUP_INTERVAL: u64 = 1024;
+/// Longest delay of over-limit requests and pushes, after which they're rejected
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Inbound message classes, limited independently
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum MessageClass {
+    Request,
+    Push,
+    Broadcast,
+}
+
+impl MessageClass {
+    /// Broadcasts are never delayed, but dropped as soon as they exceed the limit
+    fn max_delay(&self) -> Duration {
+        match self {
+            MessageClass::Request | MessageClass::Push => MAX_DELAY,
+            MessageClass::Broadcast => Duration::ZERO,
+        }
+    }
+}
+
+/// Outcome of a rate limit check
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Admission {
+    Now,
+    /// Message exceeds the limit and should be handled after a delay
+    Delayed(Duration),
+    Rejected,
+}
+
+/// Token bucket rate limiter for inbound messages, keyed by remote node and message class
+#[derive(Clone)]
+pub(crate) struct RateLimiter {
+    inner: Rc<RefCell<RateLimiterInner>>,
+}
+
+struct RateLimiterInner {
+    limits: HashMap<MessageClass, RateLimit>,
+    buckets: HashMap<(NodeId, MessageClass), Bucket>,
+    throttled: HashMap<MessageClass, u64>,
+    checks: u64,
+}
+
+impl RateLimiter {
+    pub fn new(config: &Config) -> Self {
+        Self::with_limits(vec![
+            (MessageClass::Request, config.rate_limit_request),
+            (MessageClass::Push, config.rate_limit_push),
+            (MessageClass::Broadcast, config.rate_limit_broadcast),
+        ])
+    }
+
+    fn with_limits(limits: impl IntoIterator<Item = (MessageClass, RateLimit)>) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(RateLimiterInner {
+                limits: limits.into_iter().filter(|(_, l)| l.is_enabled()).collect(),
+                buckets: Default::default(),
+                throttled: Default::default(),
+                checks: 0,
+            })),
+        }
+    }
+
+    /// Consumes a token for a message of `class` from `node_id`. Messages exceeding the limit
+    /// are queued, i.e. delayed until a token is available, unless the delay would be too long.
+    pub fn admit(&self, node_id: NodeId, class: MessageClass) -> Admission {
+        self.admit_at(node_id, class, Instant::now())
+    }
+
+    fn admit_at(&self, node_id: NodeId, class: MessageClass, now: Instant) -> Admission {
+        let mut inner = self.inner.borrow_mut();
+        let limit = match inner.limits.get(&class) {
+            Some(limit) => *limit,
+            None => return Admission::Now,
+        };
+
+        inner.checks += 1;
+        if inner.checks % CLEANUP_INTERVAL == 0 {
+            inner.cleanup(now);
+        }
+
+        let admission = inner
+            .buckets
+            .entry((node_id, class))
+            .or_insert_with(|| Bucket::new(limit, now))
+            .take(limit, now, class.max_delay());
+
+        if admission != Admission::Now {
+            *inner.throttled.entry(class).or_default() += 1;
+            match class {
+                MessageClass::Request => counter!("net.throttled.requests", 1),
+                MessageClass::Push => counter!("net.throttled.pushes", 1),
+                MessageClass::Broadcast => counter!("net.throttled.broadcasts", 1),
+            }
+            log::trace!("Rate limit of {class:?} messages exceeded by {node_id}: {admission:?}");
+        }
+        admission
+    }
+
+    pub fn stats(&self) -> ThrottleStats {
+        let inner = self.inner.borrow();
+        let now = Instant::now();
+        let count = |class| inner.throttled.get(&class).copied().unwrap_or_default();
+
+        let nodes = inner
+            .buckets
+            .iter()
+            .filter(|((_, class), bucket)| {
+                let limit = inner.limits[class];
+                bucket.tokens_at(limit, now) < 1.
+            })
+            .map(|((node_id, _), _)| *node_id)
+            .collect::<HashSet<_>>();
+
+        ThrottleStats {
+            requests: count(MessageClass::Request),
+            pushes: count(MessageClass::Push),
+            broadcasts: count(MessageClass::Broadcast),
+            nodes: nodes.len(),
+        }
+    }
+}
+
+impl RateLimiterInner {
+    /// Removes buckets which have been refilled completely
+    fn cleanup(&mut self, now: Instant) {
+        let limits = &self.limits;
+        self.buckets.retain(|(_, class), bucket| {
+            let limit = limits[class];
+            bucket.tokens_at(limit, now) < limit.burst
+        });
+    }
+}
+
+/// Token bucket, which goes into debt for delayed messages
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Bucket {
+            tokens: limit.burst,
+            updated: now,
+        }
+    }
+
+    fn tokens_at(&self, limit: RateLimit, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * limit.rate).min(limit.burst)
+    }
+
+    fn take(&mut self, limit: RateLimit, now: Instant, max_delay: Duration) -> Admission {
+        self.tokens = self.tokens_at(limit, now);
+        self.updated = now;
+
+        let remaining = self.tokens - 1.;
+        if remaining >= 0. {
+            self.tokens = remaining;
+            return Admission::Now;
+        }
+
+        let delay = Duration::from_secs_f64(-remaining / limit.rate);
+        if delay > max_delay {
+            return Admission::Rejected;
+        }
+        self.tokens = remaining;
+        Admission::Delayed(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::with_limits(vec![
+            (
+                MessageClass::Request,
+                RateLimit {
+                    rate: 2.,
+                    burst: 3.,
+                },
+            ),
+            (
+                MessageClass::Push,
+                RateLimit {
+                    rate: 0.,
+                    burst: 0.,
+                },
+            ),
+            (
+                MessageClass::Broadcast,
+                RateLimit {
+                    rate: 1.,
+                    burst: 1.,
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn token_bucket() {
+        let limiter = limiter();
+        let node_id = NodeId::default();
+        let now = Instant::now();
+        let admit = |at| limiter.admit_at(node_id, MessageClass::Request, at);
+
+        for _ in 0..3 {
+            assert_eq!(admit(now), Admission::Now);
+        }
+        assert_eq!(admit(now), Admission::Delayed(Duration::from_millis(500)));
+        assert_eq!(admit(now), Admission::Delayed(Duration::from_secs(1)));
+        assert_eq!(limiter.stats().requests, 2);
+        assert_eq!(limiter.stats().nodes, 1);
+
+        // delayed messages are paid for with future tokens
+        let later = now + Duration::from_secs(1);
+        assert_eq!(admit(later), Admission::Delayed(Duration::from_millis(500)));
+
+        let refilled = now + Duration::from_secs(3);
+        assert_eq!(admit(refilled), Admission::Now);
+    }
+
+    #[test]
+    fn max_delay() {
+        let limiter = limiter();
+        let node_id = NodeId::default();
+        let now = Instant::now();
+        let admit = || limiter.admit_at(node_id, MessageClass::Request, now);
+
+        // burst + rate * MAX_DELAY
+        for _ in 0..23 {
+            assert_ne!(admit(), Admission::Rejected);
+        }
+        assert_eq!(admit(), Admission::Rejected);
+        assert_eq!(admit(), Admission::Rejected);
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(
+            limiter.admit_at(node_id, MessageClass::Request, later),
+            Admission::Delayed(Duration::from_millis(9500))
+        );
+    }
+
+    #[test]
+    fn independent_limits() {
+        let limiter = limiter();
+        let node_id = NodeId::default();
+        let other: NodeId = "0x0000000000000000000000000000000000000001"
+            .parse()
+            .unwrap();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(
+                limiter.admit_at(node_id, MessageClass::Request, now),
+                Admission::Now
+            );
+        }
+        assert_eq!(
+            limiter.admit_at(other, MessageClass::Request, now),
+            Admission::Now
+        );
+        for _ in 0..10 {
+            assert_eq!(
+                limiter.admit_at(node_id, MessageClass::Push, now),
+                Admission::Now
+            );
+        }
+
+        // broadcasts are dropped instead of being delayed
+        assert_eq!(
+            limiter.admit_at(node_id, MessageClass::Broadcast, now),
+            Admission::Now
+        );
+        assert_eq!(
+            limiter.admit_at(node_id, MessageClass::Broadcast, now),
+            Admission::Rejected
+        );
+    }
+}
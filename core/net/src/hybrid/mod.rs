@@ -1,10 +1,15 @@
+pub(crate) mod acl;
 mod api;
 pub(crate) mod cli;
-mod codec;
-mod crypto;
+pub(crate) mod codec;
+pub(crate) mod crypto;
+pub(crate) mod limiter;
+mod relay;
 mod rest_api;
 mod service;
+pub(crate) mod stats;
 
 pub use api::*;
 pub use rest_api::web_scope;
+pub(crate) use service::{parse_from_to_addr, parse_net_to_addr};
 pub use service::{send_bcast_new_neighbour, start_network, Net};
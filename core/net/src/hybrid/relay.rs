@@ -0,0 +1,473 @@
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context};
+use metrics::counter;
+use url::Url;
+
+use ya_core_model::net::local::RelayInfo;
+use ya_relay_client::{Client, ClientBuilder, FailFast};
+use ya_utils_networking::resolver;
+
+use crate::config::Config;
+use crate::hybrid::crypto::IdentityCryptoProvider;
+
+const DEFAULT_NET_RELAY_HOST: &str = "127.0.0.1:7464";
+const NET_RELAY_SRV_RECORD: &str = "_net_relay._udp";
+
+/// Relay client handle, which is replaced on relay failover.
+/// Handlers should obtain the client on each call instead of keeping a copy.
+#[derive(Clone)]
+pub(crate) struct SharedClient {
+    client: Rc<RefCell<Client>>,
+    closed: Rc<Cell<bool>>,
+}
+
+impl SharedClient {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client: Rc::new(RefCell::new(client)),
+            closed: Default::default(),
+        }
+    }
+
+    pub fn get(&self) -> Client {
+        self.client.borrow().clone()
+    }
+
+    /// Stops relay monitoring. The client itself needs to be shut down by the caller
+    pub fn close(&self) {
+        self.closed.set(true);
+    }
+
+    fn replace(&self, client: Client) {
+        *self.client.borrow_mut() = client;
+    }
+}
+
+/// Relay server candidates, ranked by measured latency
+#[derive(Clone, Default)]
+pub(crate) struct Relays {
+    inner: Rc<RefCell<RelaysInner>>,
+}
+
+#[derive(Default)]
+struct RelaysInner {
+    candidates: Vec<Relay>,
+    active: Option<(String, SocketAddr)>,
+    switches: u64,
+}
+
+struct Relay {
+    host_port: String,
+    latency: Option<Duration>,
+    failures: u32,
+    failed: bool,
+}
+
+impl Relay {
+    fn new(host_port: String) -> Self {
+        Self {
+            host_port,
+            latency: None,
+            failures: 0,
+            failed: false,
+        }
+    }
+}
+
+impl Relays {
+    /// Replaces the candidate list, keeping statistics of already known relays
+    fn update(&self, hosts: Vec<String>) {
+        let mut inner = self.inner.borrow_mut();
+        let mut known = std::mem::take(&mut inner.candidates);
+
+        inner.candidates = hosts
+            .into_iter()
+            .map(
+                |host_port| match known.iter().position(|r| r.host_port == host_port) {
+                    Some(idx) => known.remove(idx),
+                    None => Relay::new(host_port),
+                },
+            )
+            .collect();
+    }
+
+    /// Relays which did not fail recently come first, ordered by latency.
+    /// Relays without a measurement keep their configured order.
+    fn ranked(&self) -> Vec<String> {
+        let inner = self.inner.borrow();
+        let mut candidates = inner.candidates.iter().collect::<Vec<_>>();
+        candidates.sort_by_key(|r| (r.failed, r.latency.unwrap_or(Duration::MAX)));
+        candidates
+            .into_iter()
+            .map(|r| r.host_port.clone())
+            .collect()
+    }
+
+    fn active(&self) -> Option<(String, SocketAddr)> {
+        self.inner.borrow().active.clone()
+    }
+
+    fn set_active(&self, host_port: &str, addr: SocketAddr) {
+        let mut inner = self.inner.borrow_mut();
+        let switched = match inner.active.replace((host_port.to_string(), addr)) {
+            Some((previous, _)) => previous != host_port,
+            None => false,
+        };
+        if switched {
+            inner.switches += 1;
+            counter!("net.relay.switches", 1);
+        }
+    }
+
+    fn set_healthy(&self, host_port: &str, latency: Option<Duration>) {
+        self.with_relay(host_port, |relay| {
+            relay.failed = false;
+            if latency.is_some() {
+                relay.latency = latency;
+            }
+        });
+    }
+
+    fn set_failed(&self, host_port: &str) {
+        self.with_relay(host_port, |relay| {
+            relay.failed = true;
+            relay.failures += 1;
+        });
+    }
+
+    fn with_relay(&self, host_port: &str, f: impl FnOnce(&mut Relay)) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(relay) = inner
+            .candidates
+            .iter_mut()
+            .find(|r| r.host_port == host_port)
+        {
+            f(relay);
+        }
+    }
+
+    pub fn info(&self) -> Vec<RelayInfo> {
+        let inner = self.inner.borrow();
+        let active = inner.active.as_ref().map(|(host_port, _)| host_port);
+        self.ranked()
+            .into_iter()
+            .filter_map(|host_port| {
+                let relay = inner.candidates.iter().find(|r| r.host_port == host_port)?;
+                Some(RelayInfo {
+                    active: active == Some(&relay.host_port),
+                    address: relay.host_port.clone(),
+                    latency: relay.latency,
+                    failures: relay.failures,
+                })
+            })
+            .collect()
+    }
+
+    pub fn switches(&self) -> u64 {
+        self.inner.borrow().switches
+    }
+}
+
+/// Connects to the best ranked relay server, trying the remaining ones on failure.
+/// Connection to the last candidate does not fail fast, so that the Node can start
+/// even when no relay is reachable.
+pub(crate) async fn connect(
+    config: &Config,
+    crypto: IdentityCryptoProvider,
+    relays: &Relays,
+) -> anyhow::Result<Client> {
+    relays.update(relay_hosts(config).await);
+
+    let candidates = relays.ranked();
+    let mut result = Err(anyhow!("No relay server configured"));
+
+    for (idx, host_port) in candidates.iter().enumerate() {
+        let fail_fast = match idx + 1 == candidates.len() {
+            true => FailFast::No,
+            false => FailFast::Yes,
+        };
+
+        let started = Instant::now();
+        result = match build_client(config, crypto.clone(), host_port, fail_fast).await {
+            Ok((client, addr)) => {
+                let latency = (idx + 1 < candidates.len()).then(|| started.elapsed());
+                relays.set_healthy(host_port, latency);
+                relays.set_active(host_port, addr);
+                return Ok(client);
+            }
+            Err(e) => {
+                log::warn!("Unable to connect to relay server {host_port}: {e}");
+                relays.set_failed(host_port);
+                Err(e)
+            }
+        };
+    }
+
+    result
+}
+
+/// Periodically checks the connection to the relay server and reconnects the client
+/// to the next relay when the current one stops responding. The Node identity is kept.
+/// `on_connect` is called with each new client.
+pub(crate) async fn monitor(
+    config: Arc<Config>,
+    crypto: IdentityCryptoProvider,
+    client: SharedClient,
+    relays: Relays,
+    on_connect: impl Fn(Client) + 'static,
+) {
+    let mut unhealthy_since: Option<Instant> = None;
+
+    loop {
+        tokio::time::sleep(config.relay_check_interval).await;
+        if client.closed.get() {
+            break;
+        }
+
+        if check(&client.get(), &relays, config.relay_timeout).await {
+            unhealthy_since = None;
+            continue;
+        }
+
+        let since = *unhealthy_since.get_or_insert_with(Instant::now);
+        if since.elapsed() < config.relay_timeout {
+            continue;
+        }
+
+        if let Some((host_port, _)) = relays.active() {
+            log::warn!(
+                "Relay server {host_port} not responding for {}, switching relay",
+                humantime::format_duration(since.elapsed())
+            );
+            relays.set_failed(&host_port);
+        }
+
+        // Release the listening socket before binding a new client
+        let mut previous = client.get();
+        if let Err(e) = previous.shutdown().await {
+            log::debug!("Relay client shutdown error: {e}");
+        }
+
+        match connect(&config, crypto.clone(), &relays).await {
+            Ok(new_client) => {
+                if let Some((host_port, _)) = relays.active() {
+                    log::info!("Hybrid NET connected to relay server: udp://{host_port}");
+                }
+                client.replace(new_client.clone());
+                on_connect(new_client);
+                unhealthy_since = None;
+            }
+            Err(e) => log::error!("Unable to connect to any relay server: {e}"),
+        }
+    }
+}
+
+/// Returns `true` if the relay server session is alive
+async fn check(client: &Client, relays: &Relays, timeout: Duration) -> bool {
+    let (host_port, addr) = match relays.active() {
+        Some(active) => active,
+        None => return false,
+    };
+
+    let sessions = client.sessions().await;
+    match sessions.iter().find(|s| s.remote == addr) {
+        Some(session) if session.last_seen.elapsed() < timeout => {
+            relays.set_healthy(&host_port, None);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Periodically measures latency of all relay servers, so that relay failover
+/// picks the closest healthy one. Latency of the active relay is measured the same way,
+/// to keep the measurements comparable.
+pub(crate) async fn probe(config: Arc<Config>, client: SharedClient, relays: Relays) {
+    loop {
+        tokio::time::sleep(config.relay_probe_interval).await;
+        if client.closed.get() {
+            break;
+        }
+        probe_with(&relays, probe_relay).await;
+    }
+}
+
+async fn probe_with<F, Fut>(relays: &Relays, probe: F)
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = anyhow::Result<Duration>>,
+{
+    for host_port in relays.ranked() {
+        match probe(host_port.clone()).await {
+            Ok(latency) => relays.set_healthy(&host_port, Some(latency)),
+            Err(e) => {
+                log::debug!("Relay server {host_port} probe failed: {e}");
+                relays.set_failed(&host_port);
+            }
+        }
+    }
+}
+
+/// Measures the time of connecting to the relay server, with the same handshake
+/// as the relay client. A throwaway identity and an ephemeral port are used,
+/// so that the active client is not affected.
+async fn probe_relay(host_port: String) -> anyhow::Result<Duration> {
+    let addr = relay_addr(&host_port).await?;
+    let url = Url::parse(&format!("udp://{addr}"))?;
+
+    let started = Instant::now();
+    let mut client = ClientBuilder::from_url(url)
+        .listen(Url::parse("udp://0.0.0.0:0")?)
+        .connect(FailFast::Yes)
+        .build()
+        .await?;
+    let latency = started.elapsed();
+
+    if let Err(e) = client.shutdown().await {
+        log::debug!("Relay probe client shutdown error: {e}");
+    }
+    Ok(latency)
+}
+
+async fn build_client(
+    config: &Config,
+    crypto: IdentityCryptoProvider,
+    host_port: &str,
+    fail_fast: FailFast,
+) -> anyhow::Result<(Client, SocketAddr)> {
+    let addr = relay_addr(host_port)
+        .await
+        .map_err(|e| anyhow!("Resolving hybrid NET relay server failed. Error: {}", e))?;
+    let url = Url::parse(&format!("udp://{addr}"))?;
+
+    log::info!("Hybrid NET relay server configured on url: udp://{host_port}");
+
+    let client = ClientBuilder::from_url(url)
+        .crypto(crypto)
+        .listen(config.bind_url.clone())
+        .expire_session_after(config.session_expiration)
+        .session_request_timeout(config.session_request_timeout)
+        .connect(fail_fast)
+        .build()
+        .await?;
+    Ok((client, addr))
+}
+
+async fn relay_hosts(config: &Config) -> Vec<String> {
+    match &config.host {
+        Some(val) => parse_hosts(val),
+        None => resolver::resolve_yagna_srv_records(NET_RELAY_SRV_RECORD)
+            .await
+            // FIXME: remove
+            .unwrap_or_else(|_| vec![DEFAULT_NET_RELAY_HOST.to_string()]),
+    }
+}
+
+fn parse_hosts(hosts: &str) -> Vec<String> {
+    hosts
+        .split(',')
+        .map(str::trim)
+        .filter(|h| !h.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+async fn relay_addr(host_port: &str) -> anyhow::Result<SocketAddr> {
+    let (host, port) = host_port
+        .split_once(':')
+        .context("Please use host:port format")?;
+    let ip = resolver::try_resolve_dns_record(host).await;
+    let socket = format!("{}:{}", ip, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Invalid relay address: {ip}:{port}"))?;
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_relay_hosts() {
+        assert_eq!(
+            parse_hosts("relay1:7464, relay2:7464,,"),
+            vec!["relay1:7464".to_string(), "relay2:7464".to_string()]
+        );
+    }
+
+    #[test]
+    fn rank_relays() {
+        let relays = Relays::default();
+        relays.update(vec!["a:1".into(), "b:1".into(), "c:1".into()]);
+        assert_eq!(relays.ranked(), vec!["a:1", "b:1", "c:1"]);
+
+        relays.set_healthy("c:1", Some(Duration::from_millis(20)));
+        relays.set_healthy("b:1", Some(Duration::from_millis(50)));
+        assert_eq!(relays.ranked(), vec!["c:1", "b:1", "a:1"]);
+
+        relays.set_failed("c:1");
+        assert_eq!(relays.ranked(), vec!["b:1", "a:1", "c:1"]);
+
+        relays.update(vec!["d:1".into(), "c:1".into(), "b:1".into()]);
+        assert_eq!(relays.ranked(), vec!["b:1", "d:1", "c:1"]);
+
+        let info = relays.info();
+        assert_eq!(info[2].failures, 1);
+        assert_eq!(info[0].latency, Some(Duration::from_millis(50)));
+    }
+
+    #[actix_rt::test]
+    async fn fail_over_to_closest_relay() {
+        let relays = Relays::default();
+        relays.update(vec!["a:1".into(), "b:1".into(), "c:1".into(), "d:1".into()]);
+        relays.set_active("a:1", "127.0.0.1:1".parse().unwrap());
+
+        probe_with(&relays, |host_port: String| async move {
+            match host_port.as_str() {
+                "a:1" => Ok(Duration::from_millis(10)),
+                "b:1" => Ok(Duration::from_millis(50)),
+                "c:1" => Ok(Duration::from_millis(20)),
+                _ => Err(anyhow!("timeout")),
+            }
+        })
+        .await;
+        assert_eq!(relays.ranked(), vec!["a:1", "c:1", "b:1", "d:1"]);
+
+        // the active relay stops responding
+        relays.set_failed("a:1");
+        assert_eq!(relays.ranked()[0], "c:1");
+
+        // unreachable relay comes back, with the lowest latency
+        probe_with(&relays, |host_port: String| async move {
+            match host_port.as_str() {
+                "d:1" => Ok(Duration::from_millis(5)),
+                "a:1" => Err(anyhow!("timeout")),
+                _ => Ok(Duration::from_millis(30)),
+            }
+        })
+        .await;
+        assert_eq!(relays.ranked(), vec!["d:1", "b:1", "c:1", "a:1"]);
+    }
+
+    #[test]
+    fn count_switches() {
+        let relays = Relays::default();
+        relays.update(vec!["a:1".into(), "b:1".into()]);
+        let addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+
+        relays.set_active("a:1", addr);
+        relays.set_active("a:1", addr);
+        assert_eq!(relays.switches(), 0);
+
+        relays.set_active("b:1", addr);
+        assert_eq!(relays.switches(), 1);
+        assert!(relays.info().iter().any(|r| r.active && r.address == "b:1"));
+    }
+}
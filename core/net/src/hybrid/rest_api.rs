@@ -5,7 +5,9 @@ use ya_service_bus::{typed, RpcEndpoint};
 use crate::error::{NetError, Result};
 
 pub fn web_scope() -> Scope {
-    actix_web::web::scope(NET_API_V2_NET_PATH).service(get_info)
+    actix_web::web::scope(NET_API_V2_NET_PATH)
+        .service(get_info)
+        .service(get_stats)
 }
 
 #[actix_web::get("/status")]
@@ -23,3 +25,13 @@ async fn get_info() -> Result<impl Responder> {
     };
     Ok(HttpResponse::Ok().json(status))
 }
+
+#[actix_web::get("/stats")]
+async fn get_stats() -> Result<impl Responder> {
+    let stats = typed::service(ya_core_model::net::local::BUS_ID)
+        .send(ya_core_model::net::local::Stats {})
+        .await
+        .map_err(|e| NetError::Error(e.into()))?
+        .map_err(|e| NetError::Error(e.into()))?;
+    Ok(HttpResponse::Ok().json(stats))
+}
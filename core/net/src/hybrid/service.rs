@@ -1,7 +1,6 @@
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::future::Future;
-use std::net::{SocketAddr, ToSocketAddrs};
 use std::rc::Rc;
 use std::str::FromStr;
 use std::sync::atomic::AtomicBool;
@@ -16,7 +15,6 @@ use futures::{FutureExt, SinkExt, Stream, StreamExt, TryFutureExt, TryStreamExt}
 use metrics::counter;
 use tokio::sync::RwLock;
 use tokio_stream::wrappers::UnboundedReceiverStream;
-use url::Url;
 
 use ya_core_model::identity::event::IdentityEvent;
 use ya_core_model::net::local::{
@@ -25,9 +23,8 @@ use ya_core_model::net::local::{
 use ya_core_model::net::GenericNetError;
 use ya_core_model::{identity, net, NodeId};
 use ya_relay_client::channels::{ForwardReceiver, ForwardSender, PrefixedStream};
-use ya_relay_client::crypto::CryptoProvider;
 use ya_relay_client::model::{Payload, TransportType};
-use ya_relay_client::{Client, ClientBuilder, FailFast, GenericSender};
+use ya_relay_client::{Client, GenericSender};
 use ya_sb_proto::codec::GsbMessage;
 use ya_sb_proto::CallReplyCode;
 use ya_sb_util::RevPrefixes;
@@ -36,18 +33,19 @@ use ya_service_bus::untyped::{Fn4HandlerExt, Fn4StreamHandlerExt};
 use ya_service_bus::{
     serialization, typed, untyped as local_bus, Error, ResponseChunk, RpcEndpoint, RpcMessage,
 };
-use ya_utils_networking::resolver;
 
 use crate::bcast::BCastService;
 use crate::config::Config;
+use crate::hybrid::acl::{self, Acl};
 use crate::hybrid::codec;
 use crate::hybrid::codec::encode_message;
 use crate::hybrid::crypto::IdentityCryptoProvider;
+use crate::hybrid::limiter::{Admission, MessageClass, RateLimiter};
+use crate::hybrid::relay::{self, Relays, SharedClient};
+use crate::hybrid::stats::{Direction, TrafficStats};
 use crate::service::NET_TYPE;
 use crate::{broadcast, NetType};
 
-const DEFAULT_NET_RELAY_HOST: &str = "127.0.0.1:7464";
-
 type BusSender = mpsc::Sender<ResponseChunk>;
 type BusReceiver = mpsc::Receiver<ResponseChunk>;
 type NetSender = mpsc::Sender<Payload>;
@@ -118,10 +116,21 @@ pub async fn start_network(
     log::info!("Starting network (hybrid) with identity: {default_id}");
 
     let broadcast_size = config.broadcast_size;
+    let acl = Acl::load(config.acl_file.clone())?;
+    let limiter = RateLimiter::new(&config);
+    let stats = TrafficStats::new(config.stats_metrics);
     let crypto = IdentityCryptoProvider::new(default_id);
-    let client = build_client(config, crypto.clone()).await?;
-
-    super::cli::bind_service(client.clone());
+    let relays = Relays::default();
+    let client = relay::connect(&config, crypto.clone(), &relays).await?;
+    let shared = SharedClient::new(client.clone());
+
+    super::cli::bind_service(
+        shared.clone(),
+        limiter.clone(),
+        relays.clone(),
+        stats.clone(),
+    );
+    acl::bind_service(acl.clone());
 
     let receiver = client.clone().forward_receiver().await.unwrap();
     let mut services: HashSet<_> = Default::default();
@@ -130,7 +139,7 @@ pub async fn start_network(
         services.insert(net::net_service(id));
         services.insert(net::net_transfer_service(id));
     });
-    let state = State::new(ids, services);
+    let state = State::new(ids, services, acl, limiter, stats.clone());
 
     // outbound traffic
     let net_handler = || {
@@ -141,11 +150,12 @@ pub async fn start_network(
     };
 
     {
-        let client = client.clone();
+        let shared = shared.clone();
         typed::bind(
             ya_core_model::net::local::BUS_ID,
             move |_: ya_core_model::net::local::Shutdown| {
-                let mut client = client.clone();
+                shared.close();
+                let mut client = shared.get();
                 async move {
                     client
                         .shutdown()
@@ -157,21 +167,21 @@ pub async fn start_network(
     }
 
     bind_local_bus(
-        client.clone(),
+        shared.clone(),
         net::BUS_ID_UDP,
         state.clone(),
         TransportType::Unreliable,
         net_handler(),
     );
     bind_local_bus(
-        client.clone(),
+        shared.clone(),
         net::BUS_ID,
         state.clone(),
         TransportType::Reliable,
         net_handler(),
     );
     bind_local_bus(
-        client.clone(),
+        shared.clone(),
         net::BUS_ID_TRANSFER,
         state.clone(),
         TransportType::Transfer,
@@ -191,21 +201,21 @@ pub async fn start_network(
     };
 
     bind_local_bus(
-        client.clone(),
+        shared.clone(),
         "/from",
         state.clone(),
         TransportType::Reliable,
         from_handler(),
     );
     bind_local_bus(
-        client.clone(),
+        shared.clone(),
         "/udp/from",
         state.clone(),
         TransportType::Unreliable,
         from_handler(),
     );
     bind_local_bus(
-        client.clone(),
+        shared.clone(),
         "/transfer/from",
         state.clone(),
         TransportType::Transfer,
@@ -213,10 +223,18 @@ pub async fn start_network(
     );
 
     tokio::task::spawn_local(forward_handler(client.clone(), receiver, state.clone()));
-
-    bind_broadcast_handlers(client.clone(), broadcast_size);
-    bind_identity_event_handler(client.clone(), crypto).await;
-    bind_neighbourhood_bcast(client.clone()).await?;
+    tokio::task::spawn_local(relay::probe(config.clone(), shared.clone(), relays.clone()));
+    tokio::task::spawn_local(relay::monitor(
+        config,
+        crypto.clone(),
+        shared.clone(),
+        relays,
+        reconnect_handler(state.clone()),
+    ));
+
+    bind_broadcast_handlers(shared.clone(), broadcast_size, stats);
+    bind_identity_event_handler(shared.clone(), crypto).await;
+    bind_neighbourhood_bcast(shared).await?;
 
     if let Some(address) = client.public_addr().await {
         log::info!("Public address: {}", address);
@@ -228,49 +246,8 @@ pub async fn start_network(
     Ok(())
 }
 
-async fn build_client(
-    config: Arc<Config>,
-    crypto: impl CryptoProvider + 'static,
-) -> anyhow::Result<Client> {
-    let addr = relay_addr(&config)
-        .await
-        .map_err(|e| anyhow!("Resolving hybrid NET relay server failed. Error: {}", e))?;
-    let url = Url::parse(&format!("udp://{addr}"))?;
-
-    ClientBuilder::from_url(url)
-        .crypto(crypto)
-        .listen(config.bind_url.clone())
-        .expire_session_after(config.session_expiration)
-        .session_request_timeout(config.session_request_timeout)
-        .connect(FailFast::No)
-        .build()
-        .await
-}
-
-async fn relay_addr(config: &Config) -> anyhow::Result<SocketAddr> {
-    let host_port = match &config.host {
-        Some(val) => val.to_string(),
-        None => resolver::resolve_yagna_srv_record("_net_relay._udp")
-            .await
-            // FIXME: remove
-            .unwrap_or_else(|_| DEFAULT_NET_RELAY_HOST.to_string()),
-    };
-
-    log::info!("Hybrid NET relay server configured on url: udp://{host_port}");
-
-    let (host, port) = &host_port
-        .split_once(':')
-        .context("Please use host:port format")?;
-    let ip = resolver::try_resolve_dns_record(host).await;
-    let socket = format!("{}:{}", ip, port)
-        .to_socket_addrs()?
-        .next()
-        .ok_or_else(|| anyhow!("Invalid relay address: {ip}:{port}"))?;
-    Ok(socket)
-}
-
 fn bind_local_bus<F>(
-    base_client: Client,
+    base_client: SharedClient,
     address: &'static str,
     state: State,
     transport: TransportType,
@@ -306,7 +283,7 @@ fn bind_local_bus<F>(
                 push_bus_to_local(caller_id, addr, msg, &state_);
             } else {
                 push_bus_to_net(
-                    client.clone(),
+                    client.get(),
                     caller_id,
                     remote_id,
                     address,
@@ -324,7 +301,7 @@ fn bind_local_bus<F>(
                 rx
             } else {
                 forward_bus_to_net(
-                    client.clone(),
+                    client.get(),
                     caller_id,
                     remote_id,
                     address,
@@ -381,7 +358,7 @@ fn bind_local_bus<F>(
                 push_bus_to_local(caller_id, addr, msg, &state);
             } else {
                 push_bus_to_net(
-                    client.clone(),
+                    client.get(),
                     caller_id,
                     remote_id,
                     address,
@@ -398,7 +375,7 @@ fn bind_local_bus<F>(
                 rx
             } else {
                 forward_bus_to_net(
-                    client.clone(),
+                    client.get(),
                     caller_id,
                     remote_id,
                     address,
@@ -439,14 +416,14 @@ fn bind_local_bus<F>(
 }
 
 /// Handle identity changes
-async fn bind_identity_event_handler(client: Client, crypto: IdentityCryptoProvider) {
+async fn bind_identity_event_handler(client: SharedClient, crypto: IdentityCryptoProvider) {
     let endpoint = format!("{}/id", net::BUS_ID);
 
     typed::bind(endpoint.as_str(), move |event: IdentityEvent| {
         log::debug!("Identity event received: {:?}", event);
 
         crypto.reset_alias_cache();
-        let client = client.clone();
+        let client = client.get();
 
         async move {
             match event {
@@ -552,6 +529,9 @@ fn forward_bus_to_net(
         let mut inner = state.inner.borrow_mut();
         inner.requests.insert(request_id.clone(), request);
     }
+    state
+        .stats
+        .record(Direction::Tx, Some(remote_id), &address, msg.len());
 
     tokio::task::spawn_local(async move {
         log::debug!(
@@ -606,6 +586,9 @@ fn push_bus_to_net(
             return;
         }
     };
+    state
+        .stats
+        .record(Direction::Tx, Some(remote_id), &address, msg.len());
 
     tokio::task::spawn_local(async move {
         log::debug!(
@@ -636,6 +619,7 @@ fn broadcast_handler(
     _addr: &str,
     msg: &[u8],
     broadcast_size: u32,
+    stats: TrafficStats,
 ) -> impl Future<Output = Result<Vec<u8>, Error>> {
     let message = msg.to_vec();
     let caller = caller.to_string();
@@ -643,6 +627,7 @@ fn broadcast_handler(
     async move {
         let stub: SendBroadcastStub = serialization::from_slice(&message)
             .map_err(|e| Error::GsbFailure(format!("Invalid broadcast message: {e}")))?;
+        let service = format!("broadcast/{}", stub.topic);
 
         let request = GsbMessage::BroadcastRequest(ya_sb_proto::BroadcastRequest {
             //data: serialization::to_vec(&message)?,
@@ -652,6 +637,7 @@ fn broadcast_handler(
         });
 
         let payload = encode_message(request).map_err(|e| Error::EncodingProblem(e.to_string()))?;
+        stats.record(Direction::Tx, None, &service, payload.len());
 
         client
             .broadcast(payload, broadcast_size)
@@ -668,7 +654,7 @@ fn broadcast_handler(
     })
 }
 
-fn bind_broadcast_handlers(client: Client, broadcast_size: u32) {
+fn bind_broadcast_handlers(client: SharedClient, broadcast_size: u32, stats: TrafficStats) {
     let _ = typed::bind(
         net::local::BUS_ID,
         move |subscribe: net::local::Subscribe| {
@@ -690,7 +676,14 @@ fn bind_broadcast_handlers(client: Client, broadcast_size: u32) {
     let _ = local_bus::subscribe(
         &format!("{}/{}", net::local::BUS_ID, bcast_service_id),
         move |caller: &str, addr: &str, msg: &[u8]| {
-            broadcast_handler(client.clone(), caller, addr, msg, broadcast_size)
+            broadcast_handler(
+                client.get(),
+                caller,
+                addr,
+                msg,
+                broadcast_size,
+                stats.clone(),
+            )
         },
         (),
     );
@@ -751,6 +744,22 @@ fn forward_handler(
         .boxed_local()
 }
 
+/// Handle a relay client created on relay failover.
+/// Routes of the previous client are dropped, since its sessions are closed.
+fn reconnect_handler(state: State) -> impl Fn(Client) + 'static {
+    move |client: Client| {
+        state.inner.borrow_mut().routes.clear();
+
+        let state = state.clone();
+        tokio::task::spawn_local(async move {
+            match client.clone().forward_receiver().await {
+                Some(receiver) => forward_handler(client, receiver, state).await,
+                None => log::error!("Relay client forward receiver is not available"),
+            }
+        });
+    }
+}
+
 fn forward_channel<'a>(
     transport: TransportType,
 ) -> (mpsc::Sender<Payload>, LocalBoxStream<'a, Payload>) {
@@ -783,10 +792,20 @@ fn inbound_handler(
 
         let client = client.clone();
         async move {
-            match codec::decode_message(payload.as_ref()) {
+            let message = codec::decode_message(payload.as_ref());
+            if let Ok(Some(message)) = &message {
+                state.record_inbound(remote_id, message, payload.len());
+            }
+
+            match message {
                 Ok(Some(GsbMessage::CallRequest(request @ ya_sb_proto::CallRequest { .. }))) => {
                     if request.no_reply {
-                        handle_push(request, remote_id, state)
+                        match state.limiter.admit(remote_id, MessageClass::Push) {
+                            Admission::Rejected => {
+                                anyhow::bail!("Push rate limit exceeded, dropping message")
+                            }
+                            admission => handle_push(request, remote_id, state, admission),
+                        }
                     } else {
                         handle_request(client, request, remote_id, state, transport)
                     }
@@ -796,7 +815,12 @@ fn inbound_handler(
                 }
                 Ok(Some(GsbMessage::BroadcastRequest(
                     request @ ya_sb_proto::BroadcastRequest { .. },
-                ))) => handle_broadcast(request, remote_id),
+                ))) => {
+                    if state.limiter.admit(remote_id, MessageClass::Broadcast) != Admission::Now {
+                        anyhow::bail!("Broadcast rate limit exceeded, dropping message");
+                    }
+                    handle_broadcast(request, remote_id)
+                }
                 Ok(None) => {
                     log::trace!("Received a partial message from {remote_id}");
                     Ok(())
@@ -819,6 +843,7 @@ fn handle_push(
     request: ya_sb_proto::CallRequest,
     remote_id: NodeId,
     state: State,
+    admission: Admission,
 ) -> anyhow::Result<()> {
     let caller_id = NodeId::from_str(&request.caller).ok();
 
@@ -833,20 +858,29 @@ fn handle_push(
 
     log::debug!("Handle push request {request_id} to {address} from {remote_id}");
 
-    let fut = match state.get_public_service(address.as_str()) {
-        Some(address) => {
-            log::trace!("Handle push request: calling: {address}");
-            local_bus::push(&address, &request.caller, &request.data)
+    let address = match state.get_public_service(address.as_str()) {
+        Some(address) if !state.acl.check(remote_id, &address) => {
+            log::trace!("Handle push request failed: access denied: {address}");
+            let err = Error::GsbBadRequest(format!("Access denied: {address}"));
+            return Err(err.into());
         }
+        Some(address) => address,
         None => {
             log::trace!("Handle push request failed: unknown address: {address}");
             let err = Error::GsbBadRequest(format!("Unknown address: {address}"));
             return Err(err.into());
         }
     };
+    let caller = request.caller;
+    let data = request.data;
 
     tokio::task::spawn_local(async move {
-        let _ = fut.await;
+        if let Admission::Delayed(delay) = admission {
+            log::trace!("Handle push request: rate limit exceeded, delaying by {delay:?}");
+            tokio::time::sleep(delay).await;
+        }
+        log::trace!("Handle push request: calling: {address}");
+        let _ = local_bus::push(&address, &caller, &data).await;
         log::debug!("Handled push request: {request_id} from: {caller_id}");
     });
 
@@ -879,11 +913,34 @@ fn handle_request(
 
     let eos = Rc::new(AtomicBool::new(false));
     let eos_map = eos.clone();
+    let admission = state.limiter.admit(remote_id, MessageClass::Request);
+    let stats = state.stats.clone();
+    let service = address.clone();
 
     let stream = match state.get_public_service(address.as_str()) {
+        _ if admission == Admission::Rejected => {
+            log::trace!("Handle request failed: rate limit exceeded: {address}");
+            let err = Error::GsbFailure(format!("Rate limit exceeded: {address}"));
+            futures::stream::once(futures::future::err(err)).right_stream()
+        }
+        Some(address) if !state.acl.check(remote_id, &address) => {
+            log::trace!("Handle request failed: access denied: {address}");
+            let err = Error::GsbBadRequest(format!("Access denied: {address}"));
+            futures::stream::once(futures::future::err(err)).right_stream()
+        }
         Some(address) => {
-            log::trace!("Handle request: calling: {address}");
-            local_bus::call_stream(&address, &request.caller, &request.data).left_stream()
+            let caller = request.caller;
+            let data = request.data;
+            futures::stream::once(async move {
+                if let Admission::Delayed(delay) = admission {
+                    log::trace!("Handle request: rate limit exceeded, delaying by {delay:?}");
+                    tokio::time::sleep(delay).await;
+                }
+                log::trace!("Handle request: calling: {address}");
+                local_bus::call_stream(&address, &caller, &data)
+            })
+            .flatten()
+            .left_stream()
         }
         None => {
             log::trace!("Handle request failed: unknown address: {address}");
@@ -922,6 +979,7 @@ fn handle_request(
                     "Handle request {request_id_filter}: reply chunk ({} B)",
                     vec.len()
                 );
+                stats.record(Direction::Tx, Some(caller_id), &service, vec.len());
                 Some(Ok::<Vec<u8>, mpsc::SendError>(vec))
             }
             Err(e) => {
@@ -1052,6 +1110,9 @@ fn handle_broadcast(
 #[derive(Clone)]
 struct State {
     inner: Rc<RefCell<StateInner>>,
+    acl: Acl,
+    limiter: RateLimiter,
+    stats: TrafficStats,
 }
 
 #[derive(Default)]
@@ -1063,16 +1124,42 @@ struct StateInner {
 }
 
 impl State {
-    fn new(ids: impl IntoIterator<Item = NodeId>, services: HashSet<String>) -> Self {
+    fn new(
+        ids: impl IntoIterator<Item = NodeId>,
+        services: HashSet<String>,
+        acl: Acl,
+        limiter: RateLimiter,
+        stats: TrafficStats,
+    ) -> Self {
         Self {
             inner: Rc::new(RefCell::new(StateInner {
                 ids: ids.into_iter().collect(),
                 services,
                 ..Default::default()
             })),
+            acl,
+            limiter,
+            stats,
         }
     }
 
+    /// Accounts inbound traffic to the remote node and the addressed service
+    fn record_inbound(&self, remote_id: NodeId, message: &GsbMessage, bytes: usize) {
+        let service = match message {
+            GsbMessage::CallRequest(request) => request.address.clone(),
+            GsbMessage::CallReply(reply) => {
+                match self.inner.borrow().requests.get(&reply.request_id) {
+                    Some(request) => request.address.clone(),
+                    None => return,
+                }
+            }
+            GsbMessage::BroadcastRequest(request) => format!("broadcast/{}", request.topic),
+            _ => return,
+        };
+        self.stats
+            .record(Direction::Rx, Some(remote_id), &service, bytes);
+    }
+
     async fn forward_sink(
         &self,
         client: Client,
@@ -1114,7 +1201,6 @@ struct Request<S: Clone> {
     caller_id: NodeId,
     #[allow(unused)]
     remote_id: NodeId,
-    #[allow(unused)]
     address: String,
     tx: S,
 }
@@ -1141,7 +1227,7 @@ fn handler_reply_err(
     });
 }
 
-fn parse_net_to_addr(addr: &str) -> anyhow::Result<(NodeId, String)> {
+pub(crate) fn parse_net_to_addr(addr: &str) -> anyhow::Result<(NodeId, String)> {
     const ADDR_CONST: usize = 6;
 
     let mut it = addr.split('/').fuse().skip(1).peekable();
@@ -1159,7 +1245,7 @@ fn parse_net_to_addr(addr: &str) -> anyhow::Result<(NodeId, String)> {
     Ok((to_id, format!("{}{}", prefix, addr)))
 }
 
-fn parse_from_to_addr(addr: &str) -> anyhow::Result<(NodeId, NodeId, String)> {
+pub(crate) fn parse_from_to_addr(addr: &str) -> anyhow::Result<(NodeId, NodeId, String)> {
     const ADDR_CONST: usize = 10;
 
     let mut it = addr.split('/').fuse().skip(1).peekable();
@@ -1190,12 +1276,12 @@ fn gen_id() -> u64 {
     rng.gen::<u64>() & 0x001f_ffff_ffff_ffff_u64
 }
 
-async fn bind_neighbourhood_bcast(client: Client) -> anyhow::Result<(), BindBroadcastError> {
+async fn bind_neighbourhood_bcast(client: SharedClient) -> anyhow::Result<(), BindBroadcastError> {
     let bcast_address = format!("{}/{}", net::local::BUS_ID, NewNeighbour::TOPIC);
     crate::hybrid::bind_broadcast_with_caller(
         &bcast_address,
         move |caller, _msg: SendBroadcastMessage<NewNeighbour>| {
-            let client = client.clone();
+            let client = client.get();
             async move {
                 log::debug!(
                     "NewNeighbour notification fron [{caller}] - invalidating neighborhood cache."
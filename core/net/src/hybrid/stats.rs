@@ -0,0 +1,306 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::Instant;
+
+use metrics::counter;
+
+use ya_core_model::net::local::{NodeTraffic, ServiceTraffic, StatsResponse, TrafficMetrics};
+use ya_core_model::{net, NodeId};
+
+/// Maximum number of tracked services. Traffic of any further services is accounted as `other`
+const MAX_SERVICES: usize = 1024;
+const OTHER_SERVICE: &str = "other";
+/// Window of the `current` rate, in seconds
+const CURRENT_WINDOW: u64 = 10;
+/// Window of the `avg` rate, in seconds
+const AVG_WINDOW: u64 = 60;
+/// Nodes without any traffic for this many seconds are removed
+const NODE_IDLE_TIMEOUT: u64 = 3600;
+/// Number of recorded messages after which idle nodes are removed
+const CLEANUP_INTERVAL: u64 = 4096;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Direction {
+    Tx,
+    Rx,
+}
+
+/// Byte and message counters per remote node and per service address
+#[derive(Clone)]
+pub(crate) struct TrafficStats {
+    inner: Rc<RefCell<TrafficStatsInner>>,
+    export: bool,
+}
+
+struct TrafficStatsInner {
+    started: Instant,
+    nodes: HashMap<NodeId, Traffic>,
+    services: HashMap<String, Traffic>,
+    records: u64,
+}
+
+#[derive(Default)]
+struct Traffic {
+    tx: Counter,
+    rx: Counter,
+}
+
+#[derive(Default)]
+struct Counter {
+    total: u64,
+    messages: u64,
+    /// Bytes per second, for the last `AVG_WINDOW` seconds
+    window: VecDeque<(u64, u64)>,
+}
+
+impl TrafficStats {
+    /// With `export` enabled, per service counters are also exported as metrics
+    pub fn new(export: bool) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(TrafficStatsInner {
+                started: Instant::now(),
+                nodes: Default::default(),
+                services: Default::default(),
+                records: 0,
+            })),
+            export,
+        }
+    }
+
+    pub fn record(
+        &self,
+        direction: Direction,
+        node_id: Option<NodeId>,
+        address: &str,
+        bytes: usize,
+    ) {
+        let bytes = bytes as u64;
+        let service = service_name(address);
+        let mut inner = self.inner.borrow_mut();
+        let now = inner.started.elapsed().as_secs();
+
+        inner.records += 1;
+        if inner.records % CLEANUP_INTERVAL == 0 {
+            inner.remove_idle(now);
+        }
+
+        if let Some(node_id) = node_id {
+            let traffic = inner.nodes.entry(node_id).or_default();
+            traffic.counter(direction).add(now, bytes);
+        }
+
+        let service =
+            if inner.services.contains_key(&service) || inner.services.len() < MAX_SERVICES {
+                service
+            } else {
+                OTHER_SERVICE.to_string()
+            };
+
+        if self.export {
+            match direction {
+                Direction::Tx => {
+                    counter!("net.traffic.tx-bytes", bytes, "service" => service.clone())
+                }
+                Direction::Rx => {
+                    counter!("net.traffic.rx-bytes", bytes, "service" => service.clone())
+                }
+            }
+        }
+
+        let traffic = inner.services.entry(service).or_default();
+        traffic.counter(direction).add(now, bytes);
+    }
+
+    /// Nodes and services ordered by total traffic, descending
+    pub fn snapshot(&self) -> StatsResponse {
+        let inner = self.inner.borrow();
+        let now = inner.started.elapsed().as_secs();
+
+        let mut nodes = inner
+            .nodes
+            .iter()
+            .map(|(node_id, traffic)| NodeTraffic {
+                node_id: *node_id,
+                metrics: traffic.metrics(now),
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|n| std::cmp::Reverse(n.metrics.tx_total + n.metrics.rx_total));
+
+        let mut services = inner
+            .services
+            .iter()
+            .map(|(service, traffic)| ServiceTraffic {
+                service: service.clone(),
+                metrics: traffic.metrics(now),
+            })
+            .collect::<Vec<_>>();
+        services.sort_by_key(|s| std::cmp::Reverse(s.metrics.tx_total + s.metrics.rx_total));
+
+        StatsResponse { nodes, services }
+    }
+}
+
+impl TrafficStatsInner {
+    fn remove_idle(&mut self, now: u64) {
+        self.nodes
+            .retain(|_, traffic| traffic.last_active() + NODE_IDLE_TIMEOUT > now);
+    }
+}
+
+impl Traffic {
+    fn counter(&mut self, direction: Direction) -> &mut Counter {
+        match direction {
+            Direction::Tx => &mut self.tx,
+            Direction::Rx => &mut self.rx,
+        }
+    }
+
+    fn last_active(&self) -> u64 {
+        let last = |c: &Counter| c.window.back().map(|(second, _)| *second);
+        last(&self.tx).max(last(&self.rx)).unwrap_or_default()
+    }
+
+    fn metrics(&self, now: u64) -> TrafficMetrics {
+        TrafficMetrics {
+            tx_total: self.tx.total,
+            tx_messages: self.tx.messages,
+            tx_current: self.tx.rate(now, CURRENT_WINDOW),
+            tx_avg: self.tx.rate(now, AVG_WINDOW),
+            rx_total: self.rx.total,
+            rx_messages: self.rx.messages,
+            rx_current: self.rx.rate(now, CURRENT_WINDOW),
+            rx_avg: self.rx.rate(now, AVG_WINDOW),
+        }
+    }
+}
+
+impl Counter {
+    fn add(&mut self, now: u64, bytes: u64) {
+        self.total += bytes;
+        self.messages += 1;
+
+        match self.window.back_mut() {
+            Some((second, sum)) if *second == now => *sum += bytes,
+            _ => self.window.push_back((now, bytes)),
+        }
+        while matches!(self.window.front(), Some((second, _)) if second + AVG_WINDOW <= now) {
+            self.window.pop_front();
+        }
+    }
+
+    /// Average bytes per second over the last `window` seconds,
+    /// or since the start if the window is not filled yet
+    fn rate(&self, now: u64, window: u64) -> f32 {
+        let bytes: u64 = self
+            .window
+            .iter()
+            .filter(|(second, _)| second + window > now)
+            .map(|(_, bytes)| bytes)
+            .sum();
+        bytes as f32 / window.min(now + 1) as f32
+    }
+}
+
+/// Translates remote node addresses (`[/udp|/transfer]/net/<node id>/<path>`)
+/// to the public service address (`/public/<path>`)
+pub(crate) fn service_name(address: &str) -> String {
+    let address = ["/udp", "/transfer"]
+        .iter()
+        .find_map(|prefix| {
+            address
+                .strip_prefix(prefix)
+                .filter(|rest| rest.starts_with("/net/"))
+        })
+        .unwrap_or(address);
+
+    match address.strip_prefix("/net/") {
+        Some(rest) => match rest.split_once('/') {
+            Some((_, path)) => format!("{}/{}", net::PUBLIC_PREFIX, path),
+            None => net::PUBLIC_PREFIX.to_string(),
+        },
+        None => address.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_service_name() {
+        let id = "0x95369fc6fd02afeca110b9c32a21fb8ad899ee0a";
+        assert_eq!(
+            service_name(&format!("/net/{id}/market/protocol/Get")),
+            "/public/market/protocol/Get"
+        );
+        assert_eq!(
+            service_name(&format!("/udp/net/{id}/vpn/Packet")),
+            "/public/vpn/Packet"
+        );
+        assert_eq!(
+            service_name(&format!("/transfer/net/{id}/gftp/Get")),
+            "/public/gftp/Get"
+        );
+        assert_eq!(service_name("/public/vpn/Packet"), "/public/vpn/Packet");
+        assert_eq!(service_name("broadcast/market"), "broadcast/market");
+    }
+
+    #[test]
+    fn sliding_window_rate() {
+        let mut counter = Counter::default();
+        for second in 0..60 {
+            counter.add(second, 100);
+        }
+        assert_eq!(counter.rate(59, CURRENT_WINDOW), 100.);
+        assert_eq!(counter.rate(59, AVG_WINDOW), 100.);
+
+        counter.add(65, 600);
+        assert_eq!(counter.total, 6600);
+        assert_eq!(counter.messages, 61);
+        assert_eq!(counter.rate(65, CURRENT_WINDOW), 100.);
+        assert_eq!(counter.rate(65, AVG_WINDOW), 6000. / 60.);
+        assert_eq!(counter.rate(200, AVG_WINDOW), 0.);
+    }
+
+    #[test]
+    fn node_and_service_stats() {
+        let stats = TrafficStats::new(false);
+        let a: NodeId = "0x0000000000000000000000000000000000000001"
+            .parse()
+            .unwrap();
+        let b: NodeId = "0x0000000000000000000000000000000000000002"
+            .parse()
+            .unwrap();
+
+        stats.record(Direction::Tx, Some(a), &format!("/net/{a}/vpn/Packet"), 100);
+        stats.record(Direction::Rx, Some(a), "/public/vpn/Packet", 50);
+        stats.record(Direction::Tx, Some(b), &format!("/net/{b}/market/Get"), 500);
+        stats.record(Direction::Tx, None, "broadcast/market", 10);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.nodes.len(), 2);
+        assert_eq!(snapshot.nodes[0].node_id, b);
+        assert_eq!(snapshot.nodes[1].metrics.tx_total, 100);
+        assert_eq!(snapshot.nodes[1].metrics.rx_total, 50);
+
+        let services = snapshot
+            .services
+            .iter()
+            .map(|s| {
+                (
+                    s.service.as_str(),
+                    s.metrics.tx_messages + s.metrics.rx_messages,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            services,
+            vec![
+                ("/public/market/Get", 1),
+                ("/public/vpn/Packet", 2),
+                ("broadcast/market", 1)
+            ]
+        );
+    }
+}
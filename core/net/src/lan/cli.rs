@@ -0,0 +1,32 @@
+use std::net::SocketAddr;
+
+use ya_core_model::net::local as model;
+use ya_core_model::NodeId;
+use ya_service_bus::typed as bus;
+
+use crate::lan::service::State;
+
+pub(crate) fn bind_service(state: State, node_id: NodeId, listen_address: SocketAddr) {
+    let state_ = state.clone();
+    let _ = bus::bind(model::BUS_ID, move |_: model::Stats| {
+        let stats = state_.traffic();
+        async move { Ok(stats) }
+    });
+
+    let _ = bus::bind(model::BUS_ID, move |_: model::Status| {
+        let sessions = state.sessions();
+        let throttled = state.throttled();
+        async move {
+            Ok(model::StatusResponse {
+                node_id,
+                listen_address: Some(listen_address),
+                public_address: None,
+                sessions,
+                metrics: Default::default(),
+                throttled,
+                relays: Default::default(),
+                relay_switches: 0,
+            })
+        }
+    });
+}
@@ -0,0 +1,213 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+
+use ya_core_model::NodeId;
+
+/// Prefix of announcement datagrams, allowing to skip foreign traffic early
+const MAGIC: &[u8] = b"ya-net-lan/1\n";
+/// Number of missed announcements, after which a peer is forgotten
+const PEER_TTL_INTERVALS: u32 = 3;
+
+/// Node identities and the port of its session listener, sent periodically to the discovery
+/// address. Announcements are not signed, identities are verified by the session handshake
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Announcement {
+    pub node_ids: Vec<NodeId>,
+    pub port: u16,
+}
+
+impl Announcement {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        // serialization of ids and numbers does not fail
+        bytes.extend(serde_json::to_vec(self).unwrap_or_default());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let json = bytes.strip_prefix(MAGIC)?;
+        serde_json::from_slice::<Self>(json)
+            .ok()
+            .filter(|a| !a.node_ids.is_empty())
+    }
+}
+
+/// Peers discovered in the local network
+#[derive(Clone, Default)]
+pub(crate) struct Peers {
+    inner: Rc<RefCell<HashMap<NodeId, Peer>>>,
+}
+
+#[derive(Clone, Copy)]
+struct Peer {
+    addr: SocketAddr,
+    seen: Instant,
+}
+
+impl Peers {
+    /// Registers identities of a peer, which sent the announcement from `ip`
+    pub fn update(&self, announcement: &Announcement, ip: IpAddr, now: Instant) -> SocketAddr {
+        let addr = SocketAddr::new(ip, announcement.port);
+        let mut inner = self.inner.borrow_mut();
+        for node_id in announcement.node_ids.iter() {
+            if inner.insert(*node_id, Peer { addr, seen: now }).is_none() {
+                log::debug!("LAN net: discovered node {node_id} at {addr}");
+            }
+        }
+        addr
+    }
+
+    pub fn get(&self, node_id: &NodeId) -> Option<SocketAddr> {
+        self.inner.borrow().get(node_id).map(|peer| peer.addr)
+    }
+
+    /// Session addresses of all known peers
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        let inner = self.inner.borrow();
+        let addrs = inner.values().map(|peer| peer.addr).collect::<HashSet<_>>();
+        addrs.into_iter().collect()
+    }
+
+    fn remove_expired(&self, ttl: Duration, now: Instant) {
+        self.inner.borrow_mut().retain(|node_id, peer| {
+            let alive = now.saturating_duration_since(peer.seen) < ttl;
+            if !alive {
+                log::debug!("LAN net: node {node_id} at {} expired", peer.addr);
+            }
+            alive
+        });
+    }
+}
+
+/// Periodically announces own identities and registers announcements of other Nodes
+pub(crate) async fn run(
+    socket: UdpSocket,
+    group: SocketAddrV4,
+    interval: Duration,
+    announcement: Announcement,
+    peers: Peers,
+) {
+    let own_ids = announcement
+        .node_ids
+        .iter()
+        .copied()
+        .collect::<HashSet<_>>();
+    let payload = announcement.encode();
+    let ttl = interval * PEER_TTL_INTERVALS;
+
+    log::info!("LAN net: announcing on {group} every {interval:?}");
+
+    let mut ticker = tokio::time::interval(interval);
+    let mut buf = vec![0u8; 65536];
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                peers.remove_expired(ttl, Instant::now());
+                if let Err(e) = socket.send_to(&payload, group).await {
+                    log::debug!("LAN net: announcement error: {e}");
+                }
+            }
+            result = socket.recv_from(&mut buf) => {
+                let (len, from) = match result {
+                    Ok(received) => received,
+                    Err(e) => {
+                        log::debug!("LAN net: discovery receive error: {e}");
+                        continue;
+                    }
+                };
+                match Announcement::decode(&buf[..len]) {
+                    Some(a) if a.node_ids.iter().any(|id| own_ids.contains(id)) => {}
+                    Some(a) => {
+                        peers.update(&a, from.ip(), Instant::now());
+                    }
+                    None => log::trace!("LAN net: invalid announcement from {from}"),
+                }
+            }
+        }
+    }
+}
+
+/// Binds the discovery socket, joining the multicast group if `group` is a multicast address
+pub(crate) fn bind(group: SocketAddrV4, interface: Ipv4Addr) -> anyhow::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    // Allow multiple Nodes on a single host
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+
+    if group.ip().is_multicast() {
+        socket.join_multicast_v4(group.ip(), &interface)?;
+        socket.set_multicast_if_v4(&interface)?;
+        socket.set_multicast_loop_v4(true)?;
+    } else {
+        socket.set_broadcast(true)?;
+    }
+
+    let addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, group.port());
+    socket.bind(&addr.into())?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(n: u8) -> NodeId {
+        format!("0x{n:040x}").parse().unwrap()
+    }
+
+    #[test]
+    fn encode_announcement() {
+        let announcement = Announcement {
+            node_ids: vec![node_id(1), node_id(2)],
+            port: 11501,
+        };
+        let bytes = announcement.encode();
+        assert_eq!(Announcement::decode(&bytes), Some(announcement));
+
+        assert_eq!(Announcement::decode(&bytes[1..]), None);
+        assert_eq!(Announcement::decode(&bytes[..bytes.len() - 1]), None);
+        let empty = Announcement {
+            node_ids: vec![],
+            port: 1,
+        };
+        assert_eq!(Announcement::decode(&empty.encode()), None);
+    }
+
+    #[test]
+    fn expire_peers() {
+        let peers = Peers::default();
+        let now = Instant::now();
+        let ip: IpAddr = Ipv4Addr::new(192, 168, 1, 2).into();
+
+        let announcement = Announcement {
+            node_ids: vec![node_id(1), node_id(2)],
+            port: 11501,
+        };
+        let addr = peers.update(&announcement, ip, now);
+        assert_eq!(addr, "192.168.1.2:11501".parse().unwrap());
+        assert_eq!(peers.get(&node_id(2)), Some(addr));
+        assert_eq!(peers.addrs(), vec![addr]);
+
+        let announcement = Announcement {
+            node_ids: vec![node_id(3)],
+            port: 11505,
+        };
+        let later = now + Duration::from_secs(10);
+        peers.update(&announcement, ip, later);
+        assert_eq!(peers.inner.borrow().len(), 3);
+
+        peers.remove_expired(Duration::from_secs(15), now + Duration::from_secs(20));
+        assert_eq!(peers.inner.borrow().len(), 1);
+        assert_eq!(peers.get(&node_id(1)), None);
+        assert!(peers.get(&node_id(3)).is_some());
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::{anyhow, bail};
+use ethsign::Signature;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use ya_core_model::NodeId;
+use ya_relay_client::crypto::CryptoProvider;
+
+use crate::lan::discovery::Announcement;
+
+/// Size of the random challenge, which each side of a session sends first
+pub(crate) const CHALLENGE_SIZE: usize = 32;
+/// Domain separator of signed hellos
+const HELLO_MAGIC: &[u8] = b"ya-net-lan/hello/1\n";
+const SIGNATURE_SIZE: usize = 65;
+
+/// Session hello: an announcement signed with the keys of all announced identities.
+/// Signatures cover the challenge sent by the remote side, so hellos can't be replayed.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Hello {
+    announcement: Announcement,
+    signatures: Vec<Vec<u8>>,
+}
+
+pub(crate) fn challenge() -> [u8; CHALLENGE_SIZE] {
+    rand::thread_rng().gen()
+}
+
+/// Signs own `announcement` in response to the `challenge` of the remote side
+pub(crate) async fn sign(
+    crypto: &dyn CryptoProvider,
+    announcement: &Announcement,
+    challenge: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let digest = digest(announcement, challenge);
+    let mut signatures = Vec::with_capacity(announcement.node_ids.len());
+
+    for node_id in announcement.node_ids.iter() {
+        let signature = crypto.get(*node_id).await?.sign(&digest).await?;
+        let mut bytes = Vec::with_capacity(SIGNATURE_SIZE);
+        bytes.push(signature.v);
+        bytes.extend_from_slice(&signature.r);
+        bytes.extend_from_slice(&signature.s);
+        signatures.push(bytes);
+    }
+
+    let hello = Hello {
+        announcement: announcement.clone(),
+        signatures,
+    };
+    Ok(serde_json::to_vec(&hello)?)
+}
+
+/// Verifies that the remote hello is signed by every announced identity,
+/// in response to own `challenge`
+pub(crate) fn verify(bytes: &[u8], challenge: &[u8]) -> anyhow::Result<Announcement> {
+    let hello: Hello = serde_json::from_slice(bytes).map_err(|_| anyhow!("invalid hello"))?;
+    let announcement = hello.announcement;
+
+    if announcement.node_ids.is_empty() {
+        bail!("hello without identities");
+    }
+    if announcement.node_ids.len() != hello.signatures.len() {
+        bail!("hello signature missing");
+    }
+
+    let digest = digest(&announcement, challenge);
+    for (node_id, bytes) in announcement.node_ids.iter().zip(hello.signatures.iter()) {
+        if bytes.len() != SIGNATURE_SIZE {
+            bail!("invalid hello signature of {node_id}");
+        }
+
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r.copy_from_slice(&bytes[1..33]);
+        s.copy_from_slice(&bytes[33..65]);
+        let signature = Signature { v: bytes[0], r, s };
+
+        let key = signature
+            .recover(&digest)
+            .map_err(|_| anyhow!("invalid hello signature of {node_id}"))?;
+        if NodeId::from(key.address().as_ref()) != *node_id {
+            bail!("hello not signed by {node_id}");
+        }
+    }
+
+    Ok(announcement)
+}
+
+fn digest(announcement: &Announcement, challenge: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.input(HELLO_MAGIC);
+    hasher.input(challenge);
+    hasher.input(announcement.encode());
+
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&hasher.result());
+    digest
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::rc::Rc;
+
+    use ethsign::{PublicKey, SecretKey};
+    use futures::future::LocalBoxFuture;
+    use futures::FutureExt;
+
+    use ya_relay_client::crypto::Crypto;
+
+    use super::*;
+
+    /// Signs with an in-memory key, for any requested identity
+    pub(crate) struct TestCrypto {
+        key: Rc<TestKey>,
+    }
+
+    struct TestKey(SecretKey);
+
+    impl TestCrypto {
+        pub fn new(seed: u8) -> Self {
+            let key = SecretKey::from_raw(&[seed; 32]).unwrap();
+            Self {
+                key: Rc::new(TestKey(key)),
+            }
+        }
+
+        pub fn node_id(&self) -> NodeId {
+            NodeId::from(self.key.0.public().address().as_ref())
+        }
+    }
+
+    impl CryptoProvider for TestCrypto {
+        fn default_id<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<NodeId>> {
+            futures::future::ok(self.node_id()).boxed_local()
+        }
+
+        fn aliases<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<Vec<NodeId>>> {
+            futures::future::ok(Vec::new()).boxed_local()
+        }
+
+        fn get<'a>(&self, _: NodeId) -> LocalBoxFuture<'a, anyhow::Result<Rc<dyn Crypto>>> {
+            let crypto: Rc<dyn Crypto> = self.key.clone();
+            futures::future::ok(crypto).boxed_local()
+        }
+    }
+
+    impl Crypto for TestKey {
+        fn public_key<'a>(&self) -> LocalBoxFuture<'a, anyhow::Result<PublicKey>> {
+            futures::future::ok(self.0.public()).boxed_local()
+        }
+
+        fn sign<'a>(&self, message: &'a [u8]) -> LocalBoxFuture<'a, anyhow::Result<Signature>> {
+            let result = self.0.sign(message).map_err(|_| anyhow!("signing failed"));
+            futures::future::ready(result).boxed_local()
+        }
+
+        fn encrypt<'a>(
+            &self,
+            _message: &'a [u8],
+            _remote_key: &'a PublicKey,
+        ) -> LocalBoxFuture<'a, anyhow::Result<Vec<u8>>> {
+            futures::future::err(anyhow!("not supported")).boxed_local()
+        }
+    }
+
+    #[test]
+    fn signed_hello() {
+        let crypto = TestCrypto::new(1);
+        let announcement = Announcement {
+            node_ids: vec![crypto.node_id()],
+            port: 11501,
+        };
+        let challenge = challenge();
+
+        let hello = futures::executor::block_on(sign(&crypto, &announcement, &challenge)).unwrap();
+        assert_eq!(verify(&hello, &challenge).unwrap(), announcement);
+        // hellos are bound to the challenge of a single session
+        assert!(verify(&hello, &super::challenge()).is_err());
+        assert!(verify(&hello[1..], &challenge).is_err());
+
+        let forged = Announcement {
+            node_ids: vec![crypto.node_id(), TestCrypto::new(2).node_id()],
+            port: 11501,
+        };
+        let hello = futures::executor::block_on(sign(&crypto, &forged, &challenge)).unwrap();
+        assert!(verify(&hello, &challenge).is_err());
+    }
+}
@@ -0,0 +1,6 @@
+mod cli;
+mod discovery;
+mod handshake;
+mod service;
+
+pub use service::{start_network, Net};
@@ -0,0 +1,1053 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context as AnyhowContext};
+use futures::channel::mpsc;
+use futures::{FutureExt, SinkExt, StreamExt, TryFutureExt};
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{FramedRead, LengthDelimitedCodec};
+
+use ya_core_model::net::local::{
+    SendBroadcastMessage, SendBroadcastStub, StatsResponse, ThrottleStats,
+};
+use ya_core_model::{identity, net, NodeId};
+use ya_relay_client::crypto::CryptoProvider;
+use ya_sb_proto::codec::GsbMessage;
+use ya_sb_proto::CallReplyCode;
+use ya_sb_util::RevPrefixes;
+use ya_service_bus::untyped::{Fn4HandlerExt, Fn4StreamHandlerExt};
+use ya_service_bus::{
+    serialization, typed, untyped as local_bus, Error, ResponseChunk, RpcEndpoint, RpcMessage,
+};
+
+use crate::bcast::BCastService;
+use crate::config::Config;
+use crate::hybrid::acl::{self, Acl};
+use crate::hybrid::codec;
+use crate::hybrid::crypto::IdentityCryptoProvider;
+use crate::hybrid::limiter::{Admission, MessageClass, RateLimiter};
+use crate::hybrid::stats::{Direction, TrafficStats};
+use crate::hybrid::{parse_from_to_addr, parse_net_to_addr};
+use crate::lan::discovery::{self, Announcement, Peers};
+use crate::lan::handshake;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_FRAME_LENGTH: usize = 64 * 1024 * 1024;
+
+type BusSender = mpsc::Sender<ResponseChunk>;
+type BusReceiver = mpsc::Receiver<ResponseChunk>;
+type SessionSender = mpsc::UnboundedSender<Vec<u8>>;
+type Frames = FramedRead<OwnedReadHalf, LengthDelimitedCodec>;
+
+pub struct Net;
+
+impl Net {
+    pub async fn gsb<Context>(_: Context, config: Config) -> anyhow::Result<()> {
+        ya_service_bus::serialization::CONFIG.set_compress(true);
+
+        let (default_id, ids) = crate::service::identities().await?;
+        start_network(config, default_id, ids).await
+    }
+}
+
+pub async fn start_network(
+    config: Config,
+    default_id: NodeId,
+    ids: Vec<NodeId>,
+) -> anyhow::Result<()> {
+    log::info!("Starting network (LAN) with identity: {default_id}");
+
+    let listener = TcpListener::bind(config.lan_listen)
+        .await
+        .with_context(|| format!("Unable to listen on {}", config.lan_listen))?;
+    let listen_addr = listener.local_addr()?;
+    let socket = discovery::bind(config.lan_discovery, config.lan_interface)
+        .with_context(|| format!("Unable to bind discovery on {}", config.lan_discovery))?;
+
+    log::info!("LAN net: listening for sessions on {listen_addr}");
+
+    // Locked identities can't sign session hellos, so they're not announced
+    let unlocked = unlocked_identities().await?;
+    let mut node_ids = vec![default_id];
+    node_ids.extend(unlocked.into_iter().filter(|id| *id != default_id));
+    let announcement = Announcement {
+        node_ids,
+        port: listen_addr.port(),
+    };
+
+    let acl = Acl::load(config.acl_file.clone())?;
+    let limiter = RateLimiter::new(&config);
+    let stats = TrafficStats::new(config.stats_metrics);
+    let crypto = Rc::new(IdentityCryptoProvider::new(default_id));
+    acl::bind_service(acl.clone());
+
+    let state = State::new(ids, announcement.clone(), crypto, acl, limiter, stats);
+
+    let net_handler = || {
+        move |_: &str, addr: &str| match parse_net_to_addr(addr) {
+            Ok((to, addr)) => Ok((default_id, to, addr)),
+            Err(err) => anyhow::bail!("invalid address: {}", err),
+        }
+    };
+    for address in [net::BUS_ID, net::BUS_ID_UDP, net::BUS_ID_TRANSFER] {
+        bind_local_bus(address, state.clone(), net_handler());
+    }
+
+    let from_handler = || {
+        let state = state.clone();
+        move |_: &str, addr: &str| {
+            let (from, to, addr) =
+                parse_from_to_addr(addr).map_err(|e| anyhow!("invalid address: {}", e))?;
+            if !state.is_local(&from) {
+                anyhow::bail!("Trying to send message from unknown identity: {}", from);
+            }
+            Ok((from, to, addr))
+        }
+    };
+    for address in ["/from", "/udp/from", "/transfer/from"] {
+        bind_local_bus(address, state.clone(), from_handler());
+    }
+
+    bind_broadcast_handlers(state.clone());
+    super::cli::bind_service(state.clone(), default_id, listen_addr);
+
+    tokio::task::spawn_local(accept(listener, state.clone()));
+    tokio::task::spawn_local(discovery::run(
+        socket,
+        config.lan_discovery,
+        config.lan_announce_interval,
+        announcement,
+        state.peers.clone(),
+    ));
+
+    Ok(())
+}
+
+async fn unlocked_identities() -> anyhow::Result<Vec<NodeId>> {
+    let ids = typed::service(identity::BUS_ID)
+        .send(identity::List::default())
+        .await
+        .map_err(anyhow::Error::msg)??;
+    Ok(ids
+        .into_iter()
+        .filter(|info| !info.is_locked)
+        .map(|info| info.node_id)
+        .collect())
+}
+
+/// Accept sessions from other Nodes
+async fn accept(listener: TcpListener, state: State) {
+    loop {
+        let (stream, from) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                log::warn!("LAN net: unable to accept a session: {e}");
+                continue;
+            }
+        };
+
+        let state = state.clone();
+        tokio::task::spawn_local(async move {
+            let (read, mut write) = stream.into_split();
+            let mut frames = framed(read);
+
+            match authenticate(&state, &mut frames, &mut write).await {
+                Ok(hello) => {
+                    let addr = SocketAddr::new(from.ip(), hello.port);
+                    log::debug!("LAN net: accepted session from {addr}");
+                    open_session(&state, frames, write, addr, hello);
+                }
+                Err(e) => log::debug!("LAN net: session from {from} rejected: {e}"),
+            }
+        });
+    }
+}
+
+/// Returns an open session with a Node listening on `addr`, or connects to it
+async fn connect(state: &State, addr: SocketAddr) -> anyhow::Result<Session> {
+    if let Some(session) = state.session(&addr) {
+        return Ok(session);
+    }
+
+    let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+        .await
+        .map_err(|_| anyhow!("Connection to {addr} timed out"))??;
+    stream.set_nodelay(true)?;
+
+    let (read, mut write) = stream.into_split();
+    let mut frames = framed(read);
+    let hello = authenticate(state, &mut frames, &mut write)
+        .await
+        .with_context(|| format!("Session with {addr} rejected"))?;
+
+    // Another session may have been established in the meantime
+    if let Some(session) = state.session(&addr) {
+        return Ok(session);
+    }
+
+    log::debug!("LAN net: connected to {addr}");
+    Ok(open_session(state, frames, write, addr, hello))
+}
+
+/// Exchanges random challenges and hellos signed in response to them.
+/// Returns the announcement of the remote Node, with all identities verified.
+async fn authenticate(
+    state: &State,
+    frames: &mut Frames,
+    write: &mut OwnedWriteHalf,
+) -> anyhow::Result<Announcement> {
+    let exchange = async {
+        let challenge = handshake::challenge();
+        write.write_all(&frame(&challenge)).await?;
+
+        let remote_challenge = read_frame(frames).await?;
+        if remote_challenge.len() != handshake::CHALLENGE_SIZE {
+            bail!("invalid challenge");
+        }
+        let hello = handshake::sign(&*state.crypto, &state.announcement, &remote_challenge).await?;
+        write.write_all(&frame(&hello)).await?;
+
+        handshake::verify(&read_frame(frames).await?, &challenge)
+    };
+
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, exchange)
+        .await
+        .map_err(|_| anyhow!("handshake timeout"))?
+}
+
+/// Starts tasks of an authenticated session
+fn open_session(
+    state: &State,
+    frames: Frames,
+    write: OwnedWriteHalf,
+    addr: SocketAddr,
+    hello: Announcement,
+) -> Session {
+    let (tx, rx) = mpsc::unbounded();
+
+    state.peers.update(&hello, addr.ip(), Instant::now());
+    let node_ids = hello.node_ids.into_iter().collect::<HashSet<_>>();
+
+    let session = state.add_session(addr, tx, node_ids);
+    tokio::task::spawn_local(write_frames(write, rx, addr));
+    tokio::task::spawn_local(read_frames(state.clone(), frames, session.clone(), addr));
+
+    session
+}
+
+async fn write_frames(
+    mut write: OwnedWriteHalf,
+    mut rx: mpsc::UnboundedReceiver<Vec<u8>>,
+    addr: SocketAddr,
+) {
+    while let Some(bytes) = rx.next().await {
+        if let Err(e) = write.write_all(&bytes).await {
+            log::debug!("LAN net: session with {addr} write error: {e}");
+            break;
+        }
+    }
+}
+
+async fn read_frames(state: State, mut frames: Frames, session: Session, addr: SocketAddr) {
+    let result = async {
+        while let Some(frame) = frames.next().await {
+            let frame = frame?;
+            let message = codec::decode_message(&frame);
+            if let Ok(Some(message)) = &message {
+                state.record_inbound(&session, message, frame.len());
+            }
+
+            let result = match message {
+                Ok(Some(GsbMessage::CallRequest(request))) => {
+                    handle_request(&state, request, &session)
+                }
+                Ok(Some(GsbMessage::CallReply(reply))) => handle_reply(&state, reply, session.id),
+                Ok(Some(GsbMessage::BroadcastRequest(request))) => {
+                    handle_broadcast(&state, request, &session)
+                }
+                Ok(_) => Err(anyhow!("unexpected message type")),
+                Err(e) => Err(anyhow!("invalid message: {e}")),
+            };
+            if let Err(e) = result {
+                log::debug!("LAN net: message from {addr} error: {e}");
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => log::debug!("LAN net: session with {addr} closed"),
+        Err(e) => log::debug!("LAN net: session with {addr} closed: {e}"),
+    }
+    state.remove_session(addr, session.id);
+}
+
+/// Reads a single frame, without the length prefix
+async fn read_frame(frames: &mut Frames) -> anyhow::Result<Vec<u8>> {
+    let frame = frames
+        .next()
+        .await
+        .ok_or_else(|| anyhow!("session closed"))??;
+    Ok(frame[4..].to_vec())
+}
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+fn framed(read: OwnedReadHalf) -> Frames {
+    // Frames keep the length prefix, as expected by `codec::decode_message`
+    let codec = LengthDelimitedCodec::builder()
+        .length_field_length(4)
+        .num_skip(0)
+        .max_frame_length(MAX_FRAME_LENGTH)
+        .new_codec();
+    FramedRead::new(read, codec)
+}
+
+fn bind_local_bus<F>(address: &'static str, state: State, resolver: F)
+where
+    F: Fn(&str, &str) -> anyhow::Result<(NodeId, NodeId, String)> + 'static,
+{
+    let resolver = Rc::new(resolver);
+    let resolver_ = resolver.clone();
+    let state_ = state.clone();
+
+    let rpc = move |caller: &str, addr: &str, msg: &[u8], no_reply: bool| {
+        let rx = match forward(&state_, &*resolver_, caller, addr, msg, no_reply) {
+            Ok(rx) => rx,
+            Err(err) => {
+                log::debug!("rpc {} forward error: {}", addr, err);
+                return futures::future::err(Error::GsbFailure(err.to_string())).left_future();
+            }
+        };
+
+        async move {
+            match rx {
+                None => Ok(Vec::new()),
+                Some(mut rx) => match rx.next().await.ok_or(Error::Cancelled)? {
+                    ResponseChunk::Full(data) => codec::decode_reply(data),
+                    ResponseChunk::Part(_) => {
+                        Err(Error::GsbFailure("partial response".to_string()))
+                    }
+                },
+            }
+        }
+        .right_future()
+    };
+
+    let stream = move |caller: &str, addr: &str, msg: &[u8], no_reply: bool| {
+        let rx = match forward(&state, &*resolver, caller, addr, msg, no_reply) {
+            Ok(Some(rx)) => rx,
+            Ok(None) => return futures::stream::empty().boxed_local(),
+            Err(err) => {
+                log::debug!("local bus: stream call (egress) to {} error: {}", addr, err);
+                let err = Error::GsbFailure(err.to_string());
+                return futures::stream::once(futures::future::err(err)).boxed_local();
+            }
+        };
+
+        let eos = Rc::new(AtomicBool::new(false));
+        let eos_chain = eos.clone();
+
+        rx.map(move |chunk| match chunk {
+            ResponseChunk::Full(v) => {
+                eos.store(true, Relaxed);
+                codec::decode_reply(v).map(ResponseChunk::Full)
+            }
+            chunk => Ok(chunk),
+        })
+        .chain(futures::stream::poll_fn(move |_| {
+            if eos_chain.load(Relaxed) {
+                Poll::Ready(None)
+            } else {
+                eos_chain.store(true, Relaxed);
+                Poll::Ready(Some(Ok(ResponseChunk::Full(Vec::new()))))
+            }
+        }))
+        .boxed_local()
+    };
+
+    log::debug!("local bus: subscribing to {}", address);
+    local_bus::subscribe(address, rpc.into_handler(), stream.into_stream_handler());
+}
+
+/// Forward a call from the local bus to a local identity or a remote Node
+fn forward(
+    state: &State,
+    resolver: &dyn Fn(&str, &str) -> anyhow::Result<(NodeId, NodeId, String)>,
+    caller: &str,
+    addr: &str,
+    msg: &[u8],
+    no_reply: bool,
+) -> anyhow::Result<Option<BusReceiver>> {
+    let (caller_id, remote_id, address) = resolver(caller, addr)?;
+
+    log::trace!(
+        "local bus: call (egress): {address} ({caller_id} -> {remote_id}), no_reply: {no_reply}"
+    );
+
+    if state.is_local(&remote_id) {
+        return forward_bus_to_local(state, caller_id, &address, msg, no_reply);
+    }
+    forward_bus_to_net(state, caller_id, remote_id, address, msg, no_reply)
+}
+
+fn forward_bus_to_local(
+    state: &State,
+    caller_id: NodeId,
+    address: &str,
+    msg: &[u8],
+    no_reply: bool,
+) -> anyhow::Result<Option<BusReceiver>> {
+    let address = state
+        .get_public_service(address)
+        .ok_or_else(|| anyhow!("unknown address: {address}"))?;
+    let caller = caller_id.to_string();
+
+    if no_reply {
+        let push = local_bus::push(&address, &caller, msg);
+        tokio::task::spawn_local(async move {
+            let _ = push.await;
+        });
+        return Ok(None);
+    }
+
+    let (tx, rx) = mpsc::channel(1);
+    let send = local_bus::call_stream(&address, &caller, msg);
+    tokio::task::spawn_local(async move {
+        let _ = send
+            .forward(tx.sink_map_err(|e| Error::GsbFailure(e.to_string())))
+            .await;
+    });
+    Ok(Some(rx))
+}
+
+fn forward_bus_to_net(
+    state: &State,
+    caller_id: NodeId,
+    remote_id: NodeId,
+    address: String,
+    msg: &[u8],
+    no_reply: bool,
+) -> anyhow::Result<Option<BusReceiver>> {
+    let request_id = gen_id().to_string();
+    let msg = codec::encode_request(
+        caller_id,
+        address.clone(),
+        request_id.clone(),
+        msg.to_vec(),
+        no_reply,
+    )?;
+    state
+        .stats
+        .record(Direction::Tx, Some(remote_id), &address, msg.len());
+
+    let addr = state
+        .peers
+        .get(&remote_id)
+        .ok_or_else(|| anyhow!("Node {remote_id} not found in the local network"))?;
+
+    let (tx, rx) = match no_reply {
+        true => (None, None),
+        false => {
+            let (tx, rx) = mpsc::channel(1);
+            (Some(tx), Some(rx))
+        }
+    };
+
+    let state = state.clone();
+    tokio::task::spawn_local(async move {
+        let result = match connect(&state, addr).await {
+            // Discovery announcements are not signed, the session hello is
+            Ok(session) if !session.node_ids.contains(&remote_id) => {
+                Err(anyhow!("Node {remote_id} is not authenticated at {addr}"))
+            }
+            Ok(session) => {
+                if let Some(tx) = tx.clone() {
+                    let request = Request {
+                        session_id: session.id,
+                        remote_id,
+                        address,
+                        tx,
+                    };
+                    state.add_request(request_id.clone(), request);
+                }
+                session
+                    .tx
+                    .unbounded_send(msg)
+                    .map_err(|_| anyhow!("session closed"))
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            log::debug!("LAN net: error forwarding message to {remote_id}: {e}");
+            if let Some(tx) = tx {
+                state.remove_request(&request_id);
+                let err = format!("Net: error forwarding message: {e}");
+                reply_service_err(request_id, err, tx);
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+/// Forward node GSB requests from the network to the local bus
+fn handle_request(
+    state: &State,
+    request: ya_sb_proto::CallRequest,
+    session: &Session,
+) -> anyhow::Result<()> {
+    let caller_id = NodeId::from_str(&request.caller)?;
+    if !session.node_ids.contains(&caller_id) {
+        bail!("Invalid caller id: {}", request.caller);
+    }
+
+    let address = request.address;
+    let request_id = request.request_id;
+
+    log::debug!("Handle request {request_id} to {address} from {caller_id}");
+
+    if request.no_reply {
+        let admission = state.limiter.admit(caller_id, MessageClass::Push);
+        let address = match state.get_public_service(&address) {
+            _ if admission == Admission::Rejected => {
+                bail!("Push rate limit exceeded, dropping message")
+            }
+            Some(address) if !state.acl.check(caller_id, &address) => {
+                bail!("Access denied: {address}")
+            }
+            Some(address) => address,
+            None => bail!("Unknown address: {address}"),
+        };
+        let caller = request.caller;
+        let data = request.data;
+
+        tokio::task::spawn_local(async move {
+            if let Admission::Delayed(delay) = admission {
+                log::trace!("Handle push request: rate limit exceeded, delaying by {delay:?}");
+                tokio::time::sleep(delay).await;
+            }
+            let _ = local_bus::push(&address, &caller, &data).await;
+        });
+        return Ok(());
+    }
+
+    let request_id_chain = request_id.clone();
+    let eos = Rc::new(AtomicBool::new(false));
+    let eos_map = eos.clone();
+    let admission = state.limiter.admit(caller_id, MessageClass::Request);
+    let stats = state.stats.clone();
+    let service = address.clone();
+
+    let stream = match state.get_public_service(&address) {
+        _ if admission == Admission::Rejected => {
+            let err = Error::GsbFailure(format!("Rate limit exceeded: {address}"));
+            futures::stream::once(futures::future::err(err)).right_stream()
+        }
+        Some(address) if !state.acl.check(caller_id, &address) => {
+            let err = Error::GsbBadRequest(format!("Access denied: {address}"));
+            futures::stream::once(futures::future::err(err)).right_stream()
+        }
+        Some(address) => {
+            let caller = request.caller;
+            let data = request.data;
+            futures::stream::once(async move {
+                if let Admission::Delayed(delay) = admission {
+                    log::trace!("Handle request: rate limit exceeded, delaying by {delay:?}");
+                    tokio::time::sleep(delay).await;
+                }
+                local_bus::call_stream(&address, &caller, &data)
+            })
+            .flatten()
+            .left_stream()
+        }
+        None => {
+            let err = Error::GsbBadRequest(format!("Unknown address: {address}"));
+            futures::stream::once(futures::future::err(err)).right_stream()
+        }
+    }
+    .map(move |result| match result {
+        Ok(ResponseChunk::Full(v)) => {
+            eos_map.store(true, Relaxed);
+            match codec::decode_reply(v) {
+                Ok(v) => codec::reply_ok(request_id.clone(), ResponseChunk::Full(v)),
+                Err(err) => codec::reply_err(request_id.clone(), err),
+            }
+        }
+        Ok(chunk) => codec::reply_ok(request_id.clone(), chunk),
+        Err(err) => {
+            eos_map.store(true, Relaxed);
+            codec::reply_err(request_id.clone(), err)
+        }
+    })
+    .chain(futures::stream::poll_fn(move |_| {
+        if eos.load(Relaxed) {
+            Poll::Ready(None)
+        } else {
+            eos.store(true, Relaxed);
+            Poll::Ready(Some(codec::reply_eos(request_id_chain.clone())))
+        }
+    }));
+
+    let tx = session.tx.clone();
+    tokio::task::spawn_local(async move {
+        let mut stream = Box::pin(stream);
+        while let Some(reply) = stream.next().await {
+            match codec::encode_message(reply) {
+                Ok(bytes) => {
+                    stats.record(Direction::Tx, Some(caller_id), &service, bytes.len());
+                    if tx.unbounded_send(bytes).is_err() {
+                        log::debug!("Replying to [{caller_id}] failed: session closed");
+                        break;
+                    }
+                }
+                Err(e) => log::debug!("Handle request: encode reply error: {e}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Forward replies from the network to the local bus
+fn handle_reply(
+    state: &State,
+    reply: ya_sb_proto::CallReply,
+    session_id: u64,
+) -> anyhow::Result<()> {
+    let full = reply.reply_type == ya_sb_proto::CallReplyType::Full as i32;
+    let mut request = match state.get_request(&reply.request_id, session_id) {
+        Some(request) => {
+            if full {
+                state.remove_request(&reply.request_id);
+            }
+            request
+        }
+        None => anyhow::bail!("invalid reply request id: {}", reply.request_id),
+    };
+
+    let request_id = reply.request_id.clone();
+    let data = if reply.code == CallReplyCode::CallReplyOk as i32 {
+        reply.data
+    } else {
+        codec::encode_reply(reply)?
+    };
+
+    tokio::task::spawn_local(async move {
+        let chunk = match full {
+            true => ResponseChunk::Full(data),
+            false => ResponseChunk::Part(data),
+        };
+        if request.tx.send(chunk).await.is_err() {
+            log::debug!("Failed to forward reply {request_id}: channel closed");
+        }
+    });
+
+    Ok(())
+}
+
+/// Forward broadcasts from the network to the local bus
+fn handle_broadcast(
+    state: &State,
+    request: ya_sb_proto::BroadcastRequest,
+    session: &Session,
+) -> anyhow::Result<()> {
+    let caller_id = NodeId::from_str(&request.caller)?;
+    if !session.node_ids.contains(&caller_id) {
+        bail!("Invalid broadcast caller id: {}", request.caller);
+    }
+    if state.limiter.admit(caller_id, MessageClass::Broadcast) != Admission::Now {
+        bail!("Broadcast rate limit exceeded, dropping message");
+    }
+
+    log::trace!(
+        "Received broadcast to topic {} from [{}].",
+        &request.topic,
+        &request.caller
+    );
+
+    let bcast = state.bcast.clone();
+    tokio::task::spawn_local(async move {
+        let caller = caller_id.to_string();
+        let bcast_service_id = <SendBroadcastMessage<()> as RpcMessage>::ID;
+
+        for endpoint in bcast.resolve(&request.topic).await {
+            let addr = format!("{}/{}", endpoint, bcast_service_id);
+            if let Err(e) = local_bus::send(&addr, &caller, &request.data).await {
+                log::debug!("Forwarding broadcast from [{caller}] to local endpoint error: {e}");
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn bind_broadcast_handlers(state: State) {
+    let bcast = state.bcast.clone();
+    let _ = typed::bind(
+        net::local::BUS_ID,
+        move |subscribe: net::local::Subscribe| {
+            let bcast = bcast.clone();
+            async move {
+                let topic = subscribe.topic().to_owned();
+                let (is_new, id) = bcast.add(subscribe).await;
+                if is_new {
+                    log::debug!("NET: Created new topic: {}", topic);
+                }
+                Ok(id)
+            }
+        },
+    );
+
+    let bcast_service_id = <SendBroadcastMessage<()> as RpcMessage>::ID;
+    let _ = local_bus::subscribe(
+        &format!("{}/{}", net::local::BUS_ID, bcast_service_id),
+        move |caller: &str, _addr: &str, msg: &[u8]| broadcast(state.clone(), caller, msg),
+        (),
+    );
+}
+
+/// Send a broadcast message to all discovered Nodes
+fn broadcast(
+    state: State,
+    caller: &str,
+    msg: &[u8],
+) -> impl Future<Output = Result<Vec<u8>, Error>> {
+    let message = msg.to_vec();
+    let caller = caller.to_string();
+
+    async move {
+        let stub: SendBroadcastStub = serialization::from_slice(&message)
+            .map_err(|e| Error::GsbFailure(format!("Invalid broadcast message: {e}")))?;
+        let service = format!("broadcast/{}", stub.topic);
+        let request = GsbMessage::BroadcastRequest(ya_sb_proto::BroadcastRequest {
+            data: message,
+            caller,
+            topic: stub.topic,
+        });
+        let payload = codec::encode_message(request)?;
+        state
+            .stats
+            .record(Direction::Tx, None, &service, payload.len());
+
+        let addrs = state.peers.addrs();
+        futures::future::join_all(addrs.into_iter().map(|addr| {
+            let payload = payload.clone();
+            connect(&state, addr)
+                .and_then(|session| async move {
+                    session
+                        .tx
+                        .unbounded_send(payload)
+                        .map_err(|_| anyhow!("session closed"))
+                })
+                .map_err(move |e| log::debug!("Unable to broadcast message to {addr}: {e}"))
+        }))
+        .await;
+
+        Ok(serialization::to_vec(&Ok::<(), ()>(())).unwrap())
+    }
+}
+
+fn reply_service_err(request_id: impl ToString, error: impl ToString, mut tx: BusSender) {
+    let code = CallReplyCode::ServiceFailure as i32;
+    let err = codec::encode_error(request_id, error, code).unwrap();
+    tokio::task::spawn_local(async move {
+        let _ = tx.send(ResponseChunk::Full(err)).await;
+    });
+}
+
+fn gen_id() -> u64 {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    rng.gen::<u64>() & 0x001f_ffff_ffff_ffff_u64
+}
+
+#[derive(Clone)]
+pub(crate) struct State {
+    inner: Rc<RefCell<StateInner>>,
+    peers: Peers,
+    bcast: BCastService,
+    announcement: Rc<Announcement>,
+    crypto: Rc<dyn CryptoProvider>,
+    acl: Acl,
+    limiter: RateLimiter,
+    stats: TrafficStats,
+}
+
+#[derive(Default)]
+struct StateInner {
+    ids: HashSet<NodeId>,
+    services: HashSet<String>,
+    sessions: HashMap<SocketAddr, Session>,
+    requests: HashMap<String, Request>,
+    last_session_id: u64,
+}
+
+/// Authenticated session with a Node, which owns `node_ids`
+#[derive(Clone)]
+struct Session {
+    id: u64,
+    tx: SessionSender,
+    node_ids: Rc<HashSet<NodeId>>,
+}
+
+#[derive(Clone)]
+struct Request {
+    session_id: u64,
+    remote_id: NodeId,
+    address: String,
+    tx: BusSender,
+}
+
+impl State {
+    fn new(
+        ids: Vec<NodeId>,
+        announcement: Announcement,
+        crypto: Rc<dyn CryptoProvider>,
+        acl: Acl,
+        limiter: RateLimiter,
+        stats: TrafficStats,
+    ) -> Self {
+        let mut services = HashSet::new();
+        ids.iter().for_each(|id| {
+            services.insert(net::net_service_udp(id));
+            services.insert(net::net_service(id));
+            services.insert(net::net_transfer_service(id));
+        });
+
+        Self {
+            inner: Rc::new(RefCell::new(StateInner {
+                ids: ids.into_iter().collect(),
+                services,
+                ..Default::default()
+            })),
+            peers: Default::default(),
+            bcast: Default::default(),
+            announcement: Rc::new(announcement),
+            crypto,
+            acl,
+            limiter,
+            stats,
+        }
+    }
+
+    fn is_local(&self, node_id: &NodeId) -> bool {
+        self.inner.borrow().ids.contains(node_id)
+    }
+
+    fn get_public_service(&self, addr: &str) -> Option<String> {
+        let inner = self.inner.borrow();
+        RevPrefixes(addr)
+            .find_map(|s| inner.services.get(s))
+            .map(|s| addr.replacen(s, net::PUBLIC_PREFIX, 1))
+    }
+
+    fn session(&self, addr: &SocketAddr) -> Option<Session> {
+        let inner = self.inner.borrow();
+        inner
+            .sessions
+            .get(addr)
+            .filter(|session| !session.tx.is_closed())
+            .cloned()
+    }
+
+    pub(crate) fn sessions(&self) -> usize {
+        self.inner.borrow().sessions.len()
+    }
+
+    pub(crate) fn throttled(&self) -> ThrottleStats {
+        self.limiter.stats()
+    }
+
+    pub(crate) fn traffic(&self) -> StatsResponse {
+        self.stats.snapshot()
+    }
+
+    fn add_session(
+        &self,
+        addr: SocketAddr,
+        tx: SessionSender,
+        node_ids: HashSet<NodeId>,
+    ) -> Session {
+        let mut inner = self.inner.borrow_mut();
+        inner.last_session_id += 1;
+        let session = Session {
+            id: inner.last_session_id,
+            tx,
+            node_ids: Rc::new(node_ids),
+        };
+        inner.sessions.insert(addr, session.clone());
+        session
+    }
+
+    /// Removes the session and fails requests awaiting replies from it
+    fn remove_session(&self, addr: SocketAddr, session_id: u64) {
+        let requests = {
+            let mut inner = self.inner.borrow_mut();
+            if matches!(inner.sessions.get(&addr), Some(session) if session.id == session_id) {
+                inner.sessions.remove(&addr);
+            }
+
+            let ids = inner
+                .requests
+                .iter()
+                .filter(|(_, request)| request.session_id == session_id)
+                .map(|(id, _)| id.clone())
+                .collect::<Vec<_>>();
+            ids.into_iter()
+                .filter_map(|id| inner.requests.remove(&id).map(|r| (id, r)))
+                .collect::<Vec<_>>()
+        };
+
+        for (request_id, request) in requests {
+            let err = "Net: error sending message: session closed";
+            reply_service_err(request_id, err, request.tx);
+        }
+    }
+
+    fn add_request(&self, request_id: String, request: Request) {
+        self.inner.borrow_mut().requests.insert(request_id, request);
+    }
+
+    fn get_request(&self, request_id: &str, session_id: u64) -> Option<Request> {
+        let inner = self.inner.borrow();
+        inner
+            .requests
+            .get(request_id)
+            .filter(|request| request.session_id == session_id)
+            .cloned()
+    }
+
+    fn remove_request(&self, request_id: &str) {
+        self.inner.borrow_mut().requests.remove(request_id);
+    }
+
+    /// Accounts inbound traffic to the remote node and the addressed service
+    fn record_inbound(&self, session: &Session, message: &GsbMessage, bytes: usize) {
+        let (node_id, service) = match message {
+            GsbMessage::CallRequest(request) => {
+                (request.caller.parse().ok(), request.address.clone())
+            }
+            GsbMessage::CallReply(reply) => match self.get_request(&reply.request_id, session.id) {
+                Some(request) => (Some(request.remote_id), request.address),
+                None => return,
+            },
+            GsbMessage::BroadcastRequest(request) => (
+                request.caller.parse().ok(),
+                format!("broadcast/{}", request.topic),
+            ),
+            _ => return,
+        };
+        if let Some(node_id) = node_id.filter(|id| session.node_ids.contains(id)) {
+            self.stats
+                .record(Direction::Rx, Some(node_id), &service, bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan::handshake::tests::TestCrypto;
+
+    const SERVICE: &str = "/public/lan-test";
+
+    async fn start_node(crypto: TestCrypto, node_ids: Vec<NodeId>) -> (State, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let announcement = Announcement {
+            node_ids: node_ids.clone(),
+            port: addr.port(),
+        };
+
+        let config = Config::from_env().unwrap();
+        let state = State::new(
+            node_ids,
+            announcement,
+            Rc::new(crypto),
+            Acl::load(None).unwrap(),
+            RateLimiter::new(&config),
+            TrafficStats::new(false),
+        );
+        tokio::task::spawn_local(accept(listener, state.clone()));
+        (state, addr)
+    }
+
+    fn bind_echo() {
+        let rpc =
+            |_: &str, _: &str, msg: &[u8], _: bool| futures::future::ok::<_, Error>(msg.to_vec());
+        let stream = |_: &str, _: &str, msg: &[u8], _: bool| {
+            let chunk = ResponseChunk::Full(msg.to_vec());
+            futures::stream::once(futures::future::ok::<_, Error>(chunk)).boxed_local()
+        };
+        local_bus::subscribe(SERVICE, rpc.into_handler(), stream.into_stream_handler());
+    }
+
+    async fn call(state: &State, from: NodeId, to: NodeId) -> Result<Vec<u8>, Error> {
+        let address = format!("{}/lan-test", net::net_service(to));
+        let mut rx = forward_bus_to_net(state, from, to, address, b"ping", false)
+            .unwrap()
+            .unwrap();
+        match rx.next().await {
+            Some(ResponseChunk::Full(data)) => codec::decode_reply(data),
+            _ => panic!("no reply"),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn loopback_sessions() {
+        bind_echo();
+
+        let (crypto_a, crypto_b, crypto_c) =
+            (TestCrypto::new(1), TestCrypto::new(2), TestCrypto::new(3));
+        let (id_a, id_b, id_c) = (crypto_a.node_id(), crypto_b.node_id(), crypto_c.node_id());
+
+        let (a, addr_a) = start_node(crypto_a, vec![id_a]).await;
+        let (b, addr_b) = start_node(crypto_b, vec![id_b]).await;
+        // node C claims identity of B, without its key
+        let (_c, addr_c) = start_node(crypto_c, vec![id_c, id_b]).await;
+
+        let announce = |state: &State, node_ids: Vec<NodeId>, addr: SocketAddr| {
+            let announcement = Announcement {
+                node_ids,
+                port: addr.port(),
+            };
+            state.peers.update(&announcement, addr.ip(), Instant::now());
+        };
+
+        announce(&a, vec![id_b], addr_b);
+        assert_eq!(call(&a, id_a, id_b).await.unwrap(), b"ping".to_vec());
+        assert_eq!(a.sessions(), 1);
+        assert_eq!(b.sessions(), 1);
+
+        // replies are sent over the session accepted by B
+        assert_eq!(b.peers.get(&id_a), Some(addr_a));
+        assert_eq!(call(&b, id_b, id_a).await.unwrap(), b"ping".to_vec());
+        assert_eq!(b.sessions(), 1);
+
+        let stats = b.stats.snapshot();
+        assert!(stats.nodes.iter().any(|node| node.node_id == id_a));
+
+        // spoofed discovery announcement
+        announce(&a, vec![id_b], addr_c);
+        assert!(call(&a, id_a, id_b).await.is_err());
+        assert_eq!(a.sessions(), 1);
+    }
+}
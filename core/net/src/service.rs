@@ -2,11 +2,14 @@ use std::sync::{Arc, RwLock};
 
 use ya_core_model::net::local::{BindBroadcastError, BroadcastMessage, SendBroadcastMessage};
 use ya_core_model::{identity, NodeId};
+use ya_service_api::CliCtx;
 use ya_service_api_interfaces::{Provider, Service};
 use ya_service_bus::{Error, RpcEndpoint, RpcMessage};
 
 use crate::config::{Config, NetType};
 
+const ACL_FILE_NAME: &str = "net-acl.json";
+
 pub(crate) async fn identities() -> anyhow::Result<(NodeId, Vec<NodeId>)> {
     let ids: Vec<identity::IdentityInfo> = ya_service_bus::typed::service(identity::BUS_ID)
         .send(identity::List::default())
@@ -41,8 +44,11 @@ impl Service for Net {
 }
 
 impl Net {
-    pub async fn gsb<Context>(ctx: Context) -> anyhow::Result<()> {
-        let config = Config::from_env()?;
+    pub async fn gsb<Context: Provider<Self, CliCtx>>(ctx: &Context) -> anyhow::Result<()> {
+        let mut config = Config::from_env()?;
+        if config.acl_file.is_none() {
+            config.acl_file = Some(ctx.component().data_dir.join(ACL_FILE_NAME));
+        }
 
         {
             (*NET_TYPE.write().unwrap()) = config.net_type;
@@ -54,6 +60,7 @@ impl Net {
                 crate::central::Net::gsb(ctx, config).await
             }
             NetType::Hybrid => crate::hybrid::Net::gsb(ctx, config).await,
+            NetType::Lan => crate::lan::Net::gsb(ctx, config).await,
         }
     }
 
@@ -61,7 +68,7 @@ impl Net {
         let net_type = { *NET_TYPE.read().unwrap() };
         match net_type {
             NetType::Central => crate::central::web_scope(),
-            NetType::Hybrid => crate::hybrid::web_scope(),
+            NetType::Hybrid | NetType::Lan => crate::hybrid::web_scope(),
         }
     }
 
@@ -73,7 +80,7 @@ impl Net {
         }
 
         match &config.net_type {
-            NetType::Central => Ok(()),
+            NetType::Central | NetType::Lan => Ok(()),
             NetType::Hybrid => crate::hybrid::Net::shutdown().await,
         }
     }
@@ -98,7 +105,7 @@ where
     let net_type = { *NET_TYPE.read().unwrap() };
     match net_type {
         NetType::Central => crate::central::broadcast(caller, message).await,
-        NetType::Hybrid => crate::hybrid::broadcast(caller, message).await,
+        NetType::Hybrid | NetType::Lan => crate::hybrid::broadcast(caller, message).await,
     }
 }
 
@@ -123,7 +130,7 @@ where
         NetType::Central => {
             crate::central::bind_broadcast_with_caller(broadcast_address, handler).await
         }
-        NetType::Hybrid => {
+        NetType::Hybrid | NetType::Lan => {
             crate::hybrid::bind_broadcast_with_caller(broadcast_address, handler).await
         }
     }
@@ -0,0 +1,3 @@
+DROP TABLE vpn_node;
+DROP TABLE vpn_address;
+DROP TABLE vpn_network;
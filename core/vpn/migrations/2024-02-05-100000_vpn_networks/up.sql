@@ -0,0 +1,28 @@
+CREATE TABLE vpn_network (
+	id VARCHAR(50) NOT NULL PRIMARY KEY,
+	owner_id VARCHAR(50) NOT NULL,
+	ip VARCHAR(50) NOT NULL,
+	mask VARCHAR(50) NOT NULL,
+	gateway VARCHAR(50) NOT NULL,
+	firewall TEXT,
+	created_ts TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_ts TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE vpn_address (
+	network_id VARCHAR(50) NOT NULL,
+	ip VARCHAR(50) NOT NULL,
+	PRIMARY KEY (network_id, ip),
+	FOREIGN KEY (network_id) REFERENCES vpn_network(id) ON DELETE CASCADE
+);
+
+CREATE TABLE vpn_node (
+	network_id VARCHAR(50) NOT NULL,
+	ip VARCHAR(50) NOT NULL,
+	node_id VARCHAR(50) NOT NULL,
+	hostname VARCHAR(253),
+	PRIMARY KEY (network_id, ip),
+	FOREIGN KEY (network_id) REFERENCES vpn_network(id) ON DELETE CASCADE
+);
+
+CREATE INDEX vpn_node_node_id_idx ON vpn_node (network_id, node_id);
@@ -0,0 +1,184 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+use ya_core_model::activity::VpnFirewall;
+use ya_persistence::executor::{
+    do_with_transaction, readonly_transaction, AsDao, ConnType, PoolType,
+};
+
+use crate::db::model::{DbAddress, DbNetwork, DbNode, NewDbNetwork};
+use crate::db::schema::vpn_address::dsl as address;
+use crate::db::schema::vpn_network::dsl as network;
+use crate::db::schema::vpn_node::dsl as node;
+
+/// Network definition with address assignments and node memberships
+#[derive(Clone, Debug)]
+pub struct StoredNetwork {
+    pub network: DbNetwork,
+    pub addresses: Vec<DbAddress>,
+    pub nodes: Vec<DbNode>,
+}
+
+impl StoredNetwork {
+    pub fn firewall(&self) -> anyhow::Result<Option<VpnFirewall>> {
+        Ok(self
+            .network
+            .firewall
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()?)
+    }
+}
+
+/// Change of a network's state, applied by its actor
+#[derive(Clone, Debug)]
+pub enum NetworkChange {
+    AddAddress {
+        ip: String,
+    },
+    AddNode {
+        id: String,
+        ip: String,
+        hostname: Option<String>,
+    },
+    RemoveNode {
+        id: String,
+    },
+    SetFirewall {
+        firewall: VpnFirewall,
+    },
+    /// Traffic was seen in the network
+    Touch,
+}
+
+pub struct NetworkDao<'c> {
+    pool: &'c PoolType,
+}
+
+impl<'a> AsDao<'a> for NetworkDao<'a> {
+    fn as_dao(pool: &'a PoolType) -> Self {
+        Self { pool }
+    }
+}
+
+impl<'c> NetworkDao<'c> {
+    pub async fn create(&self, db_network: NewDbNetwork) -> anyhow::Result<()> {
+        do_with_transaction(self.pool, "vpn_network_dao_create", move |conn| {
+            diesel::insert_into(network::vpn_network)
+                .values(&db_network)
+                .execute(conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn remove(&self, network_id: String) -> anyhow::Result<()> {
+        do_with_transaction(self.pool, "vpn_network_dao_remove", move |conn| {
+            diesel::delete(network::vpn_network.find(&network_id)).execute(conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Removes networks without changes or traffic since `updated_before`,
+    /// returning their ids
+    pub async fn remove_expired(
+        &self,
+        updated_before: NaiveDateTime,
+    ) -> anyhow::Result<Vec<String>> {
+        do_with_transaction(self.pool, "vpn_network_dao_remove_expired", move |conn| {
+            let expired = network::vpn_network
+                .filter(network::updated_ts.lt(updated_before))
+                .select(network::id)
+                .load::<String>(conn)?;
+            diesel::delete(network::vpn_network.filter(network::id.eq_any(&expired)))
+                .execute(conn)?;
+            Ok(expired)
+        })
+        .await
+    }
+
+    pub async fn apply(&self, network_id: String, change: NetworkChange) -> anyhow::Result<()> {
+        do_with_transaction(self.pool, "vpn_network_dao_apply", move |conn| {
+            apply_change(conn, network_id, change)
+        })
+        .await
+    }
+
+    pub async fn list(&self) -> anyhow::Result<Vec<StoredNetwork>> {
+        readonly_transaction(self.pool, "vpn_network_dao_list", move |conn| {
+            let networks = network::vpn_network
+                .order(network::created_ts.asc())
+                .load::<DbNetwork>(conn)?;
+            let mut addresses = address::vpn_address.load::<DbAddress>(conn)?;
+            let mut nodes = node::vpn_node.load::<DbNode>(conn)?;
+
+            Ok(networks
+                .into_iter()
+                .map(|network| StoredNetwork {
+                    addresses: drain_network(&mut addresses, &network.id, |a| &a.network_id),
+                    nodes: drain_network(&mut nodes, &network.id, |n| &n.network_id),
+                    network,
+                })
+                .collect())
+        })
+        .await
+    }
+}
+
+fn apply_change(conn: &ConnType, network_id: String, change: NetworkChange) -> anyhow::Result<()> {
+    diesel::update(network::vpn_network.find(&network_id))
+        .set(network::updated_ts.eq(Utc::now().naive_utc()))
+        .execute(conn)?;
+
+    match change {
+        NetworkChange::AddAddress { ip } => {
+            diesel::insert_or_ignore_into(address::vpn_address)
+                .values(&DbAddress { network_id, ip })
+                .execute(conn)?;
+        }
+        NetworkChange::AddNode { id, ip, hostname } => {
+            diesel::insert_or_ignore_into(node::vpn_node)
+                .values(&DbNode {
+                    network_id: network_id.clone(),
+                    ip: ip.clone(),
+                    node_id: id,
+                    hostname: None,
+                })
+                .execute(conn)?;
+
+            // nodes re-added without a hostname keep the assigned one
+            if let Some(hostname) = hostname {
+                diesel::update(node::vpn_node.find((&network_id, &ip)))
+                    .set(node::hostname.eq(Some(hostname)))
+                    .execute(conn)?;
+            }
+        }
+        NetworkChange::RemoveNode { id } => {
+            diesel::delete(
+                node::vpn_node
+                    .filter(node::network_id.eq(&network_id))
+                    .filter(node::node_id.eq(&id)),
+            )
+            .execute(conn)?;
+        }
+        NetworkChange::SetFirewall { firewall } => {
+            diesel::update(network::vpn_network.find(&network_id))
+                .set(network::firewall.eq(Some(serde_json::to_string(&firewall)?)))
+                .execute(conn)?;
+        }
+        NetworkChange::Touch => {}
+    }
+    Ok(())
+}
+
+fn drain_network<T, F>(items: &mut Vec<T>, network_id: &str, id: F) -> Vec<T>
+where
+    F: Fn(&T) -> &String,
+{
+    let (network, rest) = std::mem::take(items)
+        .into_iter()
+        .partition(|item| id(item) == network_id);
+    *items = rest;
+    network
+}
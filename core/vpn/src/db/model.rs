@@ -0,0 +1,45 @@
+use chrono::NaiveDateTime;
+
+use crate::db::schema::{vpn_address, vpn_network, vpn_node};
+
+#[derive(Clone, Debug, Identifiable, Queryable)]
+#[table_name = "vpn_network"]
+pub struct DbNetwork {
+    pub id: String,
+    pub owner_id: String,
+    pub ip: String,
+    pub mask: String,
+    pub gateway: String,
+    /// Firewall rules serialized to JSON, once set
+    pub firewall: Option<String>,
+    pub created_ts: NaiveDateTime,
+    /// Time of the last change or traffic seen in the network
+    pub updated_ts: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[table_name = "vpn_network"]
+pub struct NewDbNetwork {
+    pub id: String,
+    pub owner_id: String,
+    pub ip: String,
+    pub mask: String,
+    pub gateway: String,
+}
+
+/// Requestor's address within a network
+#[derive(Clone, Debug, Insertable, Queryable)]
+#[table_name = "vpn_address"]
+pub struct DbAddress {
+    pub network_id: String,
+    pub ip: String,
+}
+
+#[derive(Clone, Debug, Insertable, Queryable)]
+#[table_name = "vpn_node"]
+pub struct DbNode {
+    pub network_id: String,
+    pub ip: String,
+    pub node_id: String,
+    pub hostname: Option<String>,
+}
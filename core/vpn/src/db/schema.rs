@@ -0,0 +1,33 @@
+table! {
+    vpn_network (id) {
+        id -> Text,
+        owner_id -> Text,
+        ip -> Text,
+        mask -> Text,
+        gateway -> Text,
+        firewall -> Nullable<Text>,
+        created_ts -> Timestamp,
+        updated_ts -> Timestamp,
+    }
+}
+
+table! {
+    vpn_address (network_id, ip) {
+        network_id -> Text,
+        ip -> Text,
+    }
+}
+
+table! {
+    vpn_node (network_id, ip) {
+        network_id -> Text,
+        ip -> Text,
+        node_id -> Text,
+        hostname -> Nullable<Text>,
+    }
+}
+
+joinable!(vpn_address -> vpn_network (network_id));
+joinable!(vpn_node -> vpn_network (network_id));
+
+allow_tables_to_appear_in_same_query!(vpn_network, vpn_address, vpn_node);
@@ -1,3 +1,9 @@
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate diesel_migrations;
+
+mod db;
 mod message;
 mod network;
 mod requestor;
@@ -1,8 +1,12 @@
 use crate::Result;
 use actix::{Message, Recipient};
 use futures::channel::mpsc;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use ya_client_model::net::*;
+use ya_core_model::activity::VpnFirewall;
 use ya_utils_networking::vpn::{
+    capture::CaptureConfig,
     stack::{
         connection::{Connection, ConnectionMeta},
         EgressEvent, IngressEvent,
@@ -29,6 +33,7 @@ pub struct GetNodes;
 pub struct AddNode {
     pub id: String,
     pub address: String,
+    pub hostname: Option<String>,
 }
 
 #[derive(Debug, Message)]
@@ -37,10 +42,35 @@ pub struct RemoveNode {
     pub id: String,
 }
 
+/// Retrieves hostnames assigned to nodes, mapped to their addresses
+#[derive(Debug, Message)]
+#[rtype(result = "Result<HashMap<String, String>>")]
+pub struct GetHosts;
+
 #[derive(Debug, Message)]
 #[rtype(result = "Result<Vec<Connection>>")]
 pub struct GetConnections;
 
+#[derive(Debug, Message)]
+#[rtype(result = "Result<VpnFirewall>")]
+pub struct GetFirewall;
+
+#[derive(Debug, Message)]
+#[rtype(result = "Result<()>")]
+pub struct SetFirewall {
+    pub firewall: VpnFirewall,
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "Result<PathBuf>")]
+pub struct StartCapture {
+    pub config: CaptureConfig,
+}
+
+#[derive(Debug, Message)]
+#[rtype(result = "Result<Vec<PathBuf>>")]
+pub struct StopCapture;
+
 #[derive(Message)]
 #[rtype(result = "Result<UserConnection>")]
 pub struct Connect {
@@ -80,6 +110,7 @@ pub struct DataSent;
 #[derive(Debug)]
 pub struct UserConnection {
     pub vpn: Recipient<Packet>,
+    pub disconnect: Recipient<Disconnect>,
     pub rx: mpsc::Receiver<Vec<u8>>,
     pub stack_connection: Connection,
 }
@@ -1,38 +1,66 @@
 use std::collections::{BTreeSet, HashMap};
 use std::net::IpAddr;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 use actix::prelude::*;
+use chrono::Utc;
 use futures::channel::oneshot::Canceled;
 use futures::channel::{mpsc, oneshot};
 use futures::{future, future::BoxFuture, Future, FutureExt, SinkExt, StreamExt, TryFutureExt};
+use rand::Rng;
 use smoltcp::iface::Route;
 use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpEndpoint};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use uuid::Uuid;
 
 use ya_utils_networking::vpn::socket::TCP_CONN_TIMEOUT;
+use ya_utils_networking::vpn::stack::connection::ConnectionMeta;
 use ya_utils_networking::vpn::stack::interface::{add_iface_address, add_iface_route, tap_iface};
 
+use crate::db::dao::{NetworkChange, NetworkDao, StoredNetwork};
+use crate::db::model::NewDbNetwork;
 use crate::message::*;
 use crate::Result;
 
-use ya_core_model::activity::{VpnControl, VpnPacket};
+use ya_core_model::activity::{VpnControl, VpnFirewall, VpnPacket};
 use ya_core_model::NodeId;
+use ya_persistence::executor::DbExecutor;
 use ya_service_bus::typed::{self, Endpoint};
 use ya_service_bus::{actix_rpc, RpcEndpoint, RpcEnvelope, RpcRawCall};
-use ya_utils_networking::vpn::common::{to_ip, to_net};
+use ya_utils_networking::vpn::capture::{Capture, CaptureConfig, Direction};
+use ya_utils_networking::vpn::common::{to_ip, to_net, to_octets, IpFlow};
+use ya_utils_networking::vpn::flows::FlowTable;
 use ya_utils_networking::vpn::stack::{
     self as net, EgressReceiver, IngressEvent, IngressReceiver, StackConfig,
 };
 use ya_utils_networking::vpn::*;
 
+const UDP_EPHEMERAL_PORT: u16 = 49152;
+const UDP_BIND_ATTEMPTS: usize = 32;
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+/// Nodes are retried for a while, since their ExeUnits may still be deploying.
+/// Restored nodes whose ExeUnits don't respond for `SYNC_ATTEMPTS * SYNC_INTERVAL`
+/// (15 minutes) are considered to have finished their activities, and are removed.
+const SYNC_ATTEMPTS: u32 = 90;
+/// Interval of storing the time of the last traffic seen in a network
+const TOUCH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Stored networks without changes or traffic for this long are not restored
+const NETWORK_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_HOSTNAME_LEN: usize = 253;
+const MAX_LABEL_LEN: usize = 63;
+/// Single interface of the requestor's network capture
+const CAPTURE_INTERFACE: u32 = 0;
+
 pub struct VpnSupervisor {
     networks: HashMap<String, Addr<Vpn>>,
     blueprints: HashMap<String, ya_client_model::net::Network>,
     ownership: HashMap<NodeId, BTreeSet<String>>,
     arbiter: Arbiter,
+    capture_dir: PathBuf,
+    db: Option<DbExecutor>,
 }
 
 impl Default for VpnSupervisor {
@@ -42,11 +70,17 @@ impl Default for VpnSupervisor {
             blueprints: Default::default(),
             ownership: Default::default(),
             arbiter: Arbiter::new(),
+            capture_dir: std::env::temp_dir().join("yagna-vpn-capture"),
+            db: None,
         }
     }
 }
 
 impl VpnSupervisor {
+    pub fn set_capture_dir(&mut self, dir: PathBuf) {
+        self.capture_dir = dir;
+    }
+
     pub fn get_networks(&self, node_id: &NodeId) -> Vec<ya_client_model::net::Network> {
         self.ownership
             .get(node_id)
@@ -81,6 +115,79 @@ impl VpnSupervisor {
         &mut self,
         node_id: NodeId,
         network: ya_client_model::net::NewNetwork,
+    ) -> Result<ya_client_model::net::Network> {
+        let net_id = Uuid::new_v4().to_simple().to_string();
+        let network = self
+            .start_network(node_id, net_id, network, Default::default())
+            .await?;
+
+        if let Some(db) = &self.db {
+            let db_network = NewDbNetwork {
+                id: network.id.clone(),
+                owner_id: node_id.to_string(),
+                ip: network.ip.clone(),
+                mask: network.mask.clone(),
+                gateway: network.gateway.clone(),
+            };
+            if let Err(e) = db.as_dao::<NetworkDao>().create(db_network).await {
+                log::warn!("Unable to store network {}: {e}", network.id);
+            }
+        }
+
+        Ok(network)
+    }
+
+    /// Restores networks stored in the database, removing the ones unused for `NETWORK_TTL`.
+    /// Stored nodes are re-attached once their ExeUnits respond, and removed otherwise.
+    pub async fn restore(&mut self, db: DbExecutor) -> anyhow::Result<()> {
+        let dao = db.as_dao::<NetworkDao>();
+        let updated_before = Utc::now().naive_utc() - chrono::Duration::from_std(NETWORK_TTL)?;
+        for net_id in dao.remove_expired(updated_before).await? {
+            log::info!("Removed expired network: {net_id}");
+        }
+
+        let stored = dao.list().await?;
+        self.db = Some(db);
+
+        for stored in stored {
+            let net_id = stored.network.id.clone();
+            if let Err(e) = self.restore_network(stored).await {
+                log::warn!("Unable to restore network {net_id}: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn restore_network(&mut self, stored: StoredNetwork) -> anyhow::Result<()> {
+        let node_id = NodeId::from_str(&stored.network.owner_id)?;
+        let firewall = stored.firewall()?;
+        let network = ya_client_model::net::NewNetwork {
+            ip: stored.network.ip.clone(),
+            mask: Some(stored.network.mask.clone()),
+            gateway: Some(stored.network.gateway.clone()),
+        };
+        let state = VpnState {
+            addresses: stored.addresses.into_iter().map(|a| a.ip).collect(),
+            nodes: stored
+                .nodes
+                .into_iter()
+                .map(|n| (n.node_id, n.ip, n.hostname))
+                .collect(),
+            firewall,
+        };
+
+        log::info!("Restoring network: {}", stored.network.id);
+        self.start_network(node_id, stored.network.id, network, state)
+            .await?;
+        Ok(())
+    }
+
+    async fn start_network(
+        &mut self,
+        node_id: NodeId,
+        net_id: String,
+        network: ya_client_model::net::NewNetwork,
+        state: VpnState,
     ) -> Result<ya_client_model::net::Network> {
         let net = to_net(&network.ip, network.mask.as_ref())?;
         let node_ip = IpCidr::new(
@@ -91,7 +198,6 @@ impl VpnSupervisor {
             net.prefix_len(),
         );
 
-        let net_id = Uuid::new_v4().to_simple().to_string();
         let net_ip = IpCidr::new(net.addr().into(), net.prefix_len());
 
         log::info!("Creating network: {net_id} ({net_ip})");
@@ -110,15 +216,18 @@ impl VpnSupervisor {
         };
 
         let vpn_net = Network::new(&net_id, net);
+        let db = self.db.clone();
 
         let actor = self
             .arbiter
             .spawn_ext(async move {
-                let vpn = Vpn::new(
+                let mut vpn = Vpn::new(
                     node_id,
                     vpn_net,
                     create_stack_network(node_ip, net_ip, net_gw)?,
+                    db,
                 );
+                vpn.restore(state);
                 Ok::<_, Error>(vpn.start())
             })
             .await?;
@@ -148,7 +257,18 @@ impl VpnSupervisor {
         let vpn = self.networks.remove(network_id).ok_or(Error::NetNotFound)?;
         self.blueprints.remove(network_id);
         self.remove_ownership(node_id, network_id);
-        self.forward(vpn, Shutdown {})
+
+        let db = self.db.clone();
+        let network_id = network_id.to_string();
+        let shutdown = self.forward(vpn, Shutdown {})?;
+        Ok(Box::pin(async move {
+            if let Some(db) = db {
+                if let Err(e) = db.as_dao::<NetworkDao>().remove(network_id.clone()).await {
+                    log::warn!("Unable to remove stored network {network_id}: {e}");
+                }
+            }
+            shutdown.await
+        }))
     }
 
     fn remove_ownership(&mut self, node_id: &NodeId, network_id: &str) {
@@ -173,6 +293,38 @@ impl VpnSupervisor {
         self.forward(vpn, RemoveNode { id })
     }
 
+    pub fn start_capture<'a>(
+        &mut self,
+        node_id: &NodeId,
+        network_id: &str,
+        max_file_size: Option<u64>,
+        max_files: Option<usize>,
+    ) -> Result<BoxFuture<'a, Result<PathBuf>>> {
+        log::info!("Starting traffic capture of network: {network_id}");
+
+        self.owner(node_id, network_id)?;
+        let vpn = self.vpn(network_id)?;
+        let config = CaptureConfig {
+            dir: self.capture_dir.join(network_id),
+            name: network_id.to_string(),
+            max_file_size: max_file_size.unwrap_or(capture::DEFAULT_MAX_FILE_SIZE),
+            max_files: max_files.unwrap_or(capture::DEFAULT_MAX_FILES),
+        };
+        self.forward(vpn, StartCapture { config })
+    }
+
+    pub fn stop_capture<'a>(
+        &mut self,
+        node_id: &NodeId,
+        network_id: &str,
+    ) -> Result<BoxFuture<'a, Result<Vec<PathBuf>>>> {
+        log::info!("Stopping traffic capture of network: {network_id}");
+
+        self.owner(node_id, network_id)?;
+        let vpn = self.vpn(network_id)?;
+        self.forward(vpn, StopCapture {})
+    }
+
     fn forward<'a, M, T>(
         &self,
         vpn: Addr<Vpn>,
@@ -214,6 +366,51 @@ pub struct Vpn {
     vpn: Network<network::DuoEndpoint<Endpoint>>,
     stack_network: net::Network,
     connections: HashMap<SocketDesc, InternalConnection>,
+    /// Firewall rules, once set
+    firewall: Option<VpnFirewall>,
+    /// Nodes without the current firewall rules, with the number of update attempts
+    firewall_pending: HashMap<String, u32>,
+    /// Flows accepted by the firewall, whose replies are allowed
+    flows: FlowTable,
+    /// Hostnames assigned to nodes
+    hosts: HashMap<String, IpAddr>,
+    /// Nodes without the current hostnames, with the number of update attempts
+    hosts_pending: HashMap<String, u32>,
+    /// Restored nodes not yet confirmed to be alive, with the number of attempts
+    attach_pending: HashMap<String, u32>,
+    /// Whether traffic was seen since the last touch of the stored network
+    active: bool,
+    capture: Capture,
+    db: Option<DbExecutor>,
+    /// Network changes to be stored in the database
+    changes: Option<mpsc::UnboundedSender<NetworkChange>>,
+}
+
+/// Network state restored from the database
+#[derive(Debug, Default)]
+struct VpnState {
+    addresses: Vec<String>,
+    /// Node id, IP address and an optional hostname
+    nodes: Vec<(String, String, Option<String>)>,
+    firewall: Option<VpnFirewall>,
+}
+
+/// Network configuration sent to ExeUnits of member nodes
+#[derive(Clone, Copy, Debug)]
+enum NodeSync {
+    Firewall,
+    Hosts,
+    Attach,
+}
+
+impl std::fmt::Display for NodeSync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Firewall => f.write_str("firewall rules"),
+            Self::Hosts => f.write_str("hostnames"),
+            Self::Attach => f.write_str("membership"),
+        }
+    }
 }
 
 impl Vpn {
@@ -221,12 +418,222 @@ impl Vpn {
         node_id: NodeId,
         vpn: Network<network::DuoEndpoint<Endpoint>>,
         stack_network: net::Network,
+        db: Option<DbExecutor>,
     ) -> Self {
         Self {
             node_id: node_id.to_string(),
             vpn,
             stack_network,
             connections: Default::default(),
+            firewall: None,
+            firewall_pending: Default::default(),
+            flows: Default::default(),
+            hosts: Default::default(),
+            hosts_pending: Default::default(),
+            attach_pending: Default::default(),
+            active: false,
+            capture: Default::default(),
+            db,
+            changes: None,
+        }
+    }
+
+    /// Applies the stored state, before the actor is started
+    fn restore(&mut self, state: VpnState) {
+        for address in state.addresses {
+            if let Err(e) = self.add_address(&address) {
+                log::warn!(
+                    "VPN {}: unable to restore address {address}: {e}",
+                    self.vpn.id()
+                );
+            }
+        }
+
+        for (id, address, hostname) in state.nodes {
+            match to_ip(&address).and_then(|ip| {
+                self.vpn
+                    .add_node(ip, &id, gsb_remote_url)
+                    .or_else(|e| match e {
+                        Error::IpAddrTaken(_) => Ok(()),
+                        e => Err(e),
+                    })
+                    .map(|_| ip)
+            }) {
+                Ok(ip) => {
+                    if let Some(hostname) = hostname {
+                        self.hosts.insert(hostname, ip);
+                    }
+                    self.attach_pending.insert(id, 0);
+                }
+                Err(e) => {
+                    log::warn!("VPN {}: unable to restore node {id}: {e}", self.vpn.id());
+                }
+            }
+        }
+
+        self.firewall = state.firewall;
+    }
+
+    fn add_address(&mut self, address: &str) -> Result<()> {
+        let ip: IpAddr = address.parse()?;
+
+        let net = self.vpn.as_ref();
+        if !net.contains(&ip) {
+            return Err(Error::NetAddrMismatch(ip));
+        }
+
+        let cidr = IpCidr::new(IpAddress::from(ip), net.prefix_len());
+        if !cidr.address().is_unicast() && !cidr.address().is_unspecified() {
+            return Err(Error::IpAddrNotAllowed(ip));
+        }
+
+        self.stack_network.stack.add_address(cidr);
+        self.vpn.add_address(address)?;
+        Ok(())
+    }
+
+    fn persist(&self, change: NetworkChange) {
+        if let Some(changes) = &self.changes {
+            let _ = changes.unbounded_send(change);
+        }
+    }
+
+    fn pending(&mut self, sync: NodeSync) -> &mut HashMap<String, u32> {
+        match sync {
+            NodeSync::Firewall => &mut self.firewall_pending,
+            NodeSync::Hosts => &mut self.hosts_pending,
+            NodeSync::Attach => &mut self.attach_pending,
+        }
+    }
+
+    fn sync_message(&self, sync: NodeSync) -> Option<VpnControl> {
+        let network_id = self.vpn.id().clone();
+        match sync {
+            NodeSync::Firewall => self
+                .firewall
+                .clone()
+                .map(|firewall| VpnControl::SetFirewall {
+                    network_id,
+                    firewall,
+                }),
+            NodeSync::Hosts => Some(VpnControl::SetHosts {
+                network_id,
+                hosts: self
+                    .hosts
+                    .iter()
+                    .map(|(name, ip)| (name.clone(), ip.to_string()))
+                    .collect(),
+            }),
+            NodeSync::Attach => Some(VpnControl::AddNodes {
+                network_id,
+                nodes: self
+                    .stack_network
+                    .stack
+                    .addresses()
+                    .into_iter()
+                    .map(|cidr| (cidr.address().to_string(), self.node_id.clone()))
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Sends the configuration to ExeUnits of all nodes
+    fn sync_all(&mut self, sync: NodeSync, ctx: &mut Context<Self>) {
+        *self.pending(sync) = self.vpn.nodes().keys().map(|id| (id.clone(), 0)).collect();
+        self.sync(sync, ctx);
+    }
+
+    /// Sends the configuration to ExeUnits of nodes which did not confirm receiving it
+    fn sync(&mut self, sync: NodeSync, ctx: &mut Context<Self>) {
+        let msg = match self.sync_message(sync) {
+            Some(msg) => msg,
+            None => return,
+        };
+        let vpn_id = self.vpn.id().clone();
+        let node_ids = self.pending(sync).keys().cloned().collect::<Vec<_>>();
+
+        for node_id in node_ids {
+            let attempts = self.pending(sync).entry(node_id.clone()).or_default();
+            *attempts += 1;
+            if *attempts > SYNC_ATTEMPTS {
+                self.pending(sync).remove(&node_id);
+                match sync {
+                    NodeSync::Attach => {
+                        log::warn!("VPN {vpn_id}: restored node {node_id} is gone, removing");
+                        ctx.notify(RemoveNode { id: node_id });
+                    }
+                    _ => log::warn!("VPN {vpn_id}: unable to set {sync} on node {node_id}"),
+                }
+                continue;
+            }
+
+            let endpoint = self
+                .vpn
+                .nodes()
+                .get(&node_id)
+                .and_then(|ips| ips.iter().next())
+                .and_then(|ip| self.vpn.endpoint(to_octets(*ip)));
+            let endpoint = match endpoint {
+                Some(endpoint) => endpoint,
+                None => {
+                    self.pending(sync).remove(&node_id);
+                    continue;
+                }
+            };
+
+            let sent = msg.clone();
+            endpoint
+                .tcp
+                .send(msg.clone())
+                .into_actor(self)
+                .map(move |result, this, _| match result {
+                    Ok(Ok(())) => {
+                        // a responding ExeUnit belongs to an ongoing activity
+                        this.active = true;
+                        if this.sync_message(sync).as_ref() == Some(&sent) {
+                            this.pending(sync).remove(&node_id);
+                        }
+                    }
+                    Ok(Err(e)) => log::debug!("Setting {sync} on node {node_id}: {e}"),
+                    Err(e) => log::debug!("Setting {sync} on node {node_id}: {e}"),
+                })
+                .spawn(ctx);
+        }
+    }
+
+    fn egress_allowed(&mut self, frame: &[u8]) -> bool {
+        let (firewall, flow) = match (&self.firewall, IpFlow::from_frame(frame)) {
+            (Some(firewall), Some(flow)) => (firewall, flow),
+            _ => return true,
+        };
+        let dst = match self.vpn.node_id(&flow.dst) {
+            Some(dst) => dst,
+            None => return true,
+        };
+
+        let now = Instant::now();
+        if firewall.allows(&self.node_id, dst, flow.protocol, flow.ports) {
+            self.flows.insert(flow, now);
+            return true;
+        }
+        self.flows.is_reply(&flow, now)
+    }
+
+    fn ingress_allowed(&mut self, caller: &str, frame: &[u8]) -> bool {
+        let (firewall, flow) = match (&self.firewall, IpFlow::from_frame(frame)) {
+            (Some(firewall), Some(flow)) => (firewall, flow),
+            _ => return true,
+        };
+
+        let now = Instant::now();
+        if firewall.allows(caller, &self.node_id, flow.protocol, flow.ports) {
+            self.flows.insert(flow, now);
+            return true;
+        }
+        // replies are only accepted from the address of the calling node
+        match self.vpn.node_id(&flow.src) {
+            Some(src) if src.eq_ignore_ascii_case(caller) => self.flows.is_reply(&flow, now),
+            _ => false,
         }
     }
 }
@@ -261,6 +668,26 @@ impl Actor for Vpn {
             .into_actor(self)
             .spawn(ctx);
 
+        if let Some(db) = self.db.take() {
+            let (tx, rx) = mpsc::unbounded();
+            self.changes = Some(tx);
+            persist_changes(db, rx, id.clone())
+                .into_actor(self)
+                .spawn(ctx);
+        }
+
+        ctx.run_interval(SYNC_INTERVAL, |this, ctx| {
+            this.sync(NodeSync::Attach, ctx);
+            this.sync(NodeSync::Firewall, ctx);
+            this.sync(NodeSync::Hosts, ctx);
+        });
+
+        ctx.run_interval(TOUCH_INTERVAL, |this, _| {
+            if std::mem::take(&mut this.active) {
+                this.persist(NetworkChange::Touch);
+            }
+        });
+
         log::info!("VPN {id} started");
     }
 
@@ -308,20 +735,8 @@ impl Handler<AddAddress> for Vpn {
             self.node_id
         );
 
-        let ip: IpAddr = msg.address.parse()?;
-
-        let net = self.vpn.as_ref();
-        if !net.contains(&ip) {
-            return Err(Error::NetAddrMismatch(ip));
-        }
-
-        let cidr = IpCidr::new(IpAddress::from(ip), net.prefix_len());
-        if !cidr.address().is_unicast() && !cidr.address().is_unspecified() {
-            return Err(Error::IpAddrNotAllowed(ip));
-        }
-
-        self.stack_network.stack.add_address(cidr);
-        self.vpn.add_address(&msg.address)?;
+        self.add_address(&msg.address)?;
+        self.persist(NetworkChange::AddAddress { ip: msg.address });
         Ok(())
     }
 }
@@ -349,10 +764,21 @@ impl Handler<GetNodes> for Vpn {
 impl Handler<AddNode> for Vpn {
     type Result = <AddNode as Message>::Result;
 
-    fn handle(&mut self, msg: AddNode, _: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: AddNode, ctx: &mut Self::Context) -> Self::Result {
         log::info!("Adding Node: {} to network: {}", msg.address, self.vpn.id());
 
         let ip = to_ip(&msg.address)?;
+        let hostname = msg.hostname.as_deref().map(to_hostname).transpose()?;
+        if let Some(hostname) = &hostname {
+            match self.hosts.get(hostname) {
+                Some(other) if other != &ip => {
+                    return Err(Error::Other(format!(
+                        "hostname {hostname} already assigned to {other}"
+                    )))
+                }
+                _ => {}
+            }
+        }
 
         match self.vpn.add_node(ip, &msg.id, gsb_remote_url) {
             Ok(_) | Err(Error::IpAddrTaken(_)) => {}
@@ -379,6 +805,31 @@ impl Handler<AddNode> for Vpn {
             let _ = future::join_all(futs).await;
         });
 
+        if self.firewall.is_some() {
+            self.firewall_pending.insert(msg.id.clone(), 0);
+            self.sync(NodeSync::Firewall, ctx);
+        }
+
+        self.attach_pending.remove(&msg.id);
+        self.persist(NetworkChange::AddNode {
+            id: msg.id.clone(),
+            ip: msg.address.clone(),
+            hostname: hostname.clone(),
+        });
+
+        match hostname {
+            Some(hostname) => {
+                self.hosts.retain(|_, addr| addr != &ip);
+                self.hosts.insert(hostname, ip);
+                self.sync_all(NodeSync::Hosts, ctx);
+            }
+            None if !self.hosts.is_empty() => {
+                self.hosts_pending.insert(msg.id, 0);
+                self.sync(NodeSync::Hosts, ctx);
+            }
+            None => {}
+        }
+
         Ok(())
     }
 }
@@ -386,8 +837,19 @@ impl Handler<AddNode> for Vpn {
 impl Handler<RemoveNode> for Vpn {
     type Result = <RemoveNode as Message>::Result;
 
-    fn handle(&mut self, msg: RemoveNode, _: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: RemoveNode, ctx: &mut Self::Context) -> Self::Result {
+        let ips = self.vpn.nodes().get(&msg.id).cloned().unwrap_or_default();
         self.vpn.remove_node(&msg.id);
+        self.firewall_pending.remove(&msg.id);
+        self.hosts_pending.remove(&msg.id);
+        self.attach_pending.remove(&msg.id);
+        self.persist(NetworkChange::RemoveNode { id: msg.id.clone() });
+
+        let hosts = self.hosts.len();
+        self.hosts.retain(|_, ip| !ips.contains(ip));
+        if self.hosts.len() != hosts {
+            self.sync_all(NodeSync::Hosts, ctx);
+        }
 
         let vpn_id = self.vpn.id().clone();
         let futs = self
@@ -411,15 +873,68 @@ impl Handler<RemoveNode> for Vpn {
     }
 }
 
+impl Handler<GetFirewall> for Vpn {
+    type Result = <GetFirewall as Message>::Result;
+
+    fn handle(&mut self, _: GetFirewall, _: &mut Self::Context) -> Self::Result {
+        Ok(self.firewall.clone().unwrap_or_default())
+    }
+}
+
+impl Handler<SetFirewall> for Vpn {
+    type Result = <SetFirewall as Message>::Result;
+
+    fn handle(&mut self, msg: SetFirewall, ctx: &mut Self::Context) -> Self::Result {
+        msg.firewall.validate().map_err(Error::Other)?;
+        log::info!(
+            "VPN {}: setting {} firewall rules",
+            self.vpn.id(),
+            msg.firewall.rules.len()
+        );
+
+        self.persist(NetworkChange::SetFirewall {
+            firewall: msg.firewall.clone(),
+        });
+        self.firewall = Some(msg.firewall);
+        self.flows.clear();
+        self.sync_all(NodeSync::Firewall, ctx);
+        Ok(())
+    }
+}
+
+impl Handler<GetHosts> for Vpn {
+    type Result = <GetHosts as Message>::Result;
+
+    fn handle(&mut self, _: GetHosts, _: &mut Self::Context) -> Self::Result {
+        Ok(self
+            .hosts
+            .iter()
+            .map(|(name, ip)| (name.clone(), ip.to_string()))
+            .collect())
+    }
+}
+
 impl Handler<Connect> for Vpn {
     type Result = ActorResponse<Self, Result<UserConnection>>;
 
-    fn handle(&mut self, msg: Connect, _: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: Connect, ctx: &mut Self::Context) -> Self::Result {
         let remote = match to_ip(&msg.address) {
             Ok(ip) => IpEndpoint::new(ip.into(), msg.port),
             Err(err) => return ActorResponse::reply(Err(err)),
         };
 
+        match msg.protocol {
+            Protocol::Tcp => self.connect_tcp(remote),
+            Protocol::Udp => ActorResponse::reply(self.bind_udp(remote, ctx)),
+            protocol => {
+                ActorResponse::reply(Err(Error::ProtocolNotSupported(protocol.to_string())))
+            }
+        }
+    }
+}
+
+impl Vpn {
+    fn connect_tcp(&mut self, remote: IpEndpoint) -> ActorResponse<Self, Result<UserConnection>> {
         let vpn_id = self.vpn.id();
         log::info!("VPN {vpn_id}: connecting to {remote:?}");
 
@@ -431,26 +946,68 @@ impl Handler<Connect> for Vpn {
             .map(move |result, this, ctx| {
                 let stack_connection = result?;
                 log::info!("VPN {id}: connected to {remote:?}");
-                let vpn = ctx.address().recipient();
+                Ok(this.add_connection(stack_connection, ctx))
+            });
 
-                let (tx, rx) = mpsc::channel(1);
+        ActorResponse::r#async(fut)
+    }
 
-                this.connections.insert(
-                    stack_connection.meta.into(),
-                    InternalConnection {
-                        stack_connection,
-                        ingress_tx: tx,
-                    },
-                );
+    /// Binds a UDP socket on the requestor's address and an ephemeral port.
+    /// Datagrams are exchanged with the `remote` endpoint only.
+    fn bind_udp(&mut self, remote: IpEndpoint, ctx: &mut Context<Self>) -> Result<UserConnection> {
+        let ip = IpAddress::from(self.vpn.address()?);
+        let mut rng = rand::thread_rng();
 
-                Ok(UserConnection {
-                    vpn,
-                    rx,
-                    stack_connection,
-                })
-            });
+        let local = (0..UDP_BIND_ATTEMPTS)
+            .map(|_| IpEndpoint::new(ip, rng.gen_range(UDP_EPHEMERAL_PORT, u16::MAX)))
+            .find(|endpoint| {
+                self.stack_network
+                    .get_bound(Protocol::Udp, SocketEndpoint::Ip(*endpoint))
+                    .is_none()
+            })
+            .ok_or_else(|| Error::Other(format!("No free UDP port on {ip}")))?;
 
-        ActorResponse::r#async(fut)
+        let handle = self.stack_network.bind(Protocol::Udp, local)?;
+        log::info!(
+            "VPN {}: bound UDP socket {local} for {remote}",
+            self.vpn.id()
+        );
+
+        let stack_connection = stack::Connection {
+            handle,
+            meta: ConnectionMeta {
+                protocol: Protocol::Udp,
+                local,
+                remote,
+            },
+        };
+        Ok(self.add_connection(stack_connection, ctx))
+    }
+
+    fn add_connection(
+        &mut self,
+        stack_connection: stack::Connection,
+        ctx: &mut Context<Self>,
+    ) -> UserConnection {
+        let vpn = ctx.address().recipient();
+        let disconnect = ctx.address().recipient();
+
+        let (tx, rx) = mpsc::channel(1);
+
+        self.connections.insert(
+            stack_connection.meta.into(),
+            InternalConnection {
+                stack_connection,
+                ingress_tx: tx,
+            },
+        );
+
+        UserConnection {
+            vpn,
+            disconnect,
+            rx,
+            stack_connection,
+        }
     }
 }
 
@@ -517,6 +1074,11 @@ impl Handler<Packet> for Vpn {
                             e
                         );
 
+                        // A single undeliverable datagram does not invalidate the socket
+                        if connection.stack_connection.meta.protocol == Protocol::Udp {
+                            return;
+                        }
+
                         ctx.address().do_send(Disconnect::new(
                             connection.stack_connection.meta.into(),
                             DisconnectReason::ConnectionError,
@@ -538,7 +1100,16 @@ impl Handler<RpcEnvelope<VpnPacket>> for Vpn {
     type Result = <RpcEnvelope<VpnPacket> as Message>::Result;
 
     fn handle(&mut self, msg: RpcEnvelope<VpnPacket>, _: &mut Self::Context) -> Self::Result {
-        self.stack_network.receive(msg.into_inner().0);
+        let caller = msg.caller().to_string();
+        let frame = msg.into_inner().0;
+        if !self.ingress_allowed(&caller, &frame) {
+            log::trace!("[vpn] firewall: dropping ingress packet from {caller}");
+            return Ok(());
+        }
+        self.active = true;
+        self.capture
+            .record(CAPTURE_INTERFACE, Direction::Inbound, &frame);
+        self.stack_network.receive(frame);
         self.stack_network.poll();
         Ok(())
     }
@@ -548,6 +1119,16 @@ impl Handler<RpcRawCall> for Vpn {
     type Result = std::result::Result<Vec<u8>, ya_service_bus::Error>;
 
     fn handle(&mut self, msg: RpcRawCall, _: &mut Self::Context) -> Self::Result {
+        if !self.ingress_allowed(&msg.caller, &msg.body) {
+            log::trace!(
+                "[vpn] firewall: dropping ingress packet from {}",
+                msg.caller
+            );
+            return Ok(Vec::new());
+        }
+        self.active = true;
+        self.capture
+            .record(CAPTURE_INTERFACE, Direction::Inbound, &msg.body);
         self.stack_network.receive(msg.body);
         self.stack_network.poll();
         Ok(Vec::new())
@@ -613,6 +1194,13 @@ impl Handler<Egress> for Vpn {
 
     fn handle(&mut self, msg: Egress, _: &mut Self::Context) -> Self::Result {
         let frame = msg.event.payload.into_vec();
+        if !self.egress_allowed(&frame) {
+            log::trace!("[vpn] firewall: dropping egress packet");
+            return ActorResponse::reply(Ok(()));
+        }
+        self.active = true;
+        self.capture
+            .record(CAPTURE_INTERFACE, Direction::Outbound, &frame);
 
         // packet tracing is also done when the packet data is no longer available,
         // so we have to make a temporary copy. This incurs no runtime overhead on builds
@@ -651,10 +1239,34 @@ impl Handler<Egress> for Vpn {
     }
 }
 
+impl Handler<StartCapture> for Vpn {
+    type Result = <StartCapture as Message>::Result;
+
+    fn handle(&mut self, msg: StartCapture, _: &mut Self::Context) -> Self::Result {
+        let interface = format!("vpn-{}", self.vpn.id());
+        self.capture
+            .start(msg.config, &[interface.as_str()])
+            .map_err(|e| Error::Other(format!("unable to start capture: {e}")))
+    }
+}
+
+impl Handler<StopCapture> for Vpn {
+    type Result = <StopCapture as Message>::Result;
+
+    fn handle(&mut self, _: StopCapture, _: &mut Self::Context) -> Self::Result {
+        self.capture
+            .stop()
+            .map_err(|e| Error::Other(format!("unable to stop capture: {e}")))
+    }
+}
+
 impl Handler<Shutdown> for Vpn {
     type Result = <Shutdown as Message>::Result;
 
     fn handle(&mut self, _: Shutdown, ctx: &mut Self::Context) -> Self::Result {
+        if let Err(e) = self.capture.stop() {
+            log::warn!("[vpn] unable to finish traffic capture: {e}");
+        }
         ctx.stop();
         Ok(())
     }
@@ -666,6 +1278,22 @@ struct InternalConnection {
     pub ingress_tx: mpsc::Sender<Vec<u8>>,
 }
 
+async fn persist_changes(
+    db: DbExecutor,
+    mut rx: mpsc::UnboundedReceiver<NetworkChange>,
+    vpn_id: String,
+) {
+    while let Some(change) = rx.next().await {
+        if let Err(e) = db
+            .as_dao::<NetworkDao>()
+            .apply(vpn_id.clone(), change)
+            .await
+        {
+            log::warn!("VPN {vpn_id}: unable to store network change: {e}");
+        }
+    }
+}
+
 async fn vpn_ingress_handler(rx: IngressReceiver, addr: Addr<Vpn>, vpn_id: String) {
     let mut rx = UnboundedReceiverStream::new(rx);
     while let Some(event) = rx.next().await {
@@ -745,6 +1373,24 @@ fn gsb_remote_url(node_id: &str, net_id: &str) -> network::DuoEndpoint<Endpoint>
     }
 }
 
+/// Validates a hostname assigned to a node, e.g. `worker-3.mynet`. Returns it in lower case.
+fn to_hostname(name: &str) -> Result<String> {
+    let hostname = name.trim_end_matches('.').to_ascii_lowercase();
+    let valid = hostname.len() <= MAX_HOSTNAME_LEN
+        && hostname.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= MAX_LABEL_LEN
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+
+    match valid {
+        true => Ok(hostname),
+        false => Err(Error::Other(format!("invalid hostname: {name}"))),
+    }
+}
+
 trait ArbiterExt {
     fn spawn_ext<'a, F, T, E>(&self, f: F) -> BoxFuture<'a, std::result::Result<T, E>>
     where
@@ -816,9 +1462,63 @@ fn create_stack_network(
 
 #[cfg(test)]
 mod tests {
-    use crate::network::VpnSupervisor;
+    use std::time::Duration;
+
+    use futures::StreamExt;
+    use smoltcp::phy::ChecksumCapabilities;
+    use smoltcp::wire::{
+        EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr, IpProtocol, Ipv4Address,
+        Ipv4Packet, Ipv4Repr, UdpPacket, UdpRepr,
+    };
+
+    use crate::db::dao::{NetworkChange, NetworkDao};
+    use crate::db::model::NewDbNetwork;
+    use crate::message::{AddAddress, Connect, GetAddresses, GetHosts, GetNodes, Packet};
+    use crate::network::{to_hostname, VpnSupervisor};
+    use chrono::Utc;
     use ya_client_model::net::NewNetwork;
+    use ya_core_model::activity::VpnPacket;
     use ya_core_model::NodeId;
+    use ya_persistence::executor::DbExecutor;
+    use ya_service_bus::RpcEnvelope;
+    use ya_utils_networking::vpn::{Error, Protocol};
+
+    /// Ethernet frame carrying a UDP datagram
+    fn udp_frame(src: (Ipv4Address, u16), dst: (Ipv4Address, u16), payload: &[u8]) -> Vec<u8> {
+        let checksum = ChecksumCapabilities::default();
+        let udp_repr = UdpRepr {
+            src_port: src.1,
+            dst_port: dst.1,
+        };
+        let ip_repr = Ipv4Repr {
+            src_addr: src.0,
+            dst_addr: dst.0,
+            protocol: IpProtocol::Udp,
+            payload_len: udp_repr.header_len() + payload.len(),
+            hop_limit: 64,
+        };
+        let eth_repr = EthernetRepr {
+            src_addr: EthernetAddress([0xA0, 0x13, 10, 0, 0, 3]),
+            dst_addr: EthernetAddress::BROADCAST,
+            ethertype: EthernetProtocol::Ipv4,
+        };
+
+        let mut buf = vec![0u8; eth_repr.buffer_len() + ip_repr.buffer_len() + ip_repr.payload_len];
+        let mut frame = EthernetFrame::new_unchecked(&mut buf);
+        eth_repr.emit(&mut frame);
+        let mut ip = Ipv4Packet::new_unchecked(frame.payload_mut());
+        ip_repr.emit(&mut ip, &checksum);
+        let mut udp = UdpPacket::new_unchecked(ip.payload_mut());
+        udp_repr.emit(
+            &mut udp,
+            &src.0.into(),
+            &dst.0.into(),
+            payload.len(),
+            |buf| buf.copy_from_slice(payload),
+            &checksum,
+        );
+        buf
+    }
 
     #[actix_rt::test]
     async fn create_remove_network() -> anyhow::Result<()> {
@@ -877,4 +1577,202 @@ mod tests {
         assert!(supervisor.get_network(&node_id, &network2.id).is_ok());
         Ok(())
     }
+
+    #[actix_rt::test]
+    async fn udp_requires_address() -> anyhow::Result<()> {
+        let node_id = NodeId::default();
+
+        let mut supervisor = VpnSupervisor::default();
+        let network = supervisor
+            .create_network(
+                node_id,
+                NewNetwork {
+                    ip: "10.0.0.0".to_string(),
+                    mask: None,
+                    gateway: None,
+                },
+            )
+            .await?;
+        let vpn = supervisor.get_network(&node_id, &network.id)?;
+
+        let connect = |protocol| Connect {
+            protocol,
+            address: "10.0.0.3".to_string(),
+            port: 53,
+        };
+        assert!(matches!(
+            vpn.send(connect(Protocol::Udp)).await?,
+            Err(Error::NetEmpty)
+        ));
+        assert!(matches!(
+            vpn.send(connect(Protocol::Icmp)).await?,
+            Err(Error::ProtocolNotSupported(_))
+        ));
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn udp_datagrams() -> anyhow::Result<()> {
+        let node_id = NodeId::default();
+
+        let mut supervisor = VpnSupervisor::default();
+        let network = supervisor
+            .create_network(
+                node_id,
+                NewNetwork {
+                    ip: "10.0.0.0".to_string(),
+                    mask: None,
+                    gateway: None,
+                },
+            )
+            .await?;
+        let vpn = supervisor.get_network(&node_id, &network.id)?;
+        vpn.send(AddAddress {
+            address: "10.0.0.2".to_string(),
+        })
+        .await??;
+
+        let mut conn = vpn
+            .send(Connect {
+                protocol: Protocol::Udp,
+                address: "10.0.0.3".to_string(),
+                port: 53,
+            })
+            .await??;
+        let meta = conn.stack_connection.meta;
+        assert_eq!(meta.protocol, Protocol::Udp);
+        assert_eq!(meta.local.addr, Ipv4Address::new(10, 0, 0, 2).into());
+        assert_eq!(meta.remote.port, 53);
+
+        // ingress: a datagram from the remote endpoint is forwarded as a whole
+        let frame = udp_frame(
+            (Ipv4Address::new(10, 0, 0, 3), 53),
+            (Ipv4Address::new(10, 0, 0, 2), meta.local.port),
+            b"response",
+        );
+        vpn.send(RpcEnvelope::local(VpnPacket(frame))).await??;
+        let received = tokio::time::timeout(Duration::from_secs(5), conn.rx.next()).await?;
+        assert_eq!(received.as_deref(), Some(b"response".as_ref()));
+
+        // ingress: datagrams from other endpoints are ignored
+        let frame = udp_frame(
+            (Ipv4Address::new(10, 0, 0, 4), 53),
+            (Ipv4Address::new(10, 0, 0, 2), meta.local.port),
+            b"other",
+        );
+        vpn.send(RpcEnvelope::local(VpnPacket(frame))).await??;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), conn.rx.next())
+                .await
+                .is_err()
+        );
+
+        // egress
+        conn.vpn
+            .send(Packet {
+                data: b"request".to_vec(),
+                meta,
+            })
+            .await??;
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn restore_network() -> anyhow::Result<()> {
+        let node_id = NodeId::default();
+        let db = DbExecutor::in_memory("vpn_restore_network")?;
+        db.apply_migration(crate::db::migrations::run_with_output)?;
+
+        let dao = db.as_dao::<NetworkDao>();
+        dao.create(NewDbNetwork {
+            id: "net".to_string(),
+            owner_id: node_id.to_string(),
+            ip: "10.0.0.0/24".to_string(),
+            mask: "255.255.255.0".to_string(),
+            gateway: "10.0.0.1".to_string(),
+        })
+        .await?;
+        dao.apply(
+            "net".to_string(),
+            NetworkChange::AddAddress {
+                ip: "10.0.0.2".to_string(),
+            },
+        )
+        .await?;
+        dao.apply(
+            "net".to_string(),
+            NetworkChange::AddNode {
+                id: "0x0000000000000000000000000000000000000003".to_string(),
+                ip: "10.0.0.3".to_string(),
+                hostname: Some("worker-3.mynet".to_string()),
+            },
+        )
+        .await?;
+
+        let mut supervisor = VpnSupervisor::default();
+        supervisor.restore(db).await?;
+        let vpn = supervisor.get_network(&node_id, "net")?;
+
+        let nodes = vpn.send(GetNodes {}).await??;
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].ip, "10.0.0.3");
+
+        let addresses = vpn.send(GetAddresses {}).await??;
+        assert!(addresses.iter().any(|a| a.ip.starts_with("10.0.0.2/")));
+
+        let hosts = vpn.send(GetHosts {}).await??;
+        assert_eq!(
+            hosts.get("worker-3.mynet").map(String::as_str),
+            Some("10.0.0.3")
+        );
+
+        supervisor.remove_network(&node_id, "net")?.await?;
+        assert!(supervisor.get_network(&node_id, "net").is_err());
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn expire_network() -> anyhow::Result<()> {
+        let db = DbExecutor::in_memory("vpn_expire_network")?;
+        db.apply_migration(crate::db::migrations::run_with_output)?;
+
+        let dao = db.as_dao::<NetworkDao>();
+        dao.create(NewDbNetwork {
+            id: "net".to_string(),
+            owner_id: NodeId::default().to_string(),
+            ip: "10.0.0.0/24".to_string(),
+            mask: "255.255.255.0".to_string(),
+            gateway: "10.0.0.1".to_string(),
+        })
+        .await?;
+        dao.apply("net".to_string(), NetworkChange::Touch).await?;
+
+        let now = Utc::now().naive_utc();
+        let updated_ts = dao.list().await?[0].network.updated_ts;
+        assert!(updated_ts > now - chrono::Duration::minutes(1));
+
+        assert!(dao
+            .remove_expired(now - chrono::Duration::hours(1))
+            .await?
+            .is_empty());
+        assert_eq!(dao.list().await?.len(), 1);
+
+        let expired = dao
+            .remove_expired(now + chrono::Duration::minutes(1))
+            .await?;
+        assert_eq!(expired, vec!["net".to_string()]);
+        assert!(dao.list().await?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn validate_hostname() {
+        assert_eq!(to_hostname("Worker-3.MyNet.").unwrap(), "worker-3.mynet");
+        assert_eq!(to_hostname("storage").unwrap(), "storage");
+        assert!(to_hostname("").is_err());
+        assert!(to_hostname("worker..mynet").is_err());
+        assert!(to_hostname("-worker.mynet").is_err());
+        assert!(to_hostname("worker_3.mynet").is_err());
+        assert!(to_hostname(&"a".repeat(64)).is_err());
+    }
 }
@@ -13,6 +13,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use ya_client_model::net::*;
 use ya_client_model::ErrorMessage;
+use ya_core_model::activity::VpnFirewall;
 use ya_service_api_web::middleware::Identity;
 use ya_utils_networking::vpn::stack::connection::ConnectionMeta;
 use ya_utils_networking::vpn::{Error as VpnError, Protocol};
@@ -50,7 +51,13 @@ fn vpn_web_scope(path: &str) -> actix_web::Scope {
         .service(get_nodes)
         .service(add_node)
         .service(remove_node)
+        .service(get_hosts)
+        .service(get_firewall)
+        .service(set_firewall)
+        .service(start_capture)
+        .service(stop_capture)
         .service(connect_tcp)
+        .service(connect_udp)
 }
 
 /// Retrieves existing virtual private networks.
@@ -166,7 +173,7 @@ async fn get_nodes(
 async fn add_node(
     vpn_sup: web::Data<Arc<Mutex<VpnSupervisor>>>,
     path: web::Path<PathNetwork>,
-    model: web::Json<Node>,
+    model: web::Json<NewNode>,
     identity: Identity,
 ) -> impl Responder {
     let path = path.into_inner();
@@ -179,6 +186,7 @@ async fn add_node(
         .send(AddNode {
             id: node.id,
             address: node.ip,
+            hostname: node.hostname,
         })
         .await??;
     Ok::<_, ApiError>(web::Json(response))
@@ -199,6 +207,97 @@ async fn remove_node(
     Ok::<_, ApiError>(web::Json(fut.await?))
 }
 
+/// Retrieves hostnames of nodes within a virtual private network, mapped to their addresses.
+/// Hostnames are resolved by the nameserver of each node's ExeUnit.
+#[actix_web::get("/net/{net_id}/hosts")]
+async fn get_hosts(
+    vpn_sup: web::Data<Arc<Mutex<VpnSupervisor>>>,
+    path: web::Path<PathNetwork>,
+    identity: Identity,
+) -> impl Responder {
+    let path = path.into_inner();
+    let vpn = {
+        let supervisor = vpn_sup.lock().await;
+        supervisor.get_network(&identity.identity, &path.net_id)?
+    };
+    let response = vpn.send(GetHosts {}).await??;
+    Ok::<_, ApiError>(web::Json(response))
+}
+
+/// Retrieves firewall rules of a virtual private network.
+#[actix_web::get("/net/{net_id}/firewall")]
+async fn get_firewall(
+    vpn_sup: web::Data<Arc<Mutex<VpnSupervisor>>>,
+    path: web::Path<PathNetwork>,
+    identity: Identity,
+) -> impl Responder {
+    let path = path.into_inner();
+    let vpn = {
+        let supervisor = vpn_sup.lock().await;
+        supervisor.get_network(&identity.identity, &path.net_id)?
+    };
+    let response = vpn.send(GetFirewall {}).await??;
+    Ok::<_, ApiError>(web::Json(response))
+}
+
+/// Replaces firewall rules of a virtual private network.
+/// Rules are enforced by the requestor and by ExeUnits of all nodes in the network.
+#[actix_web::put("/net/{net_id}/firewall")]
+async fn set_firewall(
+    vpn_sup: web::Data<Arc<Mutex<VpnSupervisor>>>,
+    path: web::Path<PathNetwork>,
+    model: web::Json<VpnFirewall>,
+    identity: Identity,
+) -> impl Responder {
+    let path = path.into_inner();
+    let vpn = {
+        let supervisor = vpn_sup.lock().await;
+        supervisor.get_network(&identity.identity, &path.net_id)?
+    };
+    let firewall = model.into_inner();
+    let response = vpn.send(SetFirewall { firewall }).await??;
+    Ok::<_, ApiError>(web::Json(response))
+}
+
+/// Starts capturing traffic of a virtual private network to pcapng files.
+/// Responds with the directory the files are written to.
+#[actix_web::post("/net/{net_id}/capture")]
+async fn start_capture(
+    vpn_sup: web::Data<Arc<Mutex<VpnSupervisor>>>,
+    path: web::Path<PathNetwork>,
+    model: web::Json<NewCapture>,
+    identity: Identity,
+) -> impl Responder {
+    let path = path.into_inner();
+    let capture = model.into_inner();
+    let fut = {
+        let mut supervisor = vpn_sup.lock().await;
+        supervisor.start_capture(
+            &identity.identity,
+            &path.net_id,
+            capture.max_file_size,
+            capture.max_files,
+        )?
+    };
+    Ok::<_, ApiError>(web::Json(fut.await?))
+}
+
+/// Stops capturing traffic of a virtual private network.
+/// Responds with paths of the capture files kept.
+#[actix_web::delete("/net/{net_id}/capture")]
+async fn stop_capture(
+    vpn_sup: web::Data<Arc<Mutex<VpnSupervisor>>>,
+    path: web::Path<PathNetwork>,
+    identity: Identity,
+) -> impl Responder {
+    let path = path.into_inner();
+    let fut = {
+        let mut supervisor = vpn_sup.lock().await;
+        supervisor.stop_capture(&identity.identity, &path.net_id)?
+    };
+    Ok::<_, ApiError>(web::Json(fut.await?))
+}
+
 /// Initiates a new TCP connection via WebSockets to the destination address.
 #[actix_web::get("/net/{net_id}/tcp/{ip}/{port}")]
 async fn connect_tcp(
@@ -207,6 +306,30 @@ async fn connect_tcp(
     req: HttpRequest,
     stream: web::Payload,
     identity: Identity,
+) -> Result<HttpResponse> {
+    connect(vpn_sup, path, Protocol::Tcp, req, stream, identity).await
+}
+
+/// Exchanges UDP datagrams with the destination address via WebSockets.
+/// Each binary message carries a single datagram.
+#[actix_web::get("/net/{net_id}/udp/{ip}/{port}")]
+async fn connect_udp(
+    vpn_sup: web::Data<Arc<Mutex<VpnSupervisor>>>,
+    path: web::Path<PathConnect>,
+    req: HttpRequest,
+    stream: web::Payload,
+    identity: Identity,
+) -> Result<HttpResponse> {
+    connect(vpn_sup, path, Protocol::Udp, req, stream, identity).await
+}
+
+async fn connect(
+    vpn_sup: web::Data<Arc<Mutex<VpnSupervisor>>>,
+    path: web::Path<PathConnect>,
+    protocol: Protocol,
+    req: HttpRequest,
+    stream: web::Payload,
+    identity: Identity,
 ) -> Result<HttpResponse> {
     let path = path.into_inner();
     let vpn = {
@@ -215,7 +338,7 @@ async fn connect_tcp(
     };
     let conn = vpn
         .send(Connect {
-            protocol: Protocol::Tcp,
+            protocol,
             address: path.ip.to_string(),
             port: path.port,
         })
@@ -231,6 +354,7 @@ pub struct VpnWebSocket {
     network_id: String,
     heartbeat: Instant,
     vpn: Recipient<Packet>,
+    vpn_disconnect: Recipient<Disconnect>,
     vpn_rx: Option<mpsc::Receiver<Vec<u8>>>,
     meta: ConnectionMeta,
 }
@@ -241,6 +365,7 @@ impl VpnWebSocket {
             network_id,
             heartbeat: Instant::now(),
             vpn: conn.vpn,
+            vpn_disconnect: conn.disconnect,
             vpn_rx: Some(conn.rx),
             meta: conn.stack_connection.meta,
         }
@@ -294,6 +419,14 @@ impl Actor for VpnWebSocket {
 
     fn stopped(&mut self, _: &mut Self::Context) {
         log::info!("VPN WebSocket: VPN {} connection stopped", self.network_id);
+
+        // UDP sockets are never closed by the remote side
+        if self.meta.protocol == Protocol::Udp {
+            self.vpn_disconnect.do_send(Disconnect::new(
+                self.meta.into(),
+                DisconnectReason::SinkClosed,
+            ));
+        }
     }
 }
 
@@ -365,6 +498,26 @@ impl ResponseError for ApiError {
     }
 }
 
+/// Node with an optional hostname, e.g. `worker-3.mynet`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewNode {
+    id: String,
+    ip: String,
+    #[serde(default)]
+    hostname: Option<String>,
+}
+
+/// Capture file rotation settings; defaults are used when omitted
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewCapture {
+    #[serde(default)]
+    max_file_size: Option<u64>,
+    #[serde(default)]
+    max_files: Option<usize>,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct PathNetwork {
     net_id: String,
@@ -1,9 +1,13 @@
+use crate::db::migrations;
 use crate::network::VpnSupervisor;
 use futures::lock::Mutex;
 use std::sync::Arc;
 use ya_persistence::executor::DbExecutor;
+use ya_service_api::CliCtx;
 use ya_service_api_interfaces::Provider;
 
+const CAPTURE_DIR: &str = "vpn-capture";
+
 lazy_static::lazy_static! {
     static ref VPN_SUPERVISOR: Arc<Mutex<VpnSupervisor>> = Default::default();
 }
@@ -11,8 +15,16 @@ lazy_static::lazy_static! {
 pub struct VpnService;
 
 impl VpnService {
-    pub async fn gsb<Context: Provider<Self, DbExecutor>>(_: &Context) -> anyhow::Result<()> {
-        let _vpn = VPN_SUPERVISOR.clone();
+    pub async fn gsb<Context: Provider<Self, CliCtx> + Provider<Self, DbExecutor>>(
+        ctx: &Context,
+    ) -> anyhow::Result<()> {
+        let db: DbExecutor = ctx.component();
+        let cli_ctx: CliCtx = ctx.component();
+        db.apply_migration(migrations::run_with_output)?;
+
+        let mut supervisor = VPN_SUPERVISOR.lock().await;
+        supervisor.set_capture_dir(cli_ctx.data_dir.join(CAPTURE_DIR));
+        supervisor.restore(db).await?;
         Ok(())
     }
 
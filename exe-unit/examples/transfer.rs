@@ -217,6 +217,7 @@ async fn main() -> anyhow::Result<()> {
         acl: Default::default(),
         report_url: None,
         credentials: None,
+        capture: Default::default(),
         agreement,
         work_dir: work_dir.clone(),
         cache_dir,
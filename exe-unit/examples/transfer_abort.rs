@@ -174,6 +174,7 @@ async fn main() -> anyhow::Result<()> {
         acl: Default::default(),
         report_url: None,
         credentials: None,
+        capture: Default::default(),
         agreement,
         work_dir,
         cache_dir,
@@ -311,6 +311,7 @@ async fn run() -> anyhow::Result<()> {
         runtime_args: cli.runtime_arg.clone(),
         acl: Default::default(),
         credentials: None,
+        capture: Default::default(),
         #[cfg(feature = "sgx")]
         crypto: init_crypto(
             cli.sec_key.replace("<hidden>".into()),
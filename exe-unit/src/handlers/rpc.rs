@@ -11,10 +11,12 @@ use ya_client_model::activity::encrypted::RpcMessageError as SgxMessageError;
 use ya_client_model::activity::{ActivityState, ActivityUsage, ExeScriptCommandResult};
 use ya_core_model::activity::*;
 use ya_service_bus::{Error as RpcError, RpcEnvelope, RpcStreamCall};
+use ya_utils_networking::vpn::capture::{CaptureConfig, DEFAULT_MAX_FILES, DEFAULT_MAX_FILE_SIZE};
 
 use crate::error::Error;
 use crate::manifest::{ManifestValidatorExt, ScriptValidator};
 use crate::message::{GetBatchResults, GetMetrics};
+use crate::network::{CAPTURE_DIR, CAPTURE_INTERFACES};
 use crate::runtime::Runtime;
 use crate::{ExeUnit, RuntimeRef};
 
@@ -298,6 +300,49 @@ impl<R: Runtime> Handler<RpcEnvelope<sgx::CallEncryptedService>> for ExeUnit<R>
     }
 }
 
+impl<R: Runtime> Handler<RpcEnvelope<StartCapture>> for ExeUnit<R> {
+    type Result = <RpcEnvelope<StartCapture> as Message>::Result;
+
+    fn handle(&mut self, msg: RpcEnvelope<StartCapture>, _: &mut Self::Context) -> Self::Result {
+        self.ctx.verify_activity_id(&msg.activity_id)?;
+
+        let config = CaptureConfig {
+            dir: self.ctx.work_dir.join(CAPTURE_DIR),
+            name: msg.activity_id.clone(),
+            max_file_size: msg.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE),
+            max_files: msg.max_files.unwrap_or(DEFAULT_MAX_FILES),
+        };
+        let dir = self
+            .ctx
+            .capture
+            .start(config, &CAPTURE_INTERFACES)
+            .map_err(|e| RpcMessageError::Service(format!("Unable to start capture: {e}")))?;
+
+        log::info!("Capturing network traffic to {}", dir.display());
+        Ok(dir.display().to_string())
+    }
+}
+
+impl<R: Runtime> Handler<RpcEnvelope<StopCapture>> for ExeUnit<R> {
+    type Result = <RpcEnvelope<StopCapture> as Message>::Result;
+
+    fn handle(&mut self, msg: RpcEnvelope<StopCapture>, _: &mut Self::Context) -> Self::Result {
+        self.ctx.verify_activity_id(&msg.activity_id)?;
+
+        let files = self
+            .ctx
+            .capture
+            .stop()
+            .map_err(|e| RpcMessageError::Service(format!("Unable to stop capture: {e}")))?;
+
+        log::info!("Network traffic capture stopped");
+        Ok(files
+            .into_iter()
+            .map(|path| path.display().to_string())
+            .collect())
+    }
+}
+
 #[cfg(feature = "sgx")]
 fn rpc_to_sgx_error(error: RpcMessageError) -> SgxMessageError {
     match error {
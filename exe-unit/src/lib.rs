@@ -17,8 +17,9 @@ use ya_core_model::activity;
 use ya_core_model::activity::local::Credentials;
 use ya_runtime_api::deploy;
 use ya_service_bus::{actix_rpc, RpcEndpoint, RpcMessage};
+use ya_utils_networking::vpn::capture::Capture;
 
-use crate::acl::Acl;
+use crate::acl::{AccessRole, Acl};
 use crate::agreement::Agreement;
 use crate::error::Error;
 use crate::message::*;
@@ -73,6 +74,12 @@ impl<R: Runtime> ExeUnit<R> {
         transfers: Addr<TransferService>,
         runtime: Addr<R>,
     ) -> Self {
+        // Network configuration (VPN control messages) is accepted only from the requestor
+        match ctx.agreement.inner.requestor_id() {
+            Ok(requestor_id) => ctx.acl.grant(requestor_id.to_string(), AccessRole::Control),
+            Err(e) => log::warn!("Unable to read the requestor id from the agreement: {e}"),
+        }
+
         ExeUnit {
             ctx,
             state: ExeUnitState::default(),
@@ -96,6 +103,8 @@ impl<R: Runtime> ExeUnit<R> {
         let supervisor_template = OfferTemplate::new(serde_json::json!({
             "golem.com.usage.vector": MetricsService::usage_vector(),
             "golem.activity.caps.transfer.protocol": TransferService::schemes(),
+            "golem.activity.caps.transfer.format": TransferService::formats(),
+            "golem.activity.caps.transfer.sync": TransferService::sync_modes(),
         }));
 
         Ok(supervisor_template.patch(runtime_template))
@@ -392,6 +401,10 @@ impl<R: Runtime> Actor for ExeUnit<R> {
                     addr.clone().recipient(),
                 );
             }
+
+            let local_id = activity::exeunit::local_bus_id(activity_id);
+            actix_rpc::bind::<activity::StartCapture>(&local_id, addr.clone().recipient());
+            actix_rpc::bind::<activity::StopCapture>(&local_id, addr.clone().recipient());
         }
 
         IntervalFunc::new(*DEFAULT_REPORT_INTERVAL, Self::report_usage)
@@ -463,6 +476,9 @@ pub struct ExeUnitContext {
     pub runtime_args: Vec<String>,
     pub acl: Acl,
     pub credentials: Option<Credentials>,
+    /// Capture of VPN and outbound internet traffic, controlled via the local bus
+    #[derivative(Debug = "ignore")]
+    pub capture: Capture,
     #[cfg(feature = "sgx")]
     #[derivative(Debug = "ignore")]
     pub crypto: crypto::Crypto,
@@ -26,6 +26,7 @@ use crate::error::Error;
 use crate::state::DeploymentNetwork;
 use crate::Result;
 
+pub(crate) mod dns;
 pub(crate) mod inet;
 pub(crate) mod vpn;
 
@@ -34,6 +35,13 @@ type SocketChannel = (
     mpsc::UnboundedReceiver<Result<Vec<u8>>>,
 );
 
+/// Directory of traffic capture files, relative to the work dir
+pub(crate) const CAPTURE_DIR: &str = "capture";
+/// Names of capture interfaces, indexed by `CAPTURE_VPN` and `CAPTURE_INET`
+pub(crate) const CAPTURE_INTERFACES: [&str; 2] = ["vpn", "inet"];
+pub(crate) const CAPTURE_VPN: u32 = 0;
+pub(crate) const CAPTURE_INET: u32 = 1;
+
 const SOCKET_BUFFER_SIZE: usize = 2097152;
 const BUFFER_SIZE: usize = DEFAULT_MAX_FRAME_SIZE * 4;
 
@@ -338,11 +346,7 @@ impl<'a> TryFrom<&'a DeploymentNetwork> for Network {
     fn try_from(net: &'a DeploymentNetwork) -> Result<Self> {
         let ip = net.network.addr();
         let mask = net.network.netmask();
-        let gateway = net
-            .network
-            .hosts()
-            .find(|ip_| ip_ != &ip)
-            .ok_or(NetError::NetAddrTaken(ip))?;
+        let gateway = net.gateway().ok_or(NetError::NetAddrTaken(ip))?;
 
         Ok(Network {
             addr: ip.to_string(),
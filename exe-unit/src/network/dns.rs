@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+
+use trust_dns_resolver::proto::op::{Message, MessageType, OpCode, ResponseCode};
+use trust_dns_resolver::proto::rr::{RData, Record, RecordType};
+
+const ETHER_HEADER_LEN: usize = 14;
+const ETHER_TYPE_ARP: [u8; 2] = [0x08, 0x06];
+const ARP_PACKET_LEN: usize = 28;
+/// Ethernet hardware type, IPv4 protocol type, address lengths and the request operation
+const ARP_IPV4_REQUEST: [u8; 8] = [0, 1, 8, 0, 6, 4, 0, 1];
+const ARP_IPV4_REPLY: [u8; 8] = [0, 1, 8, 0, 6, 4, 0, 2];
+const IPV4_HEADER_LEN: usize = 20;
+const IPV4_TTL: u8 = 64;
+const UDP_HEADER_LEN: usize = 8;
+const UDP_PROTOCOL: u8 = 17;
+/// Locally administered hardware address of the nameserver, used when no node owns its IP
+const NAMESERVER_MAC: [u8; 6] = [0x02, 0x79, 0x61, 0x64, 0x6e, 0x73];
+/// Hostnames may change at any time, so answers are not meant to be cached for long
+const RECORD_TTL: u32 = 5;
+
+/// Hostnames answered by the VPN nameserver
+#[derive(Debug, Default)]
+pub(crate) struct Hosts {
+    /// Entries provided on deployment
+    deployment: HashMap<String, IpAddr>,
+    /// Hostnames assigned to nodes by the requestor, per network id
+    networks: HashMap<String, HashMap<String, IpAddr>>,
+}
+
+impl Hosts {
+    pub fn new(hosts: &HashMap<String, String>) -> Self {
+        let deployment = hosts
+            .iter()
+            .filter_map(|(name, ip)| match ip.parse() {
+                Ok(ip) => Some((normalize(name), ip)),
+                Err(_) => {
+                    log::warn!("[vpn] invalid IP address of host '{name}': {ip}");
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            deployment,
+            networks: Default::default(),
+        }
+    }
+
+    /// Replaces hostnames assigned within a network
+    pub fn set(&mut self, network_id: String, hosts: HashMap<String, IpAddr>) {
+        let hosts = hosts
+            .into_iter()
+            .map(|(name, ip)| (normalize(&name), ip))
+            .collect();
+        self.networks.insert(network_id, hosts);
+    }
+
+    /// Resolves a hostname assigned within the querying network, or provided on deployment
+    pub fn resolve(&self, network_id: Option<&str>, name: &str) -> Option<IpAddr> {
+        let name = normalize(name);
+        network_id
+            .and_then(|id| self.networks.get(id))
+            .and_then(|hosts| hosts.get(&name))
+            .or_else(|| self.deployment.get(&name))
+            .copied()
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Builds a reply frame to a DNS query carried in an Ethernet / IPv4 / UDP frame,
+/// sent from within the `network_id` network.
+/// Queries for unknown names are refused, so that the runtime falls back
+/// to the next nameserver.
+pub(crate) fn reply(frame: &[u8], hosts: &Hosts, network_id: Option<&str>) -> Option<Vec<u8>> {
+    let ip = frame.get(ETHER_HEADER_LEN..)?;
+    if ip.first()? >> 4 != 4 || ip.len() < IPV4_HEADER_LEN {
+        return None;
+    }
+    let udp = ip.get((ip[0] & 0x0f) as usize * 4..)?;
+    let udp_len = u16::from_be_bytes([*udp.get(4)?, *udp.get(5)?]) as usize;
+    let query = udp.get(UDP_HEADER_LEN..udp_len)?;
+    let answer = answer(query, hosts, network_id)?;
+
+    let mut reply =
+        Vec::with_capacity(ETHER_HEADER_LEN + IPV4_HEADER_LEN + UDP_HEADER_LEN + answer.len());
+    reply.extend_from_slice(&frame[6..12]);
+    reply.extend_from_slice(&frame[0..6]);
+    reply.extend_from_slice(&frame[12..14]);
+
+    let mut header = [0u8; IPV4_HEADER_LEN];
+    let total_len = (IPV4_HEADER_LEN + UDP_HEADER_LEN + answer.len()) as u16;
+    header[0] = 0x45;
+    header[2..4].copy_from_slice(&total_len.to_be_bytes());
+    header[8] = IPV4_TTL;
+    header[9] = UDP_PROTOCOL;
+    header[12..16].copy_from_slice(&ip[16..20]);
+    header[16..20].copy_from_slice(&ip[12..16]);
+    let checksum = checksum(&header);
+    header[10..12].copy_from_slice(&checksum.to_be_bytes());
+    reply.extend_from_slice(&header);
+
+    // UDP checksum is optional over IPv4
+    let udp_len = (UDP_HEADER_LEN + answer.len()) as u16;
+    reply.extend_from_slice(&udp[2..4]);
+    reply.extend_from_slice(&udp[0..2]);
+    reply.extend_from_slice(&udp_len.to_be_bytes());
+    reply.extend_from_slice(&[0, 0]);
+    reply.extend(answer);
+    Some(reply)
+}
+
+/// Builds a reply frame to an ARP request for the nameserver address
+pub(crate) fn arp_reply(frame: &[u8], ip: Ipv4Addr) -> Option<Vec<u8>> {
+    let arp = frame.get(ETHER_HEADER_LEN..ETHER_HEADER_LEN + ARP_PACKET_LEN)?;
+    if frame[12..14] != ETHER_TYPE_ARP || arp[..8] != ARP_IPV4_REQUEST || arp[24..28] != ip.octets()
+    {
+        return None;
+    }
+
+    let mut reply = Vec::with_capacity(ETHER_HEADER_LEN + ARP_PACKET_LEN);
+    reply.extend_from_slice(&arp[8..14]);
+    reply.extend_from_slice(&NAMESERVER_MAC);
+    reply.extend_from_slice(&ETHER_TYPE_ARP);
+    reply.extend_from_slice(&ARP_IPV4_REPLY);
+    reply.extend_from_slice(&NAMESERVER_MAC);
+    reply.extend_from_slice(&ip.octets());
+    reply.extend_from_slice(&arp[8..18]);
+    Some(reply)
+}
+
+fn answer(query: &[u8], hosts: &Hosts, network_id: Option<&str>) -> Option<Vec<u8>> {
+    let request = Message::from_vec(query).ok()?;
+    if request.message_type() != MessageType::Query || request.op_code() != OpCode::Query {
+        return None;
+    }
+
+    let mut response = Message::new();
+    response
+        .set_id(request.id())
+        .set_message_type(MessageType::Response)
+        .set_op_code(OpCode::Query)
+        .set_authoritative(true)
+        .set_recursion_desired(request.recursion_desired());
+
+    for query in request.queries() {
+        response.add_query(query.clone());
+
+        let ip = hosts.resolve(network_id, &query.name().to_ascii());
+        let rdata = match (query.query_type(), ip) {
+            (RecordType::A, Some(IpAddr::V4(ip))) => RData::A(ip),
+            (RecordType::AAAA, Some(IpAddr::V6(ip))) => RData::AAAA(ip),
+            // known name without an address of the requested type
+            (_, Some(_)) => continue,
+            (_, None) => {
+                response.take_answers();
+                response.set_response_code(ResponseCode::Refused);
+                break;
+            }
+        };
+        response.add_answer(Record::from_rdata(query.name().clone(), RECORD_TTL, rdata));
+    }
+
+    response.to_vec().ok()
+}
+
+fn checksum(header: &[u8]) -> u16 {
+    let sum = header
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]) as u32)
+        .sum::<u32>();
+    let sum = (sum & 0xffff) + (sum >> 16);
+    !(((sum & 0xffff) + (sum >> 16)) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::DNS_PORT;
+    use std::str::FromStr;
+    use trust_dns_resolver::proto::op::Query;
+    use trust_dns_resolver::proto::rr::Name;
+
+    const VM_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x01];
+
+    fn hosts() -> Hosts {
+        let deployment = vec![("Storage".to_string(), "192.168.0.5".to_string())]
+            .into_iter()
+            .collect();
+        let mut hosts = Hosts::new(&deployment);
+        hosts.set(
+            "net".into(),
+            vec![("worker-3.mynet".to_string(), "192.168.0.3".parse().unwrap())]
+                .into_iter()
+                .collect(),
+        );
+        hosts.set(
+            "other".into(),
+            vec![("worker-3.mynet".to_string(), "10.0.0.3".parse().unwrap())]
+                .into_iter()
+                .collect(),
+        );
+        hosts
+    }
+
+    fn query_frame(name: &str, record_type: RecordType) -> Vec<u8> {
+        let mut message = Message::new();
+        message
+            .set_id(7)
+            .add_query(Query::query(Name::from_str(name).unwrap(), record_type));
+        let query = message.to_vec().unwrap();
+
+        let mut frame = Vec::new();
+        frame.extend_from_slice(&NAMESERVER_MAC);
+        frame.extend_from_slice(&VM_MAC);
+        frame.extend_from_slice(&[0x08, 0x00]);
+        let total_len = (IPV4_HEADER_LEN + UDP_HEADER_LEN + query.len()) as u16;
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&total_len.to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0, 0, 64, UDP_PROTOCOL, 0, 0]);
+        frame.extend_from_slice(&[192, 168, 0, 2, 192, 168, 0, 1]);
+        frame.extend_from_slice(&40000u16.to_be_bytes());
+        frame.extend_from_slice(&DNS_PORT.to_be_bytes());
+        frame.extend_from_slice(&((UDP_HEADER_LEN + query.len()) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend(query);
+        frame
+    }
+
+    fn response(reply: &[u8]) -> Message {
+        let offset = ETHER_HEADER_LEN + IPV4_HEADER_LEN + UDP_HEADER_LEN;
+        Message::from_vec(&reply[offset..]).unwrap()
+    }
+
+    #[test]
+    fn resolve_hosts() {
+        let hosts = hosts();
+        assert_eq!(
+            hosts.resolve(Some("net"), "Worker-3.mynet."),
+            Some("192.168.0.3".parse().unwrap())
+        );
+        assert_eq!(
+            hosts.resolve(Some("other"), "worker-3.mynet"),
+            Some("10.0.0.3".parse().unwrap())
+        );
+        // hostnames are assigned per network
+        assert_eq!(hosts.resolve(None, "worker-3.mynet"), None);
+        assert_eq!(
+            hosts.resolve(Some("other"), "storage"),
+            Some("192.168.0.5".parse().unwrap())
+        );
+        assert_eq!(hosts.resolve(Some("net"), "worker-4.mynet"), None);
+    }
+
+    #[test]
+    fn reply_to_query() {
+        let hosts = hosts();
+        let query = query_frame("worker-3.mynet.", RecordType::A);
+        let frame = reply(&query, &hosts, Some("net")).unwrap();
+        assert_eq!(&frame[0..6], &VM_MAC);
+        assert_eq!(&frame[26..34], &[192, 168, 0, 1, 192, 168, 0, 2]);
+        assert_eq!(checksum(&frame[14..34]), 0);
+        assert_eq!(u16::from_be_bytes([frame[34], frame[35]]), DNS_PORT);
+
+        let message = response(&frame);
+        assert_eq!(message.id(), 7);
+        assert_eq!(message.response_code(), ResponseCode::NoError);
+        assert_eq!(
+            message.answers()[0].data(),
+            Some(&RData::A(Ipv4Addr::new(192, 168, 0, 3)))
+        );
+
+        let query = query_frame("worker-3.mynet.", RecordType::AAAA);
+        let frame = reply(&query, &hosts, Some("net")).unwrap();
+        assert!(response(&frame).answers().is_empty());
+
+        let query = query_frame("golem.network.", RecordType::A);
+        let frame = reply(&query, &hosts, Some("net")).unwrap();
+        assert_eq!(response(&frame).response_code(), ResponseCode::Refused);
+    }
+
+    #[test]
+    fn reply_to_arp() {
+        let ip = Ipv4Addr::new(192, 168, 0, 1);
+        let mut frame = vec![0xff; 6];
+        frame.extend_from_slice(&VM_MAC);
+        frame.extend_from_slice(&ETHER_TYPE_ARP);
+        frame.extend_from_slice(&ARP_IPV4_REQUEST);
+        frame.extend_from_slice(&VM_MAC);
+        frame.extend_from_slice(&[192, 168, 0, 2]);
+        frame.extend_from_slice(&[0; 6]);
+        frame.extend_from_slice(&ip.octets());
+
+        assert_eq!(arp_reply(&frame, Ipv4Addr::new(192, 168, 0, 4)), None);
+
+        let reply = arp_reply(&frame, ip).unwrap();
+        assert_eq!(reply.len(), ETHER_HEADER_LEN + ARP_PACKET_LEN);
+        assert_eq!(&reply[0..6], &VM_MAC);
+        assert_eq!(&reply[22..28], &NAMESERVER_MAC);
+        assert_eq!(&reply[28..32], &ip.octets());
+        assert_eq!(&reply[38..42], &[192, 168, 0, 2]);
+    }
+}
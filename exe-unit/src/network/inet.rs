@@ -32,6 +32,7 @@ use net::{Error as NetError, Protocol};
 use ya_runtime_api::deploy::ContainerEndpoint;
 use ya_runtime_api::server::{CreateNetwork, NetworkInterface, RuntimeService};
 use ya_std_utils::LogErr;
+use ya_utils_networking::vpn::capture::{Capture, Direction};
 use ya_utils_networking::vpn::common::ntoh;
 use ya_utils_networking::vpn::stack as net;
 use ya_utils_networking::vpn::stack::ya_smoltcp::iface::SocketHandle;
@@ -46,7 +47,7 @@ use ya_utils_networking::vpn::{
 use crate::dns::DNS_PORT;
 use crate::manifest::UrlValidator;
 use crate::message::Shutdown;
-use crate::network::Endpoint;
+use crate::network::{Endpoint, CAPTURE_INET};
 use crate::{dns, Error, Result};
 
 // 10.0.0.0/8 is a reserved private address space
@@ -74,6 +75,7 @@ pub(crate) async fn start_inet<R: RuntimeService>(
     mut endpoint: Endpoint,
     service: &R,
     filter: Option<UrlValidator>,
+    capture: Capture,
 ) -> Result<Addr<Inet>> {
     use ya_runtime_api::server::Network;
 
@@ -106,6 +108,7 @@ pub(crate) async fn start_inet<R: RuntimeService>(
             networks,
             hosts: Default::default(),
             interface: NetworkInterface::Inet as i32,
+            nameservers: Default::default(),
         })
         .await
         .map_err(|e| Error::Other(format!("initialization error: {:?}", e)))?;
@@ -125,23 +128,25 @@ pub(crate) async fn start_inet<R: RuntimeService>(
         }
     };
 
-    Ok(Inet::new(endpoint, filter).start())
+    Ok(Inet::new(endpoint, filter, capture).start())
 }
 
 pub(crate) struct Inet {
     network: net::Network,
     endpoint: Endpoint,
     proxy: Proxy,
+    capture: Capture,
 }
 
 impl Inet {
-    pub fn new(endpoint: Endpoint, filter: Option<UrlValidator>) -> Self {
+    pub fn new(endpoint: Endpoint, filter: Option<UrlValidator>, capture: Capture) -> Self {
         let network = Self::create_network();
         let proxy = Proxy::new(network.clone(), filter);
         Self {
             network,
             endpoint,
             proxy,
+            capture,
         }
     }
 
@@ -206,7 +211,7 @@ impl Actor for Inet {
             .egress_receiver()
             .expect("Egress receiver already taken");
 
-        inet_endpoint_egress_handler(rx, router)
+        inet_endpoint_egress_handler(rx, router, self.capture.clone())
             .into_actor(self)
             .spawn(ctx);
 
@@ -214,7 +219,7 @@ impl Actor for Inet {
             .into_actor(self)
             .spawn(ctx);
 
-        inet_egress_handler(egress_rx, tx)
+        inet_egress_handler(egress_rx, tx, self.capture.clone())
             .into_actor(self)
             .spawn(ctx);
     }
@@ -239,12 +244,17 @@ impl Handler<Shutdown> for Inet {
 }
 
 /// Receives packets from ExeUnit Runtime and forwards them to proxy network stack for dispatching.
-async fn inet_endpoint_egress_handler(mut rx: BoxStream<'static, Result<Vec<u8>>>, router: Router) {
+async fn inet_endpoint_egress_handler(
+    mut rx: BoxStream<'static, Result<Vec<u8>>>,
+    router: Router,
+    capture: Capture,
+) {
     while let Some(result) = rx.next().await {
         let packet = match result {
             Ok(vec) => vec,
             Err(err) => return log::debug!("[inet] runtime -> inet error: {err}"),
         };
+        capture.record(CAPTURE_INET, Direction::Outbound, &packet);
 
         // If we failed during handling packet, we should save the error for later.
         // First connection must be established in network stack, so we can close it.
@@ -332,10 +342,12 @@ async fn inet_ingress_handler(rx: IngressReceiver, proxy: Proxy) {
 async fn inet_egress_handler<E: std::fmt::Display>(
     rx: EgressReceiver,
     fwd: tokio::sync::mpsc::UnboundedSender<std::result::Result<Vec<u8>, E>>,
+    capture: Capture,
 ) {
     let mut rx = UnboundedReceiverStream::new(rx);
     while let Some(event) = rx.next().await {
         let frame = event.payload.into_vec();
+        capture.record(CAPTURE_INET, Direction::Inbound, &frame);
 
         let desc = dispatch_desc(&frame)
             .map(|desc| format!("{desc:?}"))
@@ -1,23 +1,32 @@
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Instant;
 
 use actix::prelude::*;
 use futures::{future, FutureExt};
 
 use ya_client_model::NodeId;
-use ya_core_model::activity::{self, RpcMessageError, VpnControl, VpnPacket};
+use ya_core_model::activity::{
+    self, RpcMessageError, VpnControl, VpnFirewall, VpnPacket, VpnProtocol,
+};
 use ya_core_model::identity;
 use ya_runtime_api::deploy::ContainerEndpoint;
 use ya_runtime_api::server::{CreateNetwork, NetworkInterface, RuntimeService};
 use ya_service_bus::typed::Endpoint as GsbEndpoint;
 use ya_service_bus::{actix_rpc, typed, RpcEndpoint, RpcEnvelope, RpcRawCall};
+use ya_utils_networking::vpn::capture::{Capture, Direction};
+use ya_utils_networking::vpn::common::{ntoh, IpFlow};
+use ya_utils_networking::vpn::flows::FlowTable;
 use ya_utils_networking::vpn::network::DuoEndpoint;
-use ya_utils_networking::vpn::{common::ntoh, Error as NetError, PeekPacket};
 use ya_utils_networking::vpn::{ArpField, ArpPacket, EtherFrame, EtherType, IpPacket, Networks};
+use ya_utils_networking::vpn::{Error as NetError, PeekPacket};
 
-use crate::acl::Acl;
+use crate::acl::{AccessRole, Acl, Error as AclError};
+use crate::dns::DNS_PORT;
 use crate::error::Error;
 use crate::message::Shutdown;
-use crate::network::{self, Endpoint};
+use crate::network::{self, dns, Endpoint, CAPTURE_VPN};
 use crate::state::Deployment;
 
 pub(crate) async fn start_vpn<R: RuntimeService>(
@@ -25,6 +34,7 @@ pub(crate) async fn start_vpn<R: RuntimeService>(
     acl: Acl,
     service: &R,
     deployment: &Deployment,
+    capture: Capture,
 ) -> crate::Result<Option<Addr<Vpn>>> {
     if !deployment.networking() {
         return Ok(None);
@@ -50,6 +60,10 @@ pub(crate) async fn start_vpn<R: RuntimeService>(
             networks,
             hosts: deployment.hosts.clone(),
             interface: NetworkInterface::Vpn as i32,
+            nameservers: nameservers(deployment)
+                .into_iter()
+                .map(|ip| ip.to_string())
+                .collect(),
         })
         .await
         .map_err(|e| Error::Other(format!("initialization error: {:?}", e)))?;
@@ -67,17 +81,35 @@ pub(crate) async fn start_vpn<R: RuntimeService>(
         }
     };
 
-    let vpn = Vpn::try_new(node_id, acl, endpoint, deployment.clone())?;
+    let vpn = Vpn::try_new(node_id, acl, endpoint, deployment.clone(), capture)?;
     Ok(Some(vpn.start()))
 }
 
+/// VPN nameservers use the gateway address of each network
+fn nameservers(deployment: &Deployment) -> HashSet<Ipv4Addr> {
+    // FIXME: IPv6 support
+    deployment
+        .networks
+        .values()
+        .filter_map(|net| match net.gateway() {
+            Some(IpAddr::V4(ip)) => Some(ip),
+            _ => None,
+        })
+        .collect()
+}
+
 pub(crate) struct Vpn {
     default_id: String,
-    // TODO: Populate & utilize ACL
-    #[allow(unused)]
     acl: Acl,
     networks: Networks<DuoEndpoint<GsbEndpoint>>,
+    /// Firewall rules set by the requestor, per network id
+    firewalls: HashMap<String, VpnFirewall>,
+    /// Flows accepted by the firewalls, whose replies are allowed
+    flows: FlowTable,
+    hosts: dns::Hosts,
+    nameservers: HashSet<Ipv4Addr>,
     endpoint: Endpoint,
+    capture: Capture,
 }
 
 impl Vpn {
@@ -86,8 +118,11 @@ impl Vpn {
         acl: Acl,
         endpoint: Endpoint,
         deployment: Deployment,
+        capture: Capture,
     ) -> crate::Result<Self> {
         let mut networks = Networks::default();
+        let hosts = dns::Hosts::new(&deployment.hosts);
+        let nameservers = nameservers(&deployment);
 
         deployment
             .networks
@@ -106,10 +141,56 @@ impl Vpn {
             default_id: node_id.to_string(),
             acl,
             networks,
+            firewalls: Default::default(),
+            flows: Default::default(),
+            hosts,
+            nameservers,
             endpoint,
+            capture,
         })
     }
 
+    /// Answers DNS queries sent to the nameservers, and ARP requests for nameserver
+    /// addresses not used by any node. Returns `false` for other frames.
+    fn handle_nameserver(&self, frame: &[u8]) -> bool {
+        let reply = match IpFlow::from_frame(frame) {
+            Some(flow) => match (flow.dst, flow.ports) {
+                (IpAddr::V4(ip), Some((_, DNS_PORT)))
+                    if self.nameservers.contains(&ip)
+                        && VpnProtocol::Udp.matches(flow.protocol) =>
+                {
+                    let network_id = self
+                        .networks
+                        .as_ref()
+                        .values()
+                        .find(|network| network.as_ref().contains(&flow.src))
+                        .map(|network| network.id().as_str());
+                    dns::reply(frame, &self.hosts, network_id)
+                }
+                _ => return false,
+            },
+            None => {
+                let reply = self
+                    .nameservers
+                    .iter()
+                    .filter(|ip| self.networks.endpoint(ip.octets()).is_none())
+                    .find_map(|ip| dns::arp_reply(frame, *ip));
+                match reply {
+                    Some(reply) => Some(reply),
+                    None => return false,
+                }
+            }
+        };
+
+        if let Some(reply) = reply {
+            self.capture.record(CAPTURE_VPN, Direction::Inbound, &reply);
+            if let Err(e) = self.endpoint.send(Ok(reply)) {
+                log::debug!("[vpn] nameserver reply error: {e}");
+            }
+        }
+        true
+    }
+
     fn handle_packet(
         &mut self,
         packet: Packet,
@@ -119,6 +200,34 @@ impl Vpn {
         let node_id = packet.caller;
         let data = packet.data;
 
+        if let Some(firewall) = self.firewalls.get(&network_id) {
+            if let Some(flow) = IpFlow::from_frame(&data) {
+                let now = Instant::now();
+                if firewall.allows(&node_id, &self.default_id, flow.protocol, flow.ports) {
+                    self.flows.insert(flow, now);
+                } else {
+                    // replies are only accepted from the address of the calling node
+                    let src = self
+                        .networks
+                        .as_ref()
+                        .get(&network_id)
+                        .and_then(|network| network.node_id(&flow.src));
+                    let reply = match src {
+                        Some(src) if src.eq_ignore_ascii_case(&node_id) => {
+                            self.flows.is_reply(&flow, now)
+                        }
+                        _ => false,
+                    };
+                    if !reply {
+                        log::trace!(
+                            "[vpn] firewall: dropping ingress packet from {node_id}: {flow:?}"
+                        );
+                        return Ok(());
+                    }
+                }
+            }
+        }
+
         // fixme: should requestor be queried for unknown IP addresses instead?
         // read and add unknown node id -> ip if it doesn't exist
         if let Ok(ether_type) = EtherFrame::peek_type(&data) {
@@ -145,6 +254,7 @@ impl Vpn {
             }
         }
 
+        self.capture.record(CAPTURE_VPN, Direction::Inbound, &data);
         if let Err(e) = self.endpoint.send(Ok(data)) {
             log::debug!("[vpn] ingress error: {}", e);
         }
@@ -155,6 +265,8 @@ impl Vpn {
     fn handle_ip(
         frame: EtherFrame,
         networks: &Networks<DuoEndpoint<GsbEndpoint>>,
+        firewalls: &HashMap<String, VpnFirewall>,
+        flows: &mut FlowTable,
         default_id: &str,
     ) {
         let ip_pkt = IpPacket::packet(frame.payload());
@@ -170,6 +282,10 @@ impl Vpn {
                 future::join_all(futs).then(|_| future::ready(())).await;
             });
         } else {
+            if !Self::egress_allowed(&frame, networks, firewalls, flows, default_id) {
+                return;
+            }
+
             let ip = ip_pkt.dst_address();
             match networks.endpoint(ip) {
                 Some(endpoint) => Self::forward_frame(endpoint, default_id, frame),
@@ -178,6 +294,46 @@ impl Vpn {
         }
     }
 
+    fn egress_allowed(
+        frame: &EtherFrame,
+        networks: &Networks<DuoEndpoint<GsbEndpoint>>,
+        firewalls: &HashMap<String, VpnFirewall>,
+        flows: &mut FlowTable,
+        default_id: &str,
+    ) -> bool {
+        let flow = match IpFlow::from_frame(frame.as_ref()) {
+            Some(flow) => flow,
+            None => return true,
+        };
+
+        let allowed = networks
+            .as_ref()
+            .iter()
+            .filter(|(_, network)| network.as_ref().contains(&flow.dst))
+            .all(
+                |(id, network)| match (firewalls.get(id), network.node_id(&flow.dst)) {
+                    (Some(firewall), Some(dst)) => {
+                        firewall.allows(default_id, dst, flow.protocol, flow.ports)
+                    }
+                    _ => true,
+                },
+            );
+
+        let now = Instant::now();
+        if allowed {
+            if !firewalls.is_empty() {
+                flows.insert(flow, now);
+            }
+            return true;
+        }
+        if flows.is_reply(&flow, now) {
+            return true;
+        }
+
+        log::trace!("[vpn] firewall: dropping egress packet: {flow:?}");
+        false
+    }
+
     fn handle_arp(
         frame: EtherFrame,
         networks: &Networks<DuoEndpoint<GsbEndpoint>>,
@@ -280,10 +436,23 @@ impl StreamHandler<crate::Result<Vec<u8>>> for Vpn {
             ya_packet_trace::try_extract_from_ip_frame(&packet)
         });
 
+        self.capture
+            .record(CAPTURE_VPN, Direction::Outbound, &packet);
+
+        if self.handle_nameserver(&packet) {
+            return;
+        }
+
         match EtherFrame::try_from(packet) {
             Ok(frame) => match &frame {
                 EtherFrame::Arp(_) => Self::handle_arp(frame, &self.networks, &self.default_id),
-                EtherFrame::Ip(_) => Self::handle_ip(frame, &self.networks, &self.default_id),
+                EtherFrame::Ip(_) => Self::handle_ip(
+                    frame,
+                    &self.networks,
+                    &self.firewalls,
+                    &mut self.flows,
+                    &self.default_id,
+                ),
                 frame => log::debug!("[vpn] unimplemented EtherType: {}", frame),
             },
             Err(err) => {
@@ -343,9 +512,10 @@ impl Handler<RpcEnvelope<VpnControl>> for Vpn {
     type Result = <RpcEnvelope<VpnControl> as Message>::Result;
 
     fn handle(&mut self, msg: RpcEnvelope<VpnControl>, _: &mut Context<Self>) -> Self::Result {
-        // if !self.acl.has_access(msg.caller(), AccessRole::Control) {
-        //     return Err(AclError::Forbidden(msg.caller().to_string(), AccessRole::Control).into());
-        // }
+        if !self.acl.has_access(msg.caller(), AccessRole::Control) {
+            let err = AclError::Forbidden(msg.caller().to_string(), AccessRole::Control);
+            return Err(Error::from(err).into());
+        }
 
         match msg.into_inner() {
             VpnControl::AddNodes { network_id, nodes } => {
@@ -363,6 +533,29 @@ impl Handler<RpcEnvelope<VpnControl>> for Vpn {
                 let network = self.networks.get_mut(&network_id).map_err(Error::from)?;
                 node_ids.into_iter().for_each(|id| network.remove_node(&id));
             }
+            VpnControl::SetFirewall {
+                network_id,
+                firewall,
+            } => {
+                self.networks.get_mut(&network_id).map_err(Error::from)?;
+                firewall.validate().map_err(Error::Other)?;
+                log::info!(
+                    "[vpn] network {network_id}: {} firewall rules set",
+                    firewall.rules.len()
+                );
+                self.firewalls.insert(network_id, firewall);
+                self.flows.clear();
+            }
+            VpnControl::SetHosts { network_id, hosts } => {
+                self.networks.get_mut(&network_id).map_err(Error::from)?;
+                let hosts = hosts
+                    .into_iter()
+                    .map(|(name, ip)| Ok((name, ip.parse::<IpAddr>()?)))
+                    .collect::<Result<_, std::net::AddrParseError>>()
+                    .map_err(|e| Error::Other(format!("invalid host address: {e}")))?;
+                log::info!("[vpn] network {network_id}: hostnames updated");
+                self.hosts.set(network_id, hosts);
+            }
         }
         Ok(())
     }
@@ -15,6 +15,7 @@ use ya_agreement_utils::agreement::OfferTemplate;
 use ya_client_model::activity::{CommandOutput, ExeScriptCommand};
 use ya_manifest_utils::Feature;
 use ya_runtime_api::server::{spawn, RunProcess, RuntimeControl, RuntimeService};
+use ya_utils_networking::vpn::capture::Capture;
 
 use crate::acl::Acl;
 use crate::error::Error;
@@ -355,13 +356,17 @@ impl RuntimeProcess {
                         endpoint,
                         &service_,
                         rt_ctx.manifest.validator::<UrlValidator>(),
+                        rt_ctx.capture.clone(),
                     )
                     .await?;
                     address.send(SetInetService(inet)).await?;
                 }
 
                 if let Some(endpoint) = vpn_endpoint {
-                    if let Some(vpn) = start_vpn(endpoint, acl, &service_, &deployment).await? {
+                    let capture = rt_ctx.capture.clone();
+                    if let Some(vpn) =
+                        start_vpn(endpoint, acl, &service_, &deployment, capture).await?
+                    {
                         address.send(SetVpnService(vpn)).await?;
                     }
                 }
@@ -572,6 +577,7 @@ struct RuntimeProcessContext {
     supervise_hardware: bool,
     infrastructure: HashMap<String, f64>,
     manifest: ManifestContext,
+    capture: Capture,
 }
 
 impl<'a> From<&'a ExeUnitContext> for RuntimeProcessContext {
@@ -583,6 +589,7 @@ impl<'a> From<&'a ExeUnitContext> for RuntimeProcessContext {
             supervise_hardware: ctx.supervise.hardware,
             infrastructure: ctx.agreement.infrastructure.clone(),
             manifest: ctx.supervise.manifest.clone(),
+            capture: ctx.capture.clone(),
         }
     }
 }
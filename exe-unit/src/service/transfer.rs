@@ -5,7 +5,8 @@ use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use actix::prelude::*;
-use futures::future::Abortable;
+use futures::future::{Abortable, LocalBoxFuture};
+use futures::FutureExt;
 use url::Url;
 
 use crate::deploy::ContainerVolume;
@@ -142,6 +143,22 @@ impl TransferProvider<TransferData, TransferError> for ContainerTransferProvider
         }
         self.file_tp.destination(&file_url, ctx)
     }
+
+    fn prepare_destination<'a>(
+        &self,
+        url: &Url,
+        ctx: &TransferContext,
+    ) -> LocalBoxFuture<'a, std::result::Result<(), TransferError>> {
+        if ctx.args.format.is_none() {
+            ctx.state.set_offset(0);
+            return futures::future::ok(()).boxed_local();
+        }
+
+        match self.resolve_url(url.path_decoded().as_str()) {
+            Ok(file_url) => self.dir_tp.prepare_destination(&file_url, ctx),
+            Err(e) => futures::future::err(e).boxed_local(),
+        }
+    }
 }
 
 /// Handles resources transfers.
@@ -155,9 +172,19 @@ pub struct TransferService {
 
 impl TransferService {
     pub fn new(ctx: &ExeUnitContext) -> TransferService {
+        let cache = Cache::new(ctx.cache_dir.clone());
+        let mut providers = Self::default_providers();
+
+        let http = HttpTransferProvider::default()
+            .with_ranged_download(Some(RangedDownload::new(cache.partial_dir())));
+        let http: Rc<dyn TransferProvider<TransferData, TransferError>> = Rc::new(http);
+        for scheme in http.schemes() {
+            providers.insert(scheme, http.clone());
+        }
+
         TransferService {
-            providers: Self::default_providers(),
-            cache: Cache::new(ctx.cache_dir.clone()),
+            providers,
+            cache,
             work_dir: ctx.work_dir.clone(),
             task_package: ctx.agreement.task_package.clone(),
             abort_handles: Default::default(),
@@ -174,6 +201,20 @@ impl TransferService {
             .collect()
     }
 
+    pub fn formats() -> Vec<String> {
+        ArchiveFormat::names()
+            .into_iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    pub fn sync_modes() -> Vec<String> {
+        SyncMode::names()
+            .into_iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
     fn default_providers(
     ) -> HashMap<&'static str, Rc<dyn TransferProvider<TransferData, TransferError>>> {
         let mut providers = HashMap::new();
@@ -181,6 +222,7 @@ impl TransferService {
         let provider_vec: Vec<Rc<dyn TransferProvider<TransferData, TransferError>>> = vec![
             Rc::new(GftpTransferProvider::default()),
             Rc::new(HttpTransferProvider::default()),
+            Rc::new(S3TransferProvider::default()),
         ];
         for provider in provider_vec {
             for scheme in provider.schemes() {
@@ -317,6 +359,14 @@ impl Handler<TransferResource> for TransferService {
         let src = actor_try!(self.provider(&src_url));
         let dst = actor_try!(self.provider(&dst_url));
 
+        // remote destinations cannot be inspected, the last synchronized state is used instead
+        let manifest_path = match SyncMode::try_from(&msg.args) {
+            Ok(_) if !matches!(dst_url.url.scheme(), "container" | "file") => {
+                Some(self.cache.to_manifest_path(&dst_url.url))
+            }
+            _ => None,
+        };
+
         let (abort, reg) = Abort::new_pair();
 
         let handles = self.abort_handles.clone();
@@ -324,12 +374,19 @@ impl Handler<TransferResource> for TransferService {
             log::info!("Transferring {:?} to {:?}", src_url.url, dst_url.url);
             {
                 let ctx = TransferContext::from(msg.args);
+                if let Some(path) = manifest_path.as_ref() {
+                    ctx.state.set_base_manifest(Manifest::load(path).ok());
+                }
                 let retry = transfer_with(src, &src_url, dst, &dst_url, &ctx);
 
                 let _guard = AbortHandleGuard::register(handles, abort);
                 Abortable::new(retry, reg)
                     .await
                     .map_err(TransferError::from)??;
+
+                if let (Some(path), Some(manifest)) = (manifest_path, ctx.state.manifest()) {
+                    manifest.save(&path)?;
+                }
             }
             log::info!(
                 "Transfer of {:?} to {:?} finished",
@@ -395,6 +395,14 @@ pub(crate) struct DeploymentNetwork {
     pub nodes: HashMap<IpAddr, String>,
 }
 
+impl DeploymentNetwork {
+    /// First host address of the network
+    pub fn gateway(&self) -> Option<IpAddr> {
+        let addr = self.network.addr();
+        self.network.hosts().find(|ip| ip != &addr)
+    }
+}
+
 impl Deployment {
     pub fn networking(&self) -> bool {
         !self.networks.is_empty()
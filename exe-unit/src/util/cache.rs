@@ -3,12 +3,12 @@ use sha3::Digest;
 use std::convert::TryFrom;
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
 use std::path::{Component, PathBuf};
+use url::Url;
 use ya_transfer::TransferUrl;
 
 #[derive(Debug, Clone)]
 pub(crate) struct Cache {
     dir: PathBuf,
-    #[allow(dead_code)]
     tmp_dir: PathBuf,
 }
 
@@ -25,6 +25,7 @@ impl Cache {
             Some(hash) => hash,
             None => return Err(TransferError::InvalidUrlError("hash required".to_owned())),
         };
+        hash.validate()?;
 
         let name = transfer_url.file_name()?;
         let location_hash = {
@@ -42,6 +43,17 @@ impl Cache {
         ProjectedPath::local(self.tmp_dir.clone(), path.temp_path())
     }
 
+    /// Directory for partially downloaded resources
+    pub fn partial_dir(&self) -> PathBuf {
+        self.tmp_dir.join("partial")
+    }
+
+    /// Location of the last manifest synchronized to `url`
+    pub fn to_manifest_path(&self, url: &Url) -> PathBuf {
+        let name = hex::encode(sha3::Sha3_224::digest(url.as_str().as_bytes()));
+        self.dir.join("sync").join(format!("{}.manifest", name))
+    }
+
     #[inline(always)]
     pub fn to_final_path(&self, path: &CachePath) -> ProjectedPath {
         ProjectedPath::local(self.dir.clone(), path.final_path())
@@ -20,24 +20,52 @@ pub async fn resolve_yagna_srv_record(prefix: &str) -> std::io::Result<String> {
     .await
 }
 
+/// Resolves prefixes in the `DEFAULT_LOOKUP_DOMAIN`, see also `resolve_srv_records`
+pub async fn resolve_yagna_srv_records(prefix: &str) -> std::io::Result<Vec<String>> {
+    resolve_srv_records(&format!(
+        "{}.{}",
+        prefix.trim_end_matches('.'),
+        DEFAULT_LOOKUP_DOMAIN
+    ))
+    .await
+}
+
 /// Performs lookup of the Service Record (SRV) in the Domain Name System
 /// If successful responds in the format of `hostname:port`
 pub async fn resolve_srv_record(record: &str) -> std::io::Result<String> {
+    resolve_srv_records(record)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| IoError::from(IoErrorKind::NotFound))
+}
+
+/// Performs lookup of all Service Records (SRV) in the Domain Name System
+/// Responds with `hostname:port` entries, ordered by priority and weight
+pub async fn resolve_srv_records(record: &str) -> std::io::Result<Vec<String>> {
     let resolver: TokioAsyncResolver =
         TokioAsyncResolver::tokio(ResolverConfig::google(), ResolverOpts::default())?;
     let lookup = resolver.srv_lookup(record).await?;
-    let srv = lookup
-        .iter()
-        .next()
-        .ok_or_else(|| IoError::from(IoErrorKind::NotFound))?;
-    let addr = format!(
-        "{}:{}",
-        srv.target().to_string().trim_end_matches('.'),
-        srv.port()
-    );
 
-    log::debug!("Resolved address: {}", addr);
-    Ok(addr)
+    let mut records = lookup.iter().collect::<Vec<_>>();
+    records.sort_by_key(|srv| (srv.priority(), std::cmp::Reverse(srv.weight())));
+
+    let addrs = records
+        .into_iter()
+        .map(|srv| {
+            format!(
+                "{}:{}",
+                srv.target().to_string().trim_end_matches('.'),
+                srv.port()
+            )
+        })
+        .collect::<Vec<_>>();
+    if addrs.is_empty() {
+        return Err(IoError::from(IoErrorKind::NotFound));
+    }
+
+    log::debug!("Resolved addresses: {:?}", addrs);
+    Ok(addrs)
 }
 
 /// Replace domain name in URL with resolved IP address
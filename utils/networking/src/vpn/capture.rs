@@ -0,0 +1,495 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+pub const DEFAULT_MAX_FILES: usize = 8;
+/// Total size of kept capture files, regardless of the requested file size and count
+pub const MAX_CAPTURE_SIZE: u64 = 1024 * 1024 * 1024;
+/// Number of frames waiting to be written. Frames are dropped when the writer falls behind.
+const QUEUE_SIZE: usize = 4096;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const LINKTYPE_ETHERNET: u16 = 1;
+const OPT_END: u16 = 0;
+const OPT_IF_NAME: u16 = 2;
+const OPT_EPB_FLAGS: u16 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+#[derive(Clone, Debug)]
+pub struct CaptureConfig {
+    pub dir: PathBuf,
+    /// Prefix of capture file names
+    pub name: String,
+    /// File size, after which the capture continues in a new file
+    pub max_file_size: u64,
+    /// Number of kept files. The oldest ones are removed.
+    pub max_files: usize,
+}
+
+/// Ethernet frame capture, shared by network handlers. Inactive until started.
+/// Frames are written to files on a separate thread, so that recording never waits for IO.
+#[derive(Clone, Default)]
+pub struct Capture {
+    inner: Arc<RwLock<Option<CaptureTask>>>,
+}
+
+impl Capture {
+    /// Starts writing frames to pcapng files, with an interface per each name in `interfaces`.
+    /// Replaces the ongoing capture.
+    pub fn start(&self, config: CaptureConfig, interfaces: &[&str]) -> io::Result<PathBuf> {
+        let dir = config.dir.clone();
+        let writer = PcapWriter::create(config, interfaces)?;
+        let task = CaptureTask::spawn(writer)?;
+
+        let previous = self.write().replace(task);
+        if let Some(previous) = previous {
+            previous.finish()?;
+        }
+        Ok(dir)
+    }
+
+    /// Stops the capture, returning paths of the files kept
+    pub fn stop(&self) -> io::Result<Vec<PathBuf>> {
+        let task = self.write().take();
+        match task {
+            Some(task) => task.finish(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        match self.read().as_ref() {
+            Some(task) => !task.handle.is_finished(),
+            None => false,
+        }
+    }
+
+    /// Queues a frame seen on interface `interface` (index in `start`) for writing.
+    /// The capture is stopped on write errors.
+    pub fn record(&self, interface: u32, direction: Direction, frame: &[u8]) {
+        if let Some(task) = self.read().as_ref() {
+            let frame = Frame {
+                interface,
+                direction,
+                time: SystemTime::now(),
+                data: frame.to_vec(),
+            };
+            if let Err(TrySendError::Full(_)) = task.tx.try_send(frame) {
+                log::trace!("Packet capture queue is full, frame dropped");
+            }
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Option<CaptureTask>> {
+        match self.inner.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Option<CaptureTask>> {
+        match self.inner.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+struct Frame {
+    interface: u32,
+    direction: Direction,
+    time: SystemTime,
+    data: Vec<u8>,
+}
+
+/// Writer thread of an ongoing capture
+struct CaptureTask {
+    tx: SyncSender<Frame>,
+    handle: JoinHandle<io::Result<Vec<PathBuf>>>,
+}
+
+impl CaptureTask {
+    fn spawn(mut writer: PcapWriter) -> io::Result<Self> {
+        let (tx, rx) = mpsc::sync_channel::<Frame>(QUEUE_SIZE);
+        let handle = thread::Builder::new()
+            .name("packet-capture".to_string())
+            .spawn(move || {
+                for frame in rx {
+                    let result =
+                        writer.write(frame.interface, frame.direction, frame.time, &frame.data);
+                    if let Err(e) = result {
+                        log::warn!("Packet capture stopped: {e}");
+                        return Err(e);
+                    }
+                }
+                writer.finish()
+            })?;
+        Ok(Self { tx, handle })
+    }
+
+    /// Writes the queued frames and waits for the files to be closed
+    fn finish(self) -> io::Result<Vec<PathBuf>> {
+        drop(self.tx);
+        match self.handle.join() {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::other("packet capture writer panicked")),
+        }
+    }
+}
+
+/// Writes pcapng files with size based rotation
+pub struct PcapWriter {
+    config: CaptureConfig,
+    /// Section and interface description blocks, starting each file
+    header: Vec<u8>,
+    started: u64,
+    /// Total size of kept files
+    max_size: u64,
+    path: PathBuf,
+    file: BufWriter<File>,
+    file_size: u64,
+    file_index: usize,
+    /// Previous files with their sizes, oldest first
+    files: VecDeque<(PathBuf, u64)>,
+}
+
+impl PcapWriter {
+    pub fn create(mut config: CaptureConfig, interfaces: &[&str]) -> io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+        config.max_file_size = config.max_file_size.min(MAX_CAPTURE_SIZE);
+
+        let mut header = section_header();
+        interfaces
+            .iter()
+            .for_each(|name| header.extend(interface_description(name)));
+
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (path, file) = Self::open(&config, &header, started, 0)?;
+
+        Ok(Self {
+            config,
+            file_size: header.len() as u64,
+            header,
+            started,
+            max_size: MAX_CAPTURE_SIZE,
+            path,
+            file,
+            file_index: 0,
+            files: Default::default(),
+        })
+    }
+
+    pub fn write(
+        &mut self,
+        interface: u32,
+        direction: Direction,
+        time: SystemTime,
+        frame: &[u8],
+    ) -> io::Result<()> {
+        let block = enhanced_packet(interface, direction, time, frame);
+        let size = block.len() as u64;
+
+        if self.file_size + size > self.config.max_file_size
+            && self.file_size > self.header.len() as u64
+        {
+            self.rotate()?;
+        }
+
+        self.file.write_all(&block)?;
+        self.file_size += size;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+        self.file.flush()?;
+        let mut files: Vec<_> = self.files.into_iter().map(|(path, _)| path).collect();
+        files.push(self.path);
+        Ok(files)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        self.file_index += 1;
+        let (path, file) = Self::open(&self.config, &self.header, self.started, self.file_index)?;
+        let previous = std::mem::replace(&mut self.path, path);
+        self.files.push_back((previous, self.file_size));
+        self.file = file;
+        self.file_size = self.header.len() as u64;
+
+        // make room for the new file, which may grow up to the maximum file size
+        let max_files = self.config.max_files.max(1);
+        while self.files.len() + 1 > max_files
+            || self.kept_size() + self.config.max_file_size > self.max_size
+        {
+            let path = match self.files.pop_front() {
+                Some((path, _)) => path,
+                None => break,
+            };
+            if let Err(e) = fs::remove_file(&path) {
+                log::debug!("Unable to remove capture file {}: {e}", path.display());
+            }
+        }
+        Ok(())
+    }
+
+    fn kept_size(&self) -> u64 {
+        self.files.iter().map(|(_, size)| size).sum()
+    }
+
+    fn open(
+        config: &CaptureConfig,
+        header: &[u8],
+        started: u64,
+        index: usize,
+    ) -> io::Result<(PathBuf, BufWriter<File>)> {
+        let path = config
+            .dir
+            .join(format!("{}-{started}-{index:04}.pcapng", config.name));
+        let mut file = BufWriter::new(File::create(&path)?);
+        file.write_all(header)?;
+        Ok((path, file))
+    }
+}
+
+fn section_header() -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+    body.extend(1u16.to_le_bytes());
+    body.extend(0u16.to_le_bytes());
+    // unspecified section length
+    body.extend((-1i64).to_le_bytes());
+    block(SECTION_HEADER_BLOCK, body)
+}
+
+fn interface_description(name: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend(LINKTYPE_ETHERNET.to_le_bytes());
+    body.extend(0u16.to_le_bytes());
+    // unlimited snapshot length
+    body.extend(0u32.to_le_bytes());
+    option(&mut body, OPT_IF_NAME, name.as_bytes());
+    option(&mut body, OPT_END, &[]);
+    block(INTERFACE_DESCRIPTION_BLOCK, body)
+}
+
+fn enhanced_packet(
+    interface: u32,
+    direction: Direction,
+    time: SystemTime,
+    frame: &[u8],
+) -> Vec<u8> {
+    // default timestamp resolution is 1 µs
+    let ts = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+    let flags: u32 = match direction {
+        Direction::Inbound => 0b01,
+        Direction::Outbound => 0b10,
+    };
+
+    let mut body = Vec::with_capacity(frame.len() + 40);
+    body.extend(interface.to_le_bytes());
+    body.extend(((ts >> 32) as u32).to_le_bytes());
+    body.extend((ts as u32).to_le_bytes());
+    body.extend((frame.len() as u32).to_le_bytes());
+    body.extend((frame.len() as u32).to_le_bytes());
+    body.extend_from_slice(frame);
+    pad(&mut body);
+    option(&mut body, OPT_EPB_FLAGS, &flags.to_le_bytes());
+    option(&mut body, OPT_END, &[]);
+    block(ENHANCED_PACKET_BLOCK, body)
+}
+
+fn block(block_type: u32, body: Vec<u8>) -> Vec<u8> {
+    let len = (body.len() + 12) as u32;
+    let mut block = Vec::with_capacity(len as usize);
+    block.extend(block_type.to_le_bytes());
+    block.extend(len.to_le_bytes());
+    block.extend(body);
+    block.extend(len.to_le_bytes());
+    block
+}
+
+fn option(buf: &mut Vec<u8>, code: u16, value: &[u8]) {
+    buf.extend(code.to_le_bytes());
+    buf.extend((value.len() as u16).to_le_bytes());
+    buf.extend_from_slice(value);
+    pad(buf);
+}
+
+fn pad(buf: &mut Vec<u8>) {
+    buf.resize((buf.len() + 3) / 4 * 4, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn u16_at(buf: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([buf[offset], buf[offset + 1]])
+    }
+
+    fn u32_at(buf: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([
+            buf[offset],
+            buf[offset + 1],
+            buf[offset + 2],
+            buf[offset + 3],
+        ])
+    }
+
+    fn assert_block(block: &[u8], block_type: u32) {
+        assert_eq!(block.len() % 4, 0);
+        assert_eq!(u32_at(block, 0), block_type);
+        assert_eq!(u32_at(block, 4) as usize, block.len());
+        assert_eq!(u32_at(block, block.len() - 4) as usize, block.len());
+    }
+
+    fn config(name: &str, max_file_size: u64, max_files: usize) -> CaptureConfig {
+        let dir = std::env::temp_dir().join(format!("ya-capture-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        CaptureConfig {
+            dir,
+            name: name.to_string(),
+            max_file_size,
+            max_files,
+        }
+    }
+
+    #[test]
+    fn block_layout() {
+        let header = section_header();
+        assert_block(&header, SECTION_HEADER_BLOCK);
+        assert_eq!(u32_at(&header, 8), BYTE_ORDER_MAGIC);
+        assert_eq!(u16_at(&header, 12), 1);
+
+        let interface = interface_description("vpn");
+        assert_block(&interface, INTERFACE_DESCRIPTION_BLOCK);
+        assert_eq!(u16_at(&interface, 8), LINKTYPE_ETHERNET);
+        assert_eq!(u16_at(&interface, 16), OPT_IF_NAME);
+        assert_eq!(u16_at(&interface, 18), 3);
+        assert_eq!(&interface[20..24], b"vpn\0");
+        assert_eq!(u32_at(&interface, 24), OPT_END as u32);
+
+        let time = UNIX_EPOCH + Duration::from_micros((1 << 32) + 2);
+        let packet = enhanced_packet(1, Direction::Outbound, time, &[0xff; 5]);
+        assert_block(&packet, ENHANCED_PACKET_BLOCK);
+        assert_eq!(packet.len(), 52);
+        assert_eq!(u32_at(&packet, 8), 1);
+        assert_eq!(u32_at(&packet, 12), 1);
+        assert_eq!(u32_at(&packet, 16), 2);
+        assert_eq!(u32_at(&packet, 20), 5);
+        assert_eq!(u32_at(&packet, 24), 5);
+        assert_eq!(&packet[28..36], &[0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0]);
+        assert_eq!(u16_at(&packet, 36), OPT_EPB_FLAGS);
+        assert_eq!(u32_at(&packet, 40), 0b10);
+    }
+
+    #[test]
+    fn rotate_files() {
+        let header_size = (section_header().len() + interface_description("vpn").len()) as u64;
+        let packet_size = enhanced_packet(0, Direction::Inbound, UNIX_EPOCH, &[0; 60]).len() as u64;
+        let config = config("rotate", header_size + 2 * packet_size, 2);
+        let dir = config.dir.clone();
+
+        let mut writer = PcapWriter::create(config, &["vpn"]).unwrap();
+        for _ in 0..7 {
+            writer
+                .write(0, Direction::Inbound, SystemTime::now(), &[0; 60])
+                .unwrap();
+        }
+        let files = writer.finish().unwrap();
+
+        assert_eq!(files.len(), 2);
+        assert!(files[0].to_str().unwrap().ends_with("-0002.pcapng"));
+        assert!(files[1].to_str().unwrap().ends_with("-0003.pcapng"));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        assert_eq!(
+            fs::metadata(&files[0]).unwrap().len(),
+            header_size + 2 * packet_size
+        );
+        assert_eq!(
+            fs::metadata(&files[1]).unwrap().len(),
+            header_size + packet_size
+        );
+        assert!(fs::read(&files[1]).unwrap().starts_with(&section_header()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn limit_capture_size() {
+        let header_size = (section_header().len() + interface_description("vpn").len()) as u64;
+        let packet_size = enhanced_packet(0, Direction::Inbound, UNIX_EPOCH, &[0; 60]).len() as u64;
+        let max_file_size = header_size + 2 * packet_size;
+        let config = config("limit", max_file_size, DEFAULT_MAX_FILES);
+        let dir = config.dir.clone();
+
+        let mut writer = PcapWriter::create(config, &["vpn"]).unwrap();
+        writer.max_size = 3 * max_file_size;
+        for _ in 0..20 {
+            writer
+                .write(0, Direction::Inbound, SystemTime::now(), &[0; 60])
+                .unwrap();
+        }
+        let files = writer.finish().unwrap();
+
+        assert_eq!(files.len(), 3);
+        let size: u64 = files.iter().map(|f| fs::metadata(f).unwrap().len()).sum();
+        assert!(size <= 3 * max_file_size);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn record_frames() {
+        let config = config("record", DEFAULT_MAX_FILE_SIZE, DEFAULT_MAX_FILES);
+        let dir = config.dir.clone();
+        let capture = Capture::default();
+
+        capture.record(0, Direction::Inbound, &[1; 60]);
+        assert!(!capture.is_active());
+
+        capture.start(config, &["vpn", "inet"]).unwrap();
+        assert!(capture.is_active());
+        for _ in 0..10 {
+            capture.record(0, Direction::Inbound, &[1; 60]);
+            capture.record(1, Direction::Outbound, &[2; 60]);
+        }
+        let files = capture.stop().unwrap();
+        assert!(!capture.is_active());
+
+        let header_size = section_header().len()
+            + interface_description("vpn").len()
+            + interface_description("inet").len();
+        let packet_size = enhanced_packet(0, Direction::Inbound, UNIX_EPOCH, &[0; 60]).len();
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            fs::metadata(&files[0]).unwrap().len() as usize,
+            header_size + 20 * packet_size
+        );
+        assert!(capture.stop().unwrap().is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,10 +1,11 @@
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 
 use ipnet::IpNet;
 
-use ya_relay_stack::Error;
+use ya_relay_stack::packet::{EtherFrame, EtherType, IpPacket, PeekPacket, TcpPacket, UdpPacket};
+use ya_relay_stack::{Error, Protocol};
 
 pub const DEFAULT_MAX_FRAME_SIZE: usize = 1500;
 pub const DEFAULT_IPV4_NET_MASK: &str = "255.255.255.0";
@@ -72,3 +73,55 @@ pub fn to_net<S: AsRef<str>>(ip: &str, mask: Option<S>) -> Result<IpNet, Error>
     };
     result.map_err(|_| Error::NetAddr(ip.to_string()))
 }
+
+/// Addresses, transport protocol number and ports of an IP packet
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IpFlow {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub protocol: u8,
+    /// Source and destination ports of TCP and UDP packets
+    pub ports: Option<(u16, u16)>,
+}
+
+impl IpFlow {
+    /// Flow of packets sent in the opposite direction
+    pub fn reversed(&self) -> Self {
+        IpFlow {
+            src: self.dst,
+            dst: self.src,
+            protocol: self.protocol,
+            ports: self.ports.map(|(src, dst)| (dst, src)),
+        }
+    }
+
+    /// Reads the IP packet carried in an Ethernet frame. Returns `None` for other frames.
+    pub fn from_frame(frame: &[u8]) -> Option<Self> {
+        if !matches!(EtherFrame::peek_type(frame), Ok(EtherType::Ip)) {
+            return None;
+        }
+        let payload = EtherFrame::peek_payload(frame).ok()?;
+        IpPacket::peek(payload).ok()?;
+
+        let packet = IpPacket::packet(payload);
+        let protocol = packet.protocol();
+        let ports = match Protocol::try_from(protocol) {
+            Ok(Protocol::Tcp) => TcpPacket::peek(packet.payload()).ok().map(|_| {
+                let tcp = TcpPacket::packet(packet.payload());
+                (tcp.src_port(), tcp.dst_port())
+            }),
+            Ok(Protocol::Udp) => UdpPacket::peek(packet.payload()).ok().map(|_| {
+                let udp = UdpPacket::packet(packet.payload());
+                (udp.src_port(), udp.dst_port())
+            }),
+            _ => None,
+        };
+
+        Some(IpFlow {
+            src: ntoh(packet.src_address())?,
+            dst: ntoh(packet.dst_address())?,
+            protocol,
+            ports,
+        })
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use crate::vpn::common::IpFlow;
+use crate::vpn::Protocol;
+
+/// Idle time after which TCP flows are forgotten
+const TCP_FLOW_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// Idle time after which UDP and ICMP flows are forgotten
+const FLOW_TIMEOUT: Duration = Duration::from_secs(60);
+/// Maximum number of tracked flows. The least recently seen flow is evicted on overflow
+const MAX_FLOWS: usize = 16384;
+
+/// Flows accepted by a firewall. Packets sent in the opposite direction of a tracked flow
+/// are replies, accepted regardless of firewall rules.
+#[derive(Default)]
+pub struct FlowTable {
+    flows: HashMap<IpFlow, Instant>,
+}
+
+impl FlowTable {
+    /// Tracks a flow accepted by the firewall, or refreshes an already tracked one
+    pub fn insert(&mut self, flow: IpFlow, now: Instant) {
+        if self.flows.len() >= MAX_FLOWS && !self.flows.contains_key(&flow) {
+            self.remove_expired(now);
+            if self.flows.len() >= MAX_FLOWS {
+                self.remove_oldest();
+            }
+        }
+        self.flows.insert(flow, now);
+    }
+
+    /// Checks whether the packet is a reply within a tracked flow, refreshing the flow
+    pub fn is_reply(&mut self, flow: &IpFlow, now: Instant) -> bool {
+        let request = flow.reversed();
+        match self.flows.get_mut(&request) {
+            Some(seen) if now.saturating_duration_since(*seen) < timeout(&request) => {
+                *seen = now;
+                true
+            }
+            Some(_) => {
+                self.flows.remove(&request);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.flows.clear();
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        self.flows
+            .retain(|flow, seen| now.saturating_duration_since(*seen) < timeout(flow));
+    }
+
+    fn remove_oldest(&mut self) {
+        let oldest = self
+            .flows
+            .iter()
+            .min_by_key(|(_, seen)| **seen)
+            .map(|(flow, _)| *flow);
+        if let Some(flow) = oldest {
+            self.flows.remove(&flow);
+        }
+    }
+}
+
+fn timeout(flow: &IpFlow) -> Duration {
+    match Protocol::try_from(flow.protocol) {
+        Ok(Protocol::Tcp) => TCP_FLOW_TIMEOUT,
+        _ => FLOW_TIMEOUT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    const TCP: u8 = 6;
+    const UDP: u8 = 17;
+
+    fn flow(src: u8, dst: u8, protocol: u8, ports: (u16, u16)) -> IpFlow {
+        IpFlow {
+            src: IpAddr::V4(Ipv4Addr::new(10, 0, 0, src)),
+            dst: IpAddr::V4(Ipv4Addr::new(10, 0, 0, dst)),
+            protocol,
+            ports: Some(ports),
+        }
+    }
+
+    #[test]
+    fn track_replies() {
+        let mut flows = FlowTable::default();
+        let now = Instant::now();
+
+        flows.insert(flow(1, 2, TCP, (40000, 5432)), now);
+        assert!(flows.is_reply(&flow(2, 1, TCP, (5432, 40000)), now));
+        // not a reply: other port, protocol, or the same direction
+        assert!(!flows.is_reply(&flow(2, 1, TCP, (5433, 40000)), now));
+        assert!(!flows.is_reply(&flow(2, 1, UDP, (5432, 40000)), now));
+        assert!(!flows.is_reply(&flow(1, 2, TCP, (40000, 5432)), now));
+        assert!(!flows.is_reply(&flow(3, 1, TCP, (5432, 40000)), now));
+
+        // replies keep the flow alive
+        let later = now + Duration::from_secs(20 * 60);
+        assert!(flows.is_reply(&flow(2, 1, TCP, (5432, 40000)), later));
+        let later = later + Duration::from_secs(20 * 60);
+        assert!(flows.is_reply(&flow(2, 1, TCP, (5432, 40000)), later));
+
+        flows.insert(flow(1, 2, UDP, (40000, 53)), now);
+        let expired = now + FLOW_TIMEOUT;
+        assert!(!flows.is_reply(&flow(2, 1, UDP, (53, 40000)), expired));
+    }
+
+    #[test]
+    fn evict_oldest() {
+        let mut flows = FlowTable::default();
+        let now = Instant::now();
+
+        for port in 0..MAX_FLOWS as u16 {
+            let seen = now + Duration::from_millis(port as u64);
+            flows.insert(flow(1, 2, TCP, (port, 80)), seen);
+        }
+        let later = now + Duration::from_secs(1);
+        flows.insert(flow(1, 2, TCP, (u16::MAX, 80)), later);
+
+        assert_eq!(flows.flows.len(), MAX_FLOWS);
+        assert!(!flows.is_reply(&flow(2, 1, TCP, (80, 0)), later));
+        assert!(flows.is_reply(&flow(2, 1, TCP, (80, 1)), later));
+        assert!(flows.is_reply(&flow(2, 1, TCP, (80, u16::MAX)), later));
+    }
+}
@@ -1,4 +1,6 @@
+pub mod capture;
 pub mod common;
+pub mod flows;
 pub mod network;
 
 pub use network::{Network, Networks};
@@ -106,6 +106,14 @@ impl<E> Network<E> {
         &self.nodes
     }
 
+    /// Id of the node using the address
+    pub fn node_id(&self, ip: &IpAddr) -> Option<&str> {
+        self.nodes
+            .iter()
+            .find(|(_, ips)| ips.contains(ip))
+            .map(|(id, _)| id.as_str())
+    }
+
     pub fn add_address(&mut self, ip: &str) -> Result<(), Error> {
         let ip = to_ip(ip)?;
         if !self.network.contains(&ip) {
@@ -3,6 +3,8 @@ use crate::TransferData;
 use async_compression::futures::bufread::{BzDecoder, BzEncoder};
 use async_compression::futures::bufread::{GzipDecoder, GzipEncoder};
 use async_compression::futures::bufread::{XzDecoder, XzEncoder};
+use async_compression::futures::bufread::{ZstdDecoder, ZstdEncoder};
+use async_compression::Level;
 use bytes::{Bytes, BytesMut};
 use futures::channel::{mpsc, mpsc::Sender};
 use futures::task::{Context, Poll};
@@ -28,10 +30,21 @@ pub enum ArchiveFormat {
     #[default]
     TarGz,
     TarXz,
+    /// Zstandard compressed tar archive, with an optional compression level
+    TarZst(Option<u32>),
     Zip,
     ZipStored,
 }
 
+impl ArchiveFormat {
+    /// Names of supported archive formats
+    pub fn names() -> Vec<&'static str> {
+        vec![
+            "tar", "tar.bz2", "tar.gz", "tar.xz", "tar.zst", "zip", "zip.0",
+        ]
+    }
+}
+
 impl FromStr for ArchiveFormat {
     type Err = Error;
 
@@ -45,6 +58,8 @@ impl FromStr for ArchiveFormat {
             Ok(ArchiveFormat::TarGz)
         } else if s.ends_with(".tar.xz") {
             Ok(ArchiveFormat::TarXz)
+        } else if s.ends_with(".tar.zst") {
+            Ok(ArchiveFormat::TarZst(None))
         } else if s.ends_with(".zip") {
             Ok(ArchiveFormat::Zip)
         } else if s.ends_with(".zip.0") {
@@ -64,9 +79,13 @@ impl<'s> TryFrom<&'s str> for ArchiveFormat {
             "tar.bz2" => ArchiveFormat::TarBz2,
             "tar.gz" => ArchiveFormat::TarGz,
             "tar.xz" => ArchiveFormat::TarXz,
+            "tar.zst" => ArchiveFormat::TarZst(None),
             "zip" => ArchiveFormat::Zip,
             "zip.0" => ArchiveFormat::ZipStored,
-            _ => return Err(Error::OutputFormat(s.to_string())),
+            f => match f.strip_prefix("tar.zst.").map(u32::from_str) {
+                Some(Ok(level)) => ArchiveFormat::TarZst(Some(level)),
+                _ => return Err(Error::OutputFormat(s.to_string())),
+            },
         };
         Ok(format)
     }
@@ -93,6 +112,14 @@ pub enum FileEvent {
     Finished {
         name: PathBuf,
     },
+    /// File left intact, since it's up to date at the destination
+    Skipped {
+        name: PathBuf,
+    },
+    /// File removed, since it's no longer present at the source
+    Deleted {
+        name: PathBuf,
+    },
 }
 
 #[inline]
@@ -115,6 +142,38 @@ pub async fn archive_stream<'a, B, E, P, R>(
     format: ArchiveFormat,
     evt_sender: Sender<FileEvent>,
 ) -> Pin<Box<dyn Stream<Item = Result<B, E>> + Send + Sync + 'a>>
+where
+    B: From<Bytes> + Unpin + Send + Sync + 'a,
+    E: From<io::Error> + Unpin + Send + Sync + 'a,
+    P: AsRef<Path> + Send + Sync + 'a,
+    R: AsRef<Path> + Unpin + Send + Sync + 'static,
+{
+    archive_stream_with(path_iter, path_root, format, None, evt_sender).await
+}
+
+/// Creates an archive stream with an additional in-memory `entry`, placed first in the archive.
+/// Only tar formats are supported.
+pub(crate) async fn archive_with_entry<'a, P, R>(
+    path_iter: impl Iterator<Item = R> + 'static,
+    path_root: P,
+    format: ArchiveFormat,
+    entry: (PathBuf, Bytes),
+    evt_sender: Sender<FileEvent>,
+) -> Pin<Box<dyn Stream<Item = Result<TransferData, Error>> + Send + Sync + 'a>>
+where
+    P: AsRef<Path> + Send + Sync + 'a,
+    R: AsRef<Path> + Unpin + Send + Sync + 'static,
+{
+    archive_stream_with(path_iter, path_root, format, Some(entry), evt_sender).await
+}
+
+async fn archive_stream_with<'a, B, E, P, R>(
+    path_iter: impl Iterator<Item = R> + 'static,
+    path_root: P,
+    format: ArchiveFormat,
+    entry: Option<(PathBuf, Bytes)>,
+    evt_sender: Sender<FileEvent>,
+) -> Pin<Box<dyn Stream<Item = Result<B, E>> + Send + Sync + 'a>>
 where
     B: From<Bytes> + Unpin + Send + Sync + 'a,
     E: From<io::Error> + Unpin + Send + Sync + 'a,
@@ -128,13 +187,13 @@ where
 
     match format {
         ArchiveFormat::Tar => Box::pin(
-            archive_tar(path_iter, path_root, evt_sender)
+            archive_tar(path_iter, path_root, entry, evt_sender)
                 .await
                 .map(BytesResult::convert),
         ),
         ArchiveFormat::TarBz2 => Box::pin(
             codec_stream(BzEncoder::new(
-                archive_tar(path_iter, path_root, evt_sender)
+                archive_tar(path_iter, path_root, entry, evt_sender)
                     .await
                     .into_async_read(),
             ))
@@ -142,7 +201,7 @@ where
         ),
         ArchiveFormat::TarGz => Box::pin(
             codec_stream(GzipEncoder::new(
-                archive_tar(path_iter, path_root, evt_sender)
+                archive_tar(path_iter, path_root, entry, evt_sender)
                     .await
                     .into_async_read(),
             ))
@@ -150,9 +209,25 @@ where
         ),
         ArchiveFormat::TarXz => Box::pin(
             codec_stream(XzEncoder::new(
-                archive_tar(path_iter, path_root, evt_sender)
+                archive_tar(path_iter, path_root, entry, evt_sender)
+                    .await
+                    .into_async_read(),
+            ))
+            .map(BytesResult::convert),
+        ),
+        ArchiveFormat::TarZst(level) => Box::pin(
+            codec_stream(ZstdEncoder::with_quality(
+                archive_tar(path_iter, path_root, entry, evt_sender)
                     .await
                     .into_async_read(),
+                level.map(Level::Precise).unwrap_or(Level::Default),
+            ))
+            .map(BytesResult::convert),
+        ),
+        ArchiveFormat::Zip | ArchiveFormat::ZipStored if entry.is_some() => Box::pin(
+            futures_stream_err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported archive format",
             ))
             .map(BytesResult::convert),
         ),
@@ -174,6 +249,7 @@ where
 async fn archive_tar<'a, P, R>(
     path_iter: impl Iterator<Item = R> + 'static,
     path_root: P,
+    entry: Option<(PathBuf, Bytes)>,
     mut evt_sender: Sender<FileEvent>,
 ) -> impl Stream<Item = Result<Bytes, io::Error>> + Unpin + Send + Sync + 'a
 where
@@ -186,7 +262,7 @@ where
 
     let fut = async move {
         let mut path_iter = path_iter.peekable();
-        if path_iter.peek().is_none() {
+        if path_iter.peek().is_none() && entry.is_none() {
             return Err(io::Error::from(io::ErrorKind::InvalidData));
         }
 
@@ -196,6 +272,16 @@ where
         );
         let mut builder = tokio_tar::Builder::new(writer);
 
+        if let Some((name, data)) = entry {
+            let mut header = tokio_tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, data.as_ref())
+                .await?;
+        }
+
         for prov in path_iter {
             let path = prov.as_ref();
             let metadata = std::fs::metadata(path)?;
@@ -367,6 +453,14 @@ where
             )
             .await?;
         }
+        ArchiveFormat::TarZst(_) => {
+            extract_tar(
+                codec_stream(ZstdDecoder::new(stream.into_async_read())),
+                path,
+                evt_sender,
+            )
+            .await?;
+        }
         ArchiveFormat::Zip | ArchiveFormat::ZipStored => {
             extract_zip(stream, path, evt_sender).await?;
         }
@@ -466,7 +560,8 @@ where
     use futures::io::AsyncReadExt;
 
     let stream = futures::stream::try_unfold(s, |mut encoder| async move {
-        let mut chunk = BytesMut::with_capacity(40 * 1024);
+        let mut chunk = BytesMut::new();
+        chunk.resize(40 * 1024, 0);
         match encoder.read(&mut chunk).await? {
             0 => Ok(None),
             len => {
@@ -497,9 +592,11 @@ where
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
-        // FIXME: cheeck
-        match Pin::new(&mut self.0).poll_read(cx, buf.filled_mut()) {
-            Poll::Ready(Ok(_)) => Poll::Ready(Ok(())),
+        match Pin::new(&mut self.0).poll_read(cx, buf.initialize_unfilled()) {
+            Poll::Ready(Ok(n)) => {
+                buf.advance(n);
+                Poll::Ready(Ok(()))
+            }
             Poll::Ready(Err(e)) => Poll::Ready(Err(io_error(e))),
             Poll::Pending => Poll::Pending,
         }
@@ -611,3 +708,80 @@ impl BytesResult for Result<Bytes, io::Error> {
         self.map(|b| B::from(b)).map_err(|e| E::from(e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_format() {
+        for name in ArchiveFormat::names() {
+            assert!(ArchiveFormat::try_from(name).is_ok(), "{}", name);
+        }
+
+        assert!(matches!(
+            ArchiveFormat::try_from("tar.zst"),
+            Ok(ArchiveFormat::TarZst(None))
+        ));
+        assert!(matches!(
+            ArchiveFormat::try_from("TAR.ZST.19"),
+            Ok(ArchiveFormat::TarZst(Some(19)))
+        ));
+        assert!(ArchiveFormat::try_from("tar.zst.").is_err());
+        assert!(ArchiveFormat::try_from("tar.zst.fast").is_err());
+        assert!(ArchiveFormat::try_from("tar.zst.-1").is_err());
+
+        assert!(matches!(
+            ArchiveFormat::from_str("/output/data.tar.zst"),
+            Ok(ArchiveFormat::TarZst(None))
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn tar_roundtrip() {
+        use crate::traverse::PathTraverse;
+
+        let src = tempdir::TempDir::new("archive-src").unwrap();
+        let dst = tempdir::TempDir::new("archive-dst").unwrap();
+        std::fs::create_dir(src.path().join("dir")).unwrap();
+        std::fs::write(src.path().join("file"), vec![1u8; 100 * 1024]).unwrap();
+        std::fs::write(src.path().join("dir").join("file"), b"contents").unwrap();
+
+        for name in [
+            "tar",
+            "tar.bz2",
+            "tar.gz",
+            "tar.xz",
+            "tar.zst",
+            "tar.zst.19",
+        ] {
+            let format = ArchiveFormat::try_from(name).unwrap();
+            let src_path = normalize_path(src.path()).unwrap();
+            let dst_path = dst.path().join(name);
+            let paths = TransferArgs::default().traverse(&src_path).unwrap();
+
+            let (evt_tx, evt_rx) = mpsc::channel(1);
+            tokio::task::spawn_local(evt_rx.for_each(|_| async {}));
+            let stream = archive(paths, src_path, format, evt_tx).await;
+
+            let (evt_tx, evt_rx) = mpsc::channel(1);
+            tokio::task::spawn_local(evt_rx.for_each(|_| async {}));
+            extract(stream, dst_path.clone(), format, evt_tx)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                std::fs::read(dst_path.join("file")).unwrap().len(),
+                100 * 1024,
+                "{}",
+                name
+            );
+            assert_eq!(
+                std::fs::read(dst_path.join("dir").join("file")).unwrap(),
+                b"contents",
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use sha3::digest::generic_array::typenum::U32;
+use sha3::digest::generic_array::GenericArray;
+use sha3::digest::{DynDigest, FixedOutput, Input, Reset};
+use sha3::{Sha3_224, Sha3_256, Sha3_384, Sha3_512};
+
+use crate::error::Error;
+
+/// Creates a hasher for digest algorithm `alg`, producing output of the same length as `hash`.
+///
+/// Supported algorithms:
+/// - `sha3` (224, 256, 384 and 512 bits)
+/// - `sha256`, `sha512` (SHA-2)
+/// - `blake3` (256 bits)
+pub(crate) fn hasher(alg: &str, hash: &[u8]) -> Result<Box<dyn DynDigest>, Error> {
+    let hasher: Box<dyn DynDigest> = match (alg, hash.len() * 8) {
+        ("sha3", 224) => Box::<Sha3_224>::default(),
+        ("sha3", 256) => Box::<Sha3_256>::default(),
+        ("sha3", 384) => Box::<Sha3_384>::default(),
+        ("sha3", 512) => Box::<Sha3_512>::default(),
+        ("sha256", 256) => Box::<sha2::Sha256>::default(),
+        ("sha512", 512) => Box::<sha2::Sha512>::default(),
+        ("blake3", 256) => Box::<Blake3>::default(),
+        ("sha3", len) | ("sha256", len) | ("sha512", len) | ("blake3", len) => {
+            return Err(Error::UnsupportedDigestError(format!(
+                "Unsupported digest {} of length {}: {}",
+                alg,
+                len,
+                hex::encode(hash),
+            )))
+        }
+        _ => {
+            return Err(Error::UnsupportedDigestError(format!(
+                "Unsupported digest: {}",
+                alg
+            )))
+        }
+    };
+    Ok(hasher)
+}
+
+/// Adapts `blake3::Hasher` to the `digest` traits used by the remaining hashers
+#[derive(Clone, Default)]
+struct Blake3(blake3::Hasher);
+
+impl Input for Blake3 {
+    fn input<B: AsRef<[u8]>>(&mut self, data: B) {
+        self.0.update(data.as_ref());
+    }
+}
+
+impl FixedOutput for Blake3 {
+    type OutputSize = U32;
+
+    fn fixed_result(self) -> GenericArray<u8, Self::OutputSize> {
+        GenericArray::clone_from_slice(self.0.finalize().as_bytes())
+    }
+}
+
+impl Reset for Blake3 {
+    fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(alg: &str, hash: &str, data: &[u8]) -> Result<bool, Error> {
+        let hash = hex::decode(hash).unwrap();
+        let mut hasher = hasher(alg, &hash)?;
+        hasher.input(data);
+        Ok(hasher.result().as_ref() == hash.as_slice())
+    }
+
+    #[test]
+    fn supported() {
+        let data = b"abc";
+
+        assert!(digest(
+            "sha3",
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532",
+            data
+        )
+        .unwrap());
+        assert!(digest(
+            "sha256",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            data
+        )
+        .unwrap());
+        assert!(digest(
+            "sha512",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            data
+        )
+        .unwrap());
+        assert!(digest(
+            "blake3",
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            data
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn unsupported() {
+        let data = b"abc";
+
+        assert!(matches!(
+            digest("md5", "900150983cd24fb0d6963f7d28e17f72", data),
+            Err(Error::UnsupportedDigestError(_))
+        ));
+        assert!(matches!(
+            digest("sha256", "900150983cd24fb0d6963f7d28e17f72", data),
+            Err(Error::UnsupportedDigestError(_))
+        ));
+        assert!(matches!(
+            digest("blake3", "900150983cd24fb0d6963f7d28e17f72", data),
+            Err(Error::UnsupportedDigestError(_))
+        ));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::archive::ArchiveFormat;
 use crate::archive::{archive, extract};
 use crate::error::Error;
+use crate::sync::{sync_destination, sync_source, Manifest, SyncMode};
 use crate::traverse::PathTraverse;
 use crate::{abortable_sink, abortable_stream};
 use crate::{TransferContext, TransferData, TransferProvider, TransferSink, TransferStream};
@@ -153,6 +154,7 @@ impl TransferProvider<TransferData, Error> for DirTransferProvider {
     fn source(&self, url: &Url, ctx: &TransferContext) -> TransferStream<TransferData, Error> {
         let dir = Path::new(&extract_file_url(url)).to_owned();
         let args = ctx.args.clone();
+        let state = ctx.state.clone();
         log::debug!("Transfer source directory: {}", dir.display());
 
         let (stream, tx, abort_reg) = TransferStream::<TransferData, Error>::create(1);
@@ -160,9 +162,6 @@ impl TransferProvider<TransferData, Error> for DirTransferProvider {
 
         spawn_local(async move {
             let fut = async move {
-                let format = ArchiveFormat::try_from(&args)?;
-                let path_iter = args.traverse(&dir)?;
-
                 let (evt_tx, mut evt_rx) = futures::channel::mpsc::channel(1);
                 spawn_local(async move {
                     while let Some(evt) = evt_rx.next().await {
@@ -170,8 +169,16 @@ impl TransferProvider<TransferData, Error> for DirTransferProvider {
                     }
                 });
 
-                archive(path_iter, dir, format, evt_tx)
-                    .await
+                let archive = match SyncMode::try_from(&args) {
+                    Ok(mode) => sync_source(dir, &args, mode, &state, evt_tx).await?,
+                    Err(_) => {
+                        let format = ArchiveFormat::try_from(&args)?;
+                        let path_iter = args.traverse(&dir)?;
+                        archive(path_iter, dir, format, evt_tx).await
+                    }
+                };
+
+                archive
                     .forward(tx.sink_map_err(Error::from).with(|b| ready(Ok(Ok(b)))))
                     .await
             };
@@ -191,8 +198,6 @@ impl TransferProvider<TransferData, Error> for DirTransferProvider {
 
         spawn_local(async move {
             let fut = async move {
-                let format = ArchiveFormat::try_from(&args)?;
-
                 let (evt_tx, mut evt_rx) = futures::channel::mpsc::channel(1);
                 spawn_local(async move {
                     while let Some(evt) = evt_rx.next().await {
@@ -200,7 +205,13 @@ impl TransferProvider<TransferData, Error> for DirTransferProvider {
                     }
                 });
 
-                extract(rx, dir, format, evt_tx).await?;
+                match SyncMode::try_from(&args) {
+                    Ok(mode) => sync_destination(rx, dir, &args, mode, evt_tx).await?,
+                    Err(_) => {
+                        let format = ArchiveFormat::try_from(&args)?;
+                        extract(rx, dir, format, evt_tx).await?;
+                    }
+                }
                 Ok::<(), Error>(())
             };
 
@@ -209,6 +220,24 @@ impl TransferProvider<TransferData, Error> for DirTransferProvider {
 
         sink
     }
+
+    fn prepare_destination<'a>(
+        &self,
+        url: &Url,
+        ctx: &TransferContext,
+    ) -> LocalBoxFuture<'a, Result<(), Error>> {
+        let dir = PathBuf::from(extract_file_url(url));
+        let args = ctx.args.clone();
+        let state = ctx.state.clone();
+        async move {
+            state.set_offset(0);
+            if SyncMode::try_from(&args).is_ok() {
+                state.set_base_manifest(Some(Manifest::scan_blocking(dir, args).await?));
+            }
+            Ok(())
+        }
+        .boxed_local()
+    }
 }
 
 pub(crate) fn extract_file_url(url: &Url) -> String {
@@ -11,7 +11,8 @@ use tokio::task::spawn_local;
 use url::Url;
 
 use crate::error::{Error, HttpError};
-use crate::{abortable_sink, abortable_stream, TransferState};
+use crate::ranged::RangedDownload;
+use crate::{abortable_sink, abortable_stream, FileTransferProvider, TransferState};
 use crate::{TransferContext, TransferData, TransferProvider, TransferSink, TransferStream};
 
 enum HttpAuth<'s> {
@@ -37,16 +38,28 @@ impl<'s> From<&'s Url> for HttpAuth<'s> {
 
 pub struct HttpTransferProvider {
     upload_method: Method,
+    ranged: Option<RangedDownload>,
 }
 
 impl Default for HttpTransferProvider {
     fn default() -> Self {
         HttpTransferProvider {
             upload_method: Method::PUT,
+            ranged: None,
         }
     }
 }
 
+impl HttpTransferProvider {
+    /// Enables parallel downloads of large resources, resumable from partial files kept in
+    /// `RangedDownload::partial_dir`. Disabled by default, i.e. resources are downloaded
+    /// with a single request
+    pub fn with_ranged_download(mut self, ranged: Option<RangedDownload>) -> Self {
+        self.ranged = ranged;
+        self
+    }
+}
+
 impl TransferProvider<TransferData, Error> for HttpTransferProvider {
     fn schemes(&self) -> Vec<&'static str> {
         vec!["http", "https"]
@@ -58,6 +71,7 @@ impl TransferProvider<TransferData, Error> for HttpTransferProvider {
 
         let url = url.clone();
         let state = ctx.state.clone();
+        let ranged = self.ranged.clone();
 
         spawn_local(async move {
             let fut = async move {
@@ -66,9 +80,66 @@ impl TransferProvider<TransferData, Error> for HttpTransferProvider {
                     let _ = tx.send(Ok(TransferData::Bytes(Bytes::new()))).await;
                     return Ok(());
                 }
-                DownloadRequest::get(url, &state)
-                    .send()
-                    .await?
+
+                let mut response = None;
+                if let Some(ranged) = ranged {
+                    // A HEAD request is sent only to verify that a partial file can be resumed.
+                    // Otherwise the response to a plain request tells whether the resource
+                    // qualifies for a segmented download.
+                    let resume = state.offset() > 0 || ranged.has_partial(&url);
+                    let probed = if resume {
+                        match DownloadRequest::head(url.clone()).send().await {
+                            Ok(response) => Some(response),
+                            Err(e) => {
+                                log::debug!("Unable to probe {} for range support: {}", url, e);
+                                None
+                            }
+                        }
+                    } else {
+                        Some(DownloadRequest::get(url.clone(), &state).send().await?)
+                    };
+
+                    let segmented = probed.as_ref().and_then(|response| {
+                        let (ranges, size) = probe(response);
+                        ranged
+                            .applies_to(size, ranges)
+                            .map(|size| (size, validator(response)))
+                    });
+                    if !resume {
+                        response = probed;
+                    }
+
+                    if let Some((size, validator)) = segmented {
+                        // Body of the plain request is not needed anymore
+                        response = None;
+                        state.set_size(Some(size));
+
+                        let retry = state.retry_policy();
+                        if let Some(partial) = ranged.download(&url, size, validator, retry).await?
+                        {
+                            let file_url = Url::from_file_path(&partial.path).map_err(|_| {
+                                Error::Other(format!("Invalid path: {:?}", partial.path))
+                            })?;
+                            let file_ctx = TransferContext::new(state.offset());
+
+                            let result = FileTransferProvider
+                                .source(&file_url, &file_ctx)
+                                .forward(tx.sink_map_err(Error::from).with(|d| ready(Ok(Ok(d)))))
+                                .await;
+
+                            // Completed partial is never reused, as its contents may fail
+                            // the hash verification taking place after the stream ends
+                            ranged.remove_partial(&url);
+                            return result;
+                        }
+                    }
+                }
+
+                let response = match response {
+                    Some(response) => response,
+                    None => DownloadRequest::get(url, &state).send().await?,
+                };
+                response
                     .into_stream()
                     .map_err(Error::from)
                     .forward(
@@ -128,14 +199,7 @@ impl TransferProvider<TransferData, Error> for HttpTransferProvider {
 
         async move {
             let response = DownloadRequest::head(url).send().await?;
-            let ranges = response
-                .headers()
-                .get_all(header::ACCEPT_RANGES)
-                .any(|v| v.to_str().map(|s| s == "bytes").unwrap_or(false));
-            let size: Option<u64> = response
-                .headers()
-                .get(header::CONTENT_LENGTH)
-                .and_then(|v| v.to_str().ok().and_then(|s| u64::from_str(s).ok()));
+            let (ranges, size) = probe(&response);
 
             state.set_size(size);
             if !ranges {
@@ -149,10 +213,46 @@ impl TransferProvider<TransferData, Error> for HttpTransferProvider {
     }
 }
 
-struct DownloadRequest {
+/// Returns the strong ETag or the Last-Modified date of the resource,
+/// used to verify that it hasn't changed between range requests
+fn validator<S>(response: &awc::ClientResponse<S>) -> Option<String> {
+    let header = |name: header::HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.trim().to_string())
+    };
+    header(header::ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(header::LAST_MODIFIED))
+        .filter(|v| !v.is_empty())
+}
+
+/// Returns whether byte range requests are supported and the size of the resource
+fn probe<S>(response: &awc::ClientResponse<S>) -> (bool, Option<u64>) {
+    if !response.status().is_success() {
+        return (false, None);
+    }
+
+    let ranges = response
+        .headers()
+        .get_all(header::ACCEPT_RANGES)
+        .any(|v| v.to_str().map(|s| s == "bytes").unwrap_or(false));
+    let size: Option<u64> = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok().and_then(|s| u64::from_str(s).ok()));
+
+    (ranges, size)
+}
+
+pub(crate) struct DownloadRequest {
     method: Method,
     url: Url,
     offset: u64,
+    end: Option<u64>,
+    if_range: Option<String>,
     max_redirects: usize,
 }
 
@@ -162,6 +262,8 @@ impl DownloadRequest {
             method: Method::GET,
             url,
             offset: state.offset(),
+            end: None,
+            if_range: None,
             max_redirects: 10,
         }
     }
@@ -171,19 +273,41 @@ impl DownloadRequest {
             method: Method::HEAD,
             url,
             offset: 0,
+            end: None,
+            if_range: None,
+            max_redirects: 10,
+        }
+    }
+
+    /// Requests bytes in range `[offset, end)`
+    pub fn range(url: Url, offset: u64, end: u64) -> Self {
+        Self {
+            method: Method::GET,
+            url,
+            offset,
+            end: Some(end),
+            if_range: None,
             max_redirects: 10,
         }
     }
 
+    /// Makes the range request conditional: the server replies with the full resource
+    /// when `validator` no longer matches
+    pub fn if_range(mut self, validator: Option<String>) -> Self {
+        self.if_range = validator;
+        self
+    }
+
     pub async fn send(
         self,
     ) -> Result<awc::ClientResponse<Decoder<Payload>>, awc::error::SendRequestError> {
         let mut redirects = self.max_redirects;
         let mut url = self.url.to_string();
 
-        let range = match self.offset {
-            0 => None,
-            off => Some(format!("bytes={}-", off)),
+        let range = match (self.offset, self.end) {
+            (0, None) => None,
+            (off, None) => Some(format!("bytes={}-", off)),
+            (off, Some(end)) => Some(format!("bytes={}-{}", off, end.saturating_sub(1))),
         };
 
         loop {
@@ -199,6 +323,9 @@ impl DownloadRequest {
 
             if let Some(ref range) = range {
                 builder = builder.add_default_header((header::RANGE, range.clone()));
+                if let Some(ref validator) = self.if_range {
+                    builder = builder.add_default_header((header::IF_RANGE, validator.clone()));
+                }
             }
 
             let resp = builder
@@ -228,7 +355,7 @@ impl DownloadRequest {
     }
 }
 
-trait HttpErr<T>
+pub(crate) trait HttpErr<T>
 where
     Self: Sized,
 {
@@ -1,11 +1,15 @@
 mod archive;
+mod digest;
 pub mod error;
 mod file;
 mod gftp;
 mod http;
 mod location;
 mod progress;
+mod ranged;
 mod retry;
+mod s3;
+mod sync;
 mod traverse;
 
 use std::cell::RefCell;
@@ -20,18 +24,20 @@ use futures::future::{AbortHandle, AbortRegistration, Abortable, Aborted, LocalB
 use futures::prelude::*;
 use futures::task::{Context, Poll};
 use sha3::digest::DynDigest;
-use sha3::{Sha3_224, Sha3_256, Sha3_384, Sha3_512};
 use url::Url;
 
 use crate::error::Error;
 
-pub use crate::archive::{archive, extract, ArchiveFormat};
+pub use crate::archive::{archive, extract, ArchiveFormat, FileEvent};
 pub use crate::file::{DirTransferProvider, FileTransferProvider};
 pub use crate::gftp::GftpTransferProvider;
 pub use crate::http::HttpTransferProvider;
 pub use crate::location::{TransferUrl, UrlExt};
 pub use crate::progress::{wrap_sink_with_progress_reporting, wrap_stream_with_progress_reporting};
+pub use crate::ranged::RangedDownload;
 pub use crate::retry::Retry;
+pub use crate::s3::{ProviderStorage, S3Credentials, S3TransferProvider};
+pub use crate::sync::{Manifest, ManifestEntry, SyncMode};
 pub use crate::traverse::PathTraverse;
 
 use ya_client_model::activity::TransferArgs;
@@ -289,15 +295,25 @@ impl From<Box<[u8]>> for TransferData {
 pub struct TransferContext {
     pub state: TransferState,
     pub args: TransferArgs,
+    /// Object storage credentials provided along with the transfer arguments.
+    /// Provider's credentials from the environment are used when not set
+    pub s3: Option<S3Credentials>,
 }
 
 impl TransferContext {
     pub fn new(offset: u64) -> Self {
-        let args = TransferArgs::default();
         let state = TransferState::default();
         state.set_offset(offset);
 
-        Self { args, state }
+        Self {
+            state,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_s3_credentials(mut self, credentials: Option<S3Credentials>) -> Self {
+        self.s3 = credentials;
+        self
     }
 }
 
@@ -350,6 +366,31 @@ impl TransferState {
         r.retry.replace(retry);
     }
 
+    /// Returns a copy of the retry policy, for operations retried independently
+    pub fn retry_policy(&self) -> Option<Retry> {
+        self.inner.borrow().retry.clone()
+    }
+
+    /// Manifest of the destination directory, compared against when synchronizing
+    pub fn base_manifest(&self) -> Option<Manifest> {
+        self.inner.borrow().base_manifest.clone()
+    }
+
+    pub fn set_base_manifest(&self, manifest: Option<Manifest>) {
+        let mut r = self.inner.borrow_mut();
+        r.base_manifest = manifest;
+    }
+
+    /// Manifest of the synchronized source directory
+    pub fn manifest(&self) -> Option<Manifest> {
+        self.inner.borrow().manifest.clone()
+    }
+
+    pub fn set_manifest(&self, manifest: Option<Manifest>) {
+        let mut r = self.inner.borrow_mut();
+        r.manifest = manifest;
+    }
+
     pub fn delay(&self, err: &Error) -> Option<Duration> {
         self.inner
             .borrow_mut()
@@ -363,6 +404,8 @@ struct TransferStateInner {
     offset: u64,
     size: Option<u64>,
     retry: Option<Retry>,
+    base_manifest: Option<Manifest>,
+    manifest: Option<Manifest>,
 }
 
 impl Default for TransferStateInner {
@@ -371,6 +414,8 @@ impl Default for TransferStateInner {
             offset: Default::default(),
             size: Default::default(),
             retry: Some(Retry::default()),
+            base_manifest: None,
+            manifest: None,
         }
     }
 }
@@ -390,28 +435,7 @@ where
     S: Stream<Item = Result<T, Error>> + Unpin,
 {
     pub fn try_new(stream: S, alg: &str, hash: Vec<u8>) -> Result<Self, Error> {
-        let hasher: Box<dyn DynDigest> = match alg {
-            "sha3" => match hash.len() * 8 {
-                224 => Box::<Sha3_224>::default(),
-                256 => Box::<Sha3_256>::default(),
-                384 => Box::<Sha3_384>::default(),
-                512 => Box::<Sha3_512>::default(),
-                len => {
-                    return Err(Error::UnsupportedDigestError(format!(
-                        "Unsupported digest {} of length {}: {}",
-                        alg,
-                        len,
-                        hex::encode(&hash),
-                    )))
-                }
-            },
-            _ => {
-                return Err(Error::UnsupportedDigestError(format!(
-                    "Unsupported digest: {}",
-                    alg
-                )))
-            }
-        };
+        let hasher = digest::hasher(alg, &hash)?;
 
         Ok(HashStream {
             inner: stream,
@@ -24,6 +24,13 @@ pub struct TransferHash {
     pub val: Vec<u8>,
 }
 
+impl TransferHash {
+    /// Checks whether the digest algorithm and its length are supported
+    pub fn validate(&self) -> Result<(), Error> {
+        crate::digest::hasher(&self.alg, &self.val).map(|_| ())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TransferUrl {
     pub hash: Option<TransferHash>,
@@ -171,6 +178,32 @@ mod test {
         should_succeed!("http:location.com");
     }
 
+    #[test]
+    fn hash_alg() {
+        let validate = |alg: &str, len: usize| {
+            let url = format!("hash:{}:{}:http://location.com", alg, "00".repeat(len));
+            TransferUrl::parse(&url, "container")
+                .unwrap()
+                .hash
+                .unwrap()
+                .validate()
+        };
+
+        assert!(validate("sha3", 28).is_ok());
+        assert!(validate("sha3", 32).is_ok());
+        assert!(validate("sha3", 48).is_ok());
+        assert!(validate("sha3", 64).is_ok());
+        assert!(validate("sha256", 32).is_ok());
+        assert!(validate("sha512", 64).is_ok());
+        assert!(validate("blake3", 32).is_ok());
+
+        assert!(validate("sha3", 16).is_err());
+        assert!(validate("sha256", 64).is_err());
+        assert!(validate("sha512", 32).is_err());
+        assert!(validate("blake3", 64).is_err());
+        assert!(validate("md5", 16).is_err());
+    }
+
     #[test]
     #[cfg(windows)]
     fn fallback_to_file_on_windows_path() {
@@ -0,0 +1,706 @@
+use std::cell::RefCell;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use fs2::FileExt;
+use futures::StreamExt;
+use sha3::Digest;
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, SeekFrom};
+use url::Url;
+
+use crate::error::{Error, HttpError};
+use crate::http::{DownloadRequest, HttpErr};
+use crate::Retry;
+
+pub const DEFAULT_SEGMENTS: usize = 4;
+pub const DEFAULT_MIN_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Amount of downloaded data after which segment progress is persisted
+const SAVE_INTERVAL: u64 = 4 * 1024 * 1024;
+
+/// Configuration of parallel downloads using HTTP range requests
+#[derive(Clone, Debug)]
+pub struct RangedDownload {
+    /// Number of concurrent range requests
+    pub segments: usize,
+    /// Minimum resource size for the download to be split into segments
+    pub min_size: u64,
+    /// Directory for partially downloaded files
+    pub partial_dir: PathBuf,
+}
+
+impl RangedDownload {
+    pub fn new(partial_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            segments: DEFAULT_SEGMENTS,
+            min_size: DEFAULT_MIN_SIZE,
+            partial_dir: partial_dir.into(),
+        }
+    }
+
+    /// Returns the resource size if it qualifies for a segmented download
+    pub fn applies_to(&self, size: Option<u64>, ranges: bool) -> Option<u64> {
+        match size {
+            Some(size) if ranges && self.segments > 1 && size >= self.min_size => Some(size),
+            _ => None,
+        }
+    }
+
+    /// Location of the partial file for `url`
+    pub fn partial_path(&self, url: &Url) -> PathBuf {
+        let name = hex::encode(sha3::Sha3_224::digest(url.as_str().as_bytes()));
+        self.partial_dir.join(format!("{}.part", name))
+    }
+
+    /// Returns whether an interrupted download of `url` can be resumed
+    pub fn has_partial(&self, url: &Url) -> bool {
+        meta_path(&self.partial_path(url)).exists()
+    }
+
+    /// Downloads `url` of known `size` into a partial file, using concurrent range requests.
+    /// Progress of each segment is persisted, so that an interrupted download can be resumed.
+    /// Each segment is retried independently, following the `retry` policy.
+    ///
+    /// A download is resumed only when the resource `validator` (ETag or Last-Modified)
+    /// matches the persisted one. Returns `None` after removing the partial file when
+    /// the server replies to a range request with the full resource, i.e. when the resource
+    /// has changed or ranges are not supported after all.
+    ///
+    /// The partial file is locked until the returned `PartialFile` is dropped. `None` is
+    /// returned when it is locked by another transfer of the same resource.
+    pub(crate) async fn download(
+        &self,
+        url: &Url,
+        size: u64,
+        validator: Option<String>,
+        retry: Option<Retry>,
+    ) -> Result<Option<PartialFile>, Error> {
+        std::fs::create_dir_all(&self.partial_dir)?;
+
+        let path = self.partial_path(url);
+        let lock = match PartialFile::lock(&path)? {
+            Some(lock) => lock,
+            None => {
+                log::info!(
+                    "{} is being downloaded by another transfer, downloading as a whole",
+                    url
+                );
+                return Ok(None);
+            }
+        };
+        let meta_path = meta_path(&path);
+        let segments = match Segments::load(&meta_path) {
+            Some(segments) if segments.resumable(size, &validator) && path.exists() => {
+                log::debug!("Resuming segmented download of {}", url);
+                segments
+            }
+            _ => {
+                let file = std::fs::File::create(&path)?;
+                file.set_len(size)?;
+                Segments::split(size, validator, self.segments)
+            }
+        };
+        segments.save(&meta_path)?;
+
+        log::info!(
+            "Downloading {} ({} B) in {} segments",
+            url,
+            size,
+            segments.inner.len()
+        );
+
+        let pending = segments.pending();
+        let segments = Rc::new(RefCell::new(segments));
+        let result = futures::future::try_join_all(pending.into_iter().map(|idx| {
+            download_segment(
+                url.clone(),
+                path.clone(),
+                meta_path.clone(),
+                segments.clone(),
+                idx,
+                retry.clone(),
+            )
+        }))
+        .await;
+
+        match result {
+            Ok(_) => Ok(Some(lock)),
+            Err(SegmentError::RangeIgnored(status)) => {
+                log::warn!(
+                    "Range request for {} not honored by the server ({}), downloading as a whole",
+                    url,
+                    status
+                );
+                self.remove_partial(url);
+                Ok(None)
+            }
+            Err(SegmentError::Transfer(err)) => Err(err),
+        }
+    }
+
+    /// Removes the partial file for `url` along with its download progress
+    pub fn remove_partial(&self, url: &Url) {
+        let path = self.partial_path(url);
+        let _ = std::fs::remove_file(meta_path(&path));
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Partial file of a finished segmented download.
+/// Concurrent downloads of the resource are prevented until it is dropped.
+pub(crate) struct PartialFile {
+    pub path: PathBuf,
+    lock: std::fs::File,
+}
+
+impl PartialFile {
+    /// Returns `None` if the partial file at `path` is already locked.
+    /// Lock files are kept, as removing a lock file held by another process is racy.
+    fn lock(path: &Path) -> Result<Option<Self>, Error> {
+        let lock = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(path.with_extension("part.lock"))?;
+        match lock.try_lock_exclusive() {
+            Ok(_) => Ok(Some(PartialFile {
+                path: path.to_path_buf(),
+                lock,
+            })),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        let _ = self.lock.unlock();
+    }
+}
+
+/// Reason of a failed segment download
+enum SegmentError {
+    /// Server replied with the full resource instead of the requested range
+    RangeIgnored(awc::http::StatusCode),
+    Transfer(Error),
+}
+
+impl From<Error> for SegmentError {
+    fn from(err: Error) -> Self {
+        SegmentError::Transfer(err)
+    }
+}
+
+impl From<std::io::Error> for SegmentError {
+    fn from(err: std::io::Error) -> Self {
+        SegmentError::Transfer(err.into())
+    }
+}
+
+async fn download_segment(
+    url: Url,
+    path: PathBuf,
+    meta_path: PathBuf,
+    segments: Rc<RefCell<Segments>>,
+    idx: usize,
+    mut retry: Option<Retry>,
+) -> Result<(), SegmentError> {
+    loop {
+        let result = fetch_segment(&url, &path, &meta_path, &segments, idx).await;
+        let _ = segments.borrow().save(&meta_path);
+
+        match result {
+            Ok(_) => return Ok(()),
+            Err(SegmentError::Transfer(err)) => match retry.as_mut().and_then(|r| r.delay(&err)) {
+                Some(delay) => {
+                    log::warn!(
+                        "Retrying segment {} in {}s because: {}",
+                        idx,
+                        delay.as_secs_f32(),
+                        err
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                None => return Err(SegmentError::Transfer(err)),
+            },
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+async fn fetch_segment(
+    url: &Url,
+    path: &Path,
+    meta_path: &Path,
+    segments: &Rc<RefCell<Segments>>,
+    idx: usize,
+) -> Result<(), SegmentError> {
+    let (start, mut offset, end, validator) = {
+        let segments = segments.borrow();
+        let segment = &segments.inner[idx];
+        let validator = segments.validator.clone();
+        (segment.start, segment.offset(), segment.end, validator)
+    };
+    if offset >= end {
+        return Ok(());
+    }
+
+    log::debug!("Downloading segment {}: bytes {}-{}", idx, offset, end - 1);
+
+    let response = DownloadRequest::range(url.clone(), offset, end)
+        .if_range(validator)
+        .send()
+        .await
+        .map_err(Error::from)?
+        .http_err()?;
+    if response.status() != awc::http::StatusCode::PARTIAL_CONTENT {
+        return Err(SegmentError::RangeIgnored(response.status()));
+    }
+
+    let mut file = OpenOptions::new().write(true).open(path).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+
+    let mut body = response.boxed_local();
+    let mut unsaved = 0;
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(Error::from)?;
+        let len = chunk.len().min((end - offset) as usize);
+
+        file.write_all(&chunk[..len]).await?;
+        offset += len as u64;
+        unsaved += len as u64;
+        segments.borrow_mut().inner[idx].done = offset - start;
+
+        if unsaved >= SAVE_INTERVAL {
+            file.sync_data().await?;
+            segments.borrow().save(meta_path)?;
+            unsaved = 0;
+        }
+        if offset >= end {
+            break;
+        }
+    }
+    file.sync_data().await?;
+
+    if offset < end {
+        return Err(Error::from(HttpError::Io(ErrorKind::ConnectionAborted)).into());
+    }
+    Ok(())
+}
+
+fn meta_path(path: &Path) -> PathBuf {
+    path.with_extension("part.meta")
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Segment {
+    start: u64,
+    end: u64,
+    done: u64,
+}
+
+impl Segment {
+    fn offset(&self) -> u64 {
+        self.start + self.done
+    }
+
+    fn finished(&self) -> bool {
+        self.offset() >= self.end
+    }
+}
+
+/// Download progress of a resource, stored as text:
+/// resource size and optional validator in the first line,
+/// followed by `start end done` lines for each segment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Segments {
+    size: u64,
+    validator: Option<String>,
+    inner: Vec<Segment>,
+}
+
+impl Segments {
+    fn split(size: u64, validator: Option<String>, count: usize) -> Self {
+        let count = (count as u64).clamp(1, size.max(1));
+        let len = size / count;
+
+        let inner = (0..count)
+            .map(|i| Segment {
+                start: i * len,
+                end: if i == count - 1 { size } else { (i + 1) * len },
+                done: 0,
+            })
+            .collect();
+
+        Segments {
+            size,
+            validator,
+            inner,
+        }
+    }
+
+    /// Progress can be reused only for an unchanged resource,
+    /// which can't be verified without a validator
+    fn resumable(&self, size: u64, validator: &Option<String>) -> bool {
+        self.size == size && self.validator.is_some() && &self.validator == validator
+    }
+
+    fn pending(&self) -> Vec<usize> {
+        self.inner
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !s.finished())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn load(path: &Path) -> Option<Self> {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| Self::from_str(&s).ok())
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        let tmp_path = path.with_extension("meta.tmp");
+        std::fs::write(&tmp_path, self.to_string())?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+impl FromStr for Segments {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::Other(format!("Invalid segment data: {}", s));
+        let parse = |v: Option<&str>| v.and_then(|v| u64::from_str(v).ok()).ok_or_else(err);
+
+        let mut lines = s.lines();
+        let mut header = lines.next().unwrap_or_default().splitn(2, ' ');
+        let size = parse(header.next())?;
+        let validator = header.next().map(|v| v.trim().to_string());
+        let inner = lines
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                let mut values = l.split_whitespace();
+                let segment = Segment {
+                    start: parse(values.next())?,
+                    end: parse(values.next())?,
+                    done: parse(values.next())?,
+                };
+                match segment.start <= segment.end && segment.end <= size {
+                    true => Ok(segment),
+                    false => Err(err()),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Segments {
+            size,
+            validator,
+            inner,
+        })
+    }
+}
+
+impl std::fmt::Display for Segments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.validator {
+            Some(validator) => writeln!(f, "{} {}", self.size, validator)?,
+            None => writeln!(f, "{}", self.size)?,
+        }
+        for segment in self.inner.iter() {
+            writeln!(f, "{} {} {}", segment.start, segment.end, segment.done)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_http::header;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn split() {
+        let segments = Segments::split(10, None, 3);
+        assert_eq!(
+            segments.inner,
+            vec![
+                Segment {
+                    start: 0,
+                    end: 3,
+                    done: 0
+                },
+                Segment {
+                    start: 3,
+                    end: 6,
+                    done: 0
+                },
+                Segment {
+                    start: 6,
+                    end: 10,
+                    done: 0
+                },
+            ]
+        );
+
+        assert_eq!(Segments::split(2, None, 4).inner.len(), 2);
+        assert_eq!(Segments::split(0, None, 4).inner.len(), 1);
+    }
+
+    #[test]
+    fn pending() {
+        let mut segments = Segments::split(100, None, 4);
+        segments.inner[0].done = 25;
+        segments.inner[2].done = 10;
+        assert_eq!(segments.pending(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn serialize() {
+        let mut segments = Segments::split(1000, None, 4);
+        segments.inner[1].done = 100;
+
+        let parsed = Segments::from_str(&segments.to_string()).unwrap();
+        assert_eq!(parsed, segments);
+
+        let modified = Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string());
+        let segments = Segments::split(1000, modified.clone(), 4);
+        let parsed = Segments::from_str(&segments.to_string()).unwrap();
+        assert_eq!(parsed, segments);
+        assert!(parsed.resumable(1000, &modified));
+        assert!(!parsed.resumable(1000, &Some("\"etag\"".to_string())));
+        assert!(!Segments::split(1000, None, 4).resumable(1000, &None));
+
+        assert!(Segments::from_str("").is_err());
+        assert!(Segments::from_str("100\n0 200 0\n").is_err());
+        assert!(Segments::from_str("100\n0 50\n").is_err());
+    }
+
+    #[test]
+    fn applies_to() {
+        let ranged = RangedDownload::new(std::env::temp_dir());
+        assert_eq!(
+            ranged.applies_to(Some(DEFAULT_MIN_SIZE), true),
+            Some(DEFAULT_MIN_SIZE)
+        );
+        assert_eq!(ranged.applies_to(Some(DEFAULT_MIN_SIZE), false), None);
+        assert_eq!(ranged.applies_to(Some(DEFAULT_MIN_SIZE - 1), true), None);
+        assert_eq!(ranged.applies_to(None, true), None);
+    }
+
+    const ETAG: &str = "\"v1\"";
+
+    fn content() -> Vec<u8> {
+        (0..1000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Serves `content()` honoring `Range` requests, unless `If-Range` doesn't match `ETAG`.
+    /// Returns the server url and received requests, i.e. methods with requested ranges.
+    fn serve() -> (Url, Arc<Mutex<Vec<String>>>) {
+        use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let requests = requested.clone();
+
+        let server = HttpServer::new(move || {
+            let requests = requests.clone();
+            App::new().default_service(web::to(move |req: HttpRequest| {
+                let requests = requests.clone();
+                async move {
+                    let header = |name: header::HeaderName| {
+                        req.headers()
+                            .get(name)
+                            .and_then(|v| v.to_str().ok())
+                            .map(|s| s.to_string())
+                    };
+                    let content = content();
+                    let range = header(header::RANGE);
+                    requests.lock().unwrap().push(match range {
+                        Some(ref range) => format!("{} {}", req.method(), range),
+                        None => req.method().to_string(),
+                    });
+                    let range = range.and_then(|range| {
+                        let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+                        Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?))
+                    });
+
+                    match range {
+                        Some((start, end)) if header(header::IF_RANGE).as_deref() == Some(ETAG) => {
+                            HttpResponse::PartialContent()
+                                .insert_header((header::ETAG, ETAG))
+                                .insert_header((
+                                    header::CONTENT_RANGE,
+                                    format!("bytes {}-{}/{}", start, end, content.len()),
+                                ))
+                                .body(content[start..=end].to_vec())
+                        }
+                        _ => HttpResponse::Ok()
+                            .insert_header((header::ETAG, ETAG))
+                            .insert_header((header::ACCEPT_RANGES, "bytes"))
+                            .body(content),
+                    }
+                }
+            }))
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+
+        let addr = server.addrs()[0];
+        actix_rt::spawn(server.run());
+
+        let url = Url::parse(&format!("http://{}/image.gvmi", addr)).unwrap();
+        (url, requested)
+    }
+
+    #[actix_rt::test]
+    async fn resume_download() {
+        let dir = tempdir::TempDir::new("ranged").unwrap();
+        let (url, requested) = serve();
+        let content = content();
+        let ranged = RangedDownload {
+            segments: 4,
+            min_size: 0,
+            partial_dir: dir.path().to_path_buf(),
+        };
+
+        // interrupted download: first segment finished, second one started
+        let path = ranged.partial_path(&url);
+        let mut partial = vec![0u8; content.len()];
+        partial[..350].copy_from_slice(&content[..350]);
+        std::fs::write(&path, partial).unwrap();
+
+        let mut segments = Segments::split(content.len() as u64, Some(ETAG.to_string()), 4);
+        segments.inner[0].done = 250;
+        segments.inner[1].done = 100;
+        segments.save(&meta_path(&path)).unwrap();
+
+        let downloaded = ranged
+            .download(&url, content.len() as u64, Some(ETAG.to_string()), None)
+            .await
+            .unwrap();
+
+        assert_eq!(downloaded.map(|p| p.path.clone()), Some(path.clone()));
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+
+        let mut requested = requested.lock().unwrap().clone();
+        requested.sort();
+        assert_eq!(
+            requested,
+            vec![
+                "GET bytes=350-499",
+                "GET bytes=500-749",
+                "GET bytes=750-999"
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn skip_locked_partial() {
+        let dir = tempdir::TempDir::new("ranged").unwrap();
+        let (url, requested) = serve();
+        let ranged = RangedDownload {
+            segments: 4,
+            min_size: 0,
+            partial_dir: dir.path().to_path_buf(),
+        };
+
+        let path = ranged.partial_path(&url);
+        let locked = PartialFile::lock(&path).unwrap().unwrap();
+        assert!(PartialFile::lock(&path).unwrap().is_none());
+
+        let downloaded = ranged
+            .download(&url, 1000, Some(ETAG.to_string()), None)
+            .await
+            .unwrap();
+        assert!(downloaded.is_none());
+        assert!(requested.lock().unwrap().is_empty());
+
+        drop(locked);
+        let downloaded = ranged
+            .download(&url, 1000, Some(ETAG.to_string()), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read(downloaded.unwrap().path.clone()).unwrap(),
+            content()
+        );
+    }
+
+    /// Downloads `url` with the http provider, returning the received content
+    async fn fetch(ranged: &RangedDownload, url: &Url) -> Vec<u8> {
+        use crate::{HttpTransferProvider, TransferContext, TransferProvider};
+
+        let provider = HttpTransferProvider::default().with_ranged_download(Some(ranged.clone()));
+        let chunks = provider
+            .source(url, &TransferContext::default())
+            .collect::<Vec<_>>()
+            .await;
+        chunks
+            .into_iter()
+            .flat_map(|chunk| bytes::Bytes::from(chunk.unwrap()).to_vec())
+            .collect()
+    }
+
+    #[actix_rt::test]
+    async fn probe_only_partial() {
+        let dir = tempdir::TempDir::new("ranged").unwrap();
+        let (url, requested) = serve();
+        let take = || std::mem::take(&mut *requested.lock().unwrap());
+        let mut ranged = RangedDownload {
+            segments: 2,
+            min_size: 1001,
+            partial_dir: dir.path().to_path_buf(),
+        };
+
+        // too small for a segmented download
+        assert_eq!(fetch(&ranged, &url).await, content());
+        assert_eq!(take(), vec!["GET"]);
+
+        // plain request reveals range support
+        ranged.min_size = 0;
+        assert_eq!(fetch(&ranged, &url).await, content());
+        let mut requested = take();
+        requested.sort();
+        assert_eq!(
+            requested,
+            vec!["GET", "GET bytes=0-499", "GET bytes=500-999"]
+        );
+        assert!(!ranged.has_partial(&url));
+
+        // interrupted download is verified before resuming
+        let path = ranged.partial_path(&url);
+        let mut segments = Segments::split(1000, Some(ETAG.to_string()), 2);
+        segments.inner[0].done = 500;
+        std::fs::write(&path, &content()[..500]).unwrap();
+        segments.save(&meta_path(&path)).unwrap();
+
+        assert_eq!(fetch(&ranged, &url).await, content());
+        assert_eq!(take(), vec!["HEAD", "GET bytes=500-999"]);
+    }
+
+    #[actix_rt::test]
+    async fn fallback_on_ignored_range() {
+        let dir = tempdir::TempDir::new("ranged").unwrap();
+        let (url, _) = serve();
+        let ranged = RangedDownload {
+            segments: 4,
+            min_size: 0,
+            partial_dir: dir.path().to_path_buf(),
+        };
+
+        let downloaded = ranged
+            .download(&url, 1000, Some("\"v0\"".to_string()), None)
+            .await
+            .unwrap();
+
+        assert_eq!(downloaded, None);
+        assert!(!ranged.partial_path(&url).exists());
+        assert!(!meta_path(&ranged.partial_path(&url)).exists());
+    }
+}
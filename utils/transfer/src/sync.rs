@@ -0,0 +1,513 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+
+use bytes::Bytes;
+use futures::channel::mpsc::Sender;
+use futures::{SinkExt, Stream};
+use sha3::Digest;
+use ya_client_model::activity::TransferArgs;
+
+use crate::archive::{archive_with_entry, extract, ArchiveFormat, FileEvent};
+use crate::error::Error;
+use crate::traverse::PathTraverse;
+use crate::{TransferData, TransferState};
+
+/// Name of the manifest entry, stored in synchronization archives
+pub const MANIFEST_NAME: &str = ".sync-manifest";
+
+const HASH_BUF_SIZE: usize = 64 * 1024;
+
+/// Directory synchronization mode, parsed from the transfer format:
+/// `sync[.delete][.<archive format>]`, e.g. `sync`, `sync.tar.zst`, `sync.delete.tar`,
+/// or `sync.manifest`.
+///
+/// Only files which differ from the destination are transferred. With `delete`,
+/// destination files no longer present at the source are removed.
+///
+/// Destinations which can't be inspected, e.g. reached over gftp, are compared against
+/// the manifest of their last synchronized state. For the opposite direction, `sync.manifest`
+/// transfers only the manifest of the source directory, so that the remote side can prepare
+/// an archive of changed files to be transferred back in the `sync` mode.
+#[derive(Clone, Copy, Debug)]
+pub struct SyncMode {
+    pub delete: bool,
+    pub format: ArchiveFormat,
+    pub manifest_only: bool,
+}
+
+impl SyncMode {
+    /// Names of supported synchronization modes
+    pub fn names() -> Vec<&'static str> {
+        vec!["sync", "sync.delete", "sync.manifest"]
+    }
+}
+
+impl<'s> TryFrom<&'s str> for SyncMode {
+    type Error = Error;
+
+    fn try_from(s: &'s str) -> Result<Self, Self::Error> {
+        let err = || Error::OutputFormat(s.to_string());
+        let lower = s.to_lowercase();
+
+        let rest = lower.strip_prefix("sync").ok_or_else(err)?;
+        if rest == ".manifest" {
+            return Ok(SyncMode {
+                delete: false,
+                format: ArchiveFormat::default(),
+                manifest_only: true,
+            });
+        }
+
+        let (delete, rest) = match rest.strip_prefix(".delete") {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let format = match rest {
+            "" => ArchiveFormat::default(),
+            _ => match rest.strip_prefix('.') {
+                Some(format) => ArchiveFormat::try_from(format)?,
+                None => return Err(err()),
+            },
+        };
+
+        match format {
+            ArchiveFormat::Zip | ArchiveFormat::ZipStored => Err(err()),
+            format => Ok(SyncMode {
+                delete,
+                format,
+                manifest_only: false,
+            }),
+        }
+    }
+}
+
+impl TryFrom<&TransferArgs> for SyncMode {
+    type Error = Error;
+
+    fn try_from(args: &TransferArgs) -> Result<Self, Self::Error> {
+        match &args.format {
+            Some(format) => SyncMode::try_from(format.as_str()),
+            None => Err(Error::OutputFormat("No sync format specified".into())),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub size: u64,
+    pub hash: String,
+}
+
+/// Sizes and hashes of files within a directory, keyed by relative, '/'-separated paths.
+/// Stored as text, with a `<hash> <size> <path>` line for each file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Manifest {
+    entries: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Computes the manifest of files in `dir`, selected by transfer `args`
+    pub fn scan(dir: &Path, args: &TransferArgs) -> Result<Self, Error> {
+        let mut entries = BTreeMap::new();
+        if !dir.exists() {
+            return Ok(Manifest { entries });
+        }
+
+        let root = ya_utils_path::normalize_path(dir)?;
+        for path in args.traverse(&root)? {
+            let name = match entry_name(&root, &path) {
+                Some(name) if name != MANIFEST_NAME && path.is_file() => name,
+                _ => continue,
+            };
+            let size = path.metadata()?.len();
+            let hash = file_hash(&path)?;
+            entries.insert(name, ManifestEntry { size, hash });
+        }
+
+        Ok(Manifest { entries })
+    }
+
+    /// Computes the manifest on a blocking thread, so that walking and hashing
+    /// a large directory doesn't stall other transfers
+    pub async fn scan_blocking(dir: PathBuf, args: TransferArgs) -> Result<Self, Error> {
+        tokio::task::spawn_blocking(move || Manifest::scan(&dir, &args))
+            .await
+            .map_err(|e| Error::Other(format!("Unable to scan directory: {}", e)))?
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ManifestEntry> {
+        self.entries.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Splits entry names into the ones which differ from `base` and the ones which do not
+    pub fn diff(&self, base: Option<&Manifest>) -> (Vec<String>, Vec<String>) {
+        let mut changed = Vec::new();
+        let mut unchanged = Vec::new();
+        for (name, entry) in self.entries.iter() {
+            match base.and_then(|b| b.get(name)) {
+                Some(base_entry) if base_entry == entry => unchanged.push(name.clone()),
+                _ => changed.push(name.clone()),
+            }
+        }
+        (changed, unchanged)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        Self::from_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, self.to_string())?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |l: &str| Error::Other(format!("Invalid manifest entry: {}", l));
+
+        let entries = s
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                let mut values = l.splitn(3, ' ');
+                let hash = values.next().filter(|h| hex::decode(h).is_ok());
+                let size = values.next().and_then(|v| u64::from_str(v).ok());
+                let name = values.next().filter(|n| !n.is_empty());
+                match (hash, size, name) {
+                    (Some(hash), Some(size), Some(name)) => Ok((
+                        name.to_string(),
+                        ManifestEntry {
+                            size,
+                            hash: hash.to_string(),
+                        },
+                    )),
+                    _ => Err(err(l)),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Manifest { entries })
+    }
+}
+
+impl std::fmt::Display for Manifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, entry) in self.entries.iter() {
+            writeln!(f, "{} {} {}", entry.hash, entry.size, name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates an archive of `dir` files which differ from the base manifest in transfer `state`.
+/// The complete source manifest is included in the archive and stored in `state`.
+/// In the `manifest_only` mode, the manifest alone is transferred.
+pub(crate) async fn sync_source(
+    dir: PathBuf,
+    args: &TransferArgs,
+    mode: SyncMode,
+    state: &TransferState,
+    mut evt_sender: Sender<FileEvent>,
+) -> Result<Pin<Box<dyn Stream<Item = Result<TransferData, Error>> + Send + Sync>>, Error> {
+    let manifest = Manifest::scan_blocking(dir.clone(), args.clone()).await?;
+    if mode.manifest_only {
+        let data = TransferData::from(Bytes::from(manifest.to_string()));
+        return Ok(Box::pin(futures::stream::once(async move { Ok(data) })));
+    }
+    let (changed, unchanged) = manifest.diff(state.base_manifest().as_ref());
+
+    log::debug!(
+        "Synchronizing {}: {} changed, {} unchanged files",
+        dir.display(),
+        changed.len(),
+        unchanged.len()
+    );
+
+    for name in unchanged {
+        let name = PathBuf::from(name);
+        let _ = evt_sender.send(FileEvent::Skipped { name }).await;
+    }
+
+    let entry = (
+        PathBuf::from(MANIFEST_NAME),
+        Bytes::from(manifest.to_string()),
+    );
+    state.set_manifest(Some(manifest));
+
+    let root = ya_utils_path::normalize_path(&dir)?;
+    let paths = changed.into_iter().map(move |name| root.join(name));
+    Ok(archive_with_entry(paths, dir, mode.format, entry, evt_sender).await)
+}
+
+/// Extracts a synchronization archive into `dir`. With deletions enabled,
+/// files selected by transfer `args` and missing from the source manifest are removed.
+pub(crate) async fn sync_destination<S>(
+    stream: S,
+    dir: PathBuf,
+    args: &TransferArgs,
+    mode: SyncMode,
+    mut evt_sender: Sender<FileEvent>,
+) -> Result<(), Error>
+where
+    S: Stream<Item = Result<TransferData, Error>> + Unpin + Send + Sync + 'static,
+{
+    if mode.manifest_only {
+        return Err(Error::OutputFormat(
+            "sync.manifest can only be used by transfer sources".into(),
+        ));
+    }
+
+    let manifest_path = dir.join(MANIFEST_NAME);
+    let _ = std::fs::remove_file(&manifest_path);
+
+    extract(stream, dir.clone(), mode.format, evt_sender.clone()).await?;
+
+    let manifest = Manifest::load(&manifest_path)
+        .map_err(|e| Error::Other(format!("Invalid synchronization archive: {}", e)))?;
+    std::fs::remove_file(&manifest_path)?;
+
+    if !mode.delete {
+        return Ok(());
+    }
+
+    let root = ya_utils_path::normalize_path(&dir)?;
+    let args = args.clone();
+    let removed = tokio::task::spawn_blocking(move || {
+        Ok::<_, Error>(
+            args.traverse(&root)?
+                .filter(|path| path.is_file())
+                .filter_map(|path| entry_name(&root, &path).map(|name| (path, name)))
+                .filter(|(_, name)| !manifest.contains(name))
+                .collect::<Vec<_>>(),
+        )
+    })
+    .await
+    .map_err(|e| Error::Other(format!("Unable to scan directory: {}", e)))??;
+
+    for (path, name) in removed {
+        std::fs::remove_file(&path)?;
+        let name = PathBuf::from(name);
+        let _ = evt_sender.send(FileEvent::Deleted { name }).await;
+    }
+    Ok(())
+}
+
+fn entry_name(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let name = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Some(name).filter(|n| !n.is_empty())
+}
+
+fn file_hash(path: &Path) -> Result<String, Error> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = sha3::Sha3_256::default();
+    let mut buf = vec![0u8; HASH_BUF_SIZE];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            n => hasher.input(&buf[..n]),
+        }
+    }
+    Ok(hex::encode(hasher.result()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(size: u64, hash: &str) -> ManifestEntry {
+        ManifestEntry {
+            size,
+            hash: hash.to_string(),
+        }
+    }
+
+    fn manifest(entries: Vec<(&str, ManifestEntry)>) -> Manifest {
+        Manifest {
+            entries: entries
+                .into_iter()
+                .map(|(n, e)| (n.to_string(), e))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn parse_mode() {
+        let mode = SyncMode::try_from("sync").unwrap();
+        assert!(!mode.delete);
+        assert!(matches!(mode.format, ArchiveFormat::TarGz));
+
+        let mode = SyncMode::try_from("sync.delete.tar.zst.3").unwrap();
+        assert!(mode.delete);
+        assert!(matches!(mode.format, ArchiveFormat::TarZst(Some(3))));
+
+        let mode = SyncMode::try_from("sync.tar").unwrap();
+        assert!(!mode.delete);
+        assert!(matches!(mode.format, ArchiveFormat::Tar));
+
+        let mode = SyncMode::try_from("sync.manifest").unwrap();
+        assert!(mode.manifest_only);
+        assert!(!SyncMode::try_from("sync.delete").unwrap().manifest_only);
+
+        for name in SyncMode::names() {
+            assert!(SyncMode::try_from(name).is_ok(), "{}", name);
+        }
+
+        assert!(SyncMode::try_from("tar.gz").is_err());
+        assert!(SyncMode::try_from("sync.manifest.tar").is_err());
+        assert!(SyncMode::try_from("sync.zip").is_err());
+        assert!(SyncMode::try_from("sync.deleted").is_err());
+        assert!(SyncMode::try_from("synctar").is_err());
+    }
+
+    #[test]
+    fn serialize() {
+        let m = manifest(vec![
+            ("a.txt", entry(3, "aabb")),
+            ("dir/file with spaces.bin", entry(1024, "ccdd")),
+        ]);
+        assert_eq!(Manifest::from_str(&m.to_string()).unwrap(), m);
+
+        assert!(Manifest::from_str("aabb 3").is_err());
+        assert!(Manifest::from_str("xyz 3 a.txt").is_err());
+        assert!(Manifest::from_str("aabb x a.txt").is_err());
+    }
+
+    #[test]
+    fn diff() {
+        let base = manifest(vec![
+            ("same", entry(1, "aa")),
+            ("changed", entry(1, "bb")),
+            ("resized", entry(1, "cc")),
+            ("removed", entry(1, "dd")),
+        ]);
+        let m = manifest(vec![
+            ("same", entry(1, "aa")),
+            ("changed", entry(1, "ff")),
+            ("resized", entry(2, "cc")),
+            ("added", entry(1, "ee")),
+        ]);
+
+        let (changed, unchanged) = m.diff(Some(&base));
+        assert_eq!(changed, vec!["added", "changed", "resized"]);
+        assert_eq!(unchanged, vec!["same"]);
+
+        let (changed, unchanged) = m.diff(None);
+        assert_eq!(changed.len(), 4);
+        assert!(unchanged.is_empty());
+    }
+
+    #[test]
+    fn scan() -> anyhow::Result<()> {
+        let dir = tempdir::TempDir::new("test-sync")?;
+        std::fs::create_dir_all(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("a.txt"), "abc")?;
+        std::fs::write(dir.path().join("sub").join("b.bin"), "")?;
+        std::fs::write(dir.path().join(MANIFEST_NAME), "")?;
+
+        let m = Manifest::scan(dir.path(), &TransferArgs::default())?;
+        assert_eq!(m.len(), 2);
+        assert_eq!(
+            m.get("a.txt"),
+            Some(&entry(
+                3,
+                "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"
+            ))
+        );
+        assert_eq!(m.get("sub/b.bin").map(|e| e.size), Some(0));
+
+        let m = Manifest::scan(&dir.path().join("missing"), &TransferArgs::default())?;
+        assert!(m.is_empty());
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn sync_dir() {
+        use crate::{transfer_with, DirTransferProvider, TransferContext, TransferUrl};
+
+        let src = tempdir::TempDir::new("sync-src").unwrap();
+        let dst = tempdir::TempDir::new("sync-dst").unwrap();
+        std::fs::create_dir_all(src.path().join("dir")).unwrap();
+        std::fs::write(src.path().join("same"), b"same").unwrap();
+        std::fs::write(src.path().join("changed"), b"new contents").unwrap();
+        std::fs::write(src.path().join("dir").join("added"), b"added").unwrap();
+        std::fs::write(dst.path().join("same"), b"same").unwrap();
+        std::fs::write(dst.path().join("changed"), b"old").unwrap();
+        std::fs::write(dst.path().join("removed"), b"removed").unwrap();
+
+        let provider = std::rc::Rc::new(DirTransferProvider::default());
+        let src_url = TransferUrl::parse(src.path().to_str().unwrap(), "file").unwrap();
+        let dst_url = TransferUrl::parse(dst.path().to_str().unwrap(), "file").unwrap();
+        let ctx = TransferContext::from(TransferArgs {
+            format: Some("sync.delete.tar".into()),
+            ..Default::default()
+        });
+
+        transfer_with(provider.clone(), &src_url, provider, &dst_url, &ctx)
+            .await
+            .unwrap();
+
+        let base = ctx.state.base_manifest().unwrap();
+        let manifest = ctx.state.manifest().unwrap();
+        assert_eq!(manifest.diff(Some(&base)).0, vec!["changed", "dir/added"]);
+        assert_eq!(Manifest::scan(dst.path(), &ctx.args).unwrap(), manifest);
+        assert!(!dst.path().join("removed").exists());
+        assert!(!dst.path().join(MANIFEST_NAME).exists());
+    }
+
+    #[actix_rt::test]
+    async fn manifest_source() {
+        use futures::TryStreamExt;
+
+        let dir = tempdir::TempDir::new("sync-manifest").unwrap();
+        std::fs::write(dir.path().join("a.txt"), b"abc").unwrap();
+
+        let mode = SyncMode::try_from("sync.manifest").unwrap();
+        let args = TransferArgs::default();
+        let state = TransferState::default();
+        let (evt_tx, _evt_rx) = futures::channel::mpsc::channel(1);
+
+        let data = sync_source(dir.path().to_path_buf(), &args, mode, &state, evt_tx)
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let manifest = data
+            .iter()
+            .map(|d| String::from_utf8_lossy(d.as_ref()).to_string())
+            .collect::<String>();
+
+        let expected = Manifest::scan(dir.path(), &args).unwrap();
+        assert_eq!(Manifest::from_str(&manifest).unwrap(), expected);
+        assert!(state.manifest().is_none());
+    }
+}